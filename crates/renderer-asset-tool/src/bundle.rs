@@ -0,0 +1,387 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    fs::File,
+    io::{self, BufReader, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use renderer_asset::{
+    animation::AnimationAsset,
+    archive::{dir::DirArchive, tar::TarArchive, xp3::Xp3Archive, Archive, Entry},
+    index::{AssetIndex, BundleAssetType, BundleIndex},
+    loader::{
+        fbx,
+        gltf::{load_glb_from_buffer_with_id, load_gltf_from_archive},
+        obj, ply, pmx, stl, AssetLoadParams,
+    },
+    node::NodeAsset,
+    physics::PhysicsAsset,
+    scene::SceneAsset,
+    vrm::VrmAsset,
+};
+use zip::ZipArchive;
+
+#[derive(Debug)]
+pub enum BundleError {
+    Io(io::Error),
+    UnknownFormat(PathBuf),
+    ModelNotFound(String),
+    Load(Box<dyn Error>),
+}
+
+impl Display for BundleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BundleError::Io(error) => Display::fmt(error, f),
+            BundleError::UnknownFormat(path) => write!(f, "Unknown format: {}", path.display()),
+            BundleError::ModelNotFound(name) => write!(f, "No model named {} in bundle", name),
+            BundleError::Load(error) => Display::fmt(error, f),
+        }
+    }
+}
+
+impl Error for BundleError {}
+
+impl From<io::Error> for BundleError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelFormat {
+    Gltf,
+    Glb,
+    Vrm,
+    Pmx,
+    Fbx,
+    Obj,
+    Ply,
+    Stl,
+}
+
+impl ModelFormat {
+    /// Formats in the order they are looked for in a bundle.
+    pub const ALL: [ModelFormat; 8] = [
+        ModelFormat::Gltf,
+        ModelFormat::Glb,
+        ModelFormat::Vrm,
+        ModelFormat::Pmx,
+        ModelFormat::Fbx,
+        ModelFormat::Obj,
+        ModelFormat::Ply,
+        ModelFormat::Stl,
+    ];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ModelFormat::Gltf => "gltf",
+            ModelFormat::Glb => "glb",
+            ModelFormat::Vrm => "vrm",
+            ModelFormat::Pmx => "pmx",
+            ModelFormat::Fbx => "fbx",
+            ModelFormat::Obj => "obj",
+            ModelFormat::Ply => "ply",
+            ModelFormat::Stl => "stl",
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|format| extension.eq_ignore_ascii_case(format.extension()))
+    }
+}
+
+impl Display for ModelFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelFormat::Gltf => write!(f, "glTF"),
+            ModelFormat::Glb => write!(f, "GLB"),
+            ModelFormat::Vrm => write!(f, "VRM"),
+            ModelFormat::Pmx => write!(f, "PMX"),
+            ModelFormat::Fbx => write!(f, "FBX"),
+            ModelFormat::Obj => write!(f, "OBJ"),
+            ModelFormat::Ply => write!(f, "PLY"),
+            ModelFormat::Stl => write!(f, "STL"),
+        }
+    }
+}
+
+/// A loaded model, with the files it referred to but were absent.
+pub struct Model {
+    pub index: BundleIndex,
+    pub format: ModelFormat,
+    pub scenes: Vec<SceneAsset>,
+    pub animations: Vec<AnimationAsset>,
+    pub vrm: Option<VrmAsset>,
+    pub physics: Option<PhysicsAsset>,
+    pub missing_files: Vec<String>,
+}
+
+/// Archive recording the paths which are looked up but not found.
+struct TrackedArchive<A> {
+    archive: A,
+    missing: Vec<String>,
+}
+
+impl<T, A: Archive<T>> Archive<T> for TrackedArchive<A> {
+    type Error = A::Error;
+
+    type Entry<'a>
+        = A::Entry<'a>
+    where
+        Self: 'a;
+
+    fn new(stream: T) -> Result<Self, Self::Error>
+    where
+        T: Read + Seek,
+    {
+        Ok(Self {
+            archive: A::new(stream)?,
+            missing: Vec::new(),
+        })
+    }
+
+    fn by_path<P: AsRef<Path>>(&mut self, path: P) -> Result<Option<Self::Entry<'_>>, Self::Error> {
+        let entry = self.archive.by_path(&path)?;
+        if entry.is_none() {
+            self.missing.push(path.as_ref().display().to_string());
+        }
+        Ok(entry)
+    }
+}
+
+fn load_error<E: Error + 'static>(error: E) -> BundleError {
+    BundleError::Load(Box::new(error))
+}
+
+/// Load a model from an archive. The format is detected from the model
+/// file found in the archive, unless specified.
+pub fn load_archive<T, A: Archive<T>>(
+    archive: A,
+    index: BundleIndex,
+    params: &AssetLoadParams,
+    format: Option<ModelFormat>,
+) -> Result<Model, BundleError>
+where
+    A::Error: 'static,
+{
+    let mut archive = TrackedArchive {
+        archive,
+        missing: Vec::new(),
+    };
+    let format = match format {
+        Some(format) => format,
+        None => {
+            let mut found = None;
+            for format in ModelFormat::ALL {
+                let file_name = format!("{}.{}", params.bundle_model_name, format.extension());
+                if archive
+                    .archive
+                    .by_path(&file_name)
+                    .map_err(load_error)?
+                    .is_some()
+                {
+                    found = Some(format);
+                    break;
+                }
+            }
+            found.ok_or_else(|| BundleError::ModelNotFound(params.bundle_model_name.clone()))?
+        }
+    };
+
+    let mut model = Model {
+        index: index.clone(),
+        format,
+        scenes: Vec::new(),
+        animations: Vec::new(),
+        vrm: None,
+        physics: None,
+        missing_files: Vec::new(),
+    };
+    match format {
+        ModelFormat::Gltf => {
+            let (scenes, animations, vrm) =
+                load_gltf_from_archive(&mut archive, index, params).map_err(load_error)?;
+            model.scenes = scenes;
+            model.animations = animations;
+            model.vrm = vrm;
+        }
+        ModelFormat::Glb | ModelFormat::Vrm => {
+            let file_name = format!("{}.{}", params.bundle_model_name, format.extension());
+            let mut entry = archive
+                .by_path(&file_name)
+                .map_err(load_error)?
+                .ok_or(BundleError::ModelNotFound(file_name))?;
+            let buffer = entry.unpack().map_err(load_error)?;
+            drop(entry);
+            let (scenes, animations, vrm) =
+                load_glb_from_buffer_with_id(&buffer, index, params).map_err(load_error)?;
+            model.scenes = scenes;
+            model.animations = animations;
+            model.vrm = vrm;
+        }
+        ModelFormat::Pmx => {
+            let (scene, physics) =
+                pmx::load_bundle(index, &mut archive, params.clone()).map_err(load_error)?;
+            model.scenes = vec![scene];
+            model.physics = Some(physics);
+        }
+        ModelFormat::Fbx => {
+            let (scene, animations) =
+                fbx::load_bundle(index, &mut archive, params).map_err(load_error)?;
+            model.scenes = vec![scene];
+            model.animations = animations;
+        }
+        ModelFormat::Obj | ModelFormat::Ply | ModelFormat::Stl => {
+            let mesh = match format {
+                ModelFormat::Obj => {
+                    obj::load_bundle(index.clone(), &mut archive, params).map_err(load_error)?
+                }
+                ModelFormat::Ply => {
+                    ply::load_bundle(index.clone(), &mut archive, params).map_err(load_error)?
+                }
+                _ => stl::load_bundle(index.clone(), &mut archive, params).map_err(load_error)?,
+            };
+            // Formats with a single mesh get a node for it
+            model.scenes = vec![SceneAsset {
+                name: None,
+                nodes: vec![NodeAsset {
+                    id: AssetIndex::BundleTypeIndex(index, BundleAssetType::Node, 0),
+                    name: None,
+                    camera: None,
+                    children: Vec::new(),
+                    skin: None,
+                    transform: None,
+                    mesh: Some(mesh),
+                    weights: Vec::new(),
+                }],
+            }];
+        }
+    }
+    model.missing_files = archive.missing;
+    Ok(model)
+}
+
+fn open_archive<A: Archive<BufReader<File>>>(
+    path: &Path,
+    params: &AssetLoadParams,
+    format: Option<ModelFormat>,
+) -> Result<Model, BundleError>
+where
+    A::Error: 'static,
+{
+    // Bundles are identified by the digest of the archive
+    let index = BundleIndex::digest_from_reader(File::open(path)?)?;
+    let archive = A::new(BufReader::new(File::open(path)?)).map_err(load_error)?;
+    load_archive(archive, index, params, format)
+}
+
+/// Load a model from a zip, tar or xp3 archive, a directory, or a model file
+/// in a directory.
+///
+/// Models in directories are identified by the digest of the model file.
+pub fn open(
+    path: &Path,
+    params: &AssetLoadParams,
+    format: Option<ModelFormat>,
+) -> Result<Model, BundleError> {
+    if path.is_dir() {
+        let format = match format {
+            Some(format) => format,
+            None => ModelFormat::ALL
+                .into_iter()
+                .find(|format| {
+                    path.join(format!(
+                        "{}.{}",
+                        params.bundle_model_name,
+                        format.extension()
+                    ))
+                    .is_file()
+                })
+                .ok_or_else(|| BundleError::ModelNotFound(params.bundle_model_name.clone()))?,
+        };
+        let file_name = format!("{}.{}", params.bundle_model_name, format.extension());
+        let index = BundleIndex::digest_from_reader(File::open(path.join(file_name))?)?;
+        let archive = DirArchive::new(path);
+        return load_archive(archive, index, params, Some(format));
+    }
+
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match extension.as_str() {
+        "zip" => open_archive::<ZipArchive<_>>(path, params, format),
+        "tar" => open_archive::<TarArchive<_>>(path, params, format),
+        "xp3" => open_archive::<Xp3Archive<_>>(path, params, format),
+        _ => {
+            let format = format
+                .or_else(|| ModelFormat::from_extension(&extension))
+                .ok_or_else(|| BundleError::UnknownFormat(path.to_path_buf()))?;
+            let (Some(parent), Some(name)) = (
+                path.parent(),
+                path.file_stem().and_then(|name| name.to_str()),
+            ) else {
+                return Err(BundleError::UnknownFormat(path.to_path_buf()));
+            };
+            let params = AssetLoadParams {
+                bundle_model_name: String::from(name),
+                bundle_model_extension: true,
+                ..params.clone()
+            };
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            let index = BundleIndex::digest_from_reader(File::open(path)?)?;
+            let archive = DirArchive::new(parent);
+            load_archive(archive, index, &params, Some(format))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Write};
+
+    use renderer_asset::{index::BundleIndex, loader::AssetLoadParams};
+    use zip::{write::SimpleFileOptions, ZipArchive, ZipWriter};
+
+    use super::{load_archive, ModelFormat};
+
+    #[test]
+    fn test_load_archive() {
+        let files = [
+            (
+                "model.obj",
+                "mtllib model.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\n",
+            ),
+            ("model.mtl", "newmtl red\nKd 1 0 0\nmap_Kd missing.png\n"),
+        ];
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, data) in files {
+            writer
+                .start_file(path, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(data.as_bytes()).unwrap();
+        }
+        let archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        let model = load_archive(
+            archive,
+            BundleIndex([0; 32]),
+            &AssetLoadParams::default(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(model.format, ModelFormat::Obj);
+        assert_eq!(model.missing_files, ["missing.png"]);
+        let mesh = model.scenes[0].nodes[0].mesh.as_ref().unwrap();
+        assert_eq!(mesh.primitives[0].attributes.position.len(), 3);
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    sync::Arc,
+};
+
+use renderer_asset::{
+    animation::{AnimationAsset, AnimationKeyFrames, AnimationSampler},
+    camera::CameraProjectionAsset,
+    index::AssetIndex,
+    material::{MaterialAsset, MaterialAssetData},
+    node::NodeAsset,
+    primitive::{PrimitiveAsset, PrimitiveAssetMode},
+    texture::{TextureAsset, TextureAssetFormat},
+};
+
+use crate::bundle::Model;
+
+/// Name of an asset, or its index if it has no name.
+pub struct Label<'a>(pub &'a Option<String>, pub &'a AssetIndex);
+
+impl Display for Label<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(name) => write!(f, "{:?}", name),
+            None => write!(f, "<{}>", self.1),
+        }
+    }
+}
+
+pub fn bytes_per_pixel(format: TextureAssetFormat) -> usize {
+    match format {
+        TextureAssetFormat::Ru8 => 1,
+        TextureAssetFormat::Rgu8 => 2,
+        TextureAssetFormat::Rgbu8 => 3,
+        TextureAssetFormat::Rgbau8 => 4,
+        TextureAssetFormat::Ru16 => 2,
+        TextureAssetFormat::Rgu16 => 4,
+        TextureAssetFormat::Rgbu16 => 6,
+        TextureAssetFormat::Rgbau16 => 8,
+    }
+}
+
+pub fn primitive_count(primitive: &PrimitiveAsset) -> usize {
+    let count = primitive
+        .indices
+        .as_ref()
+        .map_or(primitive.attributes.position.len(), Vec::len);
+    match primitive.mode {
+        PrimitiveAssetMode::Points => count,
+        PrimitiveAssetMode::LineList => count / 2,
+        PrimitiveAssetMode::LineStrip => count.saturating_sub(1),
+        PrimitiveAssetMode::TriangleList => count / 3,
+        PrimitiveAssetMode::TriangleStrip => count.saturating_sub(2),
+    }
+}
+
+fn mode_name(mode: PrimitiveAssetMode) -> &'static str {
+    match mode {
+        PrimitiveAssetMode::Points => "points",
+        PrimitiveAssetMode::LineList | PrimitiveAssetMode::LineStrip => "lines",
+        PrimitiveAssetMode::TriangleList | PrimitiveAssetMode::TriangleStrip => "triangles",
+    }
+}
+
+fn material_kind(data: &MaterialAssetData) -> &'static str {
+    match data {
+        MaterialAssetData::Pbr { .. } => "PBR",
+        MaterialAssetData::BlinnPhong { .. } => "Blinn-Phong",
+        MaterialAssetData::Unlit { .. } => "unlit",
+        MaterialAssetData::MTone { .. } => "MToon",
+        MaterialAssetData::Pmx { .. } => "PMX",
+    }
+}
+
+/// Materials and textures of the model, by their indices.
+#[derive(Default)]
+pub struct Resources<'a> {
+    pub materials: BTreeMap<&'a AssetIndex, &'a Arc<MaterialAsset>>,
+    pub textures: BTreeMap<&'a AssetIndex, &'a Arc<TextureAsset>>,
+}
+
+impl<'a> Resources<'a> {
+    pub fn collect(model: &'a Model) -> Self {
+        fn visit<'a>(resources: &mut Resources<'a>, node: &'a NodeAsset) {
+            let primitives = node.mesh.iter().flat_map(|mesh| &mesh.primitives);
+            for material in primitives.filter_map(|primitive| primitive.material.as_ref()) {
+                resources.materials.insert(&material.id, material);
+                for (_, texture) in material.textures() {
+                    resources.textures.insert(&texture.id, texture);
+                }
+            }
+            for child in &node.children {
+                visit(resources, child);
+            }
+        }
+
+        let mut resources = Self::default();
+        for node in model.scenes.iter().flat_map(|scene| &scene.nodes) {
+            visit(&mut resources, node);
+        }
+        resources
+    }
+}
+
+fn print_node(node: &NodeAsset, depth: usize) {
+    let indent = "  ".repeat(depth);
+    println!("{}Node {}", indent, Label(&node.name, &node.id));
+    if let Some(camera) = &node.camera {
+        let projection = match camera.projection {
+            CameraProjectionAsset::Perspective(_) => "perspective",
+            CameraProjectionAsset::Orthographic(_) => "orthographic",
+        };
+        println!("{}  Camera: {}", indent, projection);
+    }
+    if let Some(skin) = &node.skin {
+        println!("{}  Skin: {} joints", indent, skin.joint_ids.len());
+    }
+    if let Some(mesh) = &node.mesh {
+        let name = mesh.name.as_deref().unwrap_or("<unnamed>");
+        println!("{}  Mesh {:?}", indent, name);
+        for primitive in &mesh.primitives {
+            let material = primitive.material.as_ref().map_or_else(
+                || String::from("no material"),
+                |material| format!("material {}", Label(&material.name, &material.id)),
+            );
+            println!(
+                "{}    {} vertices, {} {}, {} morph targets, {}",
+                indent,
+                primitive.attributes.position.len(),
+                primitive_count(primitive),
+                mode_name(primitive.mode),
+                primitive.targets.len(),
+                material,
+            );
+        }
+    }
+    for child in &node.children {
+        print_node(child, depth + 1);
+    }
+}
+
+fn animation_length(animation: &AnimationAsset) -> f32 {
+    fn last<T: fmt::Debug + Clone>(keyframes: &AnimationKeyFrames<T>) -> f32 {
+        match keyframes {
+            AnimationKeyFrames::Linear(keyframes) | AnimationKeyFrames::Step(keyframes) => {
+                keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+            }
+            AnimationKeyFrames::CubicSpline(keyframes) => {
+                keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+            }
+        }
+    }
+    animation
+        .channels
+        .iter()
+        .map(|channel| match &channel.sampler {
+            AnimationSampler::Rotation(keyframes) => last(keyframes),
+            AnimationSampler::Translation(keyframes) | AnimationSampler::Scale(keyframes) => {
+                last(keyframes)
+            }
+        })
+        .fold(0.0, f32::max)
+}
+
+/// Print a tree of the model contents.
+pub fn print(model: &Model) {
+    println!("Bundle: {}", model.index);
+    println!("Format: {}", model.format);
+
+    for (index, scene) in model.scenes.iter().enumerate() {
+        let name = scene.name.as_deref().unwrap_or("<unnamed>");
+        println!("Scene {} {:?}", index, name);
+        for node in &scene.nodes {
+            print_node(node, 1);
+        }
+    }
+
+    let resources = Resources::collect(model);
+    println!("Materials: {}", resources.materials.len());
+    for material in resources.materials.values() {
+        let textures = material
+            .textures()
+            .into_iter()
+            .map(|(slot, _)| slot)
+            .collect::<Vec<_>>();
+        println!(
+            "  {} ({}), textures: [{}]",
+            Label(&material.name, &material.id),
+            material_kind(&material.data),
+            textures.join(", ")
+        );
+    }
+    println!("Textures: {}", resources.textures.len());
+    for texture in resources.textures.values() {
+        println!(
+            "  <{}> {}x{} {:?}",
+            texture.id, texture.size.0, texture.size.1, texture.format
+        );
+    }
+
+    println!("Animations: {}", model.animations.len());
+    for animation in &model.animations {
+        let name = animation.name.as_deref().unwrap_or("<unnamed>");
+        println!(
+            "  {:?}: {} channels, {:.2} s",
+            name,
+            animation.channels.len(),
+            animation_length(animation)
+        );
+    }
+
+    if let Some(vrm) = &model.vrm {
+        println!("VRM {}: {:?}", vrm.spec_version, vrm.meta.name);
+        println!("  Human bones: {}", vrm.humanoid.bones.len());
+        println!("  Expressions: {}", vrm.expressions.len());
+        if let Some(spring_bone) = &vrm.spring_bone {
+            println!("  Springs: {}", spring_bone.springs.len());
+        }
+    }
+    if let Some(physics) = &model.physics {
+        println!(
+            "Physics: {} rigid bodies, {} joints",
+            physics.rigid_bodies.len(),
+            physics.joints.len()
+        );
+    }
+}
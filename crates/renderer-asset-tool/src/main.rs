@@ -0,0 +1,161 @@
+mod bundle;
+mod inspect;
+mod validate;
+
+use std::{env, fs, path::PathBuf, process};
+
+use renderer_asset::{
+    exporter::gltf::{export_glb, export_gltf},
+    loader::AssetLoadParams,
+};
+
+use crate::bundle::ModelFormat;
+
+const USAGE: &str = "\
+Usage: renderer-asset-tool <command> [options] <path> [output]
+
+Commands:
+  info      Print the nodes, meshes, materials, textures and animations
+  validate  Check for missing files, bad indices, bad joints and NaNs
+  index     Print the bundle index
+  convert   Convert to <output>, a .glb file or a .gltf file with a .bin buffer
+
+<path> is a zip, tar or xp3 archive, a directory, or a model file.
+
+Options:
+  --model-name <name>  Name of the model file in a bundle [default: model]
+  --format <format>    One of gltf, glb, vrm, pmx, fbx, obj, ply and stl [default: detected]";
+
+#[derive(Debug, Clone, Copy)]
+enum Command {
+    Info,
+    Validate,
+    Index,
+    Convert,
+}
+
+#[derive(Debug)]
+struct Args {
+    command: Command,
+    path: PathBuf,
+    output: Option<PathBuf>,
+    params: AssetLoadParams,
+    format: Option<ModelFormat>,
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn value(args: &mut impl Iterator<Item = String>, name: &str) -> String {
+    args.next()
+        .unwrap_or_else(|| usage_error(&format!("Missing value for {}", name)))
+}
+
+impl Args {
+    fn parse() -> Self {
+        let mut args = env::args().skip(1);
+        let command = match args.next().as_deref() {
+            Some("info") => Command::Info,
+            Some("validate") => Command::Validate,
+            Some("index") => Command::Index,
+            Some("convert") => Command::Convert,
+            Some("-h" | "--help") => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            Some(other) => usage_error(&format!("Unknown command: {}", other)),
+            None => usage_error("Missing command"),
+        };
+        let mut params = AssetLoadParams::default();
+        let mut format = None;
+        let mut paths = Vec::new();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--model-name" => params.bundle_model_name = value(&mut args, &arg),
+                "--format" => {
+                    let name = value(&mut args, &arg);
+                    format = Some(
+                        ModelFormat::from_extension(&name)
+                            .unwrap_or_else(|| usage_error(&format!("Unknown format: {}", name))),
+                    );
+                }
+                _ if arg.starts_with("--") => usage_error(&format!("Unknown argument: {}", arg)),
+                _ => paths.push(PathBuf::from(arg)),
+            }
+        }
+
+        let mut paths = paths.into_iter();
+        let path = paths.next().unwrap_or_else(|| usage_error("Missing path"));
+        let output = paths.next();
+        match (command, &output) {
+            (Command::Convert, None) => usage_error("Missing output"),
+            (Command::Convert, Some(_)) | (_, None) => (),
+            (_, Some(output)) => usage_error(&format!("Unexpected argument: {}", output.display())),
+        }
+        if let Some(path) = paths.next() {
+            usage_error(&format!("Unexpected argument: {}", path.display()));
+        }
+        Self {
+            command,
+            path,
+            output,
+            params,
+            format,
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    let model = match bundle::open(&args.path, &args.params, args.format) {
+        Ok(model) => model,
+        Err(error) => {
+            eprintln!("Failed to load {}: {}", args.path.display(), error);
+            process::exit(1);
+        }
+    };
+
+    match args.command {
+        Command::Info => inspect::print(&model),
+        Command::Index => println!("{}", model.index),
+        Command::Validate => {
+            let issues = validate::validate(&model);
+            for issue in &issues {
+                println!("{}", issue);
+            }
+            if !issues.is_empty() {
+                eprintln!("{} issues found", issues.len());
+                process::exit(1);
+            }
+            println!("No issues found");
+        }
+        Command::Convert => {
+            let output = args.output.unwrap();
+            let is_gltf = output
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("gltf"));
+            let result = if is_gltf {
+                let bin = output.with_extension("bin");
+                let uri = bin.file_name().unwrap().to_string_lossy().into_owned();
+                export_gltf(&model.scenes, &model.animations, &uri).map(|(gltf, buffer)| {
+                    fs::write(&output, gltf).and_then(|_| fs::write(&bin, buffer))
+                })
+            } else {
+                export_glb(&model.scenes, &model.animations).map(|glb| fs::write(&output, glb))
+            };
+            match result {
+                Ok(Ok(())) => println!("Converted to {}", output.display()),
+                Ok(Err(error)) => {
+                    eprintln!("Failed to write {}: {}", output.display(), error);
+                    process::exit(1);
+                }
+                Err(error) => {
+                    eprintln!("Failed to convert: {}", error);
+                    process::exit(1);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,436 @@
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+};
+
+use renderer_asset::{
+    animation::{AnimationKeyFrames, AnimationSampler},
+    index::{AssetIndex, BundleAssetType},
+    node::{NodeAsset, NodeTransform},
+    primitive::PrimitiveAsset,
+};
+
+use crate::{
+    bundle::Model,
+    inspect::{bytes_per_pixel, Label, Resources},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+    MissingFile(String),
+    NonFinite(String),
+    AttributeLength {
+        location: String,
+        attribute: &'static str,
+        length: usize,
+        expected: usize,
+    },
+    IndexOutOfRange {
+        location: String,
+        index: u32,
+        count: usize,
+    },
+    JointOutOfRange {
+        location: String,
+        joint: u16,
+        count: usize,
+    },
+    MissingSkin(String),
+    UnknownJoint(String, AssetIndex),
+    TextureSize {
+        texture: AssetIndex,
+        length: usize,
+        expected: usize,
+    },
+    UnknownAnimationTarget(String, AssetIndex),
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Issue::MissingFile(path) => write!(f, "Missing file: {}", path),
+            Issue::NonFinite(location) => write!(f, "{}: Non-finite value", location),
+            Issue::AttributeLength {
+                location,
+                attribute,
+                length,
+                expected,
+            } => write!(
+                f,
+                "{}: {} has {} items, expected {}",
+                location, attribute, length, expected
+            ),
+            Issue::IndexOutOfRange {
+                location,
+                index,
+                count,
+            } => write!(
+                f,
+                "{}: Index {} is out of {} vertices",
+                location, index, count
+            ),
+            Issue::JointOutOfRange {
+                location,
+                joint,
+                count,
+            } => write!(
+                f,
+                "{}: Joint {} is out of {} joints",
+                location, joint, count
+            ),
+            Issue::MissingSkin(location) => {
+                write!(f, "{}: Skinned primitive on a node without skin", location)
+            }
+            Issue::UnknownJoint(location, id) => {
+                write!(f, "{}: Joint <{}> is not a node of the model", location, id)
+            }
+            Issue::TextureSize {
+                texture,
+                length,
+                expected,
+            } => write!(
+                f,
+                "Texture <{}>: {} bytes of data, expected {}",
+                texture, length, expected
+            ),
+            Issue::UnknownAnimationTarget(location, id) => {
+                write!(
+                    f,
+                    "{}: Target <{}> is not a node of the model",
+                    location, id
+                )
+            }
+        }
+    }
+}
+
+fn finite<'a, const N: usize>(items: impl IntoIterator<Item = &'a [f32; N]>) -> bool {
+    items
+        .into_iter()
+        .all(|item| item.iter().all(|value| value.is_finite()))
+}
+
+struct Validator<'a> {
+    node_ids: HashSet<&'a AssetIndex>,
+    issues: Vec<Issue>,
+}
+
+impl<'a> Validator<'a> {
+    fn collect_ids(&mut self, node: &'a NodeAsset) {
+        self.node_ids.insert(&node.id);
+        for child in &node.children {
+            self.collect_ids(child);
+        }
+    }
+
+    fn check_length(
+        &mut self,
+        location: &str,
+        attribute: &'static str,
+        length: usize,
+        expected: usize,
+    ) {
+        // Optional attributes may be absent
+        if length != 0 && length != expected {
+            self.issues.push(Issue::AttributeLength {
+                location: location.to_string(),
+                attribute,
+                length,
+                expected,
+            });
+        }
+    }
+
+    fn check_primitive(&mut self, location: String, primitive: &PrimitiveAsset, node: &NodeAsset) {
+        let attributes = &primitive.attributes;
+        let count = attributes.position.len();
+        self.check_length(&location, "Normal", attributes.normal.len(), count);
+        self.check_length(&location, "Tangent", attributes.tangent.len(), count);
+        for tex_coord in &attributes.tex_coord {
+            self.check_length(&location, "Texture coordinate", tex_coord.len(), count);
+        }
+        for color in &attributes.color {
+            self.check_length(&location, "Color", color.len(), count);
+        }
+        for joints in &attributes.joints {
+            self.check_length(&location, "Joints", joints.len(), count);
+        }
+        for weights in &attributes.weights {
+            self.check_length(&location, "Weights", weights.len(), count);
+        }
+        for target in &primitive.targets {
+            self.check_length(
+                &location,
+                "Morph target position",
+                target.position.len(),
+                count,
+            );
+            self.check_length(&location, "Morph target normal", target.normal.len(), count);
+            self.check_length(
+                &location,
+                "Morph target tangent",
+                target.tangent.len(),
+                count,
+            );
+        }
+
+        let finite = finite(&attributes.position)
+            && finite(&attributes.normal)
+            && finite(&attributes.tangent)
+            && attributes.tex_coord.iter().all(finite)
+            && attributes.color.iter().all(finite)
+            && attributes.weights.iter().all(finite)
+            && primitive.targets.iter().all(|target| {
+                finite(&target.position) && finite(&target.normal) && finite(&target.tangent)
+            });
+        if !finite {
+            self.issues.push(Issue::NonFinite(location.clone()));
+        }
+
+        if let Some(index) = primitive
+            .indices
+            .iter()
+            .flatten()
+            .find(|index| **index as usize >= count)
+        {
+            self.issues.push(Issue::IndexOutOfRange {
+                location: location.clone(),
+                index: *index,
+                count,
+            });
+        }
+
+        if attributes.joints.is_empty() {
+            return;
+        }
+        let Some(skin) = &node.skin else {
+            self.issues.push(Issue::MissingSkin(location));
+            return;
+        };
+        let joint_count = skin.joint_ids.len();
+        if let Some(joint) = attributes
+            .joints
+            .iter()
+            .flatten()
+            .flatten()
+            .find(|joint| **joint as usize >= joint_count)
+        {
+            self.issues.push(Issue::JointOutOfRange {
+                location,
+                joint: *joint,
+                count: joint_count,
+            });
+        }
+    }
+
+    fn check_node(&mut self, location: &str, node: &NodeAsset) {
+        let location = format!("{} / Node {}", location, Label(&node.name, &node.id));
+        let finite = match &node.transform {
+            Some(NodeTransform::Matrix(matrix)) => matrix.0.is_finite(),
+            Some(NodeTransform::Decomposed(decomposed)) => {
+                decomposed.translation.is_finite()
+                    && decomposed.rotation.is_finite()
+                    && decomposed.scale.is_finite()
+            }
+            None => true,
+        };
+        if !finite || !node.weights.iter().all(|weight| weight.is_finite()) {
+            self.issues.push(Issue::NonFinite(location.clone()));
+        }
+
+        if let Some(skin) = &node.skin {
+            let skin_location = format!("{} / Skin", location);
+            if !skin
+                .inverse_bind_matrices
+                .iter()
+                .all(|matrix| matrix.is_finite())
+            {
+                self.issues.push(Issue::NonFinite(skin_location.clone()));
+            }
+            // PMX bones aren't nodes
+            let unknown = skin.joint_ids.iter().chain(&skin.skeleton).find(|id| {
+                !matches!(id, AssetIndex::BundleTypeIndex(_, BundleAssetType::Bone, _))
+                    && !self.node_ids.contains(id)
+            });
+            if let Some(id) = unknown {
+                self.issues
+                    .push(Issue::UnknownJoint(skin_location, id.clone()));
+            }
+        }
+
+        if let Some(mesh) = &node.mesh {
+            for (index, primitive) in mesh.primitives.iter().enumerate() {
+                self.check_primitive(
+                    format!("{} / Primitive {}", location, index),
+                    primitive,
+                    node,
+                );
+            }
+        }
+        for child in &node.children {
+            self.check_node(&location, child);
+        }
+    }
+}
+
+/// Check the model for problems which loaders don't reject.
+pub fn validate(model: &Model) -> Vec<Issue> {
+    let mut validator = Validator {
+        node_ids: HashSet::new(),
+        issues: model
+            .missing_files
+            .iter()
+            .cloned()
+            .map(Issue::MissingFile)
+            .collect(),
+    };
+    for node in model.scenes.iter().flat_map(|scene| &scene.nodes) {
+        validator.collect_ids(node);
+    }
+    for (index, scene) in model.scenes.iter().enumerate() {
+        for node in &scene.nodes {
+            validator.check_node(&format!("Scene {}", index), node);
+        }
+    }
+
+    let resources = Resources::collect(model);
+    for texture in resources.textures.values() {
+        let (width, height) = texture.size;
+        let expected = width as usize * height as usize * bytes_per_pixel(texture.format);
+        if texture.data.len() != expected {
+            validator.issues.push(Issue::TextureSize {
+                texture: texture.id.clone(),
+                length: texture.data.len(),
+                expected,
+            });
+        }
+    }
+
+    for (index, animation) in model.animations.iter().enumerate() {
+        let name = animation.name.as_deref().unwrap_or("<unnamed>");
+        let location = format!("Animation {} {:?}", index, name);
+        for channel in &animation.channels {
+            if !validator.node_ids.contains(&channel.target_id) {
+                validator.issues.push(Issue::UnknownAnimationTarget(
+                    location.clone(),
+                    channel.target_id.clone(),
+                ));
+            }
+            fn finite_keyframes<const N: usize>(keyframes: &AnimationKeyFrames<[f32; N]>) -> bool {
+                match keyframes {
+                    AnimationKeyFrames::Linear(keyframes) | AnimationKeyFrames::Step(keyframes) => {
+                        keyframes
+                            .iter()
+                            .all(|keyframe| keyframe.time.is_finite() && finite([&keyframe.value]))
+                    }
+                    AnimationKeyFrames::CubicSpline(keyframes) => {
+                        keyframes.iter().all(|keyframe| {
+                            let (in_tangent, value, out_tangent) = &keyframe.value;
+                            keyframe.time.is_finite() && finite([in_tangent, value, out_tangent])
+                        })
+                    }
+                }
+            }
+            let finite = match &channel.sampler {
+                AnimationSampler::Rotation(keyframes) => finite_keyframes(keyframes),
+                AnimationSampler::Translation(keyframes) | AnimationSampler::Scale(keyframes) => {
+                    finite_keyframes(keyframes)
+                }
+            };
+            if !finite {
+                validator.issues.push(Issue::NonFinite(location.clone()));
+            }
+        }
+    }
+    validator.issues
+}
+
+#[cfg(test)]
+mod test {
+    use renderer_asset::{
+        index::{AssetIndex, BundleAssetType, BundleIndex},
+        mesh::MeshAsset,
+        node::NodeAsset,
+        primitive::{PrimitiveAsset, PrimitiveAssetAttributes, PrimitiveAssetMode},
+        scene::SceneAsset,
+    };
+
+    use super::{validate, Issue};
+    use crate::bundle::{Model, ModelFormat};
+
+    fn model(primitive: PrimitiveAsset) -> Model {
+        let id = AssetIndex::BundleTypeIndex(BundleIndex([0; 32]), BundleAssetType::Node, 0);
+        let node = NodeAsset {
+            id,
+            name: Some(String::from("node")),
+            camera: None,
+            children: Vec::new(),
+            skin: None,
+            transform: None,
+            mesh: Some(MeshAsset {
+                name: None,
+                primitives: vec![primitive],
+                weights: Vec::new(),
+            }),
+            weights: Vec::new(),
+        };
+        Model {
+            index: BundleIndex([0; 32]),
+            format: ModelFormat::Gltf,
+            scenes: vec![SceneAsset {
+                name: None,
+                nodes: vec![node],
+            }],
+            animations: Vec::new(),
+            vrm: None,
+            physics: None,
+            missing_files: vec![String::from("texture.png")],
+        }
+    }
+
+    fn primitive(position: Vec<[f32; 3]>, indices: Vec<u32>) -> PrimitiveAsset {
+        PrimitiveAsset {
+            attributes: PrimitiveAssetAttributes {
+                position,
+                normal: vec![[0.0, 0.0, 1.0]; 2],
+                tangent: Vec::new(),
+                tex_coord: Vec::new(),
+                color: Vec::new(),
+                joints: vec![vec![[0, 0, 0, 0]; 3]],
+                weights: vec![vec![[1.0, 0.0, 0.0, 0.0]; 3]],
+            },
+            indices: Some(indices),
+            material: None,
+            mode: PrimitiveAssetMode::TriangleList,
+            targets: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let location = "Scene 0 / Node \"node\" / Primitive 0";
+        let issues = validate(&model(primitive(
+            vec![[0.0, 0.0, 0.0], [1.0, f32::NAN, 0.0], [0.0, 1.0, 0.0]],
+            vec![0, 1, 3],
+        )));
+        assert_eq!(
+            issues,
+            [
+                Issue::MissingFile(String::from("texture.png")),
+                Issue::AttributeLength {
+                    location: String::from(location),
+                    attribute: "Normal",
+                    length: 2,
+                    expected: 3,
+                },
+                Issue::NonFinite(String::from(location)),
+                Issue::IndexOutOfRange {
+                    location: String::from(location),
+                    index: 3,
+                    count: 3,
+                },
+                Issue::MissingSkin(String::from(location)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use std::{
+    borrow::Cow,
+    fs, io,
+    marker::PhantomData,
+    path::{Component, Path, PathBuf},
+};
+
+use super::{Archive, Entry};
+
+pub struct DirEntry<'a> {
+    name: String,
+    path: PathBuf,
+    _marker: PhantomData<&'a ()>,
+}
+
+impl<'a> Entry<'a> for DirEntry<'a> {
+    type Error = io::Error;
+
+    fn name(&self) -> Result<Cow<'_, str>, Self::Error> {
+        Ok(Cow::Borrowed(&self.name))
+    }
+
+    fn unpack(&mut self) -> Result<Vec<u8>, Self::Error> {
+        fs::read(&self.path)
+    }
+}
+
+/// Files in a directory of the file-system.
+///
+/// Paths are always resolved inside the directory, parent components are
+/// rejected and absolute paths are treated as relative ones.
+pub struct DirArchive(PathBuf);
+
+impl DirArchive {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self(path.into())
+    }
+}
+
+impl Archive<PathBuf> for DirArchive {
+    type Error = io::Error;
+
+    type Entry<'a> = DirEntry<'a>;
+
+    fn new(stream: PathBuf) -> Result<Self, Self::Error> {
+        Ok(Self(stream))
+    }
+
+    fn by_path<P: AsRef<Path>>(&mut self, name: P) -> Result<Option<Self::Entry<'_>>, Self::Error> {
+        let mut path = self.0.clone();
+        for component in name.as_ref().components() {
+            match component {
+                Component::Normal(component) => path.push(component),
+                Component::CurDir | Component::RootDir | Component::Prefix(_) => (),
+                Component::ParentDir => return Ok(None),
+            }
+        }
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Ok(Some(DirEntry {
+            name: name.as_ref().to_string_lossy().into_owned(),
+            path,
+            _marker: PhantomData,
+        }))
+    }
+}
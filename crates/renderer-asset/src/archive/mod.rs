@@ -5,6 +5,7 @@ use std::{
     path::Path,
 };
 
+pub mod dir;
 #[cfg(feature = "tar")]
 pub mod tar;
 #[cfg(feature = "xp3")]
@@ -2,11 +2,11 @@ use std::{
     borrow::Cow,
     error::Error,
     fmt::{self, Display, Formatter},
-    io::{self, Cursor, Read, Seek},
+    io::{self, Cursor, Read, Seek, SeekFrom},
     path::Path,
 };
 
-use tar::{Archive as TarArchive, Entry as TarEntry};
+use tar::Entry as TarEntry;
 
 use super::{Archive, Entry};
 
@@ -60,6 +60,10 @@ impl<'a, R: Read> Entry<'a> for TarEntry<'a, R> {
     }
 }
 
+/// Tar entries are read sequentially, so the stream is rewound before every
+/// lookup.
+pub struct TarArchive<R: Read + Seek>(Option<tar::Archive<R>>);
+
 impl<R: Read + Seek> Archive<R> for TarArchive<R> {
     type Error = TarError;
 
@@ -68,11 +72,19 @@ impl<R: Read + Seek> Archive<R> for TarArchive<R> {
         Self: 'a;
 
     fn new(stream: R) -> Result<Self, Self::Error> {
-        Ok(TarArchive::new(stream))
+        Ok(Self(Some(tar::Archive::new(stream))))
     }
 
     fn by_path<P: AsRef<Path>>(&mut self, name: P) -> Result<Option<Self::Entry<'_>>, Self::Error> {
-        for entry in self.entries_with_seek()? {
+        let mut stream = self
+            .0
+            .take()
+            .expect("Tar archive is not restored")
+            .into_inner();
+        let rewind = stream.seek(SeekFrom::Start(0));
+        let archive = self.0.insert(tar::Archive::new(stream));
+        rewind?;
+        for entry in archive.entries_with_seek()? {
             let entry = entry?;
             if entry.path()? == name.as_ref() {
                 return Ok(Some(entry));
@@ -81,3 +93,29 @@ impl<R: Read + Seek> Archive<R> for TarArchive<R> {
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::TarArchive;
+    use crate::archive::{Archive, Entry};
+
+    #[test]
+    fn test_repeated_lookup() {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, data) in [("a.txt", b"a"), ("b.txt", b"b")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, path, &data[..]).unwrap();
+        }
+        let tar = builder.into_inner().unwrap();
+        let mut archive = TarArchive::new(Cursor::new(tar)).unwrap();
+        for (path, data) in [("b.txt", b"b"), ("a.txt", b"a"), ("b.txt", b"b")] {
+            let mut entry = archive.by_path(path).unwrap().unwrap();
+            assert_eq!(Entry::unpack(&mut entry).unwrap(), data);
+        }
+        assert!(archive.by_path("c.txt").unwrap().is_none());
+    }
+}
@@ -0,0 +1,1542 @@
+//! Export scenes to GLB, or to GLTF with a separate binary buffer.
+//!
+//! Materials other than PBR, unlit and MToon are approximated with PBR
+//! materials. Textures are re-encoded as PNG.
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use glam::Mat4;
+use gltf::{
+    binary::{Glb, Header},
+    json::{
+        self,
+        accessor::{ComponentType, GenericComponentType, Type},
+        animation::{Interpolation, Property},
+        buffer::Target,
+        extensions,
+        image::MimeType,
+        material::{AlphaCutoff, AlphaMode, EmissiveFactor, PbrBaseColorFactor, StrengthFactor},
+        mesh::{Mode, MorphTarget, Semantic},
+        scene::UnitQuaternion,
+        texture::{MagFilter, MinFilter, WrappingMode},
+        validation::{Checked, USize64},
+        Index, Value,
+    },
+};
+use image::{codecs::png::PngEncoder, ExtendedColorType, ImageEncoder, ImageError};
+
+use crate::{
+    animation::{AnimationAsset, AnimationKeyFrame, AnimationKeyFrames, AnimationSampler},
+    camera::{CameraAsset, CameraProjectionAsset},
+    index::AssetIndex,
+    material::{MaterialAlphaMode, MaterialAsset, MaterialAssetData, OutlineWidthMode},
+    mesh::MeshAsset,
+    node::{NodeAsset, NodeTransform},
+    primitive::{PrimitiveAsset, PrimitiveAssetMode},
+    scene::SceneAsset,
+    skin::SkinAsset,
+    texture::{
+        NormalTextureInfo, ShadingShiftTextureInfo, TextureAsset, TextureAssetFormat, TextureInfo,
+        TextureMagFilter, TextureMinFilter, TextureMipmapFilter, TextureWrappingMode,
+    },
+};
+
+#[derive(Debug)]
+pub enum GltfExporterError {
+    Gltf(gltf::Error),
+    Json(json::Error),
+    Image(ImageError),
+    UnknownNode(AssetIndex),
+    TooLarge,
+}
+
+impl Display for GltfExporterError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            GltfExporterError::Gltf(error) => Display::fmt(error, f),
+            GltfExporterError::Json(error) => Display::fmt(error, f),
+            GltfExporterError::Image(error) => write!(f, "Failed to encode texture: {}", error),
+            GltfExporterError::UnknownNode(id) => {
+                write!(f, "Node {:?} is not in the exported scenes", id)
+            }
+            GltfExporterError::TooLarge => write!(f, "Exported model is too large"),
+        }
+    }
+}
+
+impl Error for GltfExporterError {}
+
+impl From<gltf::Error> for GltfExporterError {
+    fn from(value: gltf::Error) -> Self {
+        Self::Gltf(value)
+    }
+}
+
+impl From<json::Error> for GltfExporterError {
+    fn from(value: json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+
+impl From<ImageError> for GltfExporterError {
+    fn from(value: ImageError) -> Self {
+        Self::Image(value)
+    }
+}
+
+const TEXTURE_TRANSFORM_EXTENSION: &str = "KHR_texture_transform";
+const MTOON_EXTENSION: &str = "VRMC_materials_mtoon";
+
+/// JSON object of the present fields.
+fn object<const N: usize>(fields: [(&str, Option<Value>); N]) -> Value {
+    Value::Object(
+        fields
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.to_string(), value?)))
+            .collect(),
+    )
+}
+
+fn vec_value<const N: usize>(value: [f32; N]) -> Value {
+    Value::from(value.to_vec())
+}
+
+fn encode_png(texture: &TextureAsset) -> Result<Vec<u8>, ImageError> {
+    let color_type = match texture.format {
+        TextureAssetFormat::Ru8 => ExtendedColorType::L8,
+        TextureAssetFormat::Rgu8 => ExtendedColorType::La8,
+        TextureAssetFormat::Rgbu8 => ExtendedColorType::Rgb8,
+        TextureAssetFormat::Rgbau8 => ExtendedColorType::Rgba8,
+        TextureAssetFormat::Ru16 => ExtendedColorType::L16,
+        TextureAssetFormat::Rgu16 => ExtendedColorType::La16,
+        TextureAssetFormat::Rgbu16 => ExtendedColorType::Rgb16,
+        TextureAssetFormat::Rgbau16 => ExtendedColorType::Rgba16,
+    };
+    let (width, height) = texture.size;
+    let mut data = Vec::new();
+    PngEncoder::new(&mut data).write_image(&texture.data, width, height, color_type)?;
+    Ok(data)
+}
+
+struct GltfExporter {
+    root: json::Root,
+    buffer: Vec<u8>,
+    extensions_used: BTreeSet<&'static str>,
+    node_indices: HashMap<AssetIndex, Index<json::Node>>,
+    texture_cache: HashMap<AssetIndex, Index<json::Texture>>,
+    material_cache: HashMap<AssetIndex, Index<json::Material>>,
+    skin_cache: HashMap<AssetIndex, Index<json::Skin>>,
+}
+
+impl GltfExporter {
+    fn new() -> Self {
+        Self {
+            root: json::Root::default(),
+            buffer: Vec::new(),
+            extensions_used: BTreeSet::new(),
+            node_indices: HashMap::new(),
+            texture_cache: HashMap::new(),
+            material_cache: HashMap::new(),
+            skin_cache: HashMap::new(),
+        }
+    }
+
+    fn push_view(&mut self, data: &[u8], target: Option<Target>) -> Index<json::buffer::View> {
+        // Keep every view aligned for any component type
+        while !self.buffer.len().is_multiple_of(4) {
+            self.buffer.push(0);
+        }
+        let offset = self.buffer.len();
+        self.buffer.extend_from_slice(data);
+        Index::push(
+            &mut self.root.buffer_views,
+            json::buffer::View {
+                buffer: Index::new(0),
+                byte_length: USize64::from(data.len()),
+                byte_offset: Some(USize64::from(offset)),
+                byte_stride: None,
+                name: None,
+                target: target.map(Checked::Valid),
+                extensions: None,
+                extras: Default::default(),
+            },
+        )
+    }
+
+    fn push_accessor(
+        &mut self,
+        data: &[u8],
+        count: usize,
+        component_type: ComponentType,
+        type_: Type,
+        bounds: Option<(Value, Value)>,
+        target: Option<Target>,
+    ) -> Index<json::Accessor> {
+        let view = self.push_view(data, target);
+        let (min, max) = bounds.unzip();
+        Index::push(
+            &mut self.root.accessors,
+            json::Accessor {
+                buffer_view: Some(view),
+                byte_offset: None,
+                count: USize64::from(count),
+                component_type: Checked::Valid(GenericComponentType(component_type)),
+                extensions: None,
+                extras: Default::default(),
+                type_: Checked::Valid(type_),
+                min,
+                max,
+                name: None,
+                normalized: false,
+                sparse: None,
+            },
+        )
+    }
+
+    /// Push float items, with bounds if required by the accessor usage.
+    fn push_f32<const N: usize>(
+        &mut self,
+        items: &[[f32; N]],
+        type_: Type,
+        bounds: bool,
+        target: Option<Target>,
+    ) -> Index<json::Accessor> {
+        let data: Vec<u8> = items
+            .iter()
+            .flatten()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let bounds = bounds.then(|| {
+            let mut min = [f32::INFINITY; N];
+            let mut max = [f32::NEG_INFINITY; N];
+            for item in items {
+                for (index, value) in item.iter().enumerate() {
+                    min[index] = min[index].min(*value);
+                    max[index] = max[index].max(*value);
+                }
+            }
+            (vec_value(min), vec_value(max))
+        });
+        self.push_accessor(
+            &data,
+            items.len(),
+            ComponentType::F32,
+            type_,
+            bounds,
+            target,
+        )
+    }
+
+    fn push_u16<const N: usize>(
+        &mut self,
+        items: &[[u16; N]],
+        type_: Type,
+        target: Option<Target>,
+    ) -> Index<json::Accessor> {
+        let data: Vec<u8> = items
+            .iter()
+            .flatten()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        self.push_accessor(&data, items.len(), ComponentType::U16, type_, None, target)
+    }
+
+    fn push_indices(&mut self, indices: &[u32]) -> Index<json::Accessor> {
+        let data: Vec<u8> = indices.iter().flat_map(|v| v.to_le_bytes()).collect();
+        self.push_accessor(
+            &data,
+            indices.len(),
+            ComponentType::U32,
+            Type::Scalar,
+            None,
+            Some(Target::ElementArrayBuffer),
+        )
+    }
+
+    fn node_index(&self, id: &AssetIndex) -> Result<Index<json::Node>, GltfExporterError> {
+        self.node_indices
+            .get(id)
+            .copied()
+            .ok_or_else(|| GltfExporterError::UnknownNode(id.clone()))
+    }
+
+    fn export_texture(
+        &mut self,
+        texture: &TextureAsset,
+    ) -> Result<Index<json::Texture>, GltfExporterError> {
+        if let Some(index) = self.texture_cache.get(&texture.id) {
+            return Ok(*index);
+        }
+
+        let data = encode_png(texture)?;
+        let view = self.push_view(&data, None);
+        let image = Index::push(
+            &mut self.root.images,
+            json::Image {
+                buffer_view: Some(view),
+                mime_type: Some(MimeType(String::from("image/png"))),
+                name: None,
+                uri: None,
+                extensions: None,
+                extras: Default::default(),
+            },
+        );
+
+        let sampler = &texture.sampler;
+        let min_filter = match (sampler.min_filter, sampler.mipmap_filter) {
+            (TextureMinFilter::Nearest, TextureMipmapFilter::Nearest) => {
+                MinFilter::NearestMipmapNearest
+            }
+            (TextureMinFilter::Nearest, TextureMipmapFilter::Linear) => {
+                MinFilter::NearestMipmapLinear
+            }
+            (TextureMinFilter::Linear, TextureMipmapFilter::Nearest) => {
+                MinFilter::LinearMipmapNearest
+            }
+            (TextureMinFilter::Linear, TextureMipmapFilter::Linear) => {
+                MinFilter::LinearMipmapLinear
+            }
+        };
+        fn wrapping_mode(mode: TextureWrappingMode) -> Checked<WrappingMode> {
+            Checked::Valid(match mode {
+                TextureWrappingMode::ClampToEdge => WrappingMode::ClampToEdge,
+                TextureWrappingMode::MirroredRepeat => WrappingMode::MirroredRepeat,
+                TextureWrappingMode::Repeat => WrappingMode::Repeat,
+            })
+        }
+        let sampler = Index::push(
+            &mut self.root.samplers,
+            json::texture::Sampler {
+                mag_filter: Some(Checked::Valid(match sampler.mag_filter {
+                    TextureMagFilter::Nearest => MagFilter::Nearest,
+                    TextureMagFilter::Linear => MagFilter::Linear,
+                })),
+                min_filter: Some(Checked::Valid(min_filter)),
+                name: None,
+                wrap_s: wrapping_mode(sampler.wrap_x),
+                wrap_t: wrapping_mode(sampler.wrap_y),
+                extensions: None,
+                extras: Default::default(),
+            },
+        );
+
+        let index = Index::push(
+            &mut self.root.textures,
+            json::Texture {
+                name: None,
+                sampler: Some(sampler),
+                source: image,
+                extensions: None,
+                extras: Default::default(),
+            },
+        );
+        self.texture_cache.insert(texture.id.clone(), index);
+        Ok(index)
+    }
+
+    fn export_texture_info(
+        &mut self,
+        info: &TextureInfo,
+    ) -> Result<json::texture::Info, GltfExporterError> {
+        let extensions = info.transform.as_ref().map(|transform| {
+            self.extensions_used.insert(TEXTURE_TRANSFORM_EXTENSION);
+            extensions::texture::Info {
+                texture_transform: Some(extensions::texture::TextureTransform {
+                    offset: extensions::texture::TextureTransformOffset(transform.offset),
+                    rotation: extensions::texture::TextureTransformRotation(transform.rotation),
+                    scale: extensions::texture::TextureTransformScale(transform.scale),
+                    tex_coord: transform.tex_coord.map(|tex_coord| tex_coord as u32),
+                    extras: Default::default(),
+                }),
+                others: Default::default(),
+            }
+        });
+        Ok(json::texture::Info {
+            index: self.export_texture(&info.texture)?,
+            tex_coord: info.tex_coord as u32,
+            extensions,
+            extras: Default::default(),
+        })
+    }
+
+    fn export_optional_texture_info(
+        &mut self,
+        info: &Option<TextureInfo>,
+    ) -> Result<Option<json::texture::Info>, GltfExporterError> {
+        info.as_ref()
+            .map(|info| self.export_texture_info(info))
+            .transpose()
+    }
+
+    /// Texture info inside extensions which are not known by `gltf` crate.
+    fn texture_info_value(
+        &mut self,
+        info: &Option<TextureInfo>,
+    ) -> Result<Option<Value>, GltfExporterError> {
+        Ok(self
+            .export_optional_texture_info(info)?
+            .map(json::serialize::to_value)
+            .transpose()?)
+    }
+
+    fn normal_texture_value(
+        &mut self,
+        info: &Option<NormalTextureInfo>,
+    ) -> Result<Option<Value>, GltfExporterError> {
+        info.as_ref()
+            .map(|info| {
+                Ok(object([
+                    (
+                        "index",
+                        Some(self.export_texture(&info.texture)?.value().into()),
+                    ),
+                    ("texCoord", Some(info.tex_coord.into())),
+                    ("scale", Some(info.scale.into())),
+                ]))
+            })
+            .transpose()
+    }
+
+    fn shading_shift_texture_value(
+        &mut self,
+        info: &Option<ShadingShiftTextureInfo>,
+    ) -> Result<Option<Value>, GltfExporterError> {
+        info.as_ref()
+            .map(|info| {
+                Ok(object([
+                    (
+                        "index",
+                        Some(self.export_texture(&info.texture)?.value().into()),
+                    ),
+                    ("texCoord", Some(info.tex_coord.into())),
+                    ("scale", Some(info.scale.into())),
+                ]))
+            })
+            .transpose()
+    }
+
+    fn export_pbr_extensions(
+        &mut self,
+        data: &MaterialAssetData,
+        extensions: &mut extensions::material::Material,
+    ) -> Result<(), GltfExporterError> {
+        let MaterialAssetData::Pbr {
+            ior,
+            clearcoat,
+            transmission,
+            sheen,
+            specular,
+            volume,
+            ..
+        } = data
+        else {
+            return Ok(());
+        };
+
+        if *ior != 1.5 {
+            self.extensions_used.insert("KHR_materials_ior");
+            extensions.ior = Some(extensions::material::Ior {
+                ior: extensions::material::IndexOfRefraction(*ior),
+                extras: Default::default(),
+            });
+        }
+        if let Some(clearcoat) = clearcoat {
+            self.extensions_used.insert("KHR_materials_clearcoat");
+            let value = object([
+                ("clearcoatFactor", Some(clearcoat.factor.into())),
+                (
+                    "clearcoatTexture",
+                    self.texture_info_value(&clearcoat.texture)?,
+                ),
+                (
+                    "clearcoatRoughnessFactor",
+                    Some(clearcoat.roughness_factor.into()),
+                ),
+                (
+                    "clearcoatRoughnessTexture",
+                    self.texture_info_value(&clearcoat.roughness_texture)?,
+                ),
+                (
+                    "clearcoatNormalTexture",
+                    self.normal_texture_value(&clearcoat.normal_texture)?,
+                ),
+            ]);
+            extensions
+                .others
+                .insert(String::from("KHR_materials_clearcoat"), value);
+        }
+        if let Some(transmission) = transmission {
+            self.extensions_used.insert("KHR_materials_transmission");
+            extensions.transmission = Some(extensions::material::Transmission {
+                transmission_factor: extensions::material::TransmissionFactor(transmission.factor),
+                transmission_texture: self.export_optional_texture_info(&transmission.texture)?,
+                extras: Default::default(),
+            });
+        }
+        if let Some(sheen) = sheen {
+            self.extensions_used.insert("KHR_materials_sheen");
+            let value = object([
+                ("sheenColorFactor", Some(vec_value(sheen.color_factor))),
+                (
+                    "sheenColorTexture",
+                    self.texture_info_value(&sheen.color_texture)?,
+                ),
+                ("sheenRoughnessFactor", Some(sheen.roughness_factor.into())),
+                (
+                    "sheenRoughnessTexture",
+                    self.texture_info_value(&sheen.roughness_texture)?,
+                ),
+            ]);
+            extensions
+                .others
+                .insert(String::from("KHR_materials_sheen"), value);
+        }
+        if let Some(specular) = specular {
+            self.extensions_used.insert("KHR_materials_specular");
+            extensions.specular = Some(extensions::material::Specular {
+                specular_factor: extensions::material::SpecularFactor(specular.factor),
+                specular_texture: self.export_optional_texture_info(&specular.texture)?,
+                specular_color_factor: extensions::material::SpecularColorFactor(
+                    specular.color_factor,
+                ),
+                specular_color_texture: self
+                    .export_optional_texture_info(&specular.color_texture)?,
+                extras: Default::default(),
+            });
+        }
+        if let Some(volume) = volume {
+            self.extensions_used.insert("KHR_materials_volume");
+            // An infinite attenuation distance is the default, and has no
+            // JSON representation
+            let attenuation_distance = volume
+                .attenuation_distance
+                .is_finite()
+                .then(|| volume.attenuation_distance.into());
+            let value = object([
+                ("thicknessFactor", Some(volume.thickness_factor.into())),
+                (
+                    "thicknessTexture",
+                    self.texture_info_value(&volume.thickness_texture)?,
+                ),
+                ("attenuationDistance", attenuation_distance),
+                (
+                    "attenuationColor",
+                    Some(vec_value(volume.attenuation_color)),
+                ),
+            ]);
+            extensions
+                .others
+                .insert(String::from("KHR_materials_volume"), value);
+        }
+        Ok(())
+    }
+
+    fn export_mtoon(&mut self, material: &MaterialAsset) -> Result<Value, GltfExporterError> {
+        let MaterialAssetData::MTone {
+            transparent_with_z_write,
+            render_queue_offset_number,
+            shade_color_factor,
+            shade_multiply_texture,
+            shading_shift_factor,
+            shading_shift_texture,
+            shading_toony_factor,
+            gi_equalization_factor,
+            matcap_factor,
+            matcap_texture,
+            parametric_rim_color_factor,
+            parametric_rim_fresnel_power_factor,
+            parametric_rim_lift_factor,
+            rim_multiply_texture,
+            rim_lighting_mix_factor,
+            outline_width_mode,
+            outline_width_factor,
+            outline_width_multiply_texture,
+            outline_color_factor,
+            outline_lighting_mix_factor,
+            ..
+        } = &material.data
+        else {
+            unreachable!("Not a MToon material");
+        };
+        let outline_width_mode = match outline_width_mode {
+            OutlineWidthMode::None => "none",
+            OutlineWidthMode::WorldCoordinates => "worldCoordinates",
+            OutlineWidthMode::ScreenCoordinates => "screenCoordinates",
+        };
+        let uv_animation = material.uv_animation.as_ref();
+        let uv_animation_mask_texture = uv_animation
+            .and_then(|uv_animation| uv_animation.mask_texture.clone())
+            .map(TextureInfo::from_texture);
+        Ok(object([
+            ("specVersion", Some("1.0".into())),
+            (
+                "transparentWithZWrite",
+                Some((*transparent_with_z_write).into()),
+            ),
+            (
+                "renderQueueOffsetNumber",
+                Some((*render_queue_offset_number as i64).into()),
+            ),
+            ("shadeColorFactor", Some(vec_value(*shade_color_factor))),
+            (
+                "shadeMultiplyTexture",
+                self.texture_info_value(shade_multiply_texture)?,
+            ),
+            ("shadingShiftFactor", Some((*shading_shift_factor).into())),
+            (
+                "shadingShiftTexture",
+                self.shading_shift_texture_value(shading_shift_texture)?,
+            ),
+            ("shadingToonyFactor", Some((*shading_toony_factor).into())),
+            (
+                "giEqualizationFactor",
+                Some((*gi_equalization_factor).into()),
+            ),
+            ("matcapFactor", Some(vec_value(*matcap_factor))),
+            ("matcapTexture", self.texture_info_value(matcap_texture)?),
+            (
+                "parametricRimColorFactor",
+                Some(vec_value(*parametric_rim_color_factor)),
+            ),
+            (
+                "parametricRimFresnelPowerFactor",
+                Some((*parametric_rim_fresnel_power_factor).into()),
+            ),
+            (
+                "parametricRimLiftFactor",
+                Some((*parametric_rim_lift_factor).into()),
+            ),
+            (
+                "rimMultiplyTexture",
+                self.texture_info_value(rim_multiply_texture)?,
+            ),
+            (
+                "rimLightingMixFactor",
+                Some((*rim_lighting_mix_factor).into()),
+            ),
+            ("outlineWidthMode", Some(outline_width_mode.into())),
+            ("outlineWidthFactor", Some((*outline_width_factor).into())),
+            (
+                "outlineWidthMultiplyTexture",
+                self.texture_info_value(outline_width_multiply_texture)?,
+            ),
+            ("outlineColorFactor", Some(vec_value(*outline_color_factor))),
+            (
+                "outlineLightingMixFactor",
+                Some((*outline_lighting_mix_factor).into()),
+            ),
+            (
+                "uvAnimationMaskTexture",
+                self.texture_info_value(&uv_animation_mask_texture)?,
+            ),
+            (
+                "uvAnimationScrollXSpeedFactor",
+                uv_animation.map(|uv_animation| uv_animation.scroll_x_speed_factor.into()),
+            ),
+            (
+                "uvAnimationScrollYSpeedFactor",
+                uv_animation.map(|uv_animation| uv_animation.scroll_y_speed_factor.into()),
+            ),
+            (
+                "uvAnimationRotationSpeedFactor",
+                uv_animation.map(|uv_animation| uv_animation.rotation_speed_factor.into()),
+            ),
+        ]))
+    }
+
+    fn export_material(
+        &mut self,
+        material: &MaterialAsset,
+    ) -> Result<Index<json::Material>, GltfExporterError> {
+        if let Some(index) = self.material_cache.get(&material.id) {
+            return Ok(*index);
+        }
+
+        let mut extensions = extensions::material::Material::default();
+        let mut double_sided = material.double_sided;
+        // Base color, base color texture, metallic and roughness
+        let (base_color_factor, base_color_texture, metallic_factor, roughness_factor) =
+            match &material.data {
+                MaterialAssetData::Pbr {
+                    base_color_factor,
+                    base_color_texture,
+                    metallic_factor,
+                    roughness_factor,
+                    ..
+                } => (
+                    *base_color_factor,
+                    base_color_texture,
+                    *metallic_factor,
+                    *roughness_factor,
+                ),
+                MaterialAssetData::Unlit {
+                    base_color_factor,
+                    base_color_texture,
+                } => {
+                    self.extensions_used.insert("KHR_materials_unlit");
+                    extensions.unlit = Some(extensions::material::Unlit {});
+                    (*base_color_factor, base_color_texture, 0.0, 1.0)
+                }
+                MaterialAssetData::MTone {
+                    base_color_factor,
+                    base_color_texture,
+                    ..
+                } => {
+                    self.extensions_used.insert(MTOON_EXTENSION);
+                    let mtoon = self.export_mtoon(material)?;
+                    extensions
+                        .others
+                        .insert(String::from(MTOON_EXTENSION), mtoon);
+                    (*base_color_factor, base_color_texture, 0.0, 1.0)
+                }
+                MaterialAssetData::BlinnPhong {
+                    diffuse_color,
+                    shininess,
+                    dissolve,
+                    diffuse_texture,
+                    ..
+                } => {
+                    let [r, g, b] = *diffuse_color;
+                    // Usual mapping between Phong exponent and GGX roughness
+                    let roughness = (2.0 / (shininess.max(0.0) + 2.0)).sqrt();
+                    (
+                        [r, g, b, dissolve.clamp(0.0, 1.0)],
+                        diffuse_texture,
+                        0.0,
+                        roughness,
+                    )
+                }
+                MaterialAssetData::Pmx {
+                    no_cull,
+                    diffuse_color,
+                    texture,
+                    ..
+                } => {
+                    double_sided |= *no_cull;
+                    (*diffuse_color, texture, 0.0, 1.0)
+                }
+            };
+        self.export_pbr_extensions(&material.data, &mut extensions)?;
+        if material.emissive_strength != 1.0 {
+            self.extensions_used
+                .insert("KHR_materials_emissive_strength");
+            extensions.emissive_strength = Some(extensions::material::EmissiveStrength {
+                emissive_strength: extensions::material::EmissiveStrengthFactor(
+                    material.emissive_strength,
+                ),
+            });
+        }
+
+        let (alpha_mode, alpha_cutoff) = match material.alpha_mode {
+            MaterialAlphaMode::Opaque => (AlphaMode::Opaque, None),
+            MaterialAlphaMode::Mask(cutoff) => (AlphaMode::Mask, Some(AlphaCutoff(cutoff))),
+            MaterialAlphaMode::Blend => (AlphaMode::Blend, None),
+        };
+        let pbr_metallic_roughness = json::material::PbrMetallicRoughness {
+            base_color_factor: PbrBaseColorFactor(base_color_factor),
+            base_color_texture: self.export_optional_texture_info(base_color_texture)?,
+            metallic_factor: StrengthFactor(metallic_factor),
+            roughness_factor: StrengthFactor(roughness_factor),
+            metallic_roughness_texture: match &material.data {
+                MaterialAssetData::Pbr {
+                    metallic_roughness_texture,
+                    ..
+                } => self.export_optional_texture_info(metallic_roughness_texture)?,
+                _ => None,
+            },
+            extensions: None,
+            extras: Default::default(),
+        };
+        let normal_texture = material
+            .normal_texture
+            .as_ref()
+            .map(|info| {
+                Ok::<_, GltfExporterError>(json::material::NormalTexture {
+                    index: self.export_texture(&info.texture)?,
+                    scale: info.scale,
+                    tex_coord: info.tex_coord as u32,
+                    extensions: None,
+                    extras: Default::default(),
+                })
+            })
+            .transpose()?;
+        let occlusion_texture = material
+            .occlusion_texture
+            .as_ref()
+            .map(|info| {
+                Ok::<_, GltfExporterError>(json::material::OcclusionTexture {
+                    index: self.export_texture(&info.texture)?,
+                    strength: StrengthFactor(info.strength),
+                    tex_coord: info.tex_coord as u32,
+                    extensions: None,
+                    extras: Default::default(),
+                })
+            })
+            .transpose()?;
+        let emissive_texture = self.export_optional_texture_info(&material.emissive_texture)?;
+
+        let index = Index::push(
+            &mut self.root.materials,
+            json::Material {
+                alpha_cutoff,
+                alpha_mode: Checked::Valid(alpha_mode),
+                double_sided,
+                name: material.name.clone(),
+                pbr_metallic_roughness,
+                normal_texture,
+                occlusion_texture,
+                emissive_texture,
+                emissive_factor: EmissiveFactor(material.emissive_factor),
+                extensions: Some(extensions),
+                extras: Default::default(),
+            },
+        );
+        self.material_cache.insert(material.id.clone(), index);
+        Ok(index)
+    }
+
+    fn export_primitive(
+        &mut self,
+        primitive: &PrimitiveAsset,
+    ) -> Result<json::mesh::Primitive, GltfExporterError> {
+        let vertex = Some(Target::ArrayBuffer);
+        let attributes = &primitive.attributes;
+        let mut semantics = BTreeMap::new();
+        let mut insert = |semantic, accessor| {
+            semantics.insert(Checked::Valid(semantic), accessor);
+        };
+        insert(
+            Semantic::Positions,
+            self.push_f32(&attributes.position, Type::Vec3, true, vertex),
+        );
+        if !attributes.normal.is_empty() {
+            insert(
+                Semantic::Normals,
+                self.push_f32(&attributes.normal, Type::Vec3, false, vertex),
+            );
+        }
+        if !attributes.tangent.is_empty() {
+            insert(
+                Semantic::Tangents,
+                self.push_f32(&attributes.tangent, Type::Vec4, false, vertex),
+            );
+        }
+        for (index, tex_coord) in attributes.tex_coord.iter().enumerate() {
+            insert(
+                Semantic::TexCoords(index as u32),
+                self.push_f32(tex_coord, Type::Vec2, false, vertex),
+            );
+        }
+        for (index, color) in attributes.color.iter().enumerate() {
+            insert(
+                Semantic::Colors(index as u32),
+                self.push_f32(color, Type::Vec4, false, vertex),
+            );
+        }
+        for (index, joints) in attributes.joints.iter().enumerate() {
+            insert(
+                Semantic::Joints(index as u32),
+                self.push_u16(joints, Type::Vec4, vertex),
+            );
+        }
+        for (index, weights) in attributes.weights.iter().enumerate() {
+            insert(
+                Semantic::Weights(index as u32),
+                self.push_f32(weights, Type::Vec4, false, vertex),
+            );
+        }
+
+        let targets = primitive
+            .targets
+            .iter()
+            .map(|target| MorphTarget {
+                positions: (!target.position.is_empty())
+                    .then(|| self.push_f32(&target.position, Type::Vec3, true, vertex)),
+                normals: (!target.normal.is_empty())
+                    .then(|| self.push_f32(&target.normal, Type::Vec3, false, vertex)),
+                tangents: (!target.tangent.is_empty())
+                    .then(|| self.push_f32(&target.tangent, Type::Vec3, false, vertex)),
+            })
+            .collect::<Vec<_>>();
+
+        Ok(json::mesh::Primitive {
+            attributes: semantics,
+            extensions: None,
+            extras: Default::default(),
+            indices: primitive
+                .indices
+                .as_ref()
+                .map(|indices| self.push_indices(indices)),
+            material: primitive
+                .material
+                .as_ref()
+                .map(|material| self.export_material(material))
+                .transpose()?,
+            mode: Checked::Valid(match primitive.mode {
+                PrimitiveAssetMode::Points => Mode::Points,
+                PrimitiveAssetMode::LineStrip => Mode::LineStrip,
+                PrimitiveAssetMode::LineList => Mode::Lines,
+                PrimitiveAssetMode::TriangleStrip => Mode::TriangleStrip,
+                PrimitiveAssetMode::TriangleList => Mode::Triangles,
+            }),
+            targets: (!targets.is_empty()).then_some(targets),
+        })
+    }
+
+    fn export_mesh(&mut self, mesh: &MeshAsset) -> Result<Index<json::Mesh>, GltfExporterError> {
+        let primitives = mesh
+            .primitives
+            .iter()
+            .map(|primitive| self.export_primitive(primitive))
+            .collect::<Result<_, _>>()?;
+        Ok(Index::push(
+            &mut self.root.meshes,
+            json::Mesh {
+                extensions: None,
+                extras: Default::default(),
+                name: mesh.name.clone(),
+                primitives,
+                weights: (!mesh.weights.is_empty()).then(|| mesh.weights.clone()),
+            },
+        ))
+    }
+
+    fn export_skin(&mut self, skin: &SkinAsset) -> Result<Index<json::Skin>, GltfExporterError> {
+        if let Some(index) = self.skin_cache.get(&skin.id) {
+            return Ok(*index);
+        }
+        let joints = skin
+            .joint_ids
+            .iter()
+            .map(|id| self.node_index(id))
+            .collect::<Result<_, _>>()?;
+        let skeleton = skin
+            .skeleton
+            .as_ref()
+            .map(|id| self.node_index(id))
+            .transpose()?;
+        let inverse_bind_matrices = (!skin.inverse_bind_matrices.is_empty()).then(|| {
+            let matrices: Vec<[f32; 16]> = skin
+                .inverse_bind_matrices
+                .iter()
+                .map(Mat4::to_cols_array)
+                .collect();
+            self.push_f32(&matrices, Type::Mat4, false, None)
+        });
+        let index = Index::push(
+            &mut self.root.skins,
+            json::Skin {
+                extensions: None,
+                extras: Default::default(),
+                inverse_bind_matrices,
+                joints,
+                name: None,
+                skeleton,
+            },
+        );
+        self.skin_cache.insert(skin.id.clone(), index);
+        Ok(index)
+    }
+
+    fn export_camera(&mut self, camera: &CameraAsset) -> Index<json::Camera> {
+        let (type_, orthographic, perspective) = match &camera.projection {
+            CameraProjectionAsset::Orthographic(orthographic) => (
+                json::camera::Type::Orthographic,
+                Some(json::camera::Orthographic {
+                    xmag: orthographic.xmag,
+                    ymag: orthographic.ymag,
+                    zfar: orthographic.zfar,
+                    znear: orthographic.znear,
+                    extensions: None,
+                    extras: Default::default(),
+                }),
+                None,
+            ),
+            CameraProjectionAsset::Perspective(perspective) => (
+                json::camera::Type::Perspective,
+                None,
+                Some(json::camera::Perspective {
+                    aspect_ratio: perspective.aspect_radio,
+                    yfov: perspective.yfov,
+                    zfar: perspective.zfar,
+                    znear: perspective.znear,
+                    extensions: None,
+                    extras: Default::default(),
+                }),
+            ),
+        };
+        Index::push(
+            &mut self.root.cameras,
+            json::Camera {
+                name: camera.name.clone(),
+                orthographic,
+                perspective,
+                type_: Checked::Valid(type_),
+                extensions: None,
+                extras: Default::default(),
+            },
+        )
+    }
+
+    /// Reserve indices of a node and its descendants, so skins and
+    /// animations can refer nodes anywhere in the scenes.
+    fn allocate_node<'a>(
+        &mut self,
+        nodes: &mut Vec<(&'a NodeAsset, Vec<Index<json::Node>>)>,
+        node: &'a NodeAsset,
+    ) -> Index<json::Node> {
+        let index = Index::push(&mut self.root.nodes, json::Node::default());
+        self.node_indices.entry(node.id.clone()).or_insert(index);
+        nodes.push((node, Vec::new()));
+        let position = nodes.len() - 1;
+        let children = node
+            .children
+            .iter()
+            .map(|child| self.allocate_node(nodes, child))
+            .collect();
+        nodes[position].1 = children;
+        index
+    }
+
+    fn export_node(
+        &mut self,
+        index: usize,
+        node: &NodeAsset,
+        children: Vec<Index<json::Node>>,
+    ) -> Result<(), GltfExporterError> {
+        let mesh = node
+            .mesh
+            .as_ref()
+            .map(|mesh| self.export_mesh(mesh))
+            .transpose()?;
+        let skin = node
+            .skin
+            .as_ref()
+            .map(|skin| self.export_skin(skin))
+            .transpose()?;
+        let camera = node
+            .camera
+            .as_ref()
+            .map(|camera| self.export_camera(camera));
+
+        let json_node = &mut self.root.nodes[index];
+        match &node.transform {
+            Some(NodeTransform::Matrix(matrix)) => {
+                json_node.matrix = Some(matrix.0.to_cols_array());
+            }
+            Some(NodeTransform::Decomposed(decomposed)) => {
+                json_node.translation = Some(decomposed.translation.to_array());
+                json_node.rotation = Some(UnitQuaternion(decomposed.rotation.to_array()));
+                json_node.scale = Some(decomposed.scale.to_array());
+            }
+            None => (),
+        }
+        json_node.name = node.name.clone();
+        json_node.mesh = mesh;
+        json_node.skin = skin;
+        json_node.camera = camera;
+        json_node.children = (!children.is_empty()).then_some(children);
+        json_node.weights = (!node.weights.is_empty()).then(|| node.weights.clone());
+        Ok(())
+    }
+
+    fn export_keyframes<T: fmt::Debug + Clone, const N: usize>(
+        &mut self,
+        keyframes: &AnimationKeyFrames<T>,
+        value: impl Fn(&T) -> [f32; N],
+        type_: Type,
+    ) -> (Index<json::Accessor>, Index<json::Accessor>, Interpolation) {
+        fn times<T: fmt::Debug + Clone>(keyframes: &[AnimationKeyFrame<T>]) -> Vec<[f32; 1]> {
+            keyframes.iter().map(|keyframe| [keyframe.time]).collect()
+        }
+        let (times, values, interpolation) = match keyframes {
+            AnimationKeyFrames::Linear(keyframes) => (
+                times(keyframes),
+                keyframes
+                    .iter()
+                    .map(|k| value(&k.value))
+                    .collect::<Vec<_>>(),
+                Interpolation::Linear,
+            ),
+            AnimationKeyFrames::Step(keyframes) => (
+                times(keyframes),
+                keyframes.iter().map(|k| value(&k.value)).collect(),
+                Interpolation::Step,
+            ),
+            AnimationKeyFrames::CubicSpline(keyframes) => (
+                times(keyframes),
+                keyframes
+                    .iter()
+                    .flat_map(|k| {
+                        let (in_tangent, point, out_tangent) = &k.value;
+                        [value(in_tangent), value(point), value(out_tangent)]
+                    })
+                    .collect(),
+                Interpolation::CubicSpline,
+            ),
+        };
+        let input = self.push_f32(&times, Type::Scalar, true, None);
+        let output = self.push_f32(&values, type_, false, None);
+        (input, output, interpolation)
+    }
+
+    fn export_animation(&mut self, animation: &AnimationAsset) -> Result<(), GltfExporterError> {
+        let mut channels = Vec::new();
+        let mut samplers = Vec::new();
+        for channel in &animation.channels {
+            let node = self.node_index(&channel.target_id)?;
+            let ((input, output, interpolation), path) = match &channel.sampler {
+                AnimationSampler::Rotation(keyframes) => (
+                    self.export_keyframes(keyframes, |value| *value, Type::Vec4),
+                    Property::Rotation,
+                ),
+                AnimationSampler::Translation(keyframes) => (
+                    self.export_keyframes(keyframes, |value| *value, Type::Vec3),
+                    Property::Translation,
+                ),
+                AnimationSampler::Scale(keyframes) => (
+                    self.export_keyframes(keyframes, |value| *value, Type::Vec3),
+                    Property::Scale,
+                ),
+            };
+            let sampler = Index::push(
+                &mut samplers,
+                json::animation::Sampler {
+                    extensions: None,
+                    extras: Default::default(),
+                    input,
+                    interpolation: Checked::Valid(interpolation),
+                    output,
+                },
+            );
+            channels.push(json::animation::Channel {
+                sampler,
+                target: json::animation::Target {
+                    extensions: None,
+                    extras: Default::default(),
+                    node,
+                    path: Checked::Valid(path),
+                },
+                extensions: None,
+                extras: Default::default(),
+            });
+        }
+        self.root.animations.push(json::Animation {
+            extensions: None,
+            extras: Default::default(),
+            channels,
+            name: animation.name.clone(),
+            samplers,
+        });
+        Ok(())
+    }
+
+    fn export(
+        mut self,
+        scenes: &[SceneAsset],
+        animations: &[AnimationAsset],
+        buffer_uri: Option<String>,
+    ) -> Result<(json::Root, Vec<u8>), GltfExporterError> {
+        let mut nodes = Vec::new();
+        for scene in scenes {
+            let roots = scene
+                .nodes
+                .iter()
+                .map(|node| self.allocate_node(&mut nodes, node))
+                .collect();
+            self.root.scenes.push(json::Scene {
+                extensions: None,
+                extras: Default::default(),
+                name: scene.name.clone(),
+                nodes: roots,
+            });
+        }
+        if !scenes.is_empty() {
+            self.root.scene = Some(Index::new(0));
+        }
+        for (index, (node, children)) in nodes.into_iter().enumerate() {
+            self.export_node(index, node, children)?;
+        }
+        for animation in animations {
+            self.export_animation(animation)?;
+        }
+
+        while !self.buffer.len().is_multiple_of(4) {
+            self.buffer.push(0);
+        }
+        if u32::try_from(self.buffer.len()).is_err() {
+            return Err(GltfExporterError::TooLarge);
+        }
+        if !self.buffer.is_empty() {
+            self.root.buffers.push(json::Buffer {
+                byte_length: USize64::from(self.buffer.len()),
+                name: None,
+                uri: buffer_uri,
+                extensions: None,
+                extras: Default::default(),
+            });
+        }
+        self.root.asset = json::Asset {
+            generator: Some(String::from(concat!(
+                env!("CARGO_PKG_NAME"),
+                " ",
+                env!("CARGO_PKG_VERSION")
+            ))),
+            ..Default::default()
+        };
+        self.root.extensions_used = self.extensions_used.into_iter().map(String::from).collect();
+        Ok((self.root, self.buffer))
+    }
+}
+
+/// Export scenes and animations as a GLB file.
+pub fn export_glb(
+    scenes: &[SceneAsset],
+    animations: &[AnimationAsset],
+) -> Result<Vec<u8>, GltfExporterError> {
+    let (root, buffer) = GltfExporter::new().export(scenes, animations, None)?;
+    let json = json::serialize::to_vec(&root)?;
+    let glb = Glb {
+        header: Header {
+            magic: *b"glTF",
+            version: 2,
+            // Calculated when writing
+            length: 0,
+        },
+        json: Cow::Owned(json),
+        bin: (!buffer.is_empty()).then_some(Cow::Owned(buffer)),
+    };
+    Ok(glb.to_vec()?)
+}
+
+/// Export scenes and animations as a GLTF file and its binary buffer, which
+/// should be saved to `buffer_uri` relative to the GLTF file.
+pub fn export_gltf(
+    scenes: &[SceneAsset],
+    animations: &[AnimationAsset],
+    buffer_uri: &str,
+) -> Result<(Vec<u8>, Vec<u8>), GltfExporterError> {
+    let (root, buffer) =
+        GltfExporter::new().export(scenes, animations, Some(buffer_uri.to_string()))?;
+    let json = json::serialize::to_vec_pretty(&root)?;
+    Ok((json, buffer))
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use glam::{Quat, Vec3};
+
+    use super::{export_glb, export_gltf};
+    use crate::{
+        animation::{
+            AnimationAsset, AnimationChannelAsset, AnimationKeyFrame, AnimationKeyFrames,
+            AnimationSampler,
+        },
+        index::{AssetIndex, BundleAssetType, BundleIndex},
+        loader::{gltf::load_glb_from_buffer_with_id, AssetLoadParams},
+        material::{
+            MaterialAlphaMode, MaterialAsset, MaterialAssetData, PbrClearcoat, PbrSheen,
+            PbrSpecular, PbrTransmission, PbrVolume,
+        },
+        mesh::MeshAsset,
+        node::{DecomposedTransform, NodeAsset, NodeTransform},
+        primitive::{PrimitiveAsset, PrimitiveAssetAttributes, PrimitiveAssetMode},
+        scene::SceneAsset,
+        skin::SkinAsset,
+        texture::{SamplerAsset, TextureAsset, TextureAssetFormat, TextureInfo},
+    };
+
+    fn id(index: usize) -> AssetIndex {
+        AssetIndex::BundleTypeIndex(BundleIndex([1; 32]), BundleAssetType::Node, index)
+    }
+
+    fn node(index: usize) -> NodeAsset {
+        NodeAsset {
+            id: id(index),
+            name: Some(format!("node{}", index)),
+            camera: None,
+            children: Vec::new(),
+            skin: None,
+            transform: None,
+            mesh: None,
+            weights: Vec::new(),
+        }
+    }
+
+    fn texture() -> Arc<TextureAsset> {
+        Arc::new(TextureAsset {
+            id: AssetIndex::BundlePath(BundleIndex([1; 32]), String::from("texture.png")),
+            size: (2, 1),
+            format: TextureAssetFormat::Rgbau8,
+            data: vec![255, 0, 0, 255, 0, 255, 0, 128],
+            sampler: SamplerAsset::default(),
+        })
+    }
+
+    fn material(data: MaterialAssetData) -> Arc<MaterialAsset> {
+        Arc::new(MaterialAsset {
+            id: AssetIndex::BundlePath(BundleIndex([1; 32]), String::from("material")),
+            name: Some(String::from("material")),
+            data,
+            normal_texture: None,
+            occlusion_texture: None,
+            emissive_texture: None,
+            emissive_factor: [0.0, 0.0, 0.0],
+            emissive_strength: 2.0,
+            alpha_mode: MaterialAlphaMode::Mask(0.25),
+            double_sided: false,
+            uv_animation: None,
+        })
+    }
+
+    /// A skinned triangle with a child joint, and an animation of the joint.
+    fn scene(material: Arc<MaterialAsset>) -> (SceneAsset, AnimationAsset) {
+        let primitive = PrimitiveAsset {
+            attributes: PrimitiveAssetAttributes {
+                position: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+                normal: vec![[0.0, 0.0, 1.0]; 3],
+                tangent: Vec::new(),
+                tex_coord: vec![vec![[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]],
+                color: Vec::new(),
+                joints: vec![vec![[0, 1, 0, 0]; 3]],
+                weights: vec![vec![[0.5, 0.5, 0.0, 0.0]; 3]],
+            },
+            indices: Some(vec![0, 1, 2]),
+            material: Some(material),
+            mode: PrimitiveAssetMode::TriangleList,
+            targets: Vec::new(),
+        };
+        let mut joint = node(1);
+        joint.transform = Some(NodeTransform::Decomposed(DecomposedTransform {
+            translation: Vec3::new(0.0, 1.0, 0.0),
+            rotation: Quat::IDENTITY,
+            scale: Vec3::ONE,
+        }));
+        let mut root = node(0);
+        root.children.push(joint);
+        root.skin = Some(Arc::new(SkinAsset {
+            id: AssetIndex::BundlePath(BundleIndex([1; 32]), String::from("skin")),
+            inverse_bind_matrices: vec![glam::Mat4::IDENTITY; 2],
+            joint_ids: vec![id(0), id(1)],
+            skeleton: Some(id(0)),
+        }));
+        root.mesh = Some(MeshAsset {
+            name: Some(String::from("triangle")),
+            primitives: vec![primitive],
+            weights: Vec::new(),
+        });
+        let scene = SceneAsset {
+            name: Some(String::from("scene")),
+            nodes: vec![root],
+        };
+        let animation = AnimationAsset {
+            name: Some(String::from("wave")),
+            channels: vec![AnimationChannelAsset {
+                sampler: AnimationSampler::Translation(AnimationKeyFrames::Linear(vec![
+                    AnimationKeyFrame {
+                        time: 0.0,
+                        value: [0.0, 1.0, 0.0],
+                    },
+                    AnimationKeyFrame {
+                        time: 1.0,
+                        value: [0.0, 2.0, 0.0],
+                    },
+                ])),
+                length: 1.0,
+                target_id: id(1),
+            }],
+        };
+        (scene, animation)
+    }
+
+    fn check(scenes: &[SceneAsset], animations: &[AnimationAsset]) {
+        let root = &scenes[0].nodes[0];
+        assert_eq!(root.name.as_deref(), Some("node0"));
+        assert_eq!(root.children[0].name.as_deref(), Some("node1"));
+        let Some(NodeTransform::Decomposed(transform)) = &root.children[0].transform else {
+            panic!("Joint transform is not decomposed");
+        };
+        assert_eq!(transform.translation, Vec3::new(0.0, 1.0, 0.0));
+
+        let skin = root.skin.as_ref().unwrap();
+        assert_eq!(
+            skin.joint_ids,
+            [root.id.clone(), root.children[0].id.clone()]
+        );
+        assert_eq!(skin.skeleton.as_ref(), Some(&root.id));
+
+        let primitive = &root.mesh.as_ref().unwrap().primitives[0];
+        assert_eq!(primitive.attributes.position[2], [0.0, 1.0, 0.0]);
+        assert_eq!(primitive.attributes.tex_coord[0][1], [1.0, 0.0]);
+        assert_eq!(primitive.attributes.joints[0][0], [0, 1, 0, 0]);
+        assert_eq!(primitive.indices.as_deref(), Some(&[0, 1, 2][..]));
+
+        let material = primitive.material.as_ref().unwrap();
+        assert_eq!(material.emissive_strength, 2.0);
+        assert!(matches!(material.alpha_mode, MaterialAlphaMode::Mask(cutoff) if cutoff == 0.25));
+        let MaterialAssetData::Pbr {
+            base_color_texture: Some(texture),
+            roughness_factor,
+            ..
+        } = &material.data
+        else {
+            panic!("Material is not PBR with a texture");
+        };
+        assert!((roughness_factor - 0.5).abs() < 1e-6);
+        assert_eq!(texture.texture.size, (2, 1));
+        assert_eq!(texture.texture.data, [255, 0, 0, 255, 0, 255, 0, 128]);
+
+        let channel = &animations[0].channels[0];
+        assert_eq!(channel.target_id, root.children[0].id);
+        let AnimationSampler::Translation(AnimationKeyFrames::Linear(keyframes)) = &channel.sampler
+        else {
+            panic!("Animation is not a linear translation");
+        };
+        assert_eq!(keyframes[1].time, 1.0);
+        assert_eq!(keyframes[1].value, [0.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn test_export_glb() {
+        let (scene, animation) = scene(material(MaterialAssetData::Pbr {
+            base_color_factor: [1.0, 1.0, 1.0, 1.0],
+            base_color_texture: Some(TextureInfo::from_texture(texture())),
+            metallic_factor: 0.0,
+            roughness_factor: 0.5,
+            metallic_roughness_texture: None,
+            ior: 1.5,
+            clearcoat: None,
+            transmission: None,
+            sheen: None,
+            specular: None,
+            volume: None,
+        }));
+        let glb = export_glb(&[scene], &[animation]).unwrap();
+        let (scenes, animations, _) =
+            load_glb_from_buffer_with_id(&glb, BundleIndex([2; 32]), &AssetLoadParams::default())
+                .unwrap();
+        check(&scenes, &animations);
+    }
+
+    #[test]
+    fn test_export_pbr_extensions() {
+        let (scene, animation) = scene(material(MaterialAssetData::Pbr {
+            base_color_factor: [1.0, 1.0, 1.0, 1.0],
+            base_color_texture: Some(TextureInfo::from_texture(texture())),
+            metallic_factor: 0.0,
+            roughness_factor: 0.5,
+            metallic_roughness_texture: None,
+            ior: 1.25,
+            clearcoat: Some(Box::new(PbrClearcoat {
+                factor: 0.5,
+                texture: Some(TextureInfo::from_texture(texture())),
+                roughness_factor: 0.25,
+                roughness_texture: None,
+                normal_texture: None,
+            })),
+            transmission: Some(Box::new(PbrTransmission {
+                factor: 0.75,
+                texture: None,
+            })),
+            sheen: Some(Box::new(PbrSheen {
+                color_factor: [1.0, 0.5, 0.0],
+                color_texture: None,
+                roughness_factor: 0.5,
+                roughness_texture: None,
+            })),
+            specular: Some(Box::new(PbrSpecular {
+                factor: 0.5,
+                texture: None,
+                color_factor: [0.0, 1.0, 0.0],
+                color_texture: None,
+            })),
+            // Not absorbing light
+            volume: Some(Box::new(PbrVolume {
+                thickness_factor: 0.5,
+                thickness_texture: None,
+                attenuation_distance: f32::INFINITY,
+                attenuation_color: [1.0, 1.0, 1.0],
+            })),
+        }));
+        let glb = export_glb(&[scene], &[animation]).unwrap();
+        let (scenes, animations, _) =
+            load_glb_from_buffer_with_id(&glb, BundleIndex([2; 32]), &AssetLoadParams::default())
+                .unwrap();
+        check(&scenes, &animations);
+
+        let primitive = &scenes[0].nodes[0].mesh.as_ref().unwrap().primitives[0];
+        let material = primitive.material.as_ref().unwrap();
+        let MaterialAssetData::Pbr {
+            ior,
+            clearcoat: Some(clearcoat),
+            transmission: Some(transmission),
+            sheen: Some(sheen),
+            specular: Some(specular),
+            volume: Some(volume),
+            ..
+        } = &material.data
+        else {
+            panic!("Material is missing PBR extensions");
+        };
+        assert_eq!(*ior, 1.25);
+        assert_eq!(clearcoat.factor, 0.5);
+        assert_eq!(clearcoat.roughness_factor, 0.25);
+        assert_eq!(clearcoat.texture.as_ref().unwrap().texture.size, (2, 1));
+        assert_eq!(transmission.factor, 0.75);
+        assert_eq!(sheen.color_factor, [1.0, 0.5, 0.0]);
+        assert_eq!(sheen.roughness_factor, 0.5);
+        assert_eq!(specular.factor, 0.5);
+        assert_eq!(specular.color_factor, [0.0, 1.0, 0.0]);
+        assert_eq!(volume.thickness_factor, 0.5);
+        assert_eq!(volume.attenuation_distance, f32::INFINITY);
+        assert_eq!(volume.attenuation_color, [1.0, 1.0, 1.0]);
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn test_export_gltf() {
+        use std::io::{Cursor, Write};
+
+        use crate::loader::gltf::load_gltf_from_archive;
+
+        // Shininess 6 maps to roughness 0.5
+        let (scene, animation) = scene(material(MaterialAssetData::BlinnPhong {
+            ambient_color: [0.0, 0.0, 0.0],
+            diffuse_color: [1.0, 1.0, 1.0],
+            specular_color: [0.0, 0.0, 0.0],
+            shininess: 6.0,
+            dissolve: 1.0,
+            optical_density: 1.0,
+            ambient_texture: None,
+            diffuse_texture: Some(TextureInfo::from_texture(texture())),
+            specular_texture: None,
+            shininess_texture: None,
+            dissolve_texture: None,
+        }));
+        let (gltf, bin) = export_gltf(&[scene], &[animation], "model.bin").unwrap();
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, data) in [("model.gltf", gltf), ("model.bin", bin)] {
+            writer
+                .start_file(path, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(&data).unwrap();
+        }
+        let zip = writer.finish().unwrap();
+        let mut archive = zip::ZipArchive::new(zip).unwrap();
+        let (scenes, animations, _) = load_gltf_from_archive(
+            &mut archive,
+            BundleIndex([2; 32]),
+            &AssetLoadParams::default(),
+        )
+        .unwrap();
+        check(&scenes, &animations);
+    }
+}
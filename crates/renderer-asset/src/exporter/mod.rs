@@ -0,0 +1,3 @@
+/// GLTF exporter with `gltf` crate.
+#[cfg(feature = "gltf")]
+pub mod gltf;
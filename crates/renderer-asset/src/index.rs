@@ -69,6 +69,8 @@ pub enum BundleAssetType {
     Skin,
     Texture,
     Material,
+    /// PMX bone, which isn't loaded as a node.
+    Bone,
 }
 
 impl Display for BundleAssetType {
@@ -78,6 +80,7 @@ impl Display for BundleAssetType {
             BundleAssetType::Texture => write!(f, "Texture"),
             BundleAssetType::Skin => write!(f, "Skin"),
             BundleAssetType::Material => write!(f, "Material"),
+            BundleAssetType::Bone => write!(f, "Bone"),
         }
     }
 }
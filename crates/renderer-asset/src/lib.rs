@@ -1,22 +1,29 @@
 //! Provide asset handling for renderer.
 //!
 //! This library provides node structure which is structured upon GLTF,
-//! and loaders for multiple formats such as GLTF, OBJ and PMX. This
-//! library also provides archive processor to isolate resource loading
-//! from file-system, allowing model and resource to be packed into an
-//! archive file to be loaded at once.
+//! and loaders for multiple formats such as GLTF, OBJ, PMX, FBX, PLY and
+//! STL. This library also provides archive processor to isolate resource
+//! loading from file-system, allowing model and resource to be packed into
+//! an archive file to be loaded at once, and a GLTF exporter to convert
+//! loaded models.
 //!
 pub mod animation;
 pub mod archive;
 pub mod camera;
+/// Model exporters
+pub mod exporter;
 pub mod index;
 /// Model loaders for various formats
 pub mod loader;
 pub mod material;
 pub mod mesh;
 pub mod node;
+pub mod normal;
+pub mod physics;
 pub mod primitive;
 pub mod scene;
 pub mod skin;
+pub mod spring_bone;
 pub mod tangent;
 pub mod texture;
+pub mod vrm;
@@ -0,0 +1,710 @@
+//! FBX 7.x node tree, parsed from binary or ASCII files.
+//!
+//! Both encodings are read into the same tree. Integer and float properties
+//! are widened to `i64` and `f64`, so the loader doesn't care about the
+//! exact type written by the exporter.
+
+use std::{
+    borrow::Cow,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io::{self, Read},
+};
+
+use flate2::read::ZlibDecoder;
+
+const BINARY_MAGIC: &[u8] = b"Kaydara FBX Binary  \0";
+const MIN_VERSION: u32 = 7000;
+/// Real files are less than 10 levels deep.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug)]
+pub enum FbxFormatError {
+    UnsupportedVersion(u32),
+    Truncated,
+    BadPropertyType(u8),
+    BadArray,
+    Zlib(io::Error),
+    TooDeep,
+    Syntax(usize),
+}
+
+impl Display for FbxFormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FbxFormatError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported FBX version: {}", version)
+            }
+            FbxFormatError::Truncated => write!(f, "Truncated FBX data"),
+            FbxFormatError::BadPropertyType(code) => {
+                write!(f, "Bad FBX property type: {:?}", *code as char)
+            }
+            FbxFormatError::BadArray => write!(f, "Bad FBX array"),
+            FbxFormatError::Zlib(error) => Display::fmt(error, f),
+            FbxFormatError::TooDeep => write!(f, "FBX nodes are nested too deeply"),
+            FbxFormatError::Syntax(line) => write!(f, "FBX syntax error at line {}", line),
+        }
+    }
+}
+
+impl Error for FbxFormatError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FbxProperty {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Raw(Vec<u8>),
+    IntegerArray(Vec<i64>),
+    FloatArray(Vec<f64>),
+}
+
+impl FbxProperty {
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            FbxProperty::Integer(value) => Some(*value),
+            FbxProperty::Bool(value) => Some(*value as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            FbxProperty::Float(value) => Some(*value),
+            FbxProperty::Integer(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            FbxProperty::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            FbxProperty::Raw(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64_array(&self) -> Option<&[i64]> {
+        match self {
+            FbxProperty::IntegerArray(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64_array(&self) -> Option<Cow<'_, [f64]>> {
+        match self {
+            FbxProperty::FloatArray(value) => Some(Cow::Borrowed(value)),
+            // ASCII arrays of whole numbers can't be told from integers
+            FbxProperty::IntegerArray(value) => Some(Cow::Owned(
+                value.iter().map(|value| *value as f64).collect(),
+            )),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FbxNode {
+    pub name: String,
+    pub properties: Vec<FbxProperty>,
+    pub children: Vec<FbxNode>,
+}
+
+impl FbxNode {
+    pub fn child(&self, name: &str) -> Option<&FbxNode> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a FbxNode> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    pub fn property(&self, index: usize) -> Option<&FbxProperty> {
+        self.properties.get(index)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FbxDocument {
+    pub version: u32,
+    pub nodes: Vec<FbxNode>,
+}
+
+impl FbxDocument {
+    pub fn node(&self, name: &str) -> Option<&FbxNode> {
+        self.nodes.iter().find(|node| node.name == name)
+    }
+}
+
+pub fn is_binary(data: &[u8]) -> bool {
+    data.starts_with(BINARY_MAGIC)
+}
+
+/// Parse a binary or ASCII FBX file.
+pub fn parse(data: &[u8]) -> Result<FbxDocument, FbxFormatError> {
+    let document = if is_binary(data) {
+        BinaryReader::new(data).read_document()?
+    } else {
+        AsciiReader::new(&String::from_utf8_lossy(data)).read_document()?
+    };
+    if document.version < MIN_VERSION {
+        return Err(FbxFormatError::UnsupportedVersion(document.version));
+    }
+    Ok(document)
+}
+
+struct BinaryReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    // Offsets and counts are 64 bit since 7.5
+    wide: bool,
+}
+
+impl<'a> BinaryReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            position: 0,
+            wide: false,
+        }
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], FbxFormatError> {
+        let bytes = self
+            .position
+            .checked_add(length)
+            .and_then(|end| self.data.get(self.position..end))
+            .ok_or(FbxFormatError::Truncated)?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], FbxFormatError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> Result<u32, FbxFormatError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn offset(&mut self) -> Result<usize, FbxFormatError> {
+        let value = if self.wide {
+            u64::from_le_bytes(self.array()?)
+        } else {
+            self.u32()? as u64
+        };
+        usize::try_from(value).map_err(|_| FbxFormatError::Truncated)
+    }
+
+    fn read_document(mut self) -> Result<FbxDocument, FbxFormatError> {
+        // Magic, then 0x1a 0x00
+        self.bytes(BINARY_MAGIC.len() + 2)?;
+        let version = self.u32()?;
+        self.wide = version >= 7500;
+        let mut nodes = Vec::new();
+        // The footer follows the null record ending the top level nodes
+        while let Some(node) = self.read_node(0)? {
+            nodes.push(node);
+        }
+        Ok(FbxDocument { version, nodes })
+    }
+
+    fn read_node(&mut self, depth: usize) -> Result<Option<FbxNode>, FbxFormatError> {
+        if depth > MAX_DEPTH {
+            return Err(FbxFormatError::TooDeep);
+        }
+        let end = self.offset()?;
+        let property_count = self.offset()?;
+        let _property_list_length = self.offset()?;
+        let name_length = self.array::<1>()?[0] as usize;
+        if end == 0 {
+            return Ok(None);
+        }
+        if end > self.data.len() || end < self.position {
+            return Err(FbxFormatError::Truncated);
+        }
+        let name = String::from_utf8_lossy(self.bytes(name_length)?).into_owned();
+
+        // Every property takes at least one byte
+        if property_count > end - self.position {
+            return Err(FbxFormatError::Truncated);
+        }
+        let properties = (0..property_count)
+            .map(|_| self.read_property())
+            .collect::<Result<_, _>>()?;
+        let mut children = Vec::new();
+        while self.position < end {
+            match self.read_node(depth + 1)? {
+                Some(child) => children.push(child),
+                None => break,
+            }
+        }
+        if self.position > end {
+            return Err(FbxFormatError::Truncated);
+        }
+        self.position = end;
+        Ok(Some(FbxNode {
+            name,
+            properties,
+            children,
+        }))
+    }
+
+    fn read_array<const N: usize, T>(
+        &mut self,
+        convert: impl Fn([u8; N]) -> T,
+    ) -> Result<Vec<T>, FbxFormatError> {
+        let count = self.u32()? as usize;
+        let encoding = self.u32()?;
+        let length = self.u32()? as usize;
+        let data = self.bytes(length)?;
+        let expected = count.checked_mul(N).ok_or(FbxFormatError::BadArray)?;
+        let data = match encoding {
+            0 => Cow::Borrowed(data),
+            1 => {
+                let mut decoded = Vec::new();
+                ZlibDecoder::new(data)
+                    .take(expected as u64 + 1)
+                    .read_to_end(&mut decoded)
+                    .map_err(FbxFormatError::Zlib)?;
+                Cow::Owned(decoded)
+            }
+            _ => return Err(FbxFormatError::BadArray),
+        };
+        if data.len() != expected {
+            return Err(FbxFormatError::BadArray);
+        }
+        Ok(data
+            .chunks_exact(N)
+            .map(|chunk| convert(chunk.try_into().unwrap()))
+            .collect())
+    }
+
+    fn read_property(&mut self) -> Result<FbxProperty, FbxFormatError> {
+        let code = self.array::<1>()?[0];
+        Ok(match code {
+            b'C' => FbxProperty::Bool(self.array::<1>()?[0] != 0),
+            b'Y' => FbxProperty::Integer(i16::from_le_bytes(self.array()?) as i64),
+            b'I' => FbxProperty::Integer(i32::from_le_bytes(self.array()?) as i64),
+            b'L' => FbxProperty::Integer(i64::from_le_bytes(self.array()?)),
+            b'F' => FbxProperty::Float(f32::from_le_bytes(self.array()?) as f64),
+            b'D' => FbxProperty::Float(f64::from_le_bytes(self.array()?)),
+            b'S' => {
+                let length = self.u32()? as usize;
+                FbxProperty::String(String::from_utf8_lossy(self.bytes(length)?).into_owned())
+            }
+            b'R' => {
+                let length = self.u32()? as usize;
+                FbxProperty::Raw(self.bytes(length)?.to_vec())
+            }
+            b'b' => FbxProperty::IntegerArray(self.read_array(|[value]: [u8; 1]| value as i64)?),
+            b'i' => FbxProperty::IntegerArray(
+                self.read_array(|value| i32::from_le_bytes(value) as i64)?,
+            ),
+            b'l' => FbxProperty::IntegerArray(self.read_array(i64::from_le_bytes)?),
+            b'f' => {
+                FbxProperty::FloatArray(self.read_array(|value| f32::from_le_bytes(value) as f64)?)
+            }
+            b'd' => FbxProperty::FloatArray(self.read_array(f64::from_le_bytes)?),
+            code => return Err(FbxFormatError::BadPropertyType(code)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token<'a> {
+    Key(&'a str),
+    Ident(&'a str),
+    String(&'a str),
+    Number(&'a str),
+    Star,
+    Comma,
+    Open,
+    Close,
+}
+
+struct AsciiReader<'a> {
+    tokens: Vec<(Token<'a>, usize)>,
+    position: usize,
+}
+
+impl<'a> AsciiReader<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            tokens: Self::tokenize(text),
+            position: 0,
+        }
+    }
+
+    fn tokenize(text: &'a str) -> Vec<(Token<'a>, usize)> {
+        let bytes = text.as_bytes();
+        let mut tokens = Vec::new();
+        let mut line = 1;
+        let mut index = 0;
+        while index < bytes.len() {
+            let start = index;
+            let token = match bytes[index] {
+                b'\n' => {
+                    line += 1;
+                    index += 1;
+                    continue;
+                }
+                byte if byte.is_ascii_whitespace() => {
+                    index += 1;
+                    continue;
+                }
+                b';' => {
+                    while index < bytes.len() && bytes[index] != b'\n' {
+                        index += 1;
+                    }
+                    continue;
+                }
+                b'{' => Token::Open,
+                b'}' => Token::Close,
+                b',' => Token::Comma,
+                b'*' => Token::Star,
+                b'"' => {
+                    index += 1;
+                    while index < bytes.len() && bytes[index] != b'"' {
+                        if bytes[index] == b'\n' {
+                            line += 1;
+                        }
+                        index += 1;
+                    }
+                    tokens.push((Token::String(&text[start + 1..index]), line));
+                    index += 1;
+                    continue;
+                }
+                byte if byte.is_ascii_digit() || matches!(byte, b'-' | b'+' | b'.') => {
+                    while index < bytes.len()
+                        && (bytes[index].is_ascii_alphanumeric()
+                            || matches!(bytes[index], b'-' | b'+' | b'.' | b'#'))
+                    {
+                        index += 1;
+                    }
+                    tokens.push((Token::Number(&text[start..index]), line));
+                    continue;
+                }
+                _ => {
+                    while index < bytes.len()
+                        && (bytes[index].is_ascii_alphanumeric()
+                            || matches!(bytes[index], b'_' | b'|'))
+                    {
+                        index += 1;
+                    }
+                    if index == start {
+                        // Unknown character, reported by the parser
+                        let length = text[start..].chars().next().map_or(1, char::len_utf8);
+                        tokens.push((Token::Ident(&text[start..start + length]), line));
+                        index += length;
+                        continue;
+                    }
+                    let word = &text[start..index];
+                    if bytes.get(index) == Some(&b':') {
+                        index += 1;
+                        tokens.push((Token::Key(word), line));
+                    } else {
+                        tokens.push((Token::Ident(word), line));
+                    }
+                    continue;
+                }
+            };
+            tokens.push((token, line));
+            index += 1;
+        }
+        tokens
+    }
+
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn error(&self) -> FbxFormatError {
+        let line = self
+            .tokens
+            .get(self.position)
+            .or(self.tokens.last())
+            .map_or(0, |(_, line)| *line);
+        FbxFormatError::Syntax(line)
+    }
+
+    fn next(&mut self) -> Result<Token<'a>, FbxFormatError> {
+        let token = self.peek().cloned().ok_or_else(|| self.error())?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token<'a>) -> Result<(), FbxFormatError> {
+        if self.peek() != Some(&expected) {
+            return Err(self.error());
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn read_document(mut self) -> Result<FbxDocument, FbxFormatError> {
+        let nodes = self.read_nodes(0)?;
+        if self.position < self.tokens.len() {
+            return Err(self.error());
+        }
+        let version = nodes
+            .iter()
+            .find(|node| node.name == "FBXHeaderExtension")
+            .and_then(|header| header.child("FBXVersion"))
+            .and_then(|version| version.property(0))
+            .and_then(FbxProperty::as_i64)
+            .map_or(7400, |version| version as u32);
+        Ok(FbxDocument { version, nodes })
+    }
+
+    fn read_nodes(&mut self, depth: usize) -> Result<Vec<FbxNode>, FbxFormatError> {
+        if depth > MAX_DEPTH {
+            return Err(FbxFormatError::TooDeep);
+        }
+        let mut nodes = Vec::new();
+        while let Some(Token::Key(name)) = self.peek().cloned() {
+            self.position += 1;
+            nodes.push(self.read_node(name, depth)?);
+        }
+        Ok(nodes)
+    }
+
+    fn read_node(&mut self, name: &str, depth: usize) -> Result<FbxNode, FbxFormatError> {
+        let mut properties = Vec::new();
+        while !matches!(
+            self.peek(),
+            None | Some(Token::Key(_) | Token::Open | Token::Close)
+        ) {
+            properties.push(self.read_property()?);
+            if self.peek() != Some(&Token::Comma) {
+                break;
+            }
+            self.position += 1;
+        }
+        let mut children = Vec::new();
+        if self.peek() == Some(&Token::Open) {
+            self.position += 1;
+            children = self.read_nodes(depth + 1)?;
+            self.expect(Token::Close)?;
+        }
+        Ok(FbxNode {
+            name: String::from(name),
+            properties,
+            children,
+        })
+    }
+
+    fn read_property(&mut self) -> Result<FbxProperty, FbxFormatError> {
+        Ok(match self.next()? {
+            Token::String(value) => FbxProperty::String(String::from(value)),
+            // Bare flags, such as `Shading: T`
+            Token::Ident(value) if value.chars().all(|c| c.is_ascii_alphabetic()) => {
+                FbxProperty::String(String::from(value))
+            }
+            Token::Number(value) => self.number(value)?,
+            Token::Star => {
+                self.next()?;
+                self.expect(Token::Open)?;
+                self.expect(Token::Key("a"))?;
+                let mut values = Vec::new();
+                while let Some(Token::Number(value)) = self.peek().cloned() {
+                    self.position += 1;
+                    values.push(self.number(value)?);
+                    if self.peek() != Some(&Token::Comma) {
+                        break;
+                    }
+                    self.position += 1;
+                }
+                self.expect(Token::Close)?;
+                if values
+                    .iter()
+                    .all(|value| matches!(value, FbxProperty::Integer(_)))
+                {
+                    FbxProperty::IntegerArray(
+                        values.iter().filter_map(FbxProperty::as_i64).collect(),
+                    )
+                } else {
+                    FbxProperty::FloatArray(values.iter().filter_map(FbxProperty::as_f64).collect())
+                }
+            }
+            _ => {
+                self.position -= 1;
+                return Err(self.error());
+            }
+        })
+    }
+
+    fn number(&self, value: &str) -> Result<FbxProperty, FbxFormatError> {
+        if let Ok(value) = value.parse() {
+            return Ok(FbxProperty::Integer(value));
+        }
+        match value.parse() {
+            Ok(value) => Ok(FbxProperty::Float(value)),
+            // Written by some exporters for infinities and NaNs
+            Err(_) if value.contains("#INF") => Ok(FbxProperty::Float(if value.starts_with('-') {
+                f64::NEG_INFINITY
+            } else {
+                f64::INFINITY
+            })),
+            Err(_) if value.contains('#') => Ok(FbxProperty::Float(f64::NAN)),
+            Err(_) => Err(FbxFormatError::Syntax(
+                self.tokens
+                    .get(self.position.saturating_sub(1))
+                    .map_or(0, |(_, line)| *line),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use flate2::{write::ZlibEncoder, Compression};
+
+    use super::{parse, FbxFormatError, FbxProperty, BINARY_MAGIC};
+
+    /// A binary node record, with 64 bit offsets.
+    fn node(
+        start: usize,
+        name: &str,
+        properties: &[u8],
+        children: &[&dyn Fn(usize) -> Vec<u8>],
+    ) -> Vec<u8> {
+        let header_length = 25 + name.len();
+        let mut body = properties.to_vec();
+        if !children.is_empty() {
+            for child in children {
+                let child = child(start + header_length + body.len());
+                body.extend(child);
+            }
+            body.extend([0; 25]);
+        }
+        let mut data = Vec::new();
+        data.extend(((start + header_length + body.len()) as u64).to_le_bytes());
+        let count = if properties.is_empty() { 0u64 } else { 1 };
+        data.extend(count.to_le_bytes());
+        data.extend((properties.len() as u64).to_le_bytes());
+        data.push(name.len() as u8);
+        data.extend(name.as_bytes());
+        data.extend(body);
+        data
+    }
+
+    #[test]
+    fn test_binary() {
+        let values = [1.0f64, 2.0, 3.0];
+        let raw: Vec<u8> = values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&raw).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut array = vec![b'd'];
+        array.extend(3u32.to_le_bytes());
+        array.extend(1u32.to_le_bytes());
+        array.extend((compressed.len() as u32).to_le_bytes());
+        array.extend(compressed);
+
+        let mut data = BINARY_MAGIC.to_vec();
+        data.extend([0x1a, 0]);
+        data.extend(7500u32.to_le_bytes());
+        let start = data.len();
+        let mut string = vec![b'S'];
+        string.extend(4u32.to_le_bytes());
+        string.extend(b"Mesh");
+        let vertices = |start| node(start, "Vertices", &array, &[]);
+        data.extend(node(start, "Geometry", &string, &[&vertices]));
+        data.extend([0; 25]);
+
+        let document = parse(&data).unwrap();
+        assert_eq!(document.version, 7500);
+        let geometry = document.node("Geometry").unwrap();
+        assert_eq!(
+            geometry.properties,
+            [FbxProperty::String(String::from("Mesh"))]
+        );
+        let vertices = geometry.child("Vertices").unwrap();
+        assert_eq!(
+            vertices.properties,
+            [FbxProperty::FloatArray(values.to_vec())]
+        );
+
+        // Cut inside the array
+        assert!(matches!(
+            parse(&data[..start + 60]),
+            Err(FbxFormatError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn test_ascii() {
+        let text = r#"; FBX 7.4.0 project file
+FBXHeaderExtension:  {
+    FBXVersion: 7400
+}
+Objects:  {
+    Geometry: 123, "Geometry::Cube", "Mesh" {
+        Vertices: *6 {
+            a: 0,0,1.5,
+            -2,1e1,0
+        }
+        PolygonVertexIndex: *3 {
+            a: 0,1,-3
+        }
+        Shading: T
+    }
+}
+"#;
+        let document = parse(text.as_bytes()).unwrap();
+        assert_eq!(document.version, 7400);
+        let geometry = document.node("Objects").unwrap().child("Geometry").unwrap();
+        assert_eq!(
+            geometry.properties,
+            [
+                FbxProperty::Integer(123),
+                FbxProperty::String(String::from("Geometry::Cube")),
+                FbxProperty::String(String::from("Mesh")),
+            ]
+        );
+        let vertices = geometry.child("Vertices").unwrap().property(0).unwrap();
+        assert_eq!(
+            vertices.as_f64_array().unwrap().as_ref(),
+            [0.0, 0.0, 1.5, -2.0, 10.0, 0.0]
+        );
+        let indices = geometry
+            .child("PolygonVertexIndex")
+            .unwrap()
+            .property(0)
+            .unwrap();
+        assert_eq!(indices.as_i64_array().unwrap(), [0, 1, -3]);
+        assert_eq!(
+            geometry.child("Shading").unwrap().properties,
+            [FbxProperty::String(String::from("T"))]
+        );
+
+        assert!(matches!(
+            parse(b"Objects: {\n Model: 1, {\n"),
+            Err(FbxFormatError::Syntax(_))
+        ));
+        assert!(matches!(
+            parse(b"FBXHeaderExtension: {\n FBXVersion: 6100\n}\n"),
+            Err(FbxFormatError::UnsupportedVersion(6100))
+        ));
+        // Characters which are not ASCII outside of strings
+        for text in [
+            "Objects: {\n é\n}\n".as_bytes(),
+            b"Objects: {\n \xff\xfe\n}\n",
+        ] {
+            assert!(matches!(parse(text), Err(FbxFormatError::Syntax(_))));
+        }
+    }
+}
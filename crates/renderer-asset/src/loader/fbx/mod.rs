@@ -0,0 +1,1441 @@
+//! Objects of FBX files, converted into scene assets.
+//!
+//! Models become nodes, mesh geometries become one primitive per material,
+//! Phong and Lambert materials become Blinn-Phong materials, skin clusters
+//! become skins and animation stacks become animations. Rotation and scaling
+//! pivots and offsets are ignored, geometric transforms are baked into the
+//! meshes, and animation curves are sampled linearly at their keys.
+//! Embedded textures are only read from binary files.
+
+pub mod format;
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    error::Error,
+    fmt::{self, Display, Formatter},
+    marker::PhantomData,
+    sync::Arc,
+};
+
+use glam::{DMat3, DMat4, DQuat, DVec3};
+use image::ImageError;
+
+use crate::{
+    animation::{
+        AnimationAsset, AnimationChannelAsset, AnimationKeyFrame, AnimationKeyFrames,
+        AnimationSampler,
+    },
+    archive::{Archive, Entry},
+    index::{AssetIndex, BundleAssetType, BundleIndex},
+    material::{MaterialAlphaMode, MaterialAsset, MaterialAssetData},
+    mesh::MeshAsset,
+    node::{DecomposedTransform, NodeAsset, NodeTransform},
+    primitive::{PrimitiveAsset, PrimitiveAssetAttributes, PrimitiveAssetMode},
+    scene::SceneAsset,
+    skin::SkinAsset,
+    texture::{NormalTextureInfo, SamplerAsset, TextureAsset, TextureInfo},
+};
+
+use self::format::{FbxDocument, FbxFormatError, FbxNode, FbxProperty};
+
+use super::{
+    texture::{TextureLoadError, TextureLoader},
+    AssetLoadParams,
+};
+
+/// Ticks of FBX time per second.
+const TICKS_PER_SECOND: f64 = 46_186_158_000.0;
+/// Models nested deeper are cut, as they are likely a connection cycle.
+const MAX_DEPTH: usize = 256;
+
+#[derive(Debug)]
+pub enum FbxLoadError<E> {
+    Io(E),
+    ModelNotFound(String),
+    Format(FbxFormatError),
+    Texture(TextureLoadError<E>),
+    MissingObjects,
+    BadGeometry(i64, &'static str),
+    TooManyJoints(i64),
+}
+
+impl<E: Display> Display for FbxLoadError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FbxLoadError::Io(err) => Display::fmt(err, f),
+            FbxLoadError::ModelNotFound(file_name) => {
+                write!(f, "File {} not found in bundle", file_name)
+            }
+            FbxLoadError::Format(err) => Display::fmt(err, f),
+            FbxLoadError::Texture(err) => Display::fmt(err, f),
+            FbxLoadError::MissingObjects => write!(f, "No objects in FBX file"),
+            FbxLoadError::BadGeometry(id, reason) => {
+                write!(f, "Bad geometry {}: {}", id, reason)
+            }
+            FbxLoadError::TooManyJoints(id) => write!(f, "Too many joints in skin {}", id),
+        }
+    }
+}
+
+impl<E: Error> Error for FbxLoadError<E> {}
+
+impl<E> From<FbxFormatError> for FbxLoadError<E> {
+    fn from(value: FbxFormatError) -> Self {
+        FbxLoadError::Format(value)
+    }
+}
+
+impl<E> From<TextureLoadError<E>> for FbxLoadError<E> {
+    fn from(value: TextureLoadError<E>) -> Self {
+        FbxLoadError::Texture(value)
+    }
+}
+
+impl<E> From<ImageError> for FbxLoadError<E> {
+    fn from(value: ImageError) -> Self {
+        FbxLoadError::Texture(TextureLoadError::Image(value))
+    }
+}
+
+/// The scene, and the animation stacks.
+pub type FbxLoadResult = (SceneAsset, Vec<AnimationAsset>);
+
+/// Name of an object, without its class.
+fn object_name(name: &str) -> &str {
+    // Binary files use "Name\0\x01Class", ASCII files "Class::Name"
+    if let Some((name, _)) = name.split_once("\0\u{1}") {
+        name
+    } else if let Some((_, name)) = name.split_once("::") {
+        name
+    } else {
+        name
+    }
+}
+
+fn property_vec3(values: &[FbxProperty]) -> Option<DVec3> {
+    match values {
+        [x, y, z, ..] => Some(DVec3::new(x.as_f64()?, y.as_f64()?, z.as_f64()?)),
+        _ => None,
+    }
+}
+
+fn property_matrix(node: &FbxNode, name: &str) -> Option<DMat4> {
+    let values = node.child(name)?.property(0)?.as_f64_array()?;
+    Some(DMat4::from_cols_slice(values.get(..16)?))
+}
+
+/// Values of `Properties70`, by property name.
+#[derive(Default)]
+struct Properties<'a>(HashMap<&'a str, &'a [FbxProperty]>);
+
+impl<'a> Properties<'a> {
+    fn new(node: &'a FbxNode) -> Self {
+        let Some(properties) = node.child("Properties70") else {
+            return Self::default();
+        };
+        Self(
+            properties
+                .children_named("P")
+                .filter_map(|property| {
+                    let name = property.property(0)?.as_str()?;
+                    // Name, type, label and flags come before values
+                    Some((name, property.properties.get(4..)?))
+                })
+                .collect(),
+        )
+    }
+
+    fn f64(&self, name: &str) -> Option<f64> {
+        self.0.get(name)?.first()?.as_f64()
+    }
+
+    fn i64(&self, name: &str) -> Option<i64> {
+        self.0.get(name)?.first()?.as_i64()
+    }
+
+    fn vec3(&self, name: &str) -> Option<DVec3> {
+        property_vec3(self.0.get(name)?)
+    }
+}
+
+struct Object<'a> {
+    node: &'a FbxNode,
+    name: &'a str,
+    // Such as "Mesh", "LimbNode", "Skin" or "Cluster"
+    kind: &'a str,
+}
+
+impl Object<'_> {
+    fn class(&self) -> &str {
+        &self.node.name
+    }
+}
+
+struct Connection<'a> {
+    id: i64,
+    property: Option<&'a str>,
+}
+
+/// Object graph of the file.
+#[derive(Default)]
+struct Objects<'a> {
+    objects: HashMap<i64, Object<'a>>,
+    // In the order of the file, which decides the order of materials
+    children: HashMap<i64, Vec<Connection<'a>>>,
+    parents: HashMap<i64, Vec<Connection<'a>>>,
+}
+
+impl<'a> Objects<'a> {
+    fn new(document: &'a FbxDocument) -> Self {
+        let mut objects = Self::default();
+        for node in document
+            .node("Objects")
+            .into_iter()
+            .flat_map(|objects| &objects.children)
+        {
+            let Some(id) = node.property(0).and_then(FbxProperty::as_i64) else {
+                continue;
+            };
+            let name = node
+                .property(1)
+                .and_then(FbxProperty::as_str)
+                .map_or("", object_name);
+            let kind = node.property(2).and_then(FbxProperty::as_str).unwrap_or("");
+            objects.objects.insert(id, Object { node, name, kind });
+        }
+        for connection in document
+            .node("Connections")
+            .into_iter()
+            .flat_map(|connections| connections.children_named("C"))
+        {
+            let (Some(child), Some(parent)) = (
+                connection.property(1).and_then(FbxProperty::as_i64),
+                connection.property(2).and_then(FbxProperty::as_i64),
+            ) else {
+                continue;
+            };
+            let property = connection.property(3).and_then(FbxProperty::as_str);
+            objects
+                .children
+                .entry(parent)
+                .or_default()
+                .push(Connection {
+                    id: child,
+                    property,
+                });
+            objects.parents.entry(child).or_default().push(Connection {
+                id: parent,
+                property,
+            });
+        }
+        objects
+    }
+
+    fn get(&self, id: i64) -> Option<&Object<'a>> {
+        self.objects.get(&id)
+    }
+
+    /// Connected objects of a class, with the connection property.
+    fn connected<'s>(
+        connections: Option<&'s Vec<Connection<'a>>>,
+        objects: &'s HashMap<i64, Object<'a>>,
+        class: &'s str,
+    ) -> impl Iterator<Item = (i64, &'s Object<'a>, Option<&'a str>)> + 's {
+        connections
+            .into_iter()
+            .flatten()
+            .filter_map(move |connection| {
+                let object = objects.get(&connection.id)?;
+                (object.class() == class).then_some((connection.id, object, connection.property))
+            })
+    }
+
+    fn children_of<'s>(
+        &'s self,
+        id: i64,
+        class: &'s str,
+    ) -> impl Iterator<Item = (i64, &'s Object<'a>, Option<&'a str>)> + 's {
+        Self::connected(self.children.get(&id), &self.objects, class)
+    }
+
+    fn parents_of<'s>(
+        &'s self,
+        id: i64,
+        class: &'s str,
+    ) -> impl Iterator<Item = (i64, &'s Object<'a>, Option<&'a str>)> + 's {
+        Self::connected(self.parents.get(&id), &self.objects, class)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Mapping {
+    PolygonVertex,
+    Vertex,
+    Polygon,
+    AllSame,
+}
+
+/// A layer element of a geometry, such as normals or UVs.
+struct LayerElement {
+    data: Vec<f64>,
+    indices: Option<Vec<i64>>,
+    mapping: Mapping,
+    components: usize,
+}
+
+impl LayerElement {
+    fn new(node: &FbxNode, data: &str, index: &str, components: usize) -> Option<Self> {
+        let mapping = match node
+            .child("MappingInformationType")?
+            .property(0)?
+            .as_str()?
+        {
+            "ByPolygonVertex" => Mapping::PolygonVertex,
+            "ByVertex" | "ByVertice" => Mapping::Vertex,
+            "ByPolygon" => Mapping::Polygon,
+            "AllSame" => Mapping::AllSame,
+            _ => return None,
+        };
+        let indices = match node
+            .child("ReferenceInformationType")?
+            .property(0)?
+            .as_str()?
+        {
+            "Direct" => None,
+            "IndexToDirect" | "Index" => {
+                Some(node.child(index)?.property(0)?.as_i64_array()?.to_vec())
+            }
+            _ => return None,
+        };
+        Some(Self {
+            data: node.child(data)?.property(0)?.as_f64_array()?.into_owned(),
+            indices,
+            mapping,
+            components,
+        })
+    }
+
+    fn get(&self, polygon_vertex: usize, vertex: usize, polygon: usize) -> Option<&[f64]> {
+        let index = match self.mapping {
+            Mapping::PolygonVertex => polygon_vertex,
+            Mapping::Vertex => vertex,
+            Mapping::Polygon => polygon,
+            Mapping::AllSame => 0,
+        };
+        let index = match &self.indices {
+            Some(indices) => usize::try_from(*indices.get(index)?).ok()?,
+            None => index,
+        };
+        let start = index.checked_mul(self.components)?;
+        self.data.get(start..start + self.components)
+    }
+}
+
+/// Joints and weights of every control point of a geometry.
+struct SkinData {
+    skin: Arc<SkinAsset>,
+    influences: Vec<([u16; 4], [f32; 4])>,
+}
+
+type MeshWithSkin = (MeshAsset, Option<Arc<SkinAsset>>);
+
+/// Keys of an animation curve, as time in seconds and value.
+type Curve = Vec<(f32, f32)>;
+
+/// Euler angles in degrees, in the FBX rotation order.
+fn euler_rotation(angles: DVec3, order: i64) -> DQuat {
+    let x = DQuat::from_rotation_x(angles.x.to_radians());
+    let y = DQuat::from_rotation_y(angles.y.to_radians());
+    let z = DQuat::from_rotation_z(angles.z.to_radians());
+    // Rotations of the first axis in the order are applied first
+    match order {
+        1 => y * z * x,
+        2 => x * z * y,
+        3 => z * x * y,
+        4 => y * x * z,
+        5 => x * y * z,
+        _ => z * y * x,
+    }
+}
+
+struct FbxLoader<'a, T, A: Archive<T>> {
+    id: BundleIndex,
+    bundle: &'a mut A,
+    objects: Objects<'a>,
+    texture_loader: TextureLoader,
+    // Indices of models, which are the indices of their nodes
+    model_indices: HashMap<i64, usize>,
+    material_cache: HashMap<i64, Arc<MaterialAsset>>,
+    skin_cache: HashMap<i64, Option<Arc<SkinData>>>,
+    // Axis and unit conversion applied to root nodes
+    root_rotation: DQuat,
+    root_scale: f64,
+    _marker: PhantomData<T>,
+}
+
+impl<'a, T, A: Archive<T>> FbxLoader<'a, T, A> {
+    fn new(id: BundleIndex, bundle: &'a mut A, document: &'a FbxDocument) -> Self {
+        let objects = Objects::new(document);
+        let model_indices = document
+            .node("Objects")
+            .into_iter()
+            .flat_map(|objects| objects.children_named("Model"))
+            .filter_map(|model| model.property(0)?.as_i64())
+            .enumerate()
+            .map(|(index, id)| (id, index))
+            .collect();
+
+        let settings = document
+            .node("GlobalSettings")
+            .map(Properties::new)
+            .unwrap_or_default();
+        let axis = |name: &str, sign: &str, default: usize| {
+            let axis = settings
+                .i64(name)
+                .and_then(|axis| usize::try_from(axis).ok())
+                .filter(|axis| *axis < 3)
+                .unwrap_or(default);
+            let sign = if settings.i64(sign).unwrap_or(1) < 0 {
+                -1.0
+            } else {
+                1.0
+            };
+            DVec3::AXES[axis] * sign
+        };
+        // Rows map the file axes to right, up and front of the scene
+        let rotation = DMat3::from_cols(
+            axis("CoordAxis", "CoordAxisSign", 0),
+            axis("UpAxis", "UpAxisSign", 1),
+            axis("FrontAxis", "FrontAxisSign", 2),
+        )
+        .transpose();
+        // Mirroring can't be expressed by node rotations
+        let root_rotation = if rotation.determinant() > 0.5 {
+            DQuat::from_mat3(&rotation)
+        } else {
+            DQuat::IDENTITY
+        };
+        // Units are centimeters by default, and scenes use meters
+        let root_scale = settings.f64("UnitScaleFactor").unwrap_or(1.0) / 100.0;
+
+        Self {
+            id,
+            bundle,
+            objects,
+            texture_loader: TextureLoader::default(),
+            model_indices,
+            material_cache: HashMap::new(),
+            skin_cache: HashMap::new(),
+            root_rotation,
+            root_scale,
+            _marker: PhantomData,
+        }
+    }
+
+    fn node_id(&self, model: i64) -> Option<AssetIndex> {
+        let index = *self.model_indices.get(&model)?;
+        Some(AssetIndex::BundleTypeIndex(
+            self.id.clone(),
+            BundleAssetType::Node,
+            index,
+        ))
+    }
+
+    fn is_root(&self, model: i64) -> bool {
+        self.objects.parents_of(model, "Model").next().is_none()
+    }
+
+    /// Local transform of a model, with the given translation, rotation and
+    /// scaling instead of the static ones.
+    fn local_transform(
+        &self,
+        model: i64,
+        properties: &Properties,
+        translation: DVec3,
+        rotation: DVec3,
+        scaling: DVec3,
+    ) -> (DVec3, DQuat, DVec3) {
+        let order = properties.i64("RotationOrder").unwrap_or(0);
+        let pre_rotation = properties
+            .vec3("PreRotation")
+            .map_or(DQuat::IDENTITY, |angles| euler_rotation(angles, 0));
+        let post_rotation = properties
+            .vec3("PostRotation")
+            .map_or(DQuat::IDENTITY, |angles| euler_rotation(angles, 0));
+        let rotation = pre_rotation * euler_rotation(rotation, order) * post_rotation.inverse();
+        if self.is_root(model) {
+            (
+                self.root_rotation * translation * self.root_scale,
+                self.root_rotation * rotation,
+                scaling * self.root_scale,
+            )
+        } else {
+            (translation, rotation, scaling)
+        }
+    }
+
+    fn static_transform(properties: &Properties) -> (DVec3, DVec3, DVec3) {
+        (
+            properties.vec3("Lcl Translation").unwrap_or(DVec3::ZERO),
+            properties.vec3("Lcl Rotation").unwrap_or(DVec3::ZERO),
+            properties.vec3("Lcl Scaling").unwrap_or(DVec3::ONE),
+        )
+    }
+
+    fn load_texture(
+        &mut self,
+        texture: &FbxNode,
+    ) -> Result<Option<Arc<TextureAsset>>, FbxLoadError<A::Error>> {
+        let file_name = |name: &str| {
+            texture
+                .child(name)
+                .and_then(|name| name.property(0))
+                .and_then(FbxProperty::as_str)
+                .filter(|name| !name.is_empty())
+                .map(|name| name.replace('\\', "/"))
+        };
+        let relative = file_name("RelativeFilename");
+        let absolute = file_name("FileName");
+        let Some(name) = relative.clone().or(absolute.clone()) else {
+            return Ok(None);
+        };
+        let id = AssetIndex::BundlePath(self.id.clone(), name.clone());
+
+        // Embedded in the video of the texture
+        let texture_id = texture.property(0).and_then(FbxProperty::as_i64);
+        let content = texture_id
+            .into_iter()
+            .flat_map(|id| self.objects.children_of(id, "Video"))
+            .find_map(|(_, video, _)| {
+                video
+                    .node
+                    .child("Content")
+                    .and_then(|content| content.property(0))
+                    .and_then(FbxProperty::as_bytes)
+                    .filter(|content| !content.is_empty())
+            });
+        if let Some(content) = content {
+            let texture =
+                self.texture_loader
+                    .load_from_buffer(id, content, SamplerAsset::default())?;
+            return Ok(Some(texture));
+        }
+
+        // Paths are often relative to the authoring machine, so the file
+        // name alone is tried as well
+        let base_name = |path: &String| path.rsplit('/').next().map(String::from);
+        let candidates: BTreeSet<String> = [relative.as_ref(), absolute.as_ref()]
+            .into_iter()
+            .flatten()
+            .filter_map(base_name)
+            .collect();
+        for path in relative.iter().chain(&candidates) {
+            let texture = self.texture_loader.load_from_archive(
+                id.clone(),
+                self.bundle,
+                path,
+                SamplerAsset::default(),
+            )?;
+            if texture.is_some() {
+                return Ok(texture);
+            }
+        }
+        Ok(None)
+    }
+
+    fn load_material(&mut self, id: i64) -> Result<Arc<MaterialAsset>, FbxLoadError<A::Error>> {
+        if let Some(material) = self.material_cache.get(&id) {
+            return Ok(material.clone());
+        }
+        let object = self.objects.get(id).unwrap();
+        let node = object.node;
+        let name = object.name;
+        let properties = Properties::new(node);
+        let lambert = node
+            .child("ShadingModel")
+            .and_then(|model| model.property(0))
+            .and_then(FbxProperty::as_str)
+            .is_some_and(|model| model.eq_ignore_ascii_case("lambert"));
+
+        let color = |name: &str, factor: &str, default: DVec3| {
+            let color = properties.vec3(name).unwrap_or(default);
+            (color * properties.f64(factor).unwrap_or(1.0))
+                .as_vec3()
+                .to_array()
+        };
+        let ambient_color = color("AmbientColor", "AmbientFactor", DVec3::ZERO);
+        let diffuse_color = color("DiffuseColor", "DiffuseFactor", DVec3::splat(0.8));
+        let (specular_color, shininess) = if lambert {
+            ([0.0; 3], 0.0)
+        } else {
+            (
+                color("SpecularColor", "SpecularFactor", DVec3::splat(0.2)),
+                properties
+                    .f64("ShininessExponent")
+                    .or_else(|| properties.f64("Shininess"))
+                    .unwrap_or(20.0) as f32,
+            )
+        };
+        let emissive_factor = color("EmissiveColor", "EmissiveFactor", DVec3::ZERO);
+        let dissolve = properties
+            .f64("Opacity")
+            .unwrap_or_else(|| 1.0 - properties.f64("TransparencyFactor").unwrap_or(0.0))
+            .clamp(0.0, 1.0) as f32;
+
+        let mut textures = HashMap::new();
+        let connected = self
+            .objects
+            .children_of(id, "Texture")
+            .filter_map(|(_, texture, property)| Some((property?, texture.node)))
+            .collect::<Vec<_>>();
+        for (property, texture) in connected {
+            let slot = match property {
+                "AmbientColor" => "ambient",
+                "DiffuseColor" => "diffuse",
+                "SpecularColor" | "SpecularFactor" => "specular",
+                "ShininessExponent" | "Shininess" => "shininess",
+                "TransparentColor" | "TransparencyFactor" => "dissolve",
+                "NormalMap" | "Bump" => "normal",
+                "EmissiveColor" | "EmissiveFactor" => "emissive",
+                _ => continue,
+            };
+            if textures.contains_key(slot) {
+                continue;
+            }
+            if let Some(texture) = self.load_texture(texture)? {
+                textures.insert(slot, texture);
+            }
+        }
+        let mut texture_info = |slot| textures.remove(slot).map(TextureInfo::from_texture);
+        let dissolve_texture = texture_info("dissolve");
+        let alpha_mode = if dissolve < 1.0 || dissolve_texture.is_some() {
+            MaterialAlphaMode::Blend
+        } else {
+            MaterialAlphaMode::Opaque
+        };
+
+        let index = self.material_cache.len();
+        let material = Arc::new(MaterialAsset {
+            id: AssetIndex::BundleTypeIndex(self.id.clone(), BundleAssetType::Material, index),
+            name: Some(String::from(name)),
+            data: MaterialAssetData::BlinnPhong {
+                ambient_color,
+                diffuse_color,
+                specular_color,
+                shininess,
+                dissolve,
+                optical_density: 1.0,
+                ambient_texture: texture_info("ambient"),
+                diffuse_texture: texture_info("diffuse"),
+                specular_texture: texture_info("specular"),
+                shininess_texture: texture_info("shininess"),
+                dissolve_texture,
+            },
+            normal_texture: textures
+                .remove("normal")
+                .map(NormalTextureInfo::from_texture),
+            occlusion_texture: None,
+            emissive_texture: textures.remove("emissive").map(TextureInfo::from_texture),
+            emissive_factor,
+            emissive_strength: 1.0,
+            alpha_mode,
+            double_sided: false,
+            uv_animation: None,
+        });
+        self.material_cache.insert(id, material.clone());
+        Ok(material)
+    }
+
+    /// Skin of a geometry, from the clusters of its skin deformer.
+    fn load_skin(
+        &mut self,
+        geometry: i64,
+        control_points: usize,
+    ) -> Result<Option<Arc<SkinData>>, FbxLoadError<A::Error>> {
+        if let Some(skin) = self.skin_cache.get(&geometry) {
+            return Ok(skin.clone());
+        }
+        let Some((skin_id, _, _)) = self
+            .objects
+            .children_of(geometry, "Deformer")
+            .find(|(_, deformer, _)| deformer.kind == "Skin")
+        else {
+            self.skin_cache.insert(geometry, None);
+            return Ok(None);
+        };
+
+        let mut joint_ids = Vec::new();
+        let mut inverse_bind_matrices = Vec::new();
+        let mut influences: Vec<Vec<(u16, f32)>> = vec![Vec::new(); control_points];
+        for (_, cluster, _) in self.objects.children_of(skin_id, "Deformer") {
+            if cluster.kind != "Cluster" {
+                continue;
+            }
+            let cluster_id = cluster.node.property(0).and_then(FbxProperty::as_i64);
+            let Some(bone) = cluster_id
+                .and_then(|id| self.objects.children_of(id, "Model").next())
+                .and_then(|(bone, _, _)| self.node_id(bone))
+            else {
+                continue;
+            };
+            let joint =
+                u16::try_from(joint_ids.len()).map_err(|_| FbxLoadError::TooManyJoints(skin_id))?;
+            let transform = property_matrix(cluster.node, "Transform").unwrap_or(DMat4::IDENTITY);
+            let transform_link =
+                property_matrix(cluster.node, "TransformLink").unwrap_or(DMat4::IDENTITY);
+            joint_ids.push(bone);
+            inverse_bind_matrices.push((transform_link.inverse() * transform).as_mat4());
+
+            let indices = cluster
+                .node
+                .child("Indexes")
+                .and_then(|indexes| indexes.property(0))
+                .and_then(FbxProperty::as_i64_array)
+                .unwrap_or_default();
+            let weights = cluster
+                .node
+                .child("Weights")
+                .and_then(|weights| weights.property(0))
+                .and_then(FbxProperty::as_f64_array)
+                .unwrap_or_default();
+            for (index, weight) in indices.iter().zip(weights.iter()) {
+                let influences = usize::try_from(*index)
+                    .ok()
+                    .and_then(|index| influences.get_mut(index))
+                    .ok_or(FbxLoadError::BadGeometry(
+                        geometry,
+                        "Bad skin cluster index",
+                    ))?;
+                influences.push((joint, *weight as f32));
+            }
+        }
+
+        // Keep the four strongest influences of every control point
+        let influences = influences
+            .into_iter()
+            .map(|mut influences| {
+                influences.sort_by(|a, b| b.1.total_cmp(&a.1));
+                influences.truncate(4);
+                let total: f32 = influences.iter().map(|(_, weight)| weight).sum();
+                let mut joints = [0; 4];
+                let mut weights = [0.0; 4];
+                for (index, (joint, weight)) in influences.into_iter().enumerate() {
+                    joints[index] = joint;
+                    weights[index] = if total > 0.0 { weight / total } else { 0.0 };
+                }
+                (joints, weights)
+            })
+            .collect();
+        let skin = Arc::new(SkinData {
+            skin: Arc::new(SkinAsset {
+                id: AssetIndex::BundleTypeIndex(
+                    self.id.clone(),
+                    BundleAssetType::Skin,
+                    self.skin_cache.len(),
+                ),
+                inverse_bind_matrices,
+                joint_ids,
+                skeleton: None,
+            }),
+            influences,
+        });
+        self.skin_cache.insert(geometry, Some(skin.clone()));
+        Ok(Some(skin))
+    }
+
+    fn load_mesh(
+        &mut self,
+        model: i64,
+        geometry_id: i64,
+        properties: &Properties,
+    ) -> Result<MeshWithSkin, FbxLoadError<A::Error>> {
+        let bad_geometry = |reason| FbxLoadError::BadGeometry(geometry_id, reason);
+        let geometry = self.objects.get(geometry_id).unwrap().node;
+        let vertices = geometry
+            .child("Vertices")
+            .and_then(|vertices| vertices.property(0))
+            .and_then(FbxProperty::as_f64_array)
+            .ok_or_else(|| bad_geometry("No vertices"))?;
+        let vertices: Vec<DVec3> = vertices.chunks_exact(3).map(DVec3::from_slice).collect();
+        let polygon_vertices = geometry
+            .child("PolygonVertexIndex")
+            .and_then(|indices| indices.property(0))
+            .and_then(FbxProperty::as_i64_array)
+            .ok_or_else(|| bad_geometry("No polygons"))?;
+
+        let layer = |name: &str, data: &str, index: &str, components: usize| {
+            geometry
+                .children_named(name)
+                .filter_map(|element| LayerElement::new(element, data, index, components))
+                .collect::<Vec<_>>()
+        };
+        let normals = layer("LayerElementNormal", "Normals", "NormalsIndex", 3);
+        let uvs = layer("LayerElementUV", "UV", "UVIndex", 2);
+        let colors = layer("LayerElementColor", "Colors", "ColorIndex", 4);
+        let material_indices = geometry.child("LayerElementMaterial").and_then(|element| {
+            let mapping = element
+                .child("MappingInformationType")?
+                .property(0)?
+                .as_str()?;
+            let indices = element.child("Materials")?.property(0)?.as_i64_array()?;
+            Some((mapping == "AllSame", indices))
+        });
+
+        // Geometric transforms only apply to the geometry, not to children
+        let geometric = DMat4::from_scale_rotation_translation(
+            properties.vec3("GeometricScaling").unwrap_or(DVec3::ONE),
+            euler_rotation(
+                properties.vec3("GeometricRotation").unwrap_or(DVec3::ZERO),
+                0,
+            ),
+            properties
+                .vec3("GeometricTranslation")
+                .unwrap_or(DVec3::ZERO),
+        );
+        let normal_matrix = DMat3::from_mat4(geometric).inverse().transpose();
+
+        let materials = self
+            .objects
+            .children_of(model, "Material")
+            .map(|(id, _, _)| id)
+            .collect::<Vec<_>>();
+        let skin = self.load_skin(geometry_id, vertices.len())?;
+
+        // Primitives by material, with vertices deduplicated by their values
+        struct Builder {
+            attributes: PrimitiveAssetAttributes,
+            indices: Vec<u32>,
+            vertex_indices: HashMap<Vec<u64>, u32>,
+        }
+        let mut builders: Vec<Option<Builder>> = Vec::new();
+        let mut polygon = Vec::new();
+        let mut polygon_index = 0;
+        for (polygon_vertex, index) in polygon_vertices.iter().enumerate() {
+            // The last vertex of a polygon is written as a bitwise not
+            let (vertex, last) = if *index < 0 {
+                (!*index, true)
+            } else {
+                (*index, false)
+            };
+            let vertex = usize::try_from(vertex)
+                .ok()
+                .filter(|vertex| *vertex < vertices.len())
+                .ok_or_else(|| bad_geometry("Vertex index out of range"))?;
+            polygon.push((polygon_vertex, vertex));
+            if !last {
+                continue;
+            }
+
+            let material = match material_indices {
+                Some((true, indices)) => indices.first().copied().unwrap_or(0),
+                Some((false, indices)) => indices.get(polygon_index).copied().unwrap_or(0),
+                None => 0,
+            };
+            let material = usize::try_from(material).unwrap_or(0);
+            if material >= materials.len().max(1) {
+                return Err(bad_geometry("Material index out of range"));
+            }
+            if builders.len() <= material {
+                builders.resize_with(material + 1, || None);
+            }
+            let builder = builders[material].get_or_insert_with(|| Builder {
+                attributes: PrimitiveAssetAttributes {
+                    position: Vec::new(),
+                    normal: Vec::new(),
+                    tangent: Vec::new(),
+                    tex_coord: vec![Vec::new(); uvs.len()],
+                    color: vec![Vec::new(); colors.len().min(1)],
+                    joints: vec![Vec::new(); skin.is_some() as usize],
+                    weights: vec![Vec::new(); skin.is_some() as usize],
+                },
+                indices: Vec::new(),
+                vertex_indices: HashMap::new(),
+            });
+
+            let mut corners = Vec::with_capacity(polygon.len());
+            for (polygon_vertex, vertex) in polygon.drain(..) {
+                let get = |element: &LayerElement| -> Result<_, FbxLoadError<A::Error>> {
+                    let value = element
+                        .get(polygon_vertex, vertex, polygon_index)
+                        .ok_or_else(|| bad_geometry("Layer element index out of range"))?;
+                    let mut padded = [0.0; 4];
+                    padded[..value.len()].copy_from_slice(value);
+                    Ok(padded)
+                };
+                let position = geometric.transform_point3(vertices[vertex]).as_vec3();
+                let normal = normals
+                    .first()
+                    .map(|normals| {
+                        get(normals).map(|normal| {
+                            (normal_matrix * DVec3::from_slice(&normal))
+                                .normalize_or_zero()
+                                .as_vec3()
+                        })
+                    })
+                    .transpose()?;
+                let tex_coord = uvs
+                    .iter()
+                    .map(|uvs| get(uvs).map(|uv| [uv[0] as f32, 1.0 - uv[1] as f32]))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let color = colors
+                    .first()
+                    .map(|colors| {
+                        get(colors).map(|color| [0, 1, 2, 3].map(|index| color[index] as f32))
+                    })
+                    .transpose()?;
+
+                let mut key = vec![vertex as u64];
+                key.extend(
+                    normal
+                        .iter()
+                        .flat_map(|normal| normal.to_array())
+                        .map(|v| v.to_bits() as u64),
+                );
+                key.extend(tex_coord.iter().flatten().map(|v| v.to_bits() as u64));
+                key.extend(color.iter().flatten().map(|v| v.to_bits() as u64));
+                let attributes = &mut builder.attributes;
+                let index = *builder.vertex_indices.entry(key).or_insert_with(|| {
+                    let index = attributes.position.len() as u32;
+                    attributes.position.push(position.to_array());
+                    if let Some(normal) = normal {
+                        attributes.normal.push(normal.to_array());
+                    }
+                    for (set, tex_coord) in tex_coord.into_iter().enumerate() {
+                        attributes.tex_coord[set].push(tex_coord);
+                    }
+                    if let Some(color) = color {
+                        attributes.color[0].push(color);
+                    }
+                    if let Some(skin) = &skin {
+                        let (joints, weights) = skin.influences[vertex];
+                        attributes.joints[0].push(joints);
+                        attributes.weights[0].push(weights);
+                    }
+                    index
+                });
+                corners.push(index);
+            }
+            // Polygons are convex in practice, so a fan is enough
+            for index in 1..corners.len().saturating_sub(1) {
+                builder
+                    .indices
+                    .extend([corners[0], corners[index], corners[index + 1]]);
+            }
+            polygon_index += 1;
+        }
+
+        let mut primitives = Vec::new();
+        for (index, builder) in builders.into_iter().enumerate() {
+            let Some(builder) = builder else {
+                continue;
+            };
+            let material = materials
+                .get(index)
+                .map(|material| self.load_material(*material))
+                .transpose()?;
+            let mut primitive = PrimitiveAsset {
+                attributes: builder.attributes,
+                indices: Some(builder.indices),
+                material,
+                mode: PrimitiveAssetMode::TriangleList,
+                targets: Vec::new(),
+            };
+            if primitive.attributes.normal.is_empty() {
+                primitive.generate_smooth_normals();
+            }
+            primitive.generate_tangents();
+            primitives.push(primitive);
+        }
+        let mesh = MeshAsset {
+            name: Some(String::from(self.objects.get(geometry_id).unwrap().name)),
+            primitives,
+            weights: Vec::new(),
+        };
+        Ok((mesh, skin.map(|skin| skin.skin.clone())))
+    }
+
+    fn load_node(&mut self, model: i64, depth: usize) -> Result<NodeAsset, FbxLoadError<A::Error>> {
+        let object = self.objects.get(model).unwrap();
+        let node = object.node;
+        let name = object.name;
+        let properties = Properties::new(node);
+        let (translation, rotation, scaling) = Self::static_transform(&properties);
+        let (translation, rotation, scale) =
+            self.local_transform(model, &properties, translation, rotation, scaling);
+
+        let geometry = self
+            .objects
+            .children_of(model, "Geometry")
+            .find(|(_, geometry, _)| geometry.kind == "Mesh")
+            .map(|(id, _, _)| id);
+        let (mesh, skin) = match geometry {
+            Some(geometry) => {
+                let (mesh, skin) = self.load_mesh(model, geometry, &properties)?;
+                (Some(mesh), skin)
+            }
+            None => (None, None),
+        };
+
+        let children = if depth < MAX_DEPTH {
+            let children = self
+                .objects
+                .children_of(model, "Model")
+                .map(|(id, _, _)| id)
+                .collect::<Vec<_>>();
+            children
+                .into_iter()
+                .map(|child| self.load_node(child, depth + 1))
+                .collect::<Result<_, _>>()?
+        } else {
+            Vec::new()
+        };
+
+        Ok(NodeAsset {
+            id: self.node_id(model).unwrap(),
+            name: Some(String::from(name)),
+            camera: None,
+            children,
+            skin,
+            transform: Some(NodeTransform::Decomposed(DecomposedTransform {
+                translation: translation.as_vec3(),
+                rotation: rotation.as_quat(),
+                scale: scale.as_vec3(),
+            })),
+            mesh,
+            weights: Vec::new(),
+        })
+    }
+
+    /// Keys of a curve, in seconds.
+    fn load_curve(curve: &FbxNode, start: i64) -> Curve {
+        let times = curve
+            .child("KeyTime")
+            .and_then(|times| times.property(0))
+            .and_then(FbxProperty::as_i64_array)
+            .unwrap_or_default();
+        let values = curve
+            .child("KeyValueFloat")
+            .and_then(|values| values.property(0))
+            .and_then(FbxProperty::as_f64_array)
+            .unwrap_or_default();
+        times
+            .iter()
+            .zip(values.iter())
+            .map(|(time, value)| {
+                let time = (time.saturating_sub(start) as f64 / TICKS_PER_SECOND) as f32;
+                (time, *value as f32)
+            })
+            .collect()
+    }
+
+    /// Value of a curve at a time, held before the first and after the last
+    /// key.
+    fn sample(curve: &[(f32, f32)], time: f32) -> Option<f32> {
+        let next = curve.partition_point(|(key, _)| *key < time);
+        match (
+            next.checked_sub(1).and_then(|index| curve.get(index)),
+            curve.get(next),
+        ) {
+            (Some((t0, v0)), Some((t1, v1))) if t1 > t0 => {
+                let factor = (time - t0) / (t1 - t0);
+                Some(v0 + (v1 - v0) * factor)
+            }
+            (_, Some((_, value))) | (Some((_, value)), None) => Some(*value),
+            (None, None) => None,
+        }
+    }
+
+    fn load_animation(&self, stack_id: i64) -> Option<AnimationAsset> {
+        let stack = self.objects.get(stack_id)?;
+        let start = Properties::new(stack.node).i64("LocalStart").unwrap_or(0);
+
+        // Curves of every model and property, from all layers
+        let mut targets: Vec<(i64, &str, [Curve; 3], DVec3)> = Vec::new();
+        for (layer, _, _) in self.objects.children_of(stack_id, "AnimationLayer") {
+            for (curve_node_id, curve_node, _) in
+                self.objects.children_of(layer, "AnimationCurveNode")
+            {
+                let Some((model, _, Some(property))) = self
+                    .objects
+                    .parents_of(curve_node_id, "Model")
+                    .find(|(_, _, property)| property.is_some())
+                else {
+                    continue;
+                };
+                if !matches!(property, "Lcl Translation" | "Lcl Rotation" | "Lcl Scaling")
+                    || targets
+                        .iter()
+                        .any(|(m, p, _, _)| *m == model && *p == property)
+                {
+                    continue;
+                }
+                let defaults = Properties::new(curve_node.node);
+                let mut curves = [Vec::new(), Vec::new(), Vec::new()];
+                let mut default = DVec3::ZERO;
+                for (axis, name) in ["d|X", "d|Y", "d|Z"].into_iter().enumerate() {
+                    default[axis] = defaults.f64(name).unwrap_or(f64::NAN);
+                    if let Some((_, curve, _)) = self
+                        .objects
+                        .children_of(curve_node_id, "AnimationCurve")
+                        .find(|(_, _, curve_property)| *curve_property == Some(name))
+                    {
+                        curves[axis] = Self::load_curve(curve.node, start);
+                    }
+                }
+                targets.push((model, property, curves, default));
+            }
+        }
+
+        let mut models = targets.iter().map(|(model, ..)| *model).collect::<Vec<_>>();
+        models.sort_unstable();
+        models.dedup();
+        let mut channels = Vec::new();
+        for model in models {
+            let Some(target_id) = self.node_id(model) else {
+                continue;
+            };
+            let properties = Properties::new(self.objects.get(model)?.node);
+            let static_transform = Self::static_transform(&properties);
+            for (_, property, curves, default) in
+                targets.iter().filter(|(target, ..)| *target == model)
+            {
+                let mut times = curves
+                    .iter()
+                    .flatten()
+                    .map(|(time, _)| *time)
+                    .collect::<Vec<_>>();
+                times.sort_by(f32::total_cmp);
+                times.dedup();
+                if times.is_empty() {
+                    continue;
+                }
+                let static_value = match *property {
+                    "Lcl Translation" => static_transform.0,
+                    "Lcl Rotation" => static_transform.1,
+                    _ => static_transform.2,
+                };
+                let value_at = |time: f32| {
+                    DVec3::from_array([0, 1, 2].map(|axis| {
+                        Self::sample(&curves[axis], time)
+                            .map(f64::from)
+                            .or(Some(default[axis]).filter(|value| !value.is_nan()))
+                            .unwrap_or(static_value[axis])
+                    }))
+                };
+                let transform_at = |time: f32| {
+                    let value = value_at(time);
+                    let (translation, rotation, scaling) = match *property {
+                        "Lcl Translation" => (value, static_transform.1, static_transform.2),
+                        "Lcl Rotation" => (static_transform.0, value, static_transform.2),
+                        _ => (static_transform.0, static_transform.1, value),
+                    };
+                    self.local_transform(model, &properties, translation, rotation, scaling)
+                };
+                let length = *times.last().unwrap();
+                let sampler = match *property {
+                    "Lcl Translation" => AnimationSampler::Translation(AnimationKeyFrames::Linear(
+                        times
+                            .iter()
+                            .map(|time| AnimationKeyFrame {
+                                time: *time,
+                                value: transform_at(*time).0.as_vec3().to_array(),
+                            })
+                            .collect(),
+                    )),
+                    "Lcl Rotation" => AnimationSampler::Rotation(AnimationKeyFrames::Linear(
+                        times
+                            .iter()
+                            .map(|time| AnimationKeyFrame {
+                                time: *time,
+                                value: transform_at(*time).1.as_quat().to_array(),
+                            })
+                            .collect(),
+                    )),
+                    _ => AnimationSampler::Scale(AnimationKeyFrames::Linear(
+                        times
+                            .iter()
+                            .map(|time| AnimationKeyFrame {
+                                time: *time,
+                                value: transform_at(*time).2.as_vec3().to_array(),
+                            })
+                            .collect(),
+                    )),
+                };
+                channels.push(AnimationChannelAsset {
+                    sampler,
+                    length,
+                    target_id: target_id.clone(),
+                });
+            }
+        }
+        Some(AnimationAsset {
+            name: Some(String::from(stack.name)),
+            channels,
+        })
+    }
+
+    fn load(&mut self, document: &FbxDocument) -> Result<FbxLoadResult, FbxLoadError<A::Error>> {
+        let objects = document
+            .node("Objects")
+            .ok_or(FbxLoadError::MissingObjects)?;
+        let roots = objects
+            .children_named("Model")
+            .filter_map(|model| model.property(0)?.as_i64())
+            .filter(|model| self.is_root(*model))
+            .collect::<Vec<_>>();
+        let nodes = roots
+            .into_iter()
+            .map(|root| self.load_node(root, 0))
+            .collect::<Result<_, _>>()?;
+        let animations = objects
+            .children_named("AnimationStack")
+            .filter_map(|stack| stack.property(0)?.as_i64())
+            .filter_map(|stack| self.load_animation(stack))
+            .collect();
+        Ok((SceneAsset { name: None, nodes }, animations))
+    }
+}
+
+pub fn load_bundle<T, A: Archive<T>>(
+    id: BundleIndex,
+    bundle: &mut A,
+    params: &AssetLoadParams,
+) -> Result<FbxLoadResult, FbxLoadError<A::Error>> {
+    let file_name = params.bundle_model_filename("fbx");
+
+    let mut file_entry = bundle
+        .by_path(&file_name)
+        .map_err(FbxLoadError::Io)?
+        .ok_or_else(|| FbxLoadError::ModelNotFound(file_name))?;
+    let file = file_entry.unpack().map_err(FbxLoadError::Io)?;
+    drop(file_entry);
+    let document = format::parse(&file)?;
+
+    let mut loader = FbxLoader::new(id, bundle, &document);
+    loader.load(&document)
+}
+
+#[cfg(all(test, feature = "zip"))]
+mod test {
+    use std::io::{Cursor, Write};
+
+    use glam::{Mat4, Vec3};
+    use image::{ImageFormat, Rgba, RgbaImage};
+
+    use super::load_bundle;
+    use crate::{
+        animation::{AnimationKeyFrames, AnimationSampler},
+        index::{AssetIndex, BundleAssetType, BundleIndex},
+        loader::AssetLoadParams,
+        material::{MaterialAlphaMode, MaterialAssetData},
+        node::NodeTransform,
+    };
+
+    const MODEL: &str = r#"; FBX 7.4.0 project file
+FBXHeaderExtension:  {
+    FBXVersion: 7400
+}
+GlobalSettings:  {
+    Properties70:  {
+        P: "UpAxis", "int", "Integer", "",1
+        P: "UnitScaleFactor", "double", "Number", "",100
+    }
+}
+Objects:  {
+    Model: 1, "Model::Root", "Null" {
+        Properties70:  {
+            P: "Lcl Translation", "Lcl Translation", "", "A",1,2,3
+        }
+    }
+    Model: 2, "Model::Body", "Mesh" {
+    }
+    Model: 3, "Model::Bone", "LimbNode" {
+    }
+    Geometry: 10, "Geometry::Quad", "Mesh" {
+        Vertices: *12 {
+            a: 0,0,0,1,0,0,1,1,0,0,1,0
+        }
+        PolygonVertexIndex: *4 {
+            a: 0,1,2,-4
+        }
+        LayerElementUV: 0 {
+            MappingInformationType: "ByPolygonVertex"
+            ReferenceInformationType: "IndexToDirect"
+            UV: *8 {
+                a: 0,0,1,0,1,1,0,1
+            }
+            UVIndex: *4 {
+                a: 0,1,2,3
+            }
+        }
+        LayerElementMaterial: 0 {
+            MappingInformationType: "AllSame"
+            ReferenceInformationType: "IndexToDirect"
+            Materials: *1 {
+                a: 0
+            }
+        }
+    }
+    Material: 20, "Material::Red", "" {
+        ShadingModel: "phong"
+        Properties70:  {
+            P: "DiffuseColor", "Color", "", "A",1,0,0
+            P: "Opacity", "double", "Number", "",0.5
+        }
+    }
+    Texture: 30, "Texture::Red", "" {
+        RelativeFilename: "textures\red.png"
+    }
+    Deformer: 40, "Deformer::Skin", "Skin" {
+    }
+    Deformer: 41, "SubDeformer::Cluster", "Cluster" {
+        Indexes: *4 {
+            a: 0,1,2,3
+        }
+        Weights: *4 {
+            a: 1,1,1,1
+        }
+        Transform: *16 {
+            a: 1,0,0,0,0,1,0,0,0,0,1,0,0,0,0,1
+        }
+        TransformLink: *16 {
+            a: 1,0,0,0,0,1,0,0,0,0,1,0,0,1,0,1
+        }
+    }
+    AnimationStack: 50, "AnimStack::Take", "" {
+    }
+    AnimationLayer: 51, "AnimLayer::Base", "" {
+    }
+    AnimationCurveNode: 52, "AnimCurveNode::T", "" {
+    }
+    AnimationCurve: 53, "AnimCurve::", "" {
+        KeyTime: *2 {
+            a: 0,46186158000
+        }
+        KeyValueFloat: *2 {
+            a: 1,5
+        }
+    }
+}
+Connections:  {
+    C: "OO",1,0
+    C: "OO",2,1
+    C: "OO",3,1
+    C: "OO",10,2
+    C: "OO",20,2
+    C: "OP",30,20, "DiffuseColor"
+    C: "OO",40,10
+    C: "OO",41,40
+    C: "OO",3,41
+    C: "OO",51,50
+    C: "OO",52,51
+    C: "OP",52,1, "Lcl Translation"
+    C: "OP",53,52, "d|X"
+}
+"#;
+
+    #[test]
+    fn test_load_bundle() {
+        let mut png = Cursor::new(Vec::new());
+        RgbaImage::from_pixel(1, 1, Rgba([255, 0, 0, 255]))
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        // The texture is found by its file name
+        for (path, data) in [("model.fbx", MODEL.as_bytes()), ("red.png", png.get_ref())] {
+            writer
+                .start_file(path, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+        let mut archive = zip::ZipArchive::new(writer.finish().unwrap()).unwrap();
+        let id = BundleIndex([0; 32]);
+        let (scene, animations) =
+            load_bundle(id.clone(), &mut archive, &AssetLoadParams::default()).unwrap();
+        let node_id = |index| AssetIndex::BundleTypeIndex(id.clone(), BundleAssetType::Node, index);
+
+        let [root] = &scene.nodes[..] else {
+            panic!("Expected a single root node");
+        };
+        assert_eq!(root.name.as_deref(), Some("Root"));
+        let Some(NodeTransform::Decomposed(transform)) = &root.transform else {
+            panic!("Expected a decomposed transform");
+        };
+        assert_eq!(transform.translation, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(root.children.len(), 2);
+
+        let body = &root.children[0];
+        let mesh = body.mesh.as_ref().unwrap();
+        let [primitive] = &mesh.primitives[..] else {
+            panic!("Expected a single primitive");
+        };
+        assert_eq!(primitive.indices.as_deref(), Some(&[0, 1, 2, 0, 2, 3][..]));
+        assert_eq!(primitive.attributes.position.len(), 4);
+        assert_eq!(primitive.attributes.normal[0], [0.0, 0.0, 1.0]);
+        assert_eq!(primitive.attributes.tex_coord[0][0], [0.0, 1.0]);
+        assert_eq!(primitive.attributes.weights[0][2], [1.0, 0.0, 0.0, 0.0]);
+
+        let material = primitive.material.as_ref().unwrap();
+        assert_eq!(material.name.as_deref(), Some("Red"));
+        assert!(matches!(material.alpha_mode, MaterialAlphaMode::Blend));
+        let MaterialAssetData::BlinnPhong {
+            diffuse_color,
+            dissolve,
+            diffuse_texture,
+            ..
+        } = &material.data
+        else {
+            panic!("Expected a Blinn-Phong material");
+        };
+        assert_eq!(*diffuse_color, [1.0, 0.0, 0.0]);
+        assert_eq!(*dissolve, 0.5);
+        assert_eq!(diffuse_texture.as_ref().unwrap().texture.size, (1, 1));
+
+        let skin = body.skin.as_ref().unwrap();
+        assert_eq!(skin.joint_ids, [node_id(2)]);
+        assert_eq!(
+            skin.inverse_bind_matrices,
+            [Mat4::from_translation(Vec3::new(0.0, -1.0, 0.0))]
+        );
+
+        let [animation] = &animations[..] else {
+            panic!("Expected a single animation");
+        };
+        assert_eq!(animation.name.as_deref(), Some("Take"));
+        let [channel] = &animation.channels[..] else {
+            panic!("Expected a single channel");
+        };
+        assert_eq!(channel.target_id, node_id(0));
+        assert_eq!(channel.length, 1.0);
+        let AnimationSampler::Translation(AnimationKeyFrames::Linear(frames)) = &channel.sampler
+        else {
+            panic!("Expected linear translation frames");
+        };
+        let values = frames
+            .iter()
+            .map(|frame| (frame.time, frame.value))
+            .collect::<Vec<_>>();
+        assert_eq!(values, [(0.0, [1.0, 2.0, 3.0]), (1.0, [5.0, 2.0, 3.0])]);
+    }
+
+    #[test]
+    fn test_material_index_out_of_range() {
+        let model = MODEL.replace(
+            "Materials: *1 {\n                a: 0",
+            "Materials: *1 {\n                a: 1000000000",
+        );
+        assert_ne!(model, MODEL);
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("model.fbx", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(model.as_bytes()).unwrap();
+        let mut archive = zip::ZipArchive::new(writer.finish().unwrap()).unwrap();
+        assert!(load_bundle(
+            BundleIndex([0; 32]),
+            &mut archive,
+            &AssetLoadParams::default()
+        )
+        .is_err());
+    }
+}
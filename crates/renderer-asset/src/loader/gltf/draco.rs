@@ -0,0 +1,183 @@
+//! Decoder for primitives compressed with `KHR_draco_mesh_compression`.
+//!
+//! The Draco stream of a primitive holds its indices and the attributes
+//! listed in the extension. They are decoded into the layout of the
+//! primitive's accessors, which have no buffer view of their own.
+
+use std::collections::HashMap;
+
+use draco_oxide_core::attribute::ComponentDataType;
+use gltf::{
+    accessor::{DataType, Dimensions},
+    json::{Index, Root, Value},
+    Accessor, Document, Primitive,
+};
+
+use super::GltfLoaderError;
+
+pub(super) const EXTENSION: &str = "KHR_draco_mesh_compression";
+
+fn component_count(dimensions: Dimensions) -> usize {
+    match dimensions {
+        Dimensions::Scalar => 1,
+        Dimensions::Vec2 => 2,
+        Dimensions::Vec3 => 3,
+        Dimensions::Vec4 | Dimensions::Mat2 => 4,
+        Dimensions::Mat3 => 9,
+        Dimensions::Mat4 => 16,
+    }
+}
+
+fn read_component(data: &[u8], component_type: ComponentDataType) -> f64 {
+    fn bytes<const N: usize>(data: &[u8]) -> [u8; N] {
+        data[..N].try_into().unwrap()
+    }
+    match component_type {
+        ComponentDataType::I8 => data[0] as i8 as f64,
+        ComponentDataType::U8 => data[0] as f64,
+        ComponentDataType::I16 => i16::from_le_bytes(bytes(data)) as f64,
+        ComponentDataType::U16 => u16::from_le_bytes(bytes(data)) as f64,
+        ComponentDataType::I32 => i32::from_le_bytes(bytes(data)) as f64,
+        ComponentDataType::U32 => u32::from_le_bytes(bytes(data)) as f64,
+        ComponentDataType::I64 => i64::from_le_bytes(bytes(data)) as f64,
+        ComponentDataType::U64 => u64::from_le_bytes(bytes(data)) as f64,
+        ComponentDataType::F32 => f32::from_le_bytes(bytes(data)) as f64,
+        ComponentDataType::F64 => f64::from_le_bytes(bytes(data)),
+        ComponentDataType::Invalid => 0.0,
+    }
+}
+
+fn write_component(output: &mut Vec<u8>, value: f64, data_type: DataType) {
+    match data_type {
+        DataType::I8 => output.push(value as i8 as u8),
+        DataType::U8 => output.push(value as u8),
+        DataType::I16 => output.extend((value as i16).to_le_bytes()),
+        DataType::U16 => output.extend((value as u16).to_le_bytes()),
+        DataType::U32 => output.extend((value as u32).to_le_bytes()),
+        DataType::F32 => output.extend((value as f32).to_le_bytes()),
+    }
+}
+
+/// Decode the Draco stream of a primitive, inserting the data of its
+/// accessors.
+fn decode_primitive<E>(
+    document: &Document,
+    mesh_index: usize,
+    primitive: &Primitive,
+    extension: &Value,
+    buffers: &[gltf::buffer::Data],
+    accessors: &mut HashMap<usize, Vec<u8>>,
+) -> Result<(), GltfLoaderError<E>> {
+    let error = |message| GltfLoaderError::BadDracoData(mesh_index, message);
+    let view = extension
+        .get("bufferView")
+        .and_then(Value::as_u64)
+        .and_then(|index| document.views().nth(index as usize))
+        .ok_or_else(|| error("bufferView"))?;
+    let data = buffers
+        .get(view.buffer().index())
+        .and_then(|buffer| buffer.get(view.offset()..view.offset() + view.length()))
+        .ok_or_else(|| error("byteLength"))?;
+    let mesh = draco_oxide_decoder::decode_mesh(data)
+        .map_err(|error| GltfLoaderError::DracoDecode(mesh_index, error))?;
+
+    let indices = primitive.indices().ok_or_else(|| error("indices"))?;
+    if mesh.faces.len() * 3 != indices.count() {
+        return Err(error("indices"));
+    }
+    let mut output = Vec::new();
+    for point in mesh.faces.iter().flatten() {
+        write_component(&mut output, usize::from(*point) as f64, indices.data_type());
+    }
+    accessors.insert(indices.index(), output);
+
+    let ids = extension
+        .get("attributes")
+        .and_then(Value::as_object)
+        .ok_or_else(|| error("attributes"))?;
+    for (semantic, accessor) in primitive.attributes() {
+        let Some(id) = ids.get(&semantic.to_string()) else {
+            continue;
+        };
+        let id = id.as_u64().ok_or_else(|| error("attributes"))? as usize;
+        let attribute = mesh
+            .attributes
+            .iter()
+            .find(|attribute| attribute.get_id().as_usize() == id)
+            .ok_or_else(|| error("attributes"))?;
+        let components = component_count(accessor.dimensions());
+        if attribute.get_num_components() != components {
+            return Err(error("components"));
+        }
+        if attribute.len() != accessor.count() {
+            return Err(error("count"));
+        }
+        accessors.insert(accessor.index(), decode_attribute(attribute, &accessor));
+    }
+    Ok(())
+}
+
+fn decode_attribute(
+    attribute: &draco_oxide_core::attribute::Attribute,
+    accessor: &Accessor,
+) -> Vec<u8> {
+    let component_type = attribute.get_component_type();
+    let component_size = component_type.size();
+    let value_size = component_size * attribute.get_num_components();
+    let values = attribute.get_data_as_bytes();
+    let mut output = Vec::new();
+    for point in 0..attribute.len() {
+        let value = usize::from(attribute.get_unique_val_idx(point.into())) * value_size;
+        for component in values[value..value + value_size].chunks_exact(component_size) {
+            let component = read_component(component, component_type);
+            write_component(&mut output, component, accessor.data_type());
+        }
+    }
+    output
+}
+
+/// Point the accessors of compressed primitives at their Draco buffer view,
+/// as validation requires accessors to have one. Their data is decoded from
+/// the Draco stream instead of read from the buffer view.
+pub(super) fn assign_views(root: &mut Root) {
+    for primitive in root.meshes.iter().flat_map(|mesh| &mesh.primitives) {
+        let Some(view) = primitive
+            .extensions
+            .as_ref()
+            .and_then(|extensions| extensions.others.get(EXTENSION))
+            .and_then(|extension| extension.get("bufferView"))
+            .and_then(Value::as_u64)
+        else {
+            continue;
+        };
+        for accessor in primitive.attributes.values().chain(&primitive.indices) {
+            if let Some(accessor) = root.accessors.get_mut(accessor.value()) {
+                accessor.buffer_view.get_or_insert(Index::new(view as u32));
+            }
+        }
+    }
+}
+
+/// Decode the compressed primitives into the data of their accessors, by
+/// accessor index.
+pub(super) fn decode_accessors<E>(
+    document: &Document,
+    buffers: &[gltf::buffer::Data],
+) -> Result<HashMap<usize, Vec<u8>>, GltfLoaderError<E>> {
+    let mut accessors = HashMap::new();
+    for mesh in document.meshes() {
+        for primitive in mesh.primitives() {
+            if let Some(extension) = primitive.extension_value(EXTENSION) {
+                decode_primitive(
+                    document,
+                    mesh.index(),
+                    &primitive,
+                    extension,
+                    buffers,
+                    &mut accessors,
+                )?;
+            }
+        }
+    }
+    Ok(accessors)
+}
@@ -0,0 +1,261 @@
+//! KTX2 images, as used by `KHR_texture_basisu`.
+//!
+//! Only the base level of the first layer and face is loaded. Uncompressed
+//! 8 bit, 16 bit and float formats are supported, optionally supercompressed
+//! with Zstandard. Basis Universal payloads, ETC1S and UASTC, are transcoded
+//! to RGBA.
+
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io,
+};
+
+use basisu::{DecodeFlags, TargetFormat, Transcoder};
+use gltf::image::Format;
+
+pub(super) const EXTENSION: &str = "KHR_texture_basisu";
+pub(super) const MIME: &str = "image/ktx2";
+
+const IDENTIFIER: [u8; 12] = [
+    0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+];
+const HEADER_LENGTH: usize = 80;
+const LEVEL_INDEX_LENGTH: usize = 24;
+
+const SUPERCOMPRESSION_NONE: u32 = 0;
+const SUPERCOMPRESSION_BASIS_LZ: u32 = 1;
+const SUPERCOMPRESSION_ZSTD: u32 = 2;
+
+#[derive(Debug)]
+pub enum Ktx2Error {
+    BadHeader,
+    Truncated,
+    BasisUniversal(basisu::Error),
+    UnsupportedFormat(u32),
+    UnsupportedSupercompression(u32),
+    Zstd(io::Error),
+}
+
+impl Display for Ktx2Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Ktx2Error::BadHeader => write!(f, "Bad KTX2 header"),
+            Ktx2Error::Truncated => write!(f, "Truncated KTX2 data"),
+            Ktx2Error::BasisUniversal(error) => {
+                write!(f, "Failed to transcode Basis Universal data: {:?}", error)
+            }
+            Ktx2Error::UnsupportedFormat(format) => {
+                write!(f, "Unsupported KTX2 format: {}", format)
+            }
+            Ktx2Error::UnsupportedSupercompression(scheme) => {
+                write!(f, "Unsupported KTX2 supercompression scheme: {}", scheme)
+            }
+            Ktx2Error::Zstd(error) => Display::fmt(error, f),
+        }
+    }
+}
+
+impl Error for Ktx2Error {}
+
+pub(super) fn is_ktx2(data: &[u8]) -> bool {
+    data.starts_with(&IDENTIFIER)
+}
+
+/// Image format and bytes per pixel of a Vulkan format.
+fn format(vk_format: u32) -> Result<(Format, usize), Ktx2Error> {
+    Ok(match vk_format {
+        // UNORM and SRGB variants
+        9 | 15 => (Format::R8, 1),
+        16 | 22 => (Format::R8G8, 2),
+        23 | 29 => (Format::R8G8B8, 3),
+        37 | 43 => (Format::R8G8B8A8, 4),
+        70 => (Format::R16, 2),
+        77 => (Format::R16G16, 4),
+        84 => (Format::R16G16B16, 6),
+        91 => (Format::R16G16B16A16, 8),
+        106 => (Format::R32G32B32FLOAT, 12),
+        109 => (Format::R32G32B32A32FLOAT, 16),
+        format => return Err(Ktx2Error::UnsupportedFormat(format)),
+    })
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<usize, Ktx2Error> {
+    let value = u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+    usize::try_from(value).map_err(|_| Ktx2Error::Truncated)
+}
+
+/// Transcode the base level of a Basis Universal image to RGBA.
+fn transcode(data: &[u8]) -> Result<gltf::image::Data, Ktx2Error> {
+    let transcoder = Transcoder::new(data).map_err(Ktx2Error::BasisUniversal)?;
+    let (width, height) = transcoder.base_dimensions();
+    let pixels = transcoder
+        .transcode(0, TargetFormat::Rgba32, DecodeFlags::NONE)
+        .map_err(Ktx2Error::BasisUniversal)?;
+    Ok(gltf::image::Data {
+        pixels,
+        format: Format::R8G8B8A8,
+        width,
+        height,
+    })
+}
+
+pub(super) fn load(data: &[u8]) -> Result<gltf::image::Data, Ktx2Error> {
+    if !is_ktx2(data) || data.len() < HEADER_LENGTH + LEVEL_INDEX_LENGTH {
+        return Err(Ktx2Error::BadHeader);
+    }
+    let vk_format = read_u32(data, 12);
+    let width = read_u32(data, 20);
+    let height = read_u32(data, 24);
+    let layer_count = read_u32(data, 32);
+    let face_count = read_u32(data, 36);
+    let supercompression = read_u32(data, 44);
+    if width == 0 || height == 0 {
+        return Err(Ktx2Error::BadHeader);
+    }
+    // Basis Universal payloads have an undefined format
+    if vk_format == 0 || supercompression == SUPERCOMPRESSION_BASIS_LZ {
+        return transcode(data);
+    }
+    match supercompression {
+        SUPERCOMPRESSION_NONE | SUPERCOMPRESSION_ZSTD => (),
+        scheme => return Err(Ktx2Error::UnsupportedSupercompression(scheme)),
+    }
+    let (format, pixel_size) = format(vk_format)?;
+
+    let offset = read_u64(data, HEADER_LENGTH)?;
+    let length = read_u64(data, HEADER_LENGTH + 8)?;
+    let level = offset
+        .checked_add(length)
+        .and_then(|end| data.get(offset..end))
+        .ok_or(Ktx2Error::Truncated)?;
+    let image_length = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|length| length.checked_mul(pixel_size))
+        .ok_or(Ktx2Error::BadHeader)?;
+    let mut pixels = if supercompression == SUPERCOMPRESSION_ZSTD {
+        // Every layer and face of the level, which bounds the allocation
+        let level_length = image_length
+            .checked_mul(layer_count.max(1) as usize)
+            .and_then(|length| length.checked_mul(face_count as usize))
+            .ok_or(Ktx2Error::BadHeader)?;
+        let uncompressed_length = read_u64(data, HEADER_LENGTH + 16)?;
+        if uncompressed_length > level_length {
+            return Err(Ktx2Error::BadHeader);
+        }
+        zstd::bulk::decompress(level, uncompressed_length).map_err(Ktx2Error::Zstd)?
+    } else {
+        level.to_vec()
+    };
+    // Other layers and faces follow the first one
+    if pixels.len() < image_length {
+        return Err(Ktx2Error::Truncated);
+    }
+    pixels.truncate(image_length);
+
+    Ok(gltf::image::Data {
+        pixels,
+        format,
+        width,
+        height,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use gltf::image::Format;
+
+    use super::{load, Ktx2Error, HEADER_LENGTH, IDENTIFIER};
+
+    fn ktx2(vk_format: u32, supercompression: u32, level: &[u8], uncompressed: usize) -> Vec<u8> {
+        let mut data = IDENTIFIER.to_vec();
+        let level_offset = (HEADER_LENGTH + 24) as u64;
+        for value in [vk_format, 1, 2, 1, 0, 0, 1, 1, supercompression] {
+            data.extend(value.to_le_bytes());
+        }
+        // Empty data format descriptor, key/value and global data
+        data.extend([0; 32]);
+        for value in [level_offset, level.len() as u64, uncompressed as u64] {
+            data.extend(value.to_le_bytes());
+        }
+        data.extend(level);
+        data
+    }
+
+    /// A 4x4 UASTC image of a solid color.
+    fn uastc(color: [u8; 4]) -> Vec<u8> {
+        let mut data = IDENTIFIER.to_vec();
+        for value in [0u32, 1, 4, 4, 0, 0, 1, 1, 0] {
+            data.extend(value.to_le_bytes());
+        }
+        let dfd_offset = HEADER_LENGTH as u32 + 24;
+        for value in [dfd_offset, 44, 0, 0] {
+            data.extend(value.to_le_bytes());
+        }
+        data.extend([0; 16]);
+        for value in [dfd_offset as u64 + 44, 16, 16] {
+            data.extend(value.to_le_bytes());
+        }
+        // Basic descriptor block of the UASTC color model, with an RGBA sample
+        let dfd: [u32; 11] = [
+            44,
+            0,
+            2 | 40 << 16,
+            166 | 1 << 8 | 2 << 16,
+            3 | 3 << 8,
+            16,
+            0,
+            127 << 16 | 3 << 24,
+            0,
+            0,
+            u32::MAX,
+        ];
+        for value in dfd {
+            data.extend(value.to_le_bytes());
+        }
+        // Solid color block, mode 8
+        let [r, g, b, a] = color.map(u128::from);
+        let block = 0x17 | r << 5 | g << 13 | b << 21 | a << 29;
+        data.extend(block.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_load() {
+        let pixels = [255, 0, 0, 255, 0, 255, 0, 128];
+        let image = load(&ktx2(43, 0, &pixels, pixels.len())).unwrap();
+        assert_eq!(image.format, Format::R8G8B8A8);
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(image.pixels, pixels);
+
+        let compressed = zstd::bulk::compress(&pixels, 0).unwrap();
+        let image = load(&ktx2(37, 2, &compressed, pixels.len())).unwrap();
+        assert_eq!(image.pixels, pixels);
+
+        assert!(matches!(
+            load(&ktx2(0, 1, &pixels, pixels.len())),
+            Err(Ktx2Error::BasisUniversal(_))
+        ));
+        assert!(matches!(
+            load(&ktx2(37, 0, &pixels[..4], 4)),
+            Err(Ktx2Error::Truncated)
+        ));
+        assert!(matches!(load(&pixels), Err(Ktx2Error::BadHeader)));
+        assert!(matches!(
+            load(&ktx2(37, 2, &compressed, 1 << 50)),
+            Err(Ktx2Error::BadHeader)
+        ));
+    }
+
+    #[test]
+    fn test_transcode() {
+        let image = load(&uastc([255, 128, 0, 255])).unwrap();
+        assert_eq!(image.format, Format::R8G8B8A8);
+        assert_eq!((image.width, image.height), (4, 4));
+        assert_eq!(image.pixels, [255, 128, 0, 255].repeat(16));
+    }
+}
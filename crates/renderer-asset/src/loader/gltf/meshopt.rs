@@ -0,0 +1,583 @@
+//! Decoder for buffer views compressed with `EXT_meshopt_compression`.
+//!
+//! Follows the bitstream of the extension specification, version 0 for
+//! attributes and versions 0 and 1 for indices.
+
+use std::collections::HashMap;
+
+use gltf::{json::Value, Document};
+
+use super::GltfLoaderError;
+
+pub(super) const EXTENSION: &str = "EXT_meshopt_compression";
+
+const ATTRIBUTES_HEADER: u8 = 0xa0;
+const TRIANGLES_HEADER: u8 = 0xe0;
+const INDICES_HEADER: u8 = 0xd0;
+
+const BYTE_GROUP_SIZE: usize = 16;
+const BYTE_GROUP_DECODE_LIMIT: usize = 24;
+const VERTEX_BLOCK_SIZE_BYTES: usize = 8192;
+const VERTEX_BLOCK_MAX_SIZE: usize = 256;
+const TAIL_MAX_SIZE: usize = 32;
+// Every group of 16 bytes has at least a 2 bit header
+const MAX_EXPANSION: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Attributes,
+    Triangles,
+    Indices,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Filter {
+    None,
+    Octahedral,
+    Quaternion,
+    Exponential,
+}
+
+fn unzigzag8(value: u8) -> u8 {
+    (0u8.wrapping_sub(value & 1)) ^ (value >> 1)
+}
+
+fn unzigzag32(value: u32) -> u32 {
+    (0u32.wrapping_sub(value & 1)) ^ (value >> 1)
+}
+
+/// Decode the 16 bytes of a group, with values of `1 << bits_log2` bits
+/// escaped to a following byte when all ones.
+fn decode_bytes_group(
+    data: &[u8],
+    output: &mut [u8],
+    bits_log2: u8,
+) -> Result<usize, &'static str> {
+    let bits = match bits_log2 {
+        0 => {
+            output.fill(0);
+            return Ok(0);
+        }
+        3 => {
+            output.copy_from_slice(&data[..BYTE_GROUP_SIZE]);
+            return Ok(BYTE_GROUP_SIZE);
+        }
+        bits_log2 => 1 << bits_log2,
+    };
+    let packed_length = BYTE_GROUP_SIZE * bits / 8;
+    let escape = (1u8 << bits) - 1;
+    let mut escaped = packed_length;
+    for (index, item) in output.iter_mut().enumerate() {
+        let byte = data[index * bits / 8];
+        let shift = 8 - bits - (index * bits) % 8;
+        let value = (byte >> shift) & escape;
+        *item = if value == escape {
+            let value = *data.get(escaped).ok_or("Truncated byte group")?;
+            escaped += 1;
+            value
+        } else {
+            value
+        };
+    }
+    Ok(escaped)
+}
+
+/// Decode `output.len()` bytes, a multiple of the group size.
+fn decode_bytes(data: &[u8], output: &mut [u8]) -> Result<usize, &'static str> {
+    let header_length = (output.len() / BYTE_GROUP_SIZE).div_ceil(4);
+    if data.len() < header_length {
+        return Err("Truncated attributes");
+    }
+    let (header, mut offset) = (&data[..header_length], header_length);
+    for (index, group) in output.chunks_exact_mut(BYTE_GROUP_SIZE).enumerate() {
+        if data.len() - offset < BYTE_GROUP_DECODE_LIMIT {
+            return Err("Truncated attributes");
+        }
+        let bits_log2 = (header[index / 4] >> ((index % 4) * 2)) & 3;
+        offset += decode_bytes_group(&data[offset..], group, bits_log2)?;
+    }
+    Ok(offset)
+}
+
+fn decode_attributes(data: &[u8], count: usize, stride: usize) -> Result<Vec<u8>, &'static str> {
+    if stride == 0 || stride > 256 || !stride.is_multiple_of(4) {
+        return Err("Bad attribute stride");
+    }
+    if data.len() < 1 + stride {
+        return Err("Truncated attributes");
+    }
+    if data[0] & 0xf0 != ATTRIBUTES_HEADER {
+        return Err("Bad attributes header");
+    }
+    if data[0] & 0x0f > 0 {
+        return Err("Unsupported attributes version");
+    }
+
+    let mut last = data[data.len() - stride..].to_vec();
+    let block_size =
+        ((VERTEX_BLOCK_SIZE_BYTES / stride) & !(BYTE_GROUP_SIZE - 1)).min(VERTEX_BLOCK_MAX_SIZE);
+    let length = count
+        .checked_mul(stride)
+        .filter(|length| length / MAX_EXPANSION <= data.len())
+        .ok_or("Bad attribute count")?;
+    let mut output = vec![0; length];
+    let mut bytes = [0; VERTEX_BLOCK_MAX_SIZE];
+    let mut offset = 1;
+    for block_start in (0..count).step_by(block_size) {
+        let block_count = block_size.min(count - block_start);
+        let aligned_count = block_count.next_multiple_of(BYTE_GROUP_SIZE);
+        let block = &mut output[block_start * stride..(block_start + block_count) * stride];
+        for (byte, last) in last.iter_mut().enumerate() {
+            offset += decode_bytes(&data[offset..], &mut bytes[..aligned_count])?;
+            for (vertex, delta) in bytes[..block_count].iter().enumerate() {
+                *last = last.wrapping_add(unzigzag8(*delta));
+                block[vertex * stride + byte] = *last;
+            }
+        }
+    }
+    if data.len() - offset != stride.max(TAIL_MAX_SIZE) {
+        return Err("Trailing attribute data");
+    }
+    Ok(output)
+}
+
+fn decode_varint(data: &[u8], offset: &mut usize) -> Result<u32, &'static str> {
+    let mut value = 0;
+    for shift in (0..35).step_by(7) {
+        let byte = *data.get(*offset).ok_or("Truncated indices")?;
+        *offset += 1;
+        value |= ((byte & 127) as u32) << shift;
+        if byte < 128 {
+            break;
+        }
+    }
+    Ok(value)
+}
+
+/// Free index, as a delta from the last one.
+fn decode_index(data: &[u8], offset: &mut usize, last: u32) -> Result<u32, &'static str> {
+    Ok(last.wrapping_add(unzigzag32(decode_varint(data, offset)?)))
+}
+
+fn write_indices(indices: &[u32], stride: usize) -> Result<Vec<u8>, &'static str> {
+    match stride {
+        2 => Ok(indices
+            .iter()
+            .flat_map(|index| (*index as u16).to_le_bytes())
+            .collect()),
+        4 => Ok(indices
+            .iter()
+            .flat_map(|index| index.to_le_bytes())
+            .collect()),
+        _ => Err("Bad index stride"),
+    }
+}
+
+/// Push to a fifo of 16 items, only moving forward if `advance`.
+fn push_fifo<T>(fifo: &mut [T; 16], offset: &mut usize, item: T, advance: bool) {
+    fifo[*offset] = item;
+    *offset = (*offset + advance as usize) & 15;
+}
+
+fn decode_triangles(data: &[u8], count: usize, stride: usize) -> Result<Vec<u8>, &'static str> {
+    if !count.is_multiple_of(3) {
+        return Err("Bad triangle index count");
+    }
+    if data.len() < 1 + count / 3 + 16 {
+        return Err("Truncated triangles");
+    }
+    if data[0] & 0xf0 != TRIANGLES_HEADER {
+        return Err("Bad triangles header");
+    }
+    let version = data[0] & 0x0f;
+    if version > 1 {
+        return Err("Unsupported triangles version");
+    }
+
+    let mut edges = [[u32::MAX; 2]; 16];
+    let mut vertices = [u32::MAX; 16];
+    let (mut edge_offset, mut vertex_offset) = (0, 0);
+    let (mut next, mut last) = (0u32, 0u32);
+    let fec_max = if version >= 1 { 13 } else { 15 };
+
+    let codes = &data[1..1 + count / 3];
+    // The data ends with a table of 16 codes for common triangles
+    let data_end = data.len() - 16;
+    let (data, code_aux_table) = data.split_at(data_end);
+    let mut offset = 1 + count / 3;
+
+    let mut indices = Vec::with_capacity(count);
+    for code in codes {
+        if offset > data_end {
+            return Err("Truncated triangles");
+        }
+        let [a, b, c] = if *code < 0xf0 {
+            // On an edge of a recent triangle
+            let [a, b] = edges[(edge_offset + 15 - (code >> 4) as usize) & 15];
+            let fec = code & 15;
+            let c = if fec == 0 {
+                next += 1;
+                next - 1
+            } else if fec < fec_max {
+                vertices[(vertex_offset + 15 - fec as usize) & 15]
+            } else if fec < 15 {
+                // Next to the last free index
+                last = last.wrapping_add(fec as u32).wrapping_sub((fec ^ 3) as u32);
+                last
+            } else {
+                last = decode_index(data, &mut offset, last)?;
+                last
+            };
+            push_fifo(
+                &mut vertices,
+                &mut vertex_offset,
+                c,
+                fec == 0 || fec >= fec_max,
+            );
+            [a, b, c]
+        } else {
+            let (fea, feb, fec) = if *code < 0xfe {
+                let code_aux = code_aux_table[(code & 15) as usize];
+                (0, code_aux >> 4, code_aux & 15)
+            } else {
+                let code_aux = *data.get(offset).ok_or("Truncated triangles")?;
+                offset += 1;
+                if code_aux == 0 {
+                    next = 0;
+                }
+                (
+                    if *code == 0xfe { 0 } else { 15 },
+                    code_aux >> 4,
+                    code_aux & 15,
+                )
+            };
+            let mut vertex = |fe: u8| {
+                if fe == 0 {
+                    next += 1;
+                    next - 1
+                } else {
+                    vertices[(vertex_offset + 16 - fe as usize) & 15]
+                }
+            };
+            let mut triangle = [
+                if fea == 0 { vertex(0) } else { 0 },
+                vertex(feb),
+                vertex(fec),
+            ];
+            for (index, fe) in [fea, feb, fec].into_iter().enumerate() {
+                if fe == 15 {
+                    last = decode_index(data, &mut offset, last)?;
+                    triangle[index] = last;
+                }
+            }
+            let [a, b, c] = triangle;
+            push_fifo(&mut vertices, &mut vertex_offset, a, true);
+            push_fifo(&mut vertices, &mut vertex_offset, b, feb == 0 || feb == 15);
+            push_fifo(&mut vertices, &mut vertex_offset, c, fec == 0 || fec == 15);
+            push_fifo(&mut edges, &mut edge_offset, [b, a], true);
+            triangle
+        };
+        push_fifo(&mut edges, &mut edge_offset, [c, b], true);
+        push_fifo(&mut edges, &mut edge_offset, [a, c], true);
+        indices.extend([a, b, c]);
+    }
+    if offset != data_end {
+        return Err("Trailing triangle data");
+    }
+    write_indices(&indices, stride)
+}
+
+fn decode_indices(data: &[u8], count: usize, stride: usize) -> Result<Vec<u8>, &'static str> {
+    if data.len() < 1 + count + 4 {
+        return Err("Truncated indices");
+    }
+    if data[0] & 0xf0 != INDICES_HEADER {
+        return Err("Bad indices header");
+    }
+    if data[0] & 0x0f > 1 {
+        return Err("Unsupported indices version");
+    }
+
+    let data_end = data.len() - 4;
+    let data = &data[..data_end];
+    let mut offset = 1;
+    let mut last = [0u32; 2];
+    let mut indices = Vec::with_capacity(count);
+    for _ in 0..count {
+        if offset >= data_end {
+            return Err("Truncated indices");
+        }
+        let value = decode_varint(data, &mut offset)?;
+        // The lowest bit picks one of two baselines
+        let baseline = &mut last[(value & 1) as usize];
+        *baseline = baseline.wrapping_add(unzigzag32(value >> 1));
+        indices.push(*baseline);
+    }
+    if offset != data_end {
+        return Err("Trailing index data");
+    }
+    write_indices(&indices, stride)
+}
+
+fn round(value: f32) -> f32 {
+    value + if value >= 0.0 { 0.5 } else { -0.5 }
+}
+
+/// Unit vectors from octahedral encoding, in 8 or 16 bit signed components
+/// with the scale of one in the third component.
+fn filter_octahedral(data: &mut [u8], stride: usize) -> Result<(), &'static str> {
+    fn decode(x: f32, y: f32, one: f32, max: f32) -> [f32; 3] {
+        let z = one - x.abs() - y.abs();
+        let t = z.min(0.0);
+        let x = x + if x >= 0.0 { t } else { -t };
+        let y = y + if y >= 0.0 { t } else { -t };
+        let scale = max / (x * x + y * y + z * z).sqrt();
+        [x, y, z].map(|value| round(value * scale))
+    }
+
+    match stride {
+        4 => {
+            for item in data.chunks_exact_mut(4) {
+                let [x, y, one] = [item[0], item[1], item[2]].map(|value| value as i8 as f32);
+                let decoded = decode(x, y, one, i8::MAX as f32);
+                for (value, decoded) in item.iter_mut().zip(decoded) {
+                    *value = decoded as i8 as u8;
+                }
+            }
+        }
+        8 => {
+            for item in data.chunks_exact_mut(8) {
+                let component = |index: usize| {
+                    i16::from_le_bytes([item[index * 2], item[index * 2 + 1]]) as f32
+                };
+                let decoded = decode(component(0), component(1), component(2), i16::MAX as f32);
+                for (index, decoded) in decoded.into_iter().enumerate() {
+                    item[index * 2..index * 2 + 2].copy_from_slice(&(decoded as i16).to_le_bytes());
+                }
+            }
+        }
+        _ => return Err("Bad octahedral filter stride"),
+    }
+    Ok(())
+}
+
+/// Unit quaternions from the three smallest components, with the index of
+/// the largest one and the scale in the fourth.
+fn filter_quaternion(data: &mut [u8], stride: usize) -> Result<(), &'static str> {
+    if stride != 8 {
+        return Err("Bad quaternion filter stride");
+    }
+    for item in data.chunks_exact_mut(8) {
+        let component = |index: usize| i16::from_le_bytes([item[index * 2], item[index * 2 + 1]]);
+        let packed = component(3);
+        let scale = std::f32::consts::FRAC_1_SQRT_2 / (packed | 3) as f32;
+        let [x, y, z] = [0, 1, 2].map(|index| component(index) as f32 * scale);
+        let w = (1.0 - x * x - y * y - z * z).max(0.0).sqrt();
+        let largest = (packed & 3) as usize;
+        for (offset, value) in [w, x, y, z].into_iter().enumerate() {
+            let index = (largest + offset) & 3;
+            let value = round(value * i16::MAX as f32) as i16;
+            item[index * 2..index * 2 + 2].copy_from_slice(&value.to_le_bytes());
+        }
+    }
+    Ok(())
+}
+
+/// Floats from a 24 bit mantissa and an 8 bit exponent.
+fn filter_exponential(data: &mut [u8], stride: usize) -> Result<(), &'static str> {
+    if !stride.is_multiple_of(4) {
+        return Err("Bad exponential filter stride");
+    }
+    for item in data.chunks_exact_mut(4) {
+        let value = u32::from_le_bytes(item.try_into().unwrap());
+        let mantissa = ((value << 8) as i32) >> 8;
+        let exponent = (value as i32) >> 24;
+        let scale = f32::from_bits(((exponent + 127) as u32) << 23);
+        item.copy_from_slice(&(scale * mantissa as f32).to_le_bytes());
+    }
+    Ok(())
+}
+
+fn decode(
+    data: &[u8],
+    mode: Mode,
+    filter: Filter,
+    count: usize,
+    stride: usize,
+) -> Result<Vec<u8>, &'static str> {
+    let mut output = match mode {
+        Mode::Attributes => decode_attributes(data, count, stride)?,
+        Mode::Triangles => decode_triangles(data, count, stride)?,
+        Mode::Indices => decode_indices(data, count, stride)?,
+    };
+    match filter {
+        Filter::None => (),
+        Filter::Octahedral => filter_octahedral(&mut output, stride)?,
+        Filter::Quaternion => filter_quaternion(&mut output, stride)?,
+        Filter::Exponential => filter_exponential(&mut output, stride)?,
+    }
+    Ok(output)
+}
+
+/// Whether a buffer only exists for loaders without meshopt support.
+pub(super) fn is_fallback(buffer: &gltf::Buffer) -> bool {
+    buffer
+        .extension_value(EXTENSION)
+        .and_then(|extension| extension.get("fallback"))
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+fn load_usize(value: &Value, key: &str) -> Option<usize> {
+    value.get(key)?.as_u64().map(|value| value as usize)
+}
+
+/// Decode the compressed buffer views, by index.
+pub(super) fn decode_views<E>(
+    document: &Document,
+    buffers: &[gltf::buffer::Data],
+) -> Result<HashMap<usize, Vec<u8>>, GltfLoaderError<E>> {
+    let mut views = HashMap::new();
+    for view in document.views() {
+        let Some(extension) = view.extension_value(EXTENSION) else {
+            continue;
+        };
+        let error = |message| GltfLoaderError::BadMeshoptData(view.index(), message);
+        let field = |key| load_usize(extension, key).ok_or_else(|| error(key));
+        let buffer = buffers
+            .get(field("buffer")?)
+            .ok_or_else(|| error("buffer"))?;
+        let offset = load_usize(extension, "byteOffset").unwrap_or(0);
+        let length = field("byteLength")?;
+        let data = offset
+            .checked_add(length)
+            .and_then(|end| buffer.get(offset..end))
+            .ok_or_else(|| error("byteLength"))?;
+        let mode = match extension.get("mode").and_then(Value::as_str) {
+            Some("ATTRIBUTES") => Mode::Attributes,
+            Some("TRIANGLES") => Mode::Triangles,
+            Some("INDICES") => Mode::Indices,
+            _ => return Err(error("mode")),
+        };
+        let filter = match extension.get("filter").and_then(Value::as_str) {
+            None | Some("NONE") => Filter::None,
+            Some("OCTAHEDRAL") => Filter::Octahedral,
+            Some("QUATERNION") => Filter::Quaternion,
+            Some("EXPONENTIAL") => Filter::Exponential,
+            _ => return Err(error("filter")),
+        };
+        let (count, stride) = (field("count")?, field("byteStride")?);
+        // Counts come from the file, so they are checked before decoding
+        let decoded_length = count
+            .checked_mul(stride)
+            .filter(|length| *length == view.length())
+            .ok_or_else(|| error("count"))?;
+        let fits = |accessor: &gltf::Accessor| {
+            accessor
+                .count()
+                .checked_mul(view.stride().unwrap_or(accessor.size()))
+                .and_then(|length| length.checked_add(accessor.offset()))
+                .is_some_and(|end| end <= decoded_length)
+        };
+        if !document
+            .accessors()
+            .filter(|accessor| accessor.view().map(|view| view.index()) == Some(view.index()))
+            .all(|accessor| fits(&accessor))
+        {
+            return Err(error("count"));
+        }
+        let decoded = decode(data, mode, filter, count, stride).map_err(error)?;
+        views.insert(view.index(), decoded);
+    }
+    Ok(views)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, Filter, Mode};
+
+    #[test]
+    fn test_attributes() {
+        let mut data = vec![0xa0];
+        // Deltas of the first byte in 2 bits, of the second in 4 bits
+        data.extend([0x01, 0x80, 0, 0, 0]);
+        data.extend([0x02, 0x40, 0, 0, 0, 0, 0, 0, 0]);
+        // Raw third byte
+        data.extend([0x03, 6]);
+        data.extend([0; 15]);
+        // Escaped 2 bit delta of the fourth byte
+        data.extend([0x01, 0xe0, 0, 0, 0, 8]);
+        // Tail, ending with the baseline
+        data.extend([0; 32]);
+        let decoded = decode(&data, Mode::Attributes, Filter::None, 2, 4).unwrap();
+        assert_eq!(decoded, [1, 2, 3, 4, 1, 2, 3, 5]);
+
+        assert!(decode(
+            &data[..data.len() - 1],
+            Mode::Attributes,
+            Filter::None,
+            2,
+            4
+        )
+        .is_err());
+        assert!(decode(&data, Mode::Attributes, Filter::None, 2, 6).is_err());
+        assert!(decode(&data, Mode::Attributes, Filter::None, 1 << 40, 4).is_err());
+        assert!(decode(&data, Mode::Attributes, Filter::None, usize::MAX, 4).is_err());
+    }
+
+    #[test]
+    fn test_indices() {
+        // The first triangle from new vertices, the second on its edge with
+        // a new vertex, the third on an edge with a free index
+        let mut data = vec![0xe1, 0xf0, 0x10, 0x1f, 14];
+        data.extend([0; 16]);
+        let decoded = decode(&data, Mode::Triangles, Filter::None, 9, 2).unwrap();
+        let indices: Vec<u16> = decoded
+            .chunks_exact(2)
+            .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
+            .collect();
+        assert_eq!(indices, [0, 1, 2, 2, 1, 3, 3, 1, 7]);
+
+        let data = [0xd1, 20, 4, 6, 0, 0, 0, 0];
+        let decoded = decode(&data, Mode::Indices, Filter::None, 3, 4).unwrap();
+        let indices: Vec<u32> = decoded
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        assert_eq!(indices, [5, 6, 4]);
+    }
+
+    #[test]
+    fn test_filters() {
+        let mut data = vec![0xa0];
+        // Raw bytes of four vertices
+        let raw: [[u8; 4]; 4] = [[127, 0, 127, 0], [0, 0, 127, 0], [0x81, 0, 127, 0], [0; 4]];
+        for byte in 0..4 {
+            data.push(0x03);
+            let mut last = 0u8;
+            for vertex in raw {
+                let delta = vertex[byte].wrapping_sub(last) as i8;
+                data.push(((delta << 1) ^ (delta >> 7)) as u8);
+                last = vertex[byte];
+            }
+            data.extend([0; 12]);
+        }
+        data.extend([0; 32]);
+        let decoded = decode(&data, Mode::Attributes, Filter::Octahedral, 4, 4).unwrap();
+        let decoded: Vec<i8> = decoded.into_iter().map(|value| value as i8).collect();
+        assert_eq!(
+            decoded,
+            [127, 0, 0, 0, 0, 0, 127, 0, -127, 0, 0, 0, 0, 0, 0, 0]
+        );
+
+        let mut quaternion = [0u8; 8];
+        quaternion[6..].copy_from_slice(&i16::MAX.to_le_bytes());
+        super::filter_quaternion(&mut quaternion, 8).unwrap();
+        assert_eq!(quaternion[..6], [0; 6]);
+        assert_eq!(i16::from_le_bytes([quaternion[6], quaternion[7]]), i16::MAX);
+
+        let mut exponential = 0xff000003u32.to_le_bytes();
+        super::filter_exponential(&mut exponential, 4).unwrap();
+        assert_eq!(f32::from_le_bytes(exponential), 1.5);
+    }
+}
@@ -5,6 +5,7 @@ use std::{
     io::{self, Cursor},
     iter,
     marker::PhantomData,
+    path::Path,
     sync::Arc,
 };
 
@@ -14,6 +15,7 @@ use gltf::{
     animation::{Channel, Interpolation, Property, Sampler},
     camera::Projection,
     image::Format,
+    json::extensions::ENABLED_EXTENSIONS,
     json::Value,
     material::AlphaMode,
     mesh::{Mode, MorphTarget},
@@ -23,7 +25,8 @@ use gltf::{
     Skin, Texture,
 };
 use image::{guess_format, DynamicImage, GenericImageView, ImageError, ImageFormat, ImageReader};
-use scheme::{Scheme, SchemeError};
+use ktx2::Ktx2Error;
+use scheme::{Scheme, SchemeData, SchemeError};
 
 use crate::{
     animation::{
@@ -33,12 +36,10 @@ use crate::{
     archive::{Archive, Entry},
     camera::{CameraAsset, CameraProjectionAsset, OrthographicCameraAsset, PerspectiveCameraAsset},
     index::{AssetIndex, BundleAssetType, BundleIndex},
-    loader::{
-        chunk_and_clamp_vec3_to_vec4_f32, chunk_and_clamp_vec4_f32, chunk_vec3, chunk_vec4,
-        clip_vec4_to_vec3, pad_vec3_to_vec4,
-    },
+    loader::{chunk_and_clamp_vec3_to_vec4_f32, chunk_and_clamp_vec4_f32, chunk_vec3, chunk_vec4},
     material::{
-        MaterialAlphaMode, MaterialAsset, MaterialAssetData, OutlineWidthMode, UvAnimation,
+        MaterialAlphaMode, MaterialAsset, MaterialAssetData, OutlineWidthMode, PbrClearcoat,
+        PbrSheen, PbrSpecular, PbrTransmission, PbrVolume, UvAnimation,
     },
     mesh::MeshAsset,
     node::{DecomposedTransform, MatrixNodeTransform, NodeAsset, NodeTransform},
@@ -48,17 +49,24 @@ use crate::{
     },
     scene::SceneAsset,
     skin::SkinAsset,
-    tangent::calculate_tangent,
     texture::{
         NormalTextureInfo, OcclusionTextureInfo, SamplerAsset, ShadingShiftTextureInfo,
         TextureAsset, TextureAssetFormat, TextureAssetTransform, TextureInfo, TextureMagFilter,
         TextureMinFilter, TextureMipmapFilter, TextureWrappingMode,
     },
+    vrm::{HumanBone, VrmAsset},
 };
 
 use super::{chunk_mat4, AssetLoadParams};
 
+mod draco;
+pub mod ktx2;
+mod meshopt;
 pub mod scheme;
+mod vrm;
+
+const CLEARCOAT_EXTENSION: &str = "KHR_materials_clearcoat";
+const SHEEN_EXTENSION: &str = "KHR_materials_sheen";
 
 #[derive(Debug, Clone)]
 pub enum GltfImageSource {
@@ -94,6 +102,16 @@ pub enum GltfLoaderError<E> {
     BadAccessorDataType(DataType, DataType),
     BadAccessorDimensions(Dimensions, Dimensions),
     BadJointWeightSets(usize, usize),
+    BadVrmData(&'static str),
+    UnsupportedVrmVersion(String),
+    MissingHumanBone(HumanBone),
+    UnsupportedExtension(String),
+    BadMeshoptData(usize, &'static str),
+    BadDracoData(usize, &'static str),
+    DracoDecode(usize, draco_oxide_decoder::Err),
+    BadKtx2(GltfImageSource, Ktx2Error),
+    MissingTextureSource(usize),
+    BadMaterialExtension(&'static str),
 }
 
 impl<E: Display> Display for GltfLoaderError<E> {
@@ -154,6 +172,34 @@ impl<E: Display> Display for GltfLoaderError<E> {
                     joints, weights
                 )
             }
+            GltfLoaderError::BadVrmData(field) => write!(f, "Bad VRM data: {}", field),
+            GltfLoaderError::UnsupportedVrmVersion(version) => {
+                write!(f, "Unsupported VRM version: {}", version)
+            }
+            GltfLoaderError::MissingHumanBone(bone) => {
+                write!(f, "Missing human bone: {}", bone.name())
+            }
+            GltfLoaderError::UnsupportedExtension(extension) => {
+                write!(f, "Unsupported required extension: {}", extension)
+            }
+            GltfLoaderError::BadMeshoptData(index, field) => {
+                write!(f, "Bad meshopt data of buffer view #{}: {}", index, field)
+            }
+            GltfLoaderError::BadDracoData(index, field) => {
+                write!(f, "Bad Draco data of mesh #{}: {}", index, field)
+            }
+            GltfLoaderError::DracoDecode(index, error) => {
+                write!(f, "Bad Draco stream of mesh #{}: {}", index, error)
+            }
+            GltfLoaderError::BadKtx2(name, error) => {
+                write!(f, "Bad KTX2 image {}: {}", name, error)
+            }
+            GltfLoaderError::MissingTextureSource(index) => {
+                write!(f, "Missing image source for texture #{}", index)
+            }
+            GltfLoaderError::BadMaterialExtension(extension) => {
+                write!(f, "Bad material extension data: {}", extension)
+            }
         }
     }
 }
@@ -183,7 +229,12 @@ enum AnimationPathType {
 struct GltfData {
     bundle_index: BundleIndex,
     buffers: Vec<gltf::buffer::Data>,
-    images: Vec<gltf::image::Data>,
+    // Buffer views decoded from EXT_meshopt_compression
+    views: HashMap<usize, Vec<u8>>,
+    // Accessors decoded from KHR_draco_mesh_compression
+    accessors: HashMap<usize, Vec<u8>>,
+    // Only images used by textures are loaded
+    images: Vec<Option<gltf::image::Data>>,
 }
 
 struct GltfDocumentLoader<'a, E> {
@@ -196,7 +247,8 @@ struct GltfDocumentLoader<'a, E> {
     _markor: PhantomData<E>,
 }
 
-type GLTFLoadResult<E> = Result<(Vec<SceneAsset>, Vec<AnimationAsset>), GltfLoaderError<E>>;
+type GLTFLoadResult<E> =
+    Result<(Vec<SceneAsset>, Vec<AnimationAsset>, Option<VrmAsset>), GltfLoaderError<E>>;
 
 impl<'a, E> GltfDocumentLoader<'a, E> {
     fn new(document: &'a Document, data: &'a GltfData, params: &'a AssetLoadParams) -> Self {
@@ -212,19 +264,10 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
     }
 
     #[inline]
-    fn check_accessor(
+    fn check_dimensions(
         accessor: &Accessor,
-        data_type: DataType,
         dimensions: Dimensions,
     ) -> Result<(), GltfLoaderError<E>> {
-        let actual_data_type = accessor.data_type();
-        if actual_data_type != data_type {
-            return Err(GltfLoaderError::BadAccessorDataType(
-                data_type,
-                actual_data_type,
-            ));
-        }
-
         let actual_dimensions = accessor.dimensions();
         if actual_dimensions != dimensions {
             return Err(GltfLoaderError::BadAccessorDimensions(
@@ -238,6 +281,9 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
 
     #[inline]
     fn load_accessor(data: &GltfData, accessor: &Accessor) -> Vec<u8> {
+        if let Some(accessor_data) = data.accessors.get(&accessor.index()) {
+            return accessor_data.clone();
+        }
         let view = if let Some(view) = accessor.view() {
             view
         } else {
@@ -263,8 +309,13 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
         let item_length: usize = num_size * item_size;
 
         let count = accessor.count();
-        let buffer = &data.buffers[view.buffer().index()];
-        let offset = accessor.offset() + view.offset();
+        let (buffer, offset) = match data.views.get(&view.index()) {
+            Some(view_data) => (view_data.as_slice(), accessor.offset()),
+            None => (
+                data.buffers[view.buffer().index()].0.as_slice(),
+                accessor.offset() + view.offset(),
+            ),
+        };
         let stride = view.stride().unwrap_or(item_length);
         let interval = stride - item_length;
         let full_length = count * (item_length + interval);
@@ -273,7 +324,7 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
 
         if stride == 0 {
             (offset..offset + full_length)
-                .map(|index| buffer.0[index])
+                .map(|index| buffer[index])
                 .collect()
         } else {
             let read_length = item_length * count;
@@ -282,7 +333,7 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
             let mut index = offset;
             let mut read_amount = 0;
             while read_amount < read_length {
-                result.push(buffer.0[index]);
+                result.push(buffer[index]);
                 read_amount += 1;
                 index += 1;
                 chunk_left -= 1;
@@ -320,6 +371,32 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
             .collect()
     }
 
+    #[inline]
+    fn normalize_i8(data: Vec<u8>) -> Vec<f32> {
+        data.into_iter()
+            .map(|item| (item as i8 as f32 / i8::MAX as f32).max(-1.0))
+            .collect()
+    }
+
+    #[inline]
+    fn load_accessor_i16(data: &GltfData, accessor: &Accessor) -> Vec<i16> {
+        assert_eq!(accessor.data_type(), DataType::I16);
+        let data = Self::load_accessor(data, accessor);
+        data.chunks_exact(2)
+            .map(|chunk| {
+                let array = chunk.try_into().unwrap();
+                i16::from_le_bytes(array)
+            })
+            .collect()
+    }
+
+    #[inline]
+    fn normalize_i16(data: Vec<i16>) -> Vec<f32> {
+        data.into_iter()
+            .map(|item| (item as f32 / i16::MAX as f32).max(-1.0))
+            .collect()
+    }
+
     #[inline]
     fn load_accessor_u16(data: &GltfData, accessor: &Accessor) -> Vec<u16> {
         assert_eq!(accessor.data_type(), DataType::U16);
@@ -361,17 +438,49 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
     #[inline]
     fn load_accessor_normalized(data: &GltfData, accessor: &Accessor) -> Vec<f32> {
         match accessor.data_type() {
+            DataType::I8 => Self::normalize_i8(Self::load_accessor(data, accessor)),
             DataType::U8 => Self::normalize_u8(Self::load_accessor_u8(data, accessor)),
+            DataType::I16 => Self::normalize_i16(Self::load_accessor_i16(data, accessor)),
             DataType::U16 => Self::normalize_u16(Self::load_accessor_u16(data, accessor)),
             DataType::U32 => Self::normalize_u32(Self::load_accessor_u32(data, accessor)),
             DataType::F32 => Self::load_accessor_f32(data, accessor),
-            _ => unreachable!("Invalid normalized data"),
+        }
+    }
+
+    /// Load any component type as floats, as allowed by `KHR_mesh_quantization`.
+    #[inline]
+    fn load_accessor_float(data: &GltfData, accessor: &Accessor) -> Vec<f32> {
+        if accessor.normalized() {
+            return Self::load_accessor_normalized(data, accessor);
+        }
+        match accessor.data_type() {
+            DataType::I8 => Self::load_accessor(data, accessor)
+                .into_iter()
+                .map(|item| item as i8 as f32)
+                .collect(),
+            DataType::U8 => Self::load_accessor_u8(data, accessor)
+                .into_iter()
+                .map(|item| item as f32)
+                .collect(),
+            DataType::I16 => Self::load_accessor_i16(data, accessor)
+                .into_iter()
+                .map(|item| item as f32)
+                .collect(),
+            DataType::U16 => Self::load_accessor_u16(data, accessor)
+                .into_iter()
+                .map(|item| item as f32)
+                .collect(),
+            DataType::U32 => Self::load_accessor_u32(data, accessor)
+                .into_iter()
+                .map(|item| item as f32)
+                .collect(),
+            DataType::F32 => Self::load_accessor_f32(data, accessor),
         }
     }
 
     fn load_vec2_value(value: &Value) -> Result<[f32; 2], GltfLoaderError<E>> {
         let array = value.as_array().ok_or(GltfLoaderError::BadMToonData)?;
-        if array.len() == 3 {
+        if array.len() == 2 {
             let x = array[0].as_f64().ok_or(GltfLoaderError::BadMToonData)? as f32;
             let y = array[1].as_f64().ok_or(GltfLoaderError::BadMToonData)? as f32;
             Ok([x, y])
@@ -463,8 +572,8 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
 
     fn load_texture(&mut self, texture: Texture) -> Result<Arc<TextureAsset>, GltfLoaderError<E>> {
         let sampler = Self::load_texture_sampler(texture.sampler());
-        let image = texture.source();
-        let index: usize = image.index();
+        let index = texture_image(&texture)
+            .ok_or(GltfLoaderError::MissingTextureSource(texture.index()))?;
         let id = AssetIndex::BundleTypeIndex(
             self.data.bundle_index.clone(),
             BundleAssetType::Texture,
@@ -475,7 +584,9 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
             return Ok(asset.clone());
         }
 
-        let image = &self.data.images[index];
+        let image = self.data.images[index]
+            .as_ref()
+            .ok_or(GltfLoaderError::MissingTextureSource(texture.index()))?;
         let format = match image.format {
             Format::R8 => TextureAssetFormat::Ru8,
             Format::R8G8 => TextureAssetFormat::Rgu8,
@@ -536,12 +647,12 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
                 .transpose()?
                 .unwrap_or(0.0),
             scale: transform
-                .get("offset")
+                .get("scale")
                 .map(Self::load_vec2_value)
                 .transpose()?
                 .unwrap_or([1.0, 1.0]),
             tex_coord: transform
-                .get("rotation")
+                .get("texCoord")
                 .map(|value| {
                     value
                         .as_u64()
@@ -569,7 +680,8 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
         Ok(TextureInfo {
             texture: self.load_texture(texture)?,
             transform: value
-                .get("transform")
+                .get("extensions")
+                .and_then(|extensions| extensions.get("KHR_texture_transform"))
                 .map(Self::load_texture_transform_from_value)
                 .transpose()?,
             tex_coord: value
@@ -616,21 +728,11 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
         &mut self,
         value: &Value,
     ) -> Result<UvAnimation, GltfLoaderError<E>> {
-        let mask_texture_index = value
+        let mask_texture = value
             .get("uvAnimationMaskTexture")
-            .map(|index| index.as_u64().ok_or(GltfLoaderError::BadMToonData))
+            .map(|texture| self.load_texture_info_from_value(texture))
             .transpose()?
-            .map(|v| v as usize);
-        let mask_texture = mask_texture_index
-            .map(|index| {
-                self.document
-                    .textures()
-                    .nth(index)
-                    .ok_or(GltfLoaderError::BadMToonData)
-            })
-            .transpose()?
-            .map(|texture| self.load_texture(texture))
-            .transpose()?;
+            .map(|info| info.texture);
 
         let scroll_x_speed_factor = value
             .get("uvAnimationScrollXSpeedFactor")
@@ -659,6 +761,72 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
         })
     }
 
+    /// Values of other material extensions are read with the MToon helpers,
+    /// so report their errors with the extension name instead.
+    fn extension_error(
+        extension: &'static str,
+    ) -> impl Fn(GltfLoaderError<E>) -> GltfLoaderError<E> {
+        move |error| match error {
+            GltfLoaderError::BadMToonData => GltfLoaderError::BadMaterialExtension(extension),
+            error => error,
+        }
+    }
+
+    fn load_f32_value(value: Option<&Value>, default: f32) -> Result<f32, GltfLoaderError<E>> {
+        value
+            .map(|value| value.as_f64().ok_or(GltfLoaderError::BadMToonData))
+            .transpose()
+            .map(|value| value.map_or(default, |value| value as f32))
+    }
+
+    fn load_clearcoat_from_value(
+        &mut self,
+        value: &Value,
+    ) -> Result<PbrClearcoat, GltfLoaderError<E>> {
+        Ok(PbrClearcoat {
+            factor: Self::load_f32_value(value.get("clearcoatFactor"), 0.0)?,
+            texture: value
+                .get("clearcoatTexture")
+                .map(|texture| self.load_texture_info_from_value(texture))
+                .transpose()?,
+            roughness_factor: Self::load_f32_value(value.get("clearcoatRoughnessFactor"), 0.0)?,
+            roughness_texture: value
+                .get("clearcoatRoughnessTexture")
+                .map(|texture| self.load_texture_info_from_value(texture))
+                .transpose()?,
+            normal_texture: value
+                .get("clearcoatNormalTexture")
+                .map(|texture| {
+                    let info = self.load_texture_info_from_value(texture)?;
+                    Ok::<_, GltfLoaderError<E>>(NormalTextureInfo {
+                        texture: info.texture,
+                        tex_coord: info.tex_coord,
+                        scale: Self::load_f32_value(texture.get("scale"), 1.0)?,
+                    })
+                })
+                .transpose()?,
+        })
+    }
+
+    fn load_sheen_from_value(&mut self, value: &Value) -> Result<PbrSheen, GltfLoaderError<E>> {
+        Ok(PbrSheen {
+            color_factor: value
+                .get("sheenColorFactor")
+                .map(Self::load_vec3_value)
+                .transpose()?
+                .unwrap_or([0.0, 0.0, 0.0]),
+            color_texture: value
+                .get("sheenColorTexture")
+                .map(|texture| self.load_texture_info_from_value(texture))
+                .transpose()?,
+            roughness_factor: Self::load_f32_value(value.get("sheenRoughnessFactor"), 0.0)?,
+            roughness_texture: value
+                .get("sheenRoughnessTexture")
+                .map(|texture| self.load_texture_info_from_value(texture))
+                .transpose()?,
+        })
+    }
+
     fn load_material(
         &mut self,
         material: Material,
@@ -821,6 +989,66 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
                     .metallic_roughness_texture()
                     .map(|texture| self.load_texture_info(texture))
                     .transpose()?,
+                ior: material.ior().unwrap_or(1.5),
+                clearcoat: material
+                    .extension_value(CLEARCOAT_EXTENSION)
+                    .map(|clearcoat| {
+                        self.load_clearcoat_from_value(clearcoat)
+                            .map(Box::new)
+                            .map_err(Self::extension_error(CLEARCOAT_EXTENSION))
+                    })
+                    .transpose()?,
+                transmission: material
+                    .transmission()
+                    .map(|transmission| {
+                        Ok::<_, GltfLoaderError<E>>(Box::new(PbrTransmission {
+                            factor: transmission.transmission_factor(),
+                            texture: transmission
+                                .transmission_texture()
+                                .map(|texture| self.load_texture_info(texture))
+                                .transpose()?,
+                        }))
+                    })
+                    .transpose()?,
+                sheen: material
+                    .extension_value(SHEEN_EXTENSION)
+                    .map(|sheen| {
+                        self.load_sheen_from_value(sheen)
+                            .map(Box::new)
+                            .map_err(Self::extension_error(SHEEN_EXTENSION))
+                    })
+                    .transpose()?,
+                specular: material
+                    .specular()
+                    .map(|specular| {
+                        Ok::<_, GltfLoaderError<E>>(Box::new(PbrSpecular {
+                            factor: specular.specular_factor(),
+                            texture: specular
+                                .specular_texture()
+                                .map(|texture| self.load_texture_info(texture))
+                                .transpose()?,
+                            color_factor: specular.specular_color_factor(),
+                            color_texture: specular
+                                .specular_color_texture()
+                                .map(|texture| self.load_texture_info(texture))
+                                .transpose()?,
+                        }))
+                    })
+                    .transpose()?,
+                volume: material
+                    .volume()
+                    .map(|volume| {
+                        Ok::<_, GltfLoaderError<E>>(Box::new(PbrVolume {
+                            thickness_factor: volume.thickness_factor(),
+                            thickness_texture: volume
+                                .thickness_texture()
+                                .map(|texture| self.load_texture_info(texture))
+                                .transpose()?,
+                            attenuation_distance: volume.attenuation_distance(),
+                            attenuation_color: volume.attenuation_color(),
+                        }))
+                    })
+                    .transpose()?,
             }
         };
 
@@ -869,6 +1097,7 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
             occlusion_texture,
             emissive_texture,
             emissive_factor: material.emissive_factor(),
+            emissive_strength: material.emissive_strength().unwrap_or(1.0),
             double_sided: material.double_sided(),
             uv_animation,
         });
@@ -880,22 +1109,20 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
     fn load_primitive_morph_target(
         &self,
         target: MorphTarget,
-        mode: PrimitiveAssetMode,
-        indices: Option<&[u32]>,
     ) -> Result<PrimitiveAssetMorphTarget, GltfLoaderError<E>> {
         let position = target
             .positions()
             .map(|accessor| {
-                Self::check_accessor(&accessor, DataType::F32, Dimensions::Vec3)?;
-                let data = Self::load_accessor_f32(self.data, &accessor);
+                Self::check_dimensions(&accessor, Dimensions::Vec3)?;
+                let data = Self::load_accessor_float(self.data, &accessor);
                 Ok::<_, GltfLoaderError<E>>(chunk_vec3(&data))
             })
             .transpose()?;
         let normal = target
             .normals()
             .map(|accessor| {
-                Self::check_accessor(&accessor, DataType::F32, Dimensions::Vec3)?;
-                let data = Self::load_accessor_f32(self.data, &accessor);
+                Self::check_dimensions(&accessor, Dimensions::Vec3)?;
+                let data = Self::load_accessor_float(self.data, &accessor);
                 Ok::<_, GltfLoaderError<E>>(chunk_vec3(&data))
             })
             .transpose()?
@@ -903,16 +1130,11 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
         let tangent = target
             .tangents()
             .map(|accessor| {
-                Self::check_accessor(&accessor, DataType::F32, Dimensions::Vec4)?;
-                let data = Self::load_accessor_f32(self.data, &accessor);
+                Self::check_dimensions(&accessor, Dimensions::Vec4)?;
+                let data = Self::load_accessor_float(self.data, &accessor);
                 Ok::<_, GltfLoaderError<E>>(chunk_vec4(&data))
             })
             .transpose()?
-            .or_else(|| {
-                position.as_ref().map(|position| {
-                    pad_vec3_to_vec4(&calculate_tangent(mode, position.as_slice(), indices), 1.0)
-                })
-            })
             .unwrap_or_default();
 
         Ok(PrimitiveAssetMorphTarget {
@@ -964,18 +1186,18 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
         for (semantic, accessor) in primitive.attributes() {
             match semantic {
                 Semantic::Positions => {
-                    Self::check_accessor(&accessor, DataType::F32, Dimensions::Vec3)?;
-                    let data = Self::load_accessor_f32(self.data, &accessor);
+                    Self::check_dimensions(&accessor, Dimensions::Vec3)?;
+                    let data = Self::load_accessor_float(self.data, &accessor);
                     position = Some(chunk_vec3(&data));
                 }
                 Semantic::Normals => {
-                    Self::check_accessor(&accessor, DataType::F32, Dimensions::Vec3)?;
-                    let data = Self::load_accessor_f32(self.data, &accessor);
+                    Self::check_dimensions(&accessor, Dimensions::Vec3)?;
+                    let data = Self::load_accessor_float(self.data, &accessor);
                     normal = Some(chunk_vec3(&data));
                 }
                 Semantic::Tangents => {
-                    Self::check_accessor(&accessor, DataType::F32, Dimensions::Vec4)?;
-                    let data = Self::load_accessor_f32(self.data, &accessor);
+                    Self::check_dimensions(&accessor, Dimensions::Vec4)?;
+                    let data = Self::load_accessor_float(self.data, &accessor);
                     tangent = Some(chunk_vec4(&data));
                 }
                 Semantic::Colors(index) => {
@@ -995,13 +1217,8 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
                 }
                 Semantic::TexCoords(index) => {
                     ensure_size(&mut tex_coords, index as usize + 1);
-                    if accessor.dimensions() != Dimensions::Vec2 {
-                        return Err(GltfLoaderError::BadAccessorDimensions(
-                            Dimensions::Vec2,
-                            accessor.dimensions(),
-                        ));
-                    }
-                    let data = Self::load_accessor_normalized(self.data, &accessor);
+                    Self::check_dimensions(&accessor, Dimensions::Vec2)?;
+                    let data = Self::load_accessor_float(self.data, &accessor);
                     let coords: Vec<_> = data
                         .chunks_exact(2)
                         .map(|chunk| chunk.try_into().unwrap())
@@ -1079,21 +1296,18 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
             Mode::TriangleStrip => PrimitiveAssetMode::TriangleStrip,
             unsupported => return Err(GltfLoaderError::UnsupportedPrimitiveMode(unsupported)),
         };
-        let tangent = tangent.unwrap_or_else(|| {
-            pad_vec3_to_vec4(&calculate_tangent(mode, &position, indices.as_deref()), 1.0)
-        });
-        let normal = normal.unwrap_or_else(|| clip_vec4_to_vec3(&tangent));
-
         let targets = primitive
             .morph_targets()
-            .map(|target| self.load_primitive_morph_target(target, mode, indices.as_deref()))
+            .map(|target| self.load_primitive_morph_target(target))
             .collect::<Result<_, _>>()?;
 
-        Ok(PrimitiveAsset {
+        let has_normal = normal.is_some();
+        let has_tangent = tangent.is_some();
+        let mut primitive = PrimitiveAsset {
             attributes: PrimitiveAssetAttributes {
                 position,
-                normal,
-                tangent,
+                normal: normal.unwrap_or_default(),
+                tangent: tangent.unwrap_or_default(),
                 tex_coord,
                 color,
                 joints,
@@ -1103,7 +1317,15 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
             material,
             mode,
             targets,
-        })
+        };
+        // The spec asks for flat normals, and tangents from the normals
+        if !has_normal {
+            primitive.generate_flat_normals();
+        }
+        if !has_normal || !has_tangent {
+            primitive.generate_tangents();
+        }
+        Ok(primitive)
     }
 
     fn load_mesh(&mut self, mesh: Mesh) -> Result<MeshAsset, GltfLoaderError<E>> {
@@ -1347,8 +1569,214 @@ impl<'a, E> GltfDocumentLoader<'a, E> {
             .scenes()
             .map(|scene| self.load_scene(scene))
             .collect::<Result<_, _>>()?;
-        Ok((scenes, animations))
+        let vrm = vrm::load_vrm(self.document, &self.data.bundle_index)?;
+        Ok((scenes, animations, vrm))
+    }
+}
+
+/// Extensions which can be listed in `extensionsRequired`.
+const SUPPORTED_EXTENSIONS: &[&str] = &[
+    "KHR_materials_unlit",
+    "KHR_texture_transform",
+    "KHR_materials_emissive_strength",
+    CLEARCOAT_EXTENSION,
+    "KHR_materials_transmission",
+    SHEEN_EXTENSION,
+    "KHR_materials_specular",
+    "KHR_materials_ior",
+    "KHR_materials_volume",
+    "KHR_mesh_quantization",
+    meshopt::EXTENSION,
+    draco::EXTENSION,
+    ktx2::EXTENSION,
+];
+
+/// Parse a glTF or GLB file, returning the document and the GLB binary chunk.
+fn load_document<E>(data: &[u8]) -> Result<(Document, Option<Vec<u8>>), GltfLoaderError<E>> {
+    let gltf = Gltf::from_slice_without_validation(data)?;
+    let mut json = gltf.document.into_json();
+    if let Some(extension) = json
+        .extensions_required
+        .iter()
+        .find(|extension| !SUPPORTED_EXTENSIONS.contains(&extension.as_str()))
+    {
+        return Err(GltfLoaderError::UnsupportedExtension(extension.clone()));
+    }
+    // Validation rejects required extensions unknown to the gltf crate
+    json.extensions_required
+        .retain(|extension| ENABLED_EXTENSIONS.contains(&extension.as_str()));
+    draco::assign_views(&mut json);
+    Ok((Document::from_json(json)?, gltf.blob))
+}
+
+/// Index of the image used by a texture, preferring the `KHR_texture_basisu`
+/// source over the core one, which is a fallback for other loaders.
+fn texture_image(texture: &Texture) -> Option<usize> {
+    texture
+        .extension_value(ktx2::EXTENSION)
+        .and_then(|extension| extension.get("source")?.as_u64())
+        .map(|index| index as usize)
+        .or_else(|| texture.source().map(|image| image.index()))
+}
+
+fn load_image<E>(
+    source: GltfImageSource,
+    data: &[u8],
+    mime: Option<&str>,
+) -> Result<gltf::image::Data, GltfLoaderError<E>> {
+    let is_ktx2 = match mime {
+        Some(mime) => mime.eq_ignore_ascii_case(ktx2::MIME),
+        None => ktx2::is_ktx2(data),
+    };
+    if is_ktx2 {
+        return ktx2::load(data).map_err(|error| GltfLoaderError::BadKtx2(source, error));
     }
+
+    let image_format = if let Some(mime) = mime {
+        ImageFormat::from_mime_type(mime)
+            .ok_or_else(|| GltfLoaderError::BadImageMime(source.clone(), mime.to_string()))?
+    } else {
+        guess_format(data).map_err(|error| GltfLoaderError::BadImage(source.clone(), error))?
+    };
+    let mut reader = ImageReader::new(Cursor::new(data));
+    reader.set_format(image_format);
+    let image = reader
+        .decode()
+        .map_err(|error| GltfLoaderError::BadImage(source.clone(), error))?;
+
+    let format = match image {
+        DynamicImage::ImageLuma8(_) => Format::R8,
+        DynamicImage::ImageLumaA8(_) => Format::R8G8,
+        DynamicImage::ImageRgb8(_) => Format::R8G8B8,
+        DynamicImage::ImageRgba8(_) => Format::R8G8B8A8,
+        DynamicImage::ImageLuma16(_) => Format::R16,
+        DynamicImage::ImageLumaA16(_) => Format::R16G16,
+        DynamicImage::ImageRgb16(_) => Format::R16G16B16,
+        DynamicImage::ImageRgba16(_) => Format::R16G16B16A16,
+        DynamicImage::ImageRgb32F(_) => Format::R32G32B32FLOAT,
+        DynamicImage::ImageRgba32F(_) => Format::R32G32B32A32FLOAT,
+        _unsupported => return Err(GltfLoaderError::BadImageFormat(source)),
+    };
+    let (width, height) = image.dimensions();
+    let pixels = image.into_bytes();
+    Ok(gltf::image::Data {
+        format,
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Load buffers and images of a document, resolving URIs with `resolve`.
+fn load_data<E>(
+    document: &Document,
+    mut blob: Option<Vec<u8>>,
+    bundle_index: BundleIndex,
+    mut resolve: impl for<'s> FnMut(&Scheme<'s>) -> Result<Option<SchemeData<'s>>, GltfLoaderError<E>>,
+) -> Result<GltfData, GltfLoaderError<E>> {
+    let mut buffers = Vec::new();
+    for buffer in document.buffers() {
+        let mut data = match buffer.source() {
+            gltf::buffer::Source::Uri(uri) => {
+                let scheme = Scheme::try_from(uri)?;
+
+                // If MIME is specified, check the MIME
+                if let Scheme::Data(mime, _) = &scheme {
+                    if let Some(mime) = mime {
+                        if !mime.eq_ignore_ascii_case("application/octet-stream")
+                            && !mime.eq_ignore_ascii_case("application/gltf-buffer")
+                        {
+                            return Err(GltfLoaderError::BadBufferMime(
+                                uri.to_string(),
+                                Some(mime.to_string()),
+                            ));
+                        }
+                    } else {
+                        return Err(GltfLoaderError::BadBufferMime(uri.to_string(), None));
+                    }
+                }
+
+                let Some((_mime, data)) = resolve(&scheme)? else {
+                    return Err(GltfLoaderError::ResourceNotFound(uri.to_string()));
+                };
+                data
+            }
+            // Meshopt fallback buffers are never read
+            gltf::buffer::Source::Bin if meshopt::is_fallback(&buffer) => {
+                buffers.push(gltf::buffer::Data(Vec::new()));
+                continue;
+            }
+            gltf::buffer::Source::Bin => blob.take().ok_or(GltfLoaderError::BadModelFile)?,
+        };
+        if data.len() < buffer.length() {
+            return Err(GltfLoaderError::Gltf(gltf::Error::BufferLength {
+                buffer: buffer.index(),
+                expected: buffer.length(),
+                actual: data.len(),
+            }));
+        }
+
+        // Pad the data to 4 bytes with zeroes
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+
+        buffers.push(gltf::buffer::Data(data));
+    }
+
+    let views = meshopt::decode_views(document, &buffers)?;
+    let accessors = draco::decode_accessors(document, &buffers)?;
+
+    let mut images: Vec<Option<gltf::image::Data>> = document.images().map(|_| None).collect();
+    for texture in document.textures() {
+        let image = texture_image(&texture)
+            .and_then(|index| document.images().nth(index))
+            .ok_or(GltfLoaderError::MissingTextureSource(texture.index()))?;
+        let index = image.index();
+        if images[index].is_some() {
+            continue;
+        }
+
+        let data =
+            match image.source() {
+                gltf::image::Source::View {
+                    view,
+                    mime_type: mime,
+                } => {
+                    let buffer_index = view.buffer().index();
+                    let buffer = buffers.get(buffer_index).ok_or(
+                        GltfLoaderError::ImageBufferOutOfBounds(index, buffer_index, buffers.len()),
+                    )?;
+                    let data = buffer
+                        .get(view.offset()..view.offset() + view.length())
+                        .ok_or(GltfLoaderError::BadModelFile)?;
+                    load_image(GltfImageSource::Buffer(buffer_index), data, Some(mime))?
+                }
+                gltf::image::Source::Uri {
+                    uri,
+                    mime_type: mime,
+                } => {
+                    let scheme = Scheme::try_from(uri)?;
+                    let Some((load_mime, data)) = resolve(&scheme)? else {
+                        return Err(GltfLoaderError::ResourceNotFound(uri.to_string()));
+                    };
+                    load_image(
+                        GltfImageSource::Uri(uri.to_string()),
+                        data.as_slice(),
+                        mime.or(load_mime),
+                    )?
+                }
+            };
+        images[index] = Some(data);
+    }
+
+    Ok(GltfData {
+        bundle_index,
+        buffers,
+        views,
+        accessors,
+        images,
+    })
 }
 
 /// Load a GLB file from a slice.
@@ -1361,17 +1789,18 @@ pub fn load_glb_from_buffer(buffer: &[u8], params: &AssetLoadParams) -> GLTFLoad
 }
 
 /// Load a GLB file from a slice, with a BundleIndex specified.
+///
+/// Only data URIs are accepted besides the binary chunk.
 pub fn load_glb_from_buffer_with_id(
     buffer: &[u8],
     id: BundleIndex,
     params: &AssetLoadParams,
 ) -> GLTFLoadResult<io::Error> {
-    let (document, buffers, images) = gltf::import_slice(buffer)?;
-    let data = GltfData {
-        bundle_index: id,
-        buffers,
-        images,
-    };
+    let (document, blob) = load_document(buffer)?;
+    let data = load_data(&document, blob, id, |scheme| match scheme {
+        Scheme::Data(mime, data) => Ok(Some((*mime, data.clone()))),
+        _ => Err(SchemeError::Unsupported.into()),
+    })?;
     let mut loader = GltfDocumentLoader::new(&document, &data, params);
     loader.load()
 }
@@ -1394,153 +1823,247 @@ pub fn load_gltf_from_archive<T, A: Archive<T>>(
         .ok_or_else(|| GltfLoaderError::ModelNotFound(file_name.clone()))?;
     let gltf_data = gltf_entry.unpack().map_err(GltfLoaderError::Io)?;
     drop(gltf_entry);
-    let gltf = Gltf::from_slice(&gltf_data)?;
-
-    let mut buffers = Vec::new();
-    for buffer in gltf.buffers() {
-        let uri = match buffer.source() {
-            gltf::buffer::Source::Bin => return Err(GltfLoaderError::BadModelFile),
-            gltf::buffer::Source::Uri(uri) => uri,
-        };
-        let scheme = Scheme::try_from(uri)?;
-
-        // If MIME is specified, check the MIME
-        if let Scheme::Data(mime, _) = &scheme {
-            if let Some(mime) = mime {
-                if !mime.eq_ignore_ascii_case("application/octet-stream")
-                    && mime.eq_ignore_ascii_case("application/gltf-buffer")
-                {
-                    return Err(GltfLoaderError::BadBufferMime(
-                        uri.to_string(),
-                        Some(mime.to_string()),
-                    ));
-                }
-            } else {
-                return Err(GltfLoaderError::BadBufferMime(uri.to_string(), None));
-            }
-        }
 
-        // Load data from archive
-        let Some((_mime, mut data)) = scheme
-            .load(archive, &file_name)
-            .map_err(GltfLoaderError::Io)?
-        else {
-            return Err(GltfLoaderError::ResourceNotFound(uri.to_string()));
-        };
+    // Relative URIs are resolved against the directory of the model
+    let base = Path::new(&file_name)
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let (document, blob) = load_document(&gltf_data)?;
+    let data = load_data(&document, blob, id, |scheme| {
+        scheme.load(archive, &base).map_err(GltfLoaderError::Io)
+    })?;
+    let mut loader = GltfDocumentLoader::new(&document, &data, params);
+    loader.load()
+}
 
-        // Pad the data to 4 bytes with zeroes
-        while data.len() % 4 != 0 {
-            data.push(0);
-        }
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use super::{load_glb_from_buffer_with_id, GLTFLoadResult, GltfLoaderError};
+    use crate::{index::BundleIndex, loader::AssetLoadParams, material::MaterialAssetData};
+
+    fn load(extensions_required: &str, material: &str) -> GLTFLoadResult<io::Error> {
+        // Normalized i16 positions with 4 byte stride padding
+        let document = format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "extensionsUsed": {0},
+                "extensionsRequired": {0},
+                "buffers": [{{
+                    "uri": "data:application/octet-stream;base64,/38AAAAAAAAAAAGAAAAAAAAAAAAAQAAA",
+                    "byteLength": 24
+                }}],
+                "bufferViews": [{{ "buffer": 0, "byteLength": 24, "byteStride": 8 }}],
+                "accessors": [{{
+                    "bufferView": 0, "componentType": 5122, "normalized": true, "count": 3,
+                    "type": "VEC3", "min": [0, -32767, 0], "max": [32767, 0, 16384]
+                }}],
+                "images": [{{
+                    "uri": "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAIAAACQd1PeAAAADElEQVR4nGP4//8/AAX+Av4N70a4AAAAAElFTkSuQmCC"
+                }}],
+                "textures": [{{ "source": 0 }}],
+                "materials": [{1}],
+                "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0 }}, "material": 0 }}] }}],
+                "nodes": [{{ "mesh": 0 }}],
+                "scenes": [{{ "nodes": [0] }}]
+            }}"#,
+            extensions_required, material
+        );
+        load_glb_from_buffer_with_id(
+            document.as_bytes(),
+            BundleIndex([0; 32]),
+            &AssetLoadParams::default(),
+        )
+    }
 
-        buffers.push(gltf::buffer::Data(data));
+    #[test]
+    fn test_quantized_positions() {
+        let (scenes, _, _) = load(r#"["KHR_mesh_quantization"]"#, "{}").unwrap();
+        let mesh = scenes[0].nodes[0].mesh.as_ref().unwrap();
+        let position = &mesh.primitives[0].attributes.position;
+        assert_eq!(position[0], [1.0, 0.0, 0.0]);
+        assert_eq!(position[1], [0.0, -1.0, 0.0]);
+        assert!((position[2][2] - 0.5).abs() < 1e-4);
     }
 
-    let mut images = Vec::new();
-    for (index, buffer) in gltf.images().enumerate() {
-        fn load_image<E>(
-            source: GltfImageSource,
-            data: &[u8],
-            format: ImageFormat,
-        ) -> Result<gltf::image::Data, GltfLoaderError<E>> {
-            let mut reader = ImageReader::new(Cursor::new(data));
-            reader.set_format(format);
-            let image = reader
-                .decode()
-                .map_err(|error| GltfLoaderError::BadImage(source.clone(), error))?;
-
-            let format = match image {
-                DynamicImage::ImageLuma8(_) => Format::R8,
-                DynamicImage::ImageLumaA8(_) => Format::R8G8,
-                DynamicImage::ImageRgb8(_) => Format::R8G8B8,
-                DynamicImage::ImageRgba8(_) => Format::R8G8B8A8,
-                DynamicImage::ImageLuma16(_) => Format::R16,
-                DynamicImage::ImageLumaA16(_) => Format::R16G16,
-                DynamicImage::ImageRgb16(_) => Format::R16G16B16,
-                DynamicImage::ImageRgba16(_) => Format::R16G16B16A16,
-                DynamicImage::ImageRgb32F(_) => Format::R32G32B32FLOAT,
-                DynamicImage::ImageRgba32F(_) => Format::R32G32B32A32FLOAT,
-                _unsupported => return Err(GltfLoaderError::BadImageFormat(source)),
-            };
-            let (width, height) = image.dimensions();
-            let pixels = image.into_bytes();
-            Ok(gltf::image::Data {
-                format,
-                width,
-                height,
-                pixels,
-            })
+    #[test]
+    fn test_draco() {
+        // A quad with normals and texture coordinates
+        let data = include_bytes!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/fixtures/draco_quad.glb"
+        ));
+        let (scenes, _, _) =
+            load_glb_from_buffer_with_id(data, BundleIndex([0; 32]), &AssetLoadParams::default())
+                .unwrap();
+        let mesh = scenes[0].nodes[0].mesh.as_ref().unwrap();
+        let primitive = &mesh.primitives[0];
+        let attributes = &primitive.attributes;
+        assert_eq!(attributes.position.len(), 4);
+        assert_eq!(primitive.indices.as_ref().unwrap().len(), 6);
+
+        let corners = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        for corner in corners {
+            let vertex = attributes
+                .position
+                .iter()
+                .position(|position| {
+                    (position[0] - corner[0]).abs() < 1e-3
+                        && (position[1] - corner[1]).abs() < 1e-3
+                        && position[2].abs() < 1e-3
+                })
+                .unwrap();
+            let normal = attributes.normal[vertex];
+            assert!((normal[2] - 1.0).abs() < 1e-2);
+            let tex_coord = attributes.tex_coord[0][vertex];
+            assert!((tex_coord[0] - corner[0]).abs() < 1e-3);
+            assert!((tex_coord[1] - (1.0 - corner[1])).abs() < 1e-3);
+        }
+        for triangle in primitive.indices.as_ref().unwrap().chunks(3) {
+            assert!(triangle.iter().all(|&index| index < 4));
         }
+    }
+
+    #[test]
+    fn test_unsupported_extension() {
+        assert!(matches!(
+            load(r#"["KHR_mesh_quantization", "EXT_mesh_gpu_instancing"]"#, "{}"),
+            Err(GltfLoaderError::UnsupportedExtension(extension))
+                if extension == "EXT_mesh_gpu_instancing"
+        ));
+    }
 
-        match buffer.source() {
-            gltf::image::Source::View {
-                view,
-                mime_type: mime,
-            } => {
-                let buffer_index = view.buffer().index();
-
-                let data = buffers
-                    .get(buffer_index)
-                    .ok_or(GltfLoaderError::ImageBufferOutOfBounds(
-                        index,
-                        buffer_index,
-                        buffers.len(),
-                    ))?
-                    .0
-                    .as_slice();
-
-                let image_format = ImageFormat::from_mime_type(mime).ok_or_else(|| {
-                    GltfLoaderError::BadImageMime(
-                        GltfImageSource::Buffer(buffer_index),
-                        mime.to_string(),
-                    )
-                })?;
-
-                let data = load_image(GltfImageSource::Buffer(buffer_index), data, image_format)?;
-                images.push(data);
+    #[test]
+    fn test_mtoon_texture_transform() {
+        let material = r#"{
+            "extensions": {
+                "VRMC_materials_mtoon": {
+                    "shadeMultiplyTexture": {
+                        "index": 0,
+                        "extensions": {
+                            "KHR_texture_transform": {
+                                "offset": [0.5, 0.25],
+                                "rotation": 1.5,
+                                "scale": [2, 3],
+                                "texCoord": 1
+                            }
+                        }
+                    }
+                }
             }
-            gltf::image::Source::Uri {
-                uri,
-                mime_type: mime,
-            } => {
-                let scheme = Scheme::try_from(uri)?;
-                let Some((load_mime, data)) = scheme
-                    .load(archive, &file_name)
-                    .map_err(GltfLoaderError::Io)?
-                else {
-                    return Err(GltfLoaderError::ResourceNotFound(uri.to_string()));
-                };
+        }"#;
+        let (scenes, _, _) = load("[]", material).unwrap();
+        let mesh = scenes[0].nodes[0].mesh.as_ref().unwrap();
+        let material = mesh.primitives[0].material.as_ref().unwrap();
+        let MaterialAssetData::MTone {
+            shade_multiply_texture,
+            ..
+        } = &material.data
+        else {
+            panic!("Not a MToon material");
+        };
+        let transform = shade_multiply_texture
+            .as_ref()
+            .unwrap()
+            .transform
+            .as_ref()
+            .unwrap();
+        assert_eq!(transform.offset, [0.5, 0.25]);
+        assert_eq!(transform.rotation, 1.5);
+        assert_eq!(transform.scale, [2.0, 3.0]);
+        assert_eq!(transform.tex_coord, Some(1));
+    }
 
-                let mime = mime.or(load_mime);
-                let image_format = if let Some(mime) = mime {
-                    ImageFormat::from_mime_type(mime).ok_or_else(|| {
-                        GltfLoaderError::BadImageMime(
-                            GltfImageSource::Uri(uri.to_string()),
-                            mime.to_string(),
-                        )
-                    })?
-                } else {
-                    guess_format(data.as_slice()).map_err(|error| {
-                        GltfLoaderError::BadImage(GltfImageSource::Uri(uri.to_string()), error)
-                    })?
-                };
+    #[test]
+    fn test_mtoon_bad_vec2() {
+        let material = r#"{
+            "extensions": {
+                "VRMC_materials_mtoon": {
+                    "shadeMultiplyTexture": {
+                        "index": 0,
+                        "extensions": { "KHR_texture_transform": { "offset": [0.5, 0.25, 0] } }
+                    }
+                }
+            }
+        }"#;
+        assert!(matches!(
+            load("[]", material),
+            Err(GltfLoaderError::BadMToonData)
+        ));
+    }
 
-                let data = load_image(
-                    GltfImageSource::Uri(uri.to_string()),
-                    data.as_slice(),
-                    image_format,
-                )?;
-                images.push(data);
+    #[test]
+    fn test_mtoon_uv_animation_mask_texture() {
+        let material = r#"{
+            "extensions": {
+                "VRMC_materials_mtoon": {
+                    "uvAnimationMaskTexture": { "index": 0, "texCoord": 1 },
+                    "uvAnimationScrollXSpeedFactor": 0.5
+                }
             }
-        }
+        }"#;
+        let (scenes, _, _) = load("[]", material).unwrap();
+        let mesh = scenes[0].nodes[0].mesh.as_ref().unwrap();
+        let material = mesh.primitives[0].material.as_ref().unwrap();
+        let uv_animation = material.uv_animation.as_ref().unwrap();
+        assert_eq!(uv_animation.mask_texture.as_ref().unwrap().size, (1, 1));
+        assert_eq!(uv_animation.scroll_x_speed_factor, 0.5);
+
+        // The index alone is not a textureInfo
+        let material = r#"{
+            "extensions": {
+                "VRMC_materials_mtoon": { "uvAnimationMaskTexture": 0 }
+            }
+        }"#;
+        assert!(load("[]", material).is_err());
     }
 
-    let data = GltfData {
-        bundle_index: id,
-        buffers,
-        images,
-    };
-    let document = gltf.document;
-    let mut loader = GltfDocumentLoader::new(&document, &data, params);
-    loader.load()
+    #[test]
+    fn test_pbr_extensions() {
+        let material = r#"{
+            "emissiveFactor": [1, 1, 1],
+            "extensions": {
+                "KHR_materials_emissive_strength": { "emissiveStrength": 4 },
+                "KHR_materials_ior": { "ior": 1.4 },
+                "KHR_materials_clearcoat": { "clearcoatFactor": 1, "clearcoatRoughnessFactor": 0.2 },
+                "KHR_materials_sheen": { "sheenColorFactor": [1, 0, 0] },
+                "KHR_materials_transmission": { "transmissionFactor": 0.5 },
+                "KHR_materials_volume": { "thicknessFactor": 2, "attenuationColor": [0, 1, 0] }
+            }
+        }"#;
+        let (scenes, _, _) = load(r#"["KHR_materials_clearcoat"]"#, material).unwrap();
+        let mesh = scenes[0].nodes[0].mesh.as_ref().unwrap();
+        let material = mesh.primitives[0].material.as_ref().unwrap();
+        assert_eq!(material.emissive_strength, 4.0);
+        let MaterialAssetData::Pbr {
+            ior,
+            clearcoat,
+            transmission,
+            sheen,
+            specular,
+            volume,
+            ..
+        } = &material.data
+        else {
+            panic!("Not a PBR material");
+        };
+        assert_eq!(*ior, 1.4);
+        let clearcoat = clearcoat.as_ref().unwrap();
+        assert_eq!((clearcoat.factor, clearcoat.roughness_factor), (1.0, 0.2));
+        assert_eq!(sheen.as_ref().unwrap().color_factor, [1.0, 0.0, 0.0]);
+        assert_eq!(transmission.as_ref().unwrap().factor, 0.5);
+        assert!(specular.is_none());
+        let volume = volume.as_ref().unwrap();
+        assert_eq!(volume.thickness_factor, 2.0);
+        assert_eq!(volume.attenuation_color, [0.0, 1.0, 0.0]);
+        assert_eq!(volume.attenuation_distance, f32::INFINITY);
+
+        let material = r#"{ "extensions": { "KHR_materials_sheen": { "sheenColorFactor": 1 } } }"#;
+        assert!(matches!(
+            load("[]", material),
+            Err(GltfLoaderError::BadMaterialExtension("KHR_materials_sheen"))
+        ));
+    }
 }
@@ -38,10 +38,14 @@ impl<'a> TryFrom<&'a str> for Scheme<'a> {
     type Error = SchemeError;
 
     fn try_from(uri: &'a str) -> Result<Self, Self::Error> {
+        let has_prefix = |prefix: &str| {
+            uri.get(0..prefix.len())
+                .is_some_and(|head| head.eq_ignore_ascii_case(prefix))
+        };
         if uri.contains(':') {
-            if uri[0..5].eq_ignore_ascii_case("data:") {
+            if has_prefix("data:") {
                 // Data URI: rfc2397
-                let content = &uri[0..5];
+                let content = &uri[5..];
                 let Some((param, value)) = content.split_once(',') else {
                     return Err(SchemeError::BadDataUri);
                 };
@@ -60,10 +64,10 @@ impl<'a> TryFrom<&'a str> for Scheme<'a> {
                     // to guess actual content from the data.
                     Ok(Scheme::Data(None, Vec::from(value.as_bytes())))
                 }
-            } else if uri[0..7].eq_ignore_ascii_case("file://") {
-                return Ok(Scheme::Absolute(&uri[0..7]));
-            } else if uri[0..5].eq_ignore_ascii_case("file:") {
-                return Ok(Scheme::Absolute(&uri[0..5]));
+            } else if has_prefix("file://") {
+                return Ok(Scheme::Absolute(&uri[7..]));
+            } else if has_prefix("file:") {
+                return Ok(Scheme::Absolute(&uri[5..]));
             } else {
                 return Err(SchemeError::Unsupported);
             }
@@ -73,7 +77,7 @@ impl<'a> TryFrom<&'a str> for Scheme<'a> {
     }
 }
 
-type SchemeData<'a> = (Option<&'a str>, Vec<u8>);
+pub(crate) type SchemeData<'a> = (Option<&'a str>, Vec<u8>);
 
 impl<'a> Scheme<'a> {
     pub(crate) fn load<T, A: Archive<T>, P: AsRef<Path>>(
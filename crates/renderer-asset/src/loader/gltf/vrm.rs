@@ -0,0 +1,719 @@
+use std::collections::BTreeMap;
+
+use glam::Vec3;
+use gltf::{json::Value, Document};
+
+use crate::{
+    index::{AssetIndex, BundleAssetType, BundleIndex},
+    spring_bone::{
+        ColliderAsset, ColliderGroupAsset, ColliderShape, SpringAsset, SpringBoneAsset,
+        SpringJointAsset,
+    },
+    vrm::{
+        AvatarPermission, CommercialUsage, CreditNotation, ExpressionAsset, ExpressionName,
+        ExpressionOverride, ExpressionPreset, FirstPersonType, HumanBone, HumanoidAsset,
+        LookAtAsset, LookAtRangeMap, LookAtType, MaterialColorBind, MaterialColorType,
+        MeshAnnotation, Modification, MorphTargetBind, TextureTransformBind, VrmAsset, VrmMeta,
+    },
+};
+
+use super::GltfLoaderError;
+
+/// Field of the object, or null if missing.
+fn field<'a>(value: &'a Value, key: &str) -> &'a Value {
+    value.get(key).unwrap_or(&Value::Null)
+}
+
+fn load_f32<E>(value: &Value, key: &'static str, default: f32) -> Result<f32, GltfLoaderError<E>> {
+    match field(value, key) {
+        Value::Null => Ok(default),
+        value => value
+            .as_f64()
+            .map(|value| value as f32)
+            .ok_or(GltfLoaderError::BadVrmData(key)),
+    }
+}
+
+fn load_bool<E>(
+    value: &Value,
+    key: &'static str,
+    default: bool,
+) -> Result<bool, GltfLoaderError<E>> {
+    match field(value, key) {
+        Value::Null => Ok(default),
+        value => value.as_bool().ok_or(GltfLoaderError::BadVrmData(key)),
+    }
+}
+
+fn load_string<E>(value: &Value, key: &'static str) -> Result<Option<String>, GltfLoaderError<E>> {
+    match field(value, key) {
+        Value::Null => Ok(None),
+        value => value
+            .as_str()
+            .map(|value| Some(value.to_string()))
+            .ok_or(GltfLoaderError::BadVrmData(key)),
+    }
+}
+
+fn load_strings<E>(value: &Value, key: &'static str) -> Result<Vec<String>, GltfLoaderError<E>> {
+    load_array(value, key)?
+        .iter()
+        .map(|item| {
+            item.as_str()
+                .map(str::to_string)
+                .ok_or(GltfLoaderError::BadVrmData(key))
+        })
+        .collect()
+}
+
+fn load_array<'a, E>(
+    value: &'a Value,
+    key: &'static str,
+) -> Result<&'a [Value], GltfLoaderError<E>> {
+    match field(value, key) {
+        Value::Null => Ok(&[]),
+        value => value
+            .as_array()
+            .map(Vec::as_slice)
+            .ok_or(GltfLoaderError::BadVrmData(key)),
+    }
+}
+
+fn load_f32_array<const N: usize, E>(
+    value: &Value,
+    key: &'static str,
+    default: [f32; N],
+) -> Result<[f32; N], GltfLoaderError<E>> {
+    let array = match field(value, key) {
+        Value::Null => return Ok(default),
+        value => value.as_array().ok_or(GltfLoaderError::BadVrmData(key))?,
+    };
+    if array.len() != N {
+        return Err(GltfLoaderError::BadVrmData(key));
+    }
+    let mut result = [0.0; N];
+    for (item, value) in result.iter_mut().zip(array) {
+        *item = value.as_f64().ok_or(GltfLoaderError::BadVrmData(key))? as f32;
+    }
+    Ok(result)
+}
+
+fn load_index<E>(value: &Value, key: &'static str) -> Result<usize, GltfLoaderError<E>> {
+    field(value, key)
+        .as_u64()
+        .map(|index| index as usize)
+        .ok_or(GltfLoaderError::BadVrmData(key))
+}
+
+/// Parse an enumeration value, using the default when missing.
+fn load_enum<T: Default, E>(
+    value: &Value,
+    key: &'static str,
+    parse: impl FnOnce(&str) -> Option<T>,
+) -> Result<T, GltfLoaderError<E>> {
+    match field(value, key) {
+        Value::Null => Ok(T::default()),
+        value => value
+            .as_str()
+            .and_then(parse)
+            .ok_or(GltfLoaderError::BadVrmData(key)),
+    }
+}
+
+struct VrmLoader<'a> {
+    document: &'a Document,
+    bundle_index: &'a BundleIndex,
+}
+
+impl VrmLoader<'_> {
+    fn node_id<E>(
+        &self,
+        value: &Value,
+        key: &'static str,
+    ) -> Result<AssetIndex, GltfLoaderError<E>> {
+        let index = load_index(value, key)?;
+        if index >= self.document.nodes().len() {
+            return Err(GltfLoaderError::BadVrmData(key));
+        }
+        Ok(AssetIndex::BundleTypeIndex(
+            self.bundle_index.clone(),
+            BundleAssetType::Node,
+            index,
+        ))
+    }
+
+    fn material_id<E>(
+        &self,
+        value: &Value,
+        key: &'static str,
+    ) -> Result<AssetIndex, GltfLoaderError<E>> {
+        let index = load_index(value, key)?;
+        if index >= self.document.materials().len() {
+            return Err(GltfLoaderError::BadVrmData(key));
+        }
+        Ok(AssetIndex::BundleTypeIndex(
+            self.bundle_index.clone(),
+            BundleAssetType::Material,
+            index,
+        ))
+    }
+
+    fn load_meta<E>(&self, meta: &Value) -> Result<VrmMeta, GltfLoaderError<E>> {
+        Ok(VrmMeta {
+            name: load_string(meta, "name")?.ok_or(GltfLoaderError::BadVrmData("name"))?,
+            version: load_string(meta, "version")?,
+            authors: load_strings(meta, "authors")?,
+            copyright_information: load_string(meta, "copyrightInformation")?,
+            contact_information: load_string(meta, "contactInformation")?,
+            references: load_strings(meta, "references")?,
+            third_party_licenses: load_string(meta, "thirdPartyLicenses")?,
+            license_url: load_string(meta, "licenseUrl")?
+                .ok_or(GltfLoaderError::BadVrmData("licenseUrl"))?,
+            avatar_permission: load_enum(meta, "avatarPermission", |value| match value {
+                "onlyAuthor" => Some(AvatarPermission::OnlyAuthor),
+                "onlySeparatelyLicensedPerson" => {
+                    Some(AvatarPermission::OnlySeparatelyLicensedPerson)
+                }
+                "everyone" => Some(AvatarPermission::Everyone),
+                _ => None,
+            })?,
+            allow_excessively_violent_usage: load_bool(
+                meta,
+                "allowExcessivelyViolentUsage",
+                false,
+            )?,
+            allow_excessively_sexual_usage: load_bool(meta, "allowExcessivelySexualUsage", false)?,
+            commercial_usage: load_enum(meta, "commercialUsage", |value| match value {
+                "personalNonProfit" => Some(CommercialUsage::PersonalNonProfit),
+                "personalProfit" => Some(CommercialUsage::PersonalProfit),
+                "corporation" => Some(CommercialUsage::Corporation),
+                _ => None,
+            })?,
+            allow_political_or_religious_usage: load_bool(
+                meta,
+                "allowPoliticalOrReligiousUsage",
+                false,
+            )?,
+            allow_antisocial_or_hate_usage: load_bool(meta, "allowAntisocialOrHateUsage", false)?,
+            credit_notation: load_enum(meta, "creditNotation", |value| match value {
+                "required" => Some(CreditNotation::Required),
+                "unnecessary" => Some(CreditNotation::Unnecessary),
+                _ => None,
+            })?,
+            allow_redistribution: load_bool(meta, "allowRedistribution", false)?,
+            modification: load_enum(meta, "modification", |value| match value {
+                "prohibited" => Some(Modification::Prohibited),
+                "allowModification" => Some(Modification::AllowModification),
+                "allowModificationRedistribution" => {
+                    Some(Modification::AllowModificationRedistribution)
+                }
+                _ => None,
+            })?,
+            other_license_url: load_string(meta, "otherLicenseUrl")?,
+        })
+    }
+
+    fn load_humanoid<E>(&self, humanoid: &Value) -> Result<HumanoidAsset, GltfLoaderError<E>> {
+        let human_bones = field(humanoid, "humanBones")
+            .as_object()
+            .ok_or(GltfLoaderError::BadVrmData("humanBones"))?;
+        let mut bones = BTreeMap::new();
+        for (name, bone) in human_bones {
+            // Bones from later versions of the specification
+            let Some(human_bone) = HumanBone::from_name(name) else {
+                continue;
+            };
+            bones.insert(human_bone, self.node_id(bone, "node")?);
+        }
+        if let Some(bone) = HumanBone::REQUIRED
+            .into_iter()
+            .find(|bone| !bones.contains_key(bone))
+        {
+            return Err(GltfLoaderError::MissingHumanBone(bone));
+        }
+        Ok(HumanoidAsset { bones })
+    }
+
+    fn load_morph_target_bind<E>(
+        &self,
+        bind: &Value,
+    ) -> Result<MorphTargetBind, GltfLoaderError<E>> {
+        let node = self.node_id(bind, "node")?;
+        let target_count = self
+            .document
+            .nodes()
+            .nth(load_index(bind, "node")?)
+            .and_then(|node| node.mesh())
+            .and_then(|mesh| mesh.primitives().next())
+            .map(|primitive| primitive.morph_targets().len())
+            .unwrap_or(0);
+        let index = load_index(bind, "index")?;
+        if index >= target_count {
+            return Err(GltfLoaderError::BadVrmData("index"));
+        }
+        Ok(MorphTargetBind {
+            node,
+            index,
+            weight: load_f32(bind, "weight", 1.0)?,
+        })
+    }
+
+    fn load_expression<E>(
+        &self,
+        name: ExpressionName,
+        expression: &Value,
+    ) -> Result<ExpressionAsset, GltfLoaderError<E>> {
+        let load_override = |key| {
+            load_enum(expression, key, |value| match value {
+                "none" => Some(ExpressionOverride::None),
+                "block" => Some(ExpressionOverride::Block),
+                "blend" => Some(ExpressionOverride::Blend),
+                _ => None,
+            })
+        };
+        let morph_target_binds = load_array(expression, "morphTargetBinds")?
+            .iter()
+            .map(|bind| self.load_morph_target_bind(bind))
+            .collect::<Result<_, _>>()?;
+        let material_color_binds = load_array(expression, "materialColorBinds")?
+            .iter()
+            .map(|bind| {
+                let kind = match field(bind, "type").as_str() {
+                    Some("color") => MaterialColorType::Color,
+                    Some("emissionColor") => MaterialColorType::EmissionColor,
+                    Some("shadeColor") => MaterialColorType::ShadeColor,
+                    Some("matcapColor") => MaterialColorType::MatcapColor,
+                    Some("rimColor") => MaterialColorType::RimColor,
+                    Some("outlineColor") => MaterialColorType::OutlineColor,
+                    _ => return Err(GltfLoaderError::BadVrmData("type")),
+                };
+                Ok(MaterialColorBind {
+                    material: self.material_id(bind, "material")?,
+                    kind,
+                    target_value: load_f32_array(bind, "targetValue", [0.0; 4])?,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        let texture_transform_binds = load_array(expression, "textureTransformBinds")?
+            .iter()
+            .map(|bind| {
+                Ok::<_, GltfLoaderError<E>>(TextureTransformBind {
+                    material: self.material_id(bind, "material")?,
+                    scale: load_f32_array(bind, "scale", [1.0, 1.0])?,
+                    offset: load_f32_array(bind, "offset", [0.0, 0.0])?,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(ExpressionAsset {
+            name,
+            morph_target_binds,
+            material_color_binds,
+            texture_transform_binds,
+            is_binary: load_bool(expression, "isBinary", false)?,
+            override_blink: load_override("overrideBlink")?,
+            override_look_at: load_override("overrideLookAt")?,
+            override_mouth: load_override("overrideMouth")?,
+        })
+    }
+
+    fn load_expressions<E>(
+        &self,
+        expressions: &Value,
+    ) -> Result<Vec<ExpressionAsset>, GltfLoaderError<E>> {
+        let mut result = Vec::new();
+        if let Some(presets) = field(expressions, "preset").as_object() {
+            for (name, expression) in presets {
+                // Presets from later versions of the specification
+                let Some(preset) = ExpressionPreset::from_name(name) else {
+                    continue;
+                };
+                result.push(self.load_expression(ExpressionName::Preset(preset), expression)?);
+            }
+        }
+        if let Some(custom) = field(expressions, "custom").as_object() {
+            for (name, expression) in custom {
+                let name = ExpressionName::Custom(name.clone());
+                result.push(self.load_expression(name, expression)?);
+            }
+        }
+        Ok(result)
+    }
+
+    fn load_look_at<E>(&self, look_at: &Value) -> Result<LookAtAsset, GltfLoaderError<E>> {
+        let load_range_map = |key| match field(look_at, key) {
+            Value::Null => Ok::<_, GltfLoaderError<E>>(LookAtRangeMap::default()),
+            range_map => Ok(LookAtRangeMap {
+                input_max_value: load_f32(range_map, "inputMaxValue", 90.0)?,
+                output_scale: load_f32(range_map, "outputScale", 10.0)?,
+            }),
+        };
+        let kind = match field(look_at, "type").as_str() {
+            Some("bone") | None => LookAtType::Bone,
+            Some("expression") => LookAtType::Expression,
+            Some(_) => return Err(GltfLoaderError::BadVrmData("type")),
+        };
+        Ok(LookAtAsset {
+            offset_from_head_bone: Vec3::from_array(load_f32_array(
+                look_at,
+                "offsetFromHeadBone",
+                [0.0; 3],
+            )?),
+            kind,
+            range_map_horizontal_inner: load_range_map("rangeMapHorizontalInner")?,
+            range_map_horizontal_outer: load_range_map("rangeMapHorizontalOuter")?,
+            range_map_vertical_down: load_range_map("rangeMapVerticalDown")?,
+            range_map_vertical_up: load_range_map("rangeMapVerticalUp")?,
+        })
+    }
+
+    fn load_mesh_annotations<E>(
+        &self,
+        first_person: &Value,
+    ) -> Result<Vec<MeshAnnotation>, GltfLoaderError<E>> {
+        load_array(first_person, "meshAnnotations")?
+            .iter()
+            .map(|annotation| {
+                Ok(MeshAnnotation {
+                    node: self.node_id(annotation, "node")?,
+                    kind: load_enum(annotation, "type", |value| match value {
+                        "auto" => Some(FirstPersonType::Auto),
+                        "both" => Some(FirstPersonType::Both),
+                        "thirdPersonOnly" => Some(FirstPersonType::ThirdPersonOnly),
+                        "firstPersonOnly" => Some(FirstPersonType::FirstPersonOnly),
+                        _ => None,
+                    })?,
+                })
+            })
+            .collect()
+    }
+
+    fn load_collider<E>(&self, collider: &Value) -> Result<ColliderAsset, GltfLoaderError<E>> {
+        let shape = field(collider, "shape");
+        let shape = if let Some(sphere) = shape.get("sphere") {
+            ColliderShape::Sphere {
+                offset: Vec3::from_array(load_f32_array(sphere, "offset", [0.0; 3])?),
+                radius: load_f32(sphere, "radius", 0.0)?,
+            }
+        } else if let Some(capsule) = shape.get("capsule") {
+            ColliderShape::Capsule {
+                offset: Vec3::from_array(load_f32_array(capsule, "offset", [0.0; 3])?),
+                radius: load_f32(capsule, "radius", 0.0)?,
+                tail: Vec3::from_array(load_f32_array(capsule, "tail", [0.0; 3])?),
+            }
+        } else {
+            return Err(GltfLoaderError::BadVrmData("shape"));
+        };
+        Ok(ColliderAsset {
+            node: self.node_id(collider, "node")?,
+            shape,
+        })
+    }
+
+    fn load_spring_joint<E>(&self, joint: &Value) -> Result<SpringJointAsset, GltfLoaderError<E>> {
+        let default = SpringJointAsset::new(self.node_id(joint, "node")?);
+        Ok(SpringJointAsset {
+            hit_radius: load_f32(joint, "hitRadius", default.hit_radius)?,
+            stiffness: load_f32(joint, "stiffness", default.stiffness)?,
+            gravity_power: load_f32(joint, "gravityPower", default.gravity_power)?,
+            gravity_dir: Vec3::from_array(load_f32_array(
+                joint,
+                "gravityDir",
+                default.gravity_dir.to_array(),
+            )?),
+            drag_force: load_f32(joint, "dragForce", default.drag_force)?,
+            ..default
+        })
+    }
+
+    /// Check that every index of `key` in the item is below `len`.
+    fn load_indices<E>(
+        item: &Value,
+        key: &'static str,
+        len: usize,
+    ) -> Result<Vec<usize>, GltfLoaderError<E>> {
+        load_array(item, key)?
+            .iter()
+            .map(|index| match index.as_u64() {
+                Some(index) if (index as usize) < len => Ok(index as usize),
+                _ => Err(GltfLoaderError::BadVrmData(key)),
+            })
+            .collect()
+    }
+
+    fn load_spring_bone<E>(
+        &self,
+        spring_bone: &Value,
+    ) -> Result<SpringBoneAsset, GltfLoaderError<E>> {
+        let colliders: Vec<ColliderAsset> = load_array(spring_bone, "colliders")?
+            .iter()
+            .map(|collider| self.load_collider(collider))
+            .collect::<Result<_, _>>()?;
+        let collider_groups: Vec<ColliderGroupAsset> = load_array(spring_bone, "colliderGroups")?
+            .iter()
+            .map(|group| {
+                Ok::<_, GltfLoaderError<E>>(ColliderGroupAsset {
+                    name: load_string(group, "name")?,
+                    colliders: Self::load_indices(group, "colliders", colliders.len())?,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        let springs = load_array(spring_bone, "springs")?
+            .iter()
+            .map(|spring| {
+                let center = match field(spring, "center") {
+                    Value::Null => None,
+                    _ => Some(self.node_id(spring, "center")?),
+                };
+                Ok::<_, GltfLoaderError<E>>(SpringAsset {
+                    name: load_string(spring, "name")?,
+                    joints: load_array(spring, "joints")?
+                        .iter()
+                        .map(|joint| self.load_spring_joint(joint))
+                        .collect::<Result<_, _>>()?,
+                    collider_groups: Self::load_indices(
+                        spring,
+                        "colliderGroups",
+                        collider_groups.len(),
+                    )?,
+                    center,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(SpringBoneAsset {
+            colliders,
+            collider_groups,
+            springs,
+        })
+    }
+
+    fn load<E>(&self, vrm: &Value) -> Result<VrmAsset, GltfLoaderError<E>> {
+        let spec_version =
+            load_string(vrm, "specVersion")?.ok_or(GltfLoaderError::BadVrmData("specVersion"))?;
+        if !spec_version.starts_with("1.") {
+            return Err(GltfLoaderError::UnsupportedVrmVersion(spec_version));
+        }
+        let look_at = match field(vrm, "lookAt") {
+            Value::Null => None,
+            look_at => Some(self.load_look_at(look_at)?),
+        };
+        Ok(VrmAsset {
+            spec_version,
+            meta: self.load_meta(field(vrm, "meta"))?,
+            humanoid: self.load_humanoid(field(vrm, "humanoid"))?,
+            expressions: self.load_expressions(field(vrm, "expressions"))?,
+            look_at,
+            mesh_annotations: self.load_mesh_annotations(field(vrm, "firstPerson"))?,
+            spring_bone: self
+                .document
+                .extension_value("VRMC_springBone")
+                .map(|spring_bone| self.load_spring_bone(spring_bone))
+                .transpose()?,
+        })
+    }
+}
+
+/// Load the `VRMC_vrm` extension of the document, if there is one, with
+/// `VRMC_springBone`.
+pub(super) fn load_vrm<E>(
+    document: &Document,
+    bundle_index: &BundleIndex,
+) -> Result<Option<VrmAsset>, GltfLoaderError<E>> {
+    let Some(vrm) = document.extension_value("VRMC_vrm") else {
+        return Ok(None);
+    };
+    let loader = VrmLoader {
+        document,
+        bundle_index,
+    };
+    loader.load(vrm).map(Some)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use glam::Vec3;
+    use gltf::Gltf;
+
+    use super::load_vrm;
+    use crate::{
+        index::{AssetIndex, BundleAssetType, BundleIndex},
+        loader::gltf::GltfLoaderError,
+        spring_bone::ColliderShape,
+        vrm::{
+            ExpressionName, ExpressionOverride, ExpressionPreset, FirstPersonType, HumanBone,
+            LookAtType, MaterialColorType,
+        },
+    };
+
+    const REQUIRED_BONES: &str = r#"
+        "hips": { "node": 0 }, "spine": { "node": 0 }, "head": { "node": 1 },
+        "leftUpperLeg": { "node": 0 }, "leftLowerLeg": { "node": 0 }, "leftFoot": { "node": 0 },
+        "rightUpperLeg": { "node": 0 }, "rightLowerLeg": { "node": 0 }, "rightFoot": { "node": 0 },
+        "leftUpperArm": { "node": 0 }, "leftLowerArm": { "node": 0 }, "leftHand": { "node": 0 },
+        "rightUpperArm": { "node": 0 }, "rightLowerArm": { "node": 0 }, "rightHand": { "node": 0 }
+    "#;
+
+    fn document(human_bones: &str, expressions: &str) -> String {
+        format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "extensionsUsed": ["VRMC_vrm"],
+                "buffers": [{{ "byteLength": 36 }}],
+                "bufferViews": [{{ "buffer": 0, "byteLength": 36 }}],
+                "accessors": [{{
+                    "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                    "min": [0, 0, 0], "max": [0, 0, 0]
+                }}],
+                "materials": [{{}}],
+                "meshes": [{{ "primitives": [{{
+                    "attributes": {{ "POSITION": 0 }},
+                    "targets": [{{ "POSITION": 0 }}, {{ "POSITION": 0 }}]
+                }}] }}],
+                "nodes": [{{ "children": [1] }}, {{ "mesh": 0 }}],
+                "extensions": {{ "VRMC_vrm": {{
+                    "specVersion": "1.0",
+                    "meta": {{
+                        "name": "Test", "authors": ["Someone"], "licenseUrl": "https://vrm.dev/licenses/1.0/",
+                        "avatarPermission": "everyone", "allowRedistribution": true
+                    }},
+                    "humanoid": {{ "humanBones": {{ {} }} }},
+                    "expressions": {},
+                    "lookAt": {{
+                        "offsetFromHeadBone": [0, 0.06, 0], "type": "expression",
+                        "rangeMapHorizontalOuter": {{ "inputMaxValue": 45, "outputScale": 1 }}
+                    }},
+                    "firstPerson": {{ "meshAnnotations": [{{ "node": 1, "type": "thirdPersonOnly" }}] }}
+                }}, "VRMC_springBone": {{
+                    "specVersion": "1.0",
+                    "colliders": [
+                        {{ "node": 0, "shape": {{ "sphere": {{ "offset": [0, 1, 0], "radius": 0.1 }} }} }},
+                        {{ "node": 0, "shape": {{ "capsule": {{ "radius": 0.1, "tail": [0, 1, 0] }} }} }}
+                    ],
+                    "colliderGroups": [{{ "colliders": [0, 1] }}],
+                    "springs": [{{
+                        "name": "Hair",
+                        "joints": [{{ "node": 0, "gravityPower": 0.5 }}, {{ "node": 1 }}],
+                        "colliderGroups": [0],
+                        "center": 0
+                    }}]
+                }} }}
+            }}"#,
+            human_bones, expressions
+        )
+    }
+
+    fn load(human_bones: &str, expressions: &str) -> Result<bool, GltfLoaderError<io::Error>> {
+        let gltf = Gltf::from_slice(document(human_bones, expressions).as_bytes()).unwrap();
+        load_vrm(&gltf.document, &BundleIndex([0; 32])).map(|vrm| vrm.is_some())
+    }
+
+    fn node(index: usize) -> AssetIndex {
+        AssetIndex::BundleTypeIndex(BundleIndex([0; 32]), BundleAssetType::Node, index)
+    }
+
+    #[test]
+    fn test_load_vrm() {
+        let expressions = r#"{
+            "preset": {
+                "blink": {
+                    "morphTargetBinds": [{ "node": 1, "index": 1, "weight": 0.5 }],
+                    "isBinary": true
+                },
+                "happy": {
+                    "materialColorBinds": [
+                        { "material": 0, "type": "emissionColor", "targetValue": [1, 0, 0, 1] }
+                    ],
+                    "overrideBlink": "block"
+                }
+            },
+            "custom": { "wink": { "textureTransformBinds": [{ "material": 0, "offset": [0.5, 0] }] } }
+        }"#;
+        let gltf = Gltf::from_slice(document(REQUIRED_BONES, expressions).as_bytes()).unwrap();
+        let vrm = load_vrm::<io::Error>(&gltf.document, &BundleIndex([0; 32]))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(vrm.meta.name, "Test");
+        assert_eq!(vrm.meta.authors, ["Someone"]);
+        assert!(vrm.meta.allow_redistribution);
+        assert!(!vrm.meta.allow_excessively_violent_usage);
+
+        assert_eq!(vrm.humanoid.bones.len(), 15);
+        assert_eq!(vrm.humanoid.node(HumanBone::Head), Some(&node(1)));
+        assert_eq!(vrm.humanoid.node(HumanBone::Neck), None);
+
+        let blink = vrm.preset(ExpressionPreset::Blink).unwrap();
+        let weights: Vec<_> = blink.morph_target_weights(0.6).collect();
+        assert_eq!(weights, [(&node(1), 1, 0.5)]);
+        let happy = vrm.preset(ExpressionPreset::Happy).unwrap();
+        assert_eq!(happy.override_blink, ExpressionOverride::Block);
+        assert_eq!(happy.override_mouth, ExpressionOverride::None);
+        assert_eq!(
+            happy.material_color_binds[0].kind,
+            MaterialColorType::EmissionColor
+        );
+        let wink = vrm
+            .expression(&ExpressionName::Custom(String::from("wink")))
+            .unwrap();
+        assert_eq!(wink.texture_transform_binds[0].scale, [1.0, 1.0]);
+        assert_eq!(wink.texture_transform_binds[0].offset, [0.5, 0.0]);
+
+        let look_at = vrm.look_at.as_ref().unwrap();
+        assert_eq!(look_at.kind, LookAtType::Expression);
+        assert_eq!(look_at.range_map_horizontal_outer.map(-90.0), 1.0);
+        assert_eq!(look_at.range_map_horizontal_inner.map(45.0), 5.0);
+
+        assert_eq!(
+            vrm.first_person_type(&node(1)),
+            FirstPersonType::ThirdPersonOnly
+        );
+        assert_eq!(vrm.first_person_type(&node(0)), FirstPersonType::Auto);
+
+        let spring_bone = vrm.spring_bone.unwrap();
+        assert_eq!(
+            spring_bone.colliders[1].shape,
+            ColliderShape::Capsule {
+                offset: Vec3::ZERO,
+                radius: 0.1,
+                tail: Vec3::Y
+            }
+        );
+        assert_eq!(spring_bone.collider_groups[0].colliders, [0, 1]);
+        let spring = &spring_bone.springs[0];
+        assert_eq!(spring.name.as_deref(), Some("Hair"));
+        assert_eq!(spring.center, Some(node(0)));
+        assert_eq!(spring.joints[0].gravity_power, 0.5);
+        assert_eq!(spring.joints[0].stiffness, 1.0);
+        assert_eq!(spring.joints[1].node, node(1));
+    }
+
+    #[test]
+    fn test_bad_vrm() {
+        assert!(load(REQUIRED_BONES, "{}").unwrap());
+        // Without the hands
+        let bones = REQUIRED_BONES.replace(r#", "rightHand": { "node": 0 }"#, "");
+        assert!(matches!(
+            load(&bones, "{}"),
+            Err(GltfLoaderError::MissingHumanBone(HumanBone::RightHand))
+        ));
+        let bones = REQUIRED_BONES.replace(r#""head": { "node": 1 }"#, r#""head": { "node": 2 }"#);
+        assert!(matches!(
+            load(&bones, "{}"),
+            Err(GltfLoaderError::BadVrmData("node"))
+        ));
+        // The mesh only has two morph targets
+        let expressions =
+            r#"{ "preset": { "aa": { "morphTargetBinds": [{ "node": 1, "index": 2 }] } } }"#;
+        assert!(matches!(
+            load(REQUIRED_BONES, expressions),
+            Err(GltfLoaderError::BadVrmData("index"))
+        ));
+        let expressions =
+            r#"{ "preset": { "aa": { "morphTargetBinds": [{ "node": 0, "index": 0 }] } } }"#;
+        assert!(matches!(
+            load(REQUIRED_BONES, expressions),
+            Err(GltfLoaderError::BadVrmData("index"))
+        ));
+    }
+}
@@ -1,5 +1,14 @@
 use glam::Mat4;
 
+#[cfg(any(feature = "ply", feature = "stl"))]
+use std::sync::Arc;
+
+#[cfg(any(feature = "ply", feature = "stl"))]
+use crate::{
+    index::{AssetIndex, BundleAssetType, BundleIndex},
+    material::{MaterialAlphaMode, MaterialAsset, MaterialAssetData},
+};
+
 /// GLTF and VRM loader with `gltf` crate.
 #[cfg(feature = "gltf")]
 pub mod gltf;
@@ -12,6 +21,18 @@ pub mod obj;
 /// PMX 2.0 loader.
 pub mod pmx;
 
+/// FBX 7.x loader, for binary and ASCII files.
+#[cfg(feature = "fbx")]
+pub mod fbx;
+
+/// PLY loader, for ASCII and binary files.
+#[cfg(feature = "ply")]
+pub mod ply;
+
+/// STL loader, for ASCII and binary files.
+#[cfg(feature = "stl")]
+pub mod stl;
+
 pub(crate) mod texture;
 
 #[inline]
@@ -19,16 +40,6 @@ fn pad_color_vec3_to_vec4(color: [f32; 3]) -> [f32; 4] {
     [color[0], color[1], color[2], 1.0]
 }
 
-#[inline]
-fn pad_vec3_to_vec4(data: &[[f32; 3]], pad: f32) -> Vec<[f32; 4]> {
-    data.iter().map(|[x, y, z]| [*x, *y, *z, pad]).collect()
-}
-
-#[inline]
-fn clip_vec4_to_vec3(data: &[[f32; 4]]) -> Vec<[f32; 3]> {
-    data.iter().map(|[x, y, z, _w]| [*x, *y, *z]).collect()
-}
-
 #[inline]
 fn chunk_vec3<T: Copy>(data: &[T]) -> Vec<[T; 3]> {
     data.chunks_exact(3)
@@ -48,8 +59,7 @@ fn chunk_and_clamp_vec3_to_vec4_f32(data: &[f32]) -> Vec<[f32; 4]> {
     data.chunks_exact(3)
         .map(|item| {
             let array: [f32; 3] = item.try_into().unwrap();
-            array.map(|num| num.clamp(0.0, 1.0));
-            pad_color_vec3_to_vec4(array)
+            pad_color_vec3_to_vec4(array.map(|num| num.clamp(0.0, 1.0)))
         })
         .collect()
 }
@@ -74,6 +84,37 @@ fn chunk_mat4(data: &[f32]) -> Vec<Mat4> {
         .collect()
 }
 
+/// White rough material, for formats which only carry geometry. Vertex
+/// colors still apply.
+#[cfg(any(feature = "ply", feature = "stl"))]
+fn default_material(id: BundleIndex) -> Arc<MaterialAsset> {
+    Arc::new(MaterialAsset {
+        id: AssetIndex::BundleTypeIndex(id, BundleAssetType::Material, 0),
+        name: None,
+        data: MaterialAssetData::Pbr {
+            base_color_factor: [1.0, 1.0, 1.0, 1.0],
+            base_color_texture: None,
+            metallic_factor: 0.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+            ior: 1.5,
+            clearcoat: None,
+            transmission: None,
+            sheen: None,
+            specular: None,
+            volume: None,
+        },
+        normal_texture: None,
+        occlusion_texture: None,
+        emissive_texture: None,
+        emissive_factor: [0.0, 0.0, 0.0],
+        emissive_strength: 1.0,
+        alpha_mode: MaterialAlphaMode::Opaque,
+        double_sided: false,
+        uv_animation: None,
+    })
+}
+
 #[derive(Debug, Clone)]
 pub struct AssetLoadParams {
     pub disable_unlit: bool,
@@ -100,3 +141,16 @@ impl AssetLoadParams {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::chunk_and_clamp_vec3_to_vec4_f32;
+
+    #[test]
+    fn test_clamp_colors() {
+        assert_eq!(
+            chunk_and_clamp_vec3_to_vec4_f32(&[-1.0, 0.5, 2.0]),
+            [[0.0, 0.5, 1.0, 1.0]]
+        );
+    }
+}
@@ -16,12 +16,11 @@ use crate::{
     material::{MaterialAlphaMode, MaterialAsset, MaterialAssetData},
     mesh::MeshAsset,
     primitive::{PrimitiveAsset, PrimitiveAssetAttributes, PrimitiveAssetMode},
-    tangent::calculate_tangent,
     texture::{NormalTextureInfo, SamplerAsset, TextureInfo},
 };
 
 use super::{
-    chunk_vec3, pad_vec3_to_vec4,
+    chunk_vec3,
     texture::{TextureLoadError, TextureLoader},
     AssetLoadParams,
 };
@@ -199,6 +198,7 @@ impl<'a, T, A: Archive<T>> ObjLoader<'a, T, A> {
             occlusion_texture: None,
             emissive_texture: None,
             emissive_factor: [0.0, 0.0, 0.0],
+            emissive_strength: 1.0,
             double_sided: false,
             uv_animation: None,
         });
@@ -210,17 +210,7 @@ impl<'a, T, A: Archive<T>> ObjLoader<'a, T, A> {
         let mesh = &model.mesh;
 
         let position = chunk_vec3(&mesh.positions);
-        let tangent = calculate_tangent(
-            PrimitiveAssetMode::TriangleList,
-            &position,
-            Some(&mesh.indices),
-        );
-        let normal: Vec<[f32; 3]> = if !mesh.normals.is_empty() {
-            chunk_vec3(&mesh.normals)
-        } else {
-            tangent.clone()
-        };
-        let tangent = pad_vec3_to_vec4(&tangent, 1.0);
+        let normal = chunk_vec3(&mesh.normals);
         let tex_coord = if !mesh.texcoords.is_empty() {
             let tex_coord = mesh
                 .texcoords
@@ -248,11 +238,11 @@ impl<'a, T, A: Archive<T>> ObjLoader<'a, T, A> {
             .transpose()?
             .flatten();
 
-        Ok(PrimitiveAsset {
+        let mut primitive = PrimitiveAsset {
             attributes: PrimitiveAssetAttributes {
                 position,
                 normal,
-                tangent,
+                tangent: vec![],
                 tex_coord: tex_coord
                     .map(|tex_coord| vec![tex_coord])
                     .unwrap_or_default(),
@@ -260,11 +250,16 @@ impl<'a, T, A: Archive<T>> ObjLoader<'a, T, A> {
                 joints: vec![],
                 weights: vec![],
             },
-            indices: None,
+            indices: Some(mesh.indices.clone()),
             material,
             mode: PrimitiveAssetMode::TriangleList,
             targets: vec![],
-        })
+        };
+        if mesh.normals.is_empty() {
+            primitive.generate_smooth_normals();
+        }
+        primitive.generate_tangents();
+        Ok(primitive)
     }
 }
 
@@ -273,7 +268,7 @@ pub fn load_bundle<T, A: Archive<T>>(
     bundle: &mut A,
     params: &AssetLoadParams,
 ) -> Result<MeshAsset, ObjLoadError<A::Error>> {
-    let file_name = params.bundle_model_filename("pmx");
+    let file_name = params.bundle_model_filename("obj");
 
     let mut file_entry = bundle
         .by_path(&file_name)
@@ -316,3 +311,57 @@ pub fn load_bundle<T, A: Archive<T>>(
         weights: vec![],
     })
 }
+
+#[cfg(all(test, feature = "zip"))]
+mod test {
+    use std::io::{Cursor, Write};
+
+    use super::{load_bundle, ObjLoadError};
+    use crate::{index::BundleIndex, loader::AssetLoadParams};
+
+    fn archive(name: &str, obj: &[u8]) -> zip::ZipArchive<Cursor<Vec<u8>>> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file(name, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(obj).unwrap();
+        zip::ZipArchive::new(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_indices() {
+        // A quad, whose triangles share two vertices
+        let obj = b"v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n";
+        let mut archive = archive("model.obj", obj);
+
+        let mesh = load_bundle(
+            BundleIndex([0; 32]),
+            &mut archive,
+            &AssetLoadParams::default(),
+        )
+        .unwrap();
+        let primitive = &mesh.primitives[0];
+        assert_eq!(primitive.attributes.position.len(), 4);
+        assert_eq!(primitive.indices.as_deref(), Some(&[0, 1, 2, 0, 2, 3][..]));
+    }
+
+    #[test]
+    fn test_model_filename() {
+        let obj = b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        let params = AssetLoadParams::default();
+        assert!(load_bundle(
+            BundleIndex([0; 32]),
+            &mut archive("model.obj", obj),
+            &params
+        )
+        .is_ok());
+        assert!(matches!(
+            load_bundle(
+                BundleIndex([0; 32]),
+                &mut archive("model.pmx", obj),
+                &params
+            ),
+            Err(ObjLoadError::ModelNotFound(file_name)) if file_name == "model.obj"
+        ));
+    }
+}
@@ -0,0 +1,545 @@
+//! Polygon files, as written by 3D scanners.
+//!
+//! Vertex positions, normals and colors and face indices are read, other
+//! elements and properties are skipped. Faces are triangulated as fans and
+//! files without faces are loaded as point clouds.
+
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    str,
+};
+
+use crate::{
+    archive::{Archive, Entry},
+    index::BundleIndex,
+    mesh::MeshAsset,
+    primitive::{PrimitiveAsset, PrimitiveAssetAttributes, PrimitiveAssetMode},
+};
+
+use super::{default_material, AssetLoadParams};
+
+#[derive(Debug)]
+pub enum PlyLoadError<E> {
+    Io(E),
+    ModelNotFound(String),
+    BadHeader(String),
+    MissingProperty(&'static str),
+    BadValue(String),
+    Truncated,
+    IndexOutOfRange(u32),
+}
+
+impl<E: Display> Display for PlyLoadError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PlyLoadError::Io(err) => Display::fmt(err, f),
+            PlyLoadError::ModelNotFound(file_name) => {
+                write!(f, "File {} not found in bundle", file_name)
+            }
+            PlyLoadError::BadHeader(line) => write!(f, "Bad PLY header line: {}", line),
+            PlyLoadError::MissingProperty(name) => {
+                write!(f, "Missing vertex property {}", name)
+            }
+            PlyLoadError::BadValue(value) => write!(f, "Bad PLY value: {}", value),
+            PlyLoadError::Truncated => write!(f, "PLY file is truncated"),
+            PlyLoadError::IndexOutOfRange(index) => {
+                write!(f, "Face index {} out of range", index)
+            }
+        }
+    }
+}
+
+impl<E: Error> Error for PlyLoadError<E> {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => ScalarType::I8,
+            "uchar" | "uint8" => ScalarType::U8,
+            "short" | "int16" => ScalarType::I16,
+            "ushort" | "uint16" => ScalarType::U16,
+            "int" | "int32" => ScalarType::I32,
+            "uint" | "uint32" => ScalarType::U32,
+            "float" | "float32" => ScalarType::F32,
+            "double" | "float64" => ScalarType::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+
+    /// Value which maps to 1.0 for colors.
+    fn color_scale(self) -> f64 {
+        match self {
+            ScalarType::U8 => 255.0,
+            ScalarType::U16 => 65535.0,
+            ScalarType::I8 => 127.0,
+            ScalarType::I16 => 32767.0,
+            ScalarType::I32 => 2147483647.0,
+            ScalarType::U32 => 4294967295.0,
+            ScalarType::F32 | ScalarType::F64 => 1.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum PropertyType {
+    Scalar(ScalarType),
+    // Count type and item type
+    List(ScalarType, ScalarType),
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    kind: PropertyType,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+struct Header {
+    encoding: Encoding,
+    elements: Vec<Element>,
+}
+
+/// Header, and the offset of the body.
+fn parse_header<E>(data: &[u8]) -> Result<(Header, usize), PlyLoadError<E>> {
+    let bad_header = |line: &str| PlyLoadError::BadHeader(String::from(line));
+    let mut offset = 0;
+    let mut lines = data.split_inclusive(|byte| *byte == b'\n').map(|line| {
+        offset += line.len();
+        str::from_utf8(line).map(str::trim)
+    });
+    if lines.next() != Some(Ok("ply")) {
+        return Err(bad_header("Missing magic"));
+    }
+
+    let mut encoding = None;
+    let mut elements: Vec<Element> = Vec::new();
+    loop {
+        let line = lines
+            .next()
+            .ok_or(PlyLoadError::Truncated)?
+            .map_err(|_| bad_header("Not UTF-8"))?;
+        let tokens = line.split_ascii_whitespace().collect::<Vec<_>>();
+        match tokens[..] {
+            ["end_header"] => break,
+            [] | ["comment", ..] | ["obj_info", ..] => (),
+            ["format", format, "1.0"] => {
+                encoding = Some(match format {
+                    "ascii" => Encoding::Ascii,
+                    "binary_little_endian" => Encoding::LittleEndian,
+                    "binary_big_endian" => Encoding::BigEndian,
+                    _ => return Err(bad_header(line)),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: String::from(name),
+                count: count.parse().map_err(|_| bad_header(line))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let (Some(count), Some(item)) = (ScalarType::parse(count), ScalarType::parse(item))
+                else {
+                    return Err(bad_header(line));
+                };
+                elements
+                    .last_mut()
+                    .ok_or_else(|| bad_header(line))?
+                    .properties
+                    .push(Property {
+                        name: String::from(name),
+                        kind: PropertyType::List(count, item),
+                    });
+            }
+            ["property", kind, name] => {
+                let kind = ScalarType::parse(kind).ok_or_else(|| bad_header(line))?;
+                elements
+                    .last_mut()
+                    .ok_or_else(|| bad_header(line))?
+                    .properties
+                    .push(Property {
+                        name: String::from(name),
+                        kind: PropertyType::Scalar(kind),
+                    });
+            }
+            _ => return Err(bad_header(line)),
+        }
+    }
+    drop(lines);
+
+    let encoding = encoding.ok_or_else(|| bad_header("Missing format"))?;
+    Ok((Header { encoding, elements }, offset))
+}
+
+/// Values of the body, in the order of the header.
+struct BodyReader<'a> {
+    encoding: Encoding,
+    data: &'a [u8],
+    tokens: str::SplitAsciiWhitespace<'a>,
+}
+
+impl<'a> BodyReader<'a> {
+    fn new<E>(encoding: Encoding, data: &'a [u8]) -> Result<Self, PlyLoadError<E>> {
+        let text = match encoding {
+            Encoding::Ascii => str::from_utf8(data)
+                .map_err(|_| PlyLoadError::BadValue(String::from("Not UTF-8")))?,
+            Encoding::LittleEndian | Encoding::BigEndian => "",
+        };
+        Ok(Self {
+            encoding,
+            data,
+            tokens: text.split_ascii_whitespace(),
+        })
+    }
+
+    fn read<E>(&mut self, kind: ScalarType) -> Result<f64, PlyLoadError<E>> {
+        if self.encoding == Encoding::Ascii {
+            let token = self.tokens.next().ok_or(PlyLoadError::Truncated)?;
+            return token
+                .parse()
+                .map_err(|_| PlyLoadError::BadValue(String::from(token)));
+        }
+
+        let (bytes, rest) = self
+            .data
+            .split_at_checked(kind.size())
+            .ok_or(PlyLoadError::Truncated)?;
+        self.data = rest;
+        macro_rules! read {
+            ($type:ty) => {{
+                let bytes = bytes.try_into().unwrap();
+                let value = if self.encoding == Encoding::LittleEndian {
+                    <$type>::from_le_bytes(bytes)
+                } else {
+                    <$type>::from_be_bytes(bytes)
+                };
+                value as f64
+            }};
+        }
+        Ok(match kind {
+            ScalarType::I8 => read!(i8),
+            ScalarType::U8 => read!(u8),
+            ScalarType::I16 => read!(i16),
+            ScalarType::U16 => read!(u16),
+            ScalarType::I32 => read!(i32),
+            ScalarType::U32 => read!(u32),
+            ScalarType::F32 => read!(f32),
+            ScalarType::F64 => read!(f64),
+        })
+    }
+
+    fn read_list<E>(
+        &mut self,
+        count: ScalarType,
+        item: ScalarType,
+    ) -> Result<Vec<f64>, PlyLoadError<E>> {
+        let count = self.read(count)?;
+        if !(0.0..=u32::MAX as f64).contains(&count) {
+            return Err(PlyLoadError::BadValue(count.to_string()));
+        }
+        (0..count as u32).map(|_| self.read(item)).collect()
+    }
+}
+
+/// Indices of the vertex properties which are loaded.
+#[derive(Default)]
+struct VertexLayout {
+    position: [Option<usize>; 3],
+    normal: [Option<usize>; 3],
+    color: [Option<usize>; 4],
+}
+
+impl VertexLayout {
+    fn new(element: &Element) -> Self {
+        let mut layout = Self::default();
+        for (index, property) in element.properties.iter().enumerate() {
+            let slot = match property.name.as_str() {
+                "x" => &mut layout.position[0],
+                "y" => &mut layout.position[1],
+                "z" => &mut layout.position[2],
+                "nx" => &mut layout.normal[0],
+                "ny" => &mut layout.normal[1],
+                "nz" => &mut layout.normal[2],
+                "red" | "diffuse_red" | "r" => &mut layout.color[0],
+                "green" | "diffuse_green" | "g" => &mut layout.color[1],
+                "blue" | "diffuse_blue" | "b" => &mut layout.color[2],
+                "alpha" | "a" => &mut layout.color[3],
+                _ => continue,
+            };
+            if matches!(property.kind, PropertyType::Scalar(_)) {
+                *slot = Some(index);
+            }
+        }
+        layout
+    }
+}
+
+fn load_from_buffer<E>(id: BundleIndex, data: &[u8]) -> Result<MeshAsset, PlyLoadError<E>> {
+    let (header, offset) = parse_header(data)?;
+    let mut reader = BodyReader::new(header.encoding, &data[offset..])?;
+
+    let mut attributes = PrimitiveAssetAttributes {
+        position: Vec::new(),
+        normal: Vec::new(),
+        tangent: Vec::new(),
+        tex_coord: Vec::new(),
+        color: Vec::new(),
+        joints: Vec::new(),
+        weights: Vec::new(),
+    };
+    let mut colors = Vec::new();
+    let mut indices = Vec::new();
+    let mut vertex_count = 0;
+    for element in &header.elements {
+        let layout = VertexLayout::new(element);
+        let is_vertex = element.name == "vertex";
+        if is_vertex {
+            if layout.position.contains(&None) {
+                return Err(PlyLoadError::MissingProperty("x, y and z"));
+            }
+            vertex_count += element.count;
+        }
+        let face_indices = element
+            .properties
+            .iter()
+            .position(|property| {
+                matches!(property.name.as_str(), "vertex_indices" | "vertex_index")
+            })
+            .filter(|_| element.name == "face");
+
+        let mut values = vec![0.0; element.properties.len()];
+        for _ in 0..element.count {
+            for (index, property) in element.properties.iter().enumerate() {
+                match property.kind {
+                    PropertyType::Scalar(kind) => values[index] = reader.read(kind)?,
+                    PropertyType::List(count, item) => {
+                        let list = reader.read_list(count, item)?;
+                        if face_indices != Some(index) {
+                            continue;
+                        }
+                        // Checked once all vertices are read, as faces may come first
+                        let list = list
+                            .into_iter()
+                            .map(|index| {
+                                if (0.0..=u32::MAX as f64).contains(&index) {
+                                    Ok(index as u32)
+                                } else {
+                                    Err(PlyLoadError::BadValue(index.to_string()))
+                                }
+                            })
+                            .collect::<Result<Vec<_>, _>>()?;
+                        for corner in 1..list.len().saturating_sub(1) {
+                            indices.extend([list[0], list[corner], list[corner + 1]]);
+                        }
+                    }
+                }
+            }
+            if !is_vertex {
+                continue;
+            }
+
+            let value = |slot: Option<usize>| slot.map(|index| values[index] as f32);
+            attributes
+                .position
+                .push(layout.position.map(|slot| value(slot).unwrap()));
+            if let [Some(x), Some(y), Some(z)] = layout.normal.map(value) {
+                attributes.normal.push([x, y, z]);
+            }
+            if let [Some(r), Some(g), Some(b), a] = layout.color {
+                let color = |index: usize| {
+                    let PropertyType::Scalar(kind) = element.properties[index].kind else {
+                        unreachable!();
+                    };
+                    (values[index] / kind.color_scale()) as f32
+                };
+                colors.push([color(r), color(g), color(b), a.map_or(1.0, color)]);
+            }
+        }
+    }
+    if let Some(&index) = indices
+        .iter()
+        .find(|&&index| index as usize >= vertex_count)
+    {
+        return Err(PlyLoadError::IndexOutOfRange(index));
+    }
+    // Attributes must cover every vertex, so partial ones are dropped
+    if attributes.normal.len() != attributes.position.len() {
+        attributes.normal.clear();
+    }
+    if !colors.is_empty() && colors.len() == attributes.position.len() {
+        attributes.color.push(colors);
+    }
+
+    let (mode, indices) = if indices.is_empty() {
+        (PrimitiveAssetMode::Points, None)
+    } else {
+        (PrimitiveAssetMode::TriangleList, Some(indices))
+    };
+    let mut primitive = PrimitiveAsset {
+        attributes,
+        indices,
+        material: Some(default_material(id)),
+        mode,
+        targets: Vec::new(),
+    };
+    if primitive.attributes.normal.is_empty() {
+        primitive.generate_smooth_normals();
+    }
+    primitive.generate_tangents();
+    Ok(MeshAsset {
+        name: None,
+        primitives: vec![primitive],
+        weights: Vec::new(),
+    })
+}
+
+pub fn load_bundle<T, A: Archive<T>>(
+    id: BundleIndex,
+    bundle: &mut A,
+    params: &AssetLoadParams,
+) -> Result<MeshAsset, PlyLoadError<A::Error>> {
+    let file_name = params.bundle_model_filename("ply");
+
+    let mut file_entry = bundle
+        .by_path(&file_name)
+        .map_err(PlyLoadError::Io)?
+        .ok_or_else(|| PlyLoadError::ModelNotFound(file_name))?;
+    let file = file_entry.unpack().map_err(PlyLoadError::Io)?;
+    drop(file_entry);
+
+    load_from_buffer(id, &file)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use super::{load_from_buffer, PlyLoadError};
+    use crate::{index::BundleIndex, primitive::PrimitiveAssetMode};
+
+    #[test]
+    fn test_ascii() {
+        let data = b"ply
+format ascii 1.0
+comment A quad with colors
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3
+";
+        let mesh = load_from_buffer::<io::Error>(BundleIndex([0; 32]), data).unwrap();
+        let primitive = &mesh.primitives[0];
+        assert_eq!(primitive.indices.as_deref(), Some(&[0, 1, 2, 0, 2, 3][..]));
+        assert_eq!(primitive.attributes.color[0][1], [0.0, 1.0, 0.0, 1.0]);
+        // Generated, as the file has none
+        assert_eq!(primitive.attributes.normal, [[0.0, 0.0, 1.0]; 4]);
+        assert!(primitive.material.is_some());
+
+        // Faces before vertices
+        let data = b"ply\nformat ascii 1.0\nelement face 1\nproperty list uchar int vertex_indices\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\nend_header\n3 0 1 2\n0 0 0\n1 0 0\n0 1 0\n";
+        let mesh = load_from_buffer::<io::Error>(BundleIndex([0; 32]), data).unwrap();
+        let primitive = &mesh.primitives[0];
+        assert_eq!(primitive.indices.as_deref(), Some(&[0, 1, 2][..]));
+        assert_eq!(primitive.attributes.position.len(), 3);
+    }
+
+    #[test]
+    fn test_index_out_of_range() {
+        let data = b"ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n0 0 0\n1 0 0\n0 1 0\n3 0 1 3\n";
+        assert!(matches!(
+            load_from_buffer::<io::Error>(BundleIndex([0; 32]), data),
+            Err(PlyLoadError::IndexOutOfRange(3))
+        ));
+
+        let data = b"ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\nproperty float z\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n0 0 0\n1 0 0\n0 1 0\n3 0 1 -1\n";
+        assert!(matches!(
+            load_from_buffer::<io::Error>(BundleIndex([0; 32]), data),
+            Err(PlyLoadError::BadValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_binary() {
+        let mut data = b"ply\r
+format binary_big_endian 1.0\r
+element vertex 2\r
+property double x\r
+property double y\r
+property double z\r
+property float nx\r
+property float ny\r
+property float nz\r
+property list uchar int extra\r
+end_header\r
+"
+        .to_vec();
+        for vertex in [[1.0f64, 2.0, 3.0], [4.0, 5.0, 6.0]] {
+            data.extend(vertex.iter().flat_map(|value| value.to_be_bytes()));
+            data.extend(
+                [0.0f32, 1.0, 0.0]
+                    .iter()
+                    .flat_map(|value| value.to_be_bytes()),
+            );
+            data.extend([1, 0, 0, 0, 7]);
+        }
+
+        let mesh = load_from_buffer::<io::Error>(BundleIndex([0; 32]), &data).unwrap();
+        let primitive = &mesh.primitives[0];
+        assert!(matches!(primitive.mode, PrimitiveAssetMode::Points));
+        assert_eq!(primitive.indices, None);
+        assert_eq!(
+            primitive.attributes.position,
+            [[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]
+        );
+        assert_eq!(primitive.attributes.normal, [[0.0, 1.0, 0.0]; 2]);
+
+        assert!(matches!(
+            load_from_buffer::<io::Error>(BundleIndex([0; 32]), &data[..data.len() - 1]),
+            Err(PlyLoadError::Truncated)
+        ));
+    }
+}
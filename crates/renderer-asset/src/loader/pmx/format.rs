@@ -724,7 +724,7 @@ pub struct PmxJoint {
     pub joint_name_universal: String,
     pub joint_type: PmxJointType,
     #[br(args(header.globals.rigidbody_index_type))]
-    pub xsrigidbody_index_a: PmxIndex,
+    pub rigidbody_index_a: PmxIndex,
     #[br(args(header.globals.rigidbody_index_type))]
     pub rigidbody_index_b: PmxIndex,
     pub position: [f32; 3],
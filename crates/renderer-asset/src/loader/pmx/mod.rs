@@ -6,7 +6,8 @@ use std::{
 };
 
 use binrw::BinRead;
-use format::{PmxFile, PmxMaterial, PmxTexture};
+use format::{PmxFile, PmxJoint, PmxMaterial, PmxRigidbody, PmxTexture};
+use glam::{EulerRot, Quat, Vec3};
 
 use crate::{
     archive::{Archive, Entry},
@@ -14,14 +15,13 @@ use crate::{
     material::{self, MaterialAlphaMode, MaterialAsset, MaterialAssetData},
     mesh::MeshAsset,
     node::NodeAsset,
+    physics::{JointAsset, PhysicsAsset, RigidBodyAsset, RigidBodyMode, RigidBodyShape},
     primitive::{PrimitiveAsset, PrimitiveAssetAttributes, PrimitiveAssetMode},
     scene::SceneAsset,
-    tangent::calculate_tangent,
     texture::{SamplerAsset, TextureAsset, TextureInfo},
 };
 
 use super::{
-    pad_vec3_to_vec4,
     texture::{TextureLoadError, TextureLoader},
     AssetLoadParams,
 };
@@ -37,6 +37,8 @@ pub enum PmxLoadError<E> {
     NoSurfaceLeft { expected: usize, actual: usize },
     BadSurfacesCount(usize),
     BadToonReference(String),
+    BadRigidbodyBone(String),
+    BadJointRigidbody(String),
 }
 
 impl<E: Display> Display for PmxLoadError<E> {
@@ -59,6 +61,16 @@ impl<E: Display> Display for PmxLoadError<E> {
             PmxLoadError::BadToonReference(material) => {
                 write!(f, "Bad toon reference for material {:?}", material)
             }
+            PmxLoadError::BadRigidbodyBone(rigidbody) => {
+                write!(
+                    f,
+                    "Rigid body {:?} is attached to a missing bone",
+                    rigidbody
+                )
+            }
+            PmxLoadError::BadJointRigidbody(joint) => {
+                write!(f, "Joint {:?} connects a missing rigid body", joint)
+            }
         }
     }
 }
@@ -71,6 +83,15 @@ impl<E> From<binrw::Error> for PmxLoadError<E> {
     }
 }
 
+/// Rotation from PMX euler angles, applied around z, then x, then y.
+fn euler_rotation([x, y, z]: [f32; 3]) -> Quat {
+    Quat::from_euler(EulerRot::YXZ, y, x, z)
+}
+
+/// The scene, with the physics of its bones. Bones are referred to as
+/// [`BundleAssetType::Bone`] indices, as they aren't loaded as nodes.
+pub type PmxLoadResult = (SceneAsset, PhysicsAsset);
+
 struct PmxLoader<'a, T, A: Archive<T>> {
     id: BundleIndex,
     bundle: &'a mut A,
@@ -184,6 +205,7 @@ impl<'a, T, A: Archive<T>> PmxLoader<'a, T, A> {
             occlusion_texture: None,
             emissive_texture: None,
             emissive_factor: [0.0, 0.0, 0.0],
+            emissive_strength: 1.0,
             alpha_mode: MaterialAlphaMode::Opaque,
             double_sided: false,
             uv_animation: None,
@@ -223,15 +245,11 @@ impl<'a, T, A: Archive<T>> PmxLoader<'a, T, A> {
                 tex_coord.push(surface.uv);
                 normal.push(surface.normal);
             }
-            let tangent = pad_vec3_to_vec4(
-                &calculate_tangent(PrimitiveAssetMode::TriangleList, &position, None),
-                1.0,
-            );
-            let primitive = PrimitiveAsset {
+            let mut primitive = PrimitiveAsset {
                 attributes: PrimitiveAssetAttributes {
                     position,
                     normal,
-                    tangent,
+                    tangent: vec![],
                     tex_coord: vec![tex_coord],
                     color: vec![],
                     joints: vec![],
@@ -242,6 +260,7 @@ impl<'a, T, A: Archive<T>> PmxLoader<'a, T, A> {
                 mode: PrimitiveAssetMode::TriangleList,
                 targets: vec![],
             };
+            primitive.generate_tangents();
             let mesh = MeshAsset {
                 name: None,
                 primitives: vec![primitive],
@@ -263,20 +282,115 @@ impl<'a, T, A: Archive<T>> PmxLoader<'a, T, A> {
         Ok(nodes)
     }
 
-    fn load_file(&mut self, file: PmxFile) -> Result<SceneAsset, PmxLoadError<A::Error>> {
-        let surfaces = self.load_surfaces(&file)?;
-        Ok(SceneAsset {
-            name: None,
-            nodes: surfaces,
+    fn bone_id(&self, index: usize) -> AssetIndex {
+        AssetIndex::BundleTypeIndex(self.id.clone(), BundleAssetType::Bone, index)
+    }
+
+    fn load_rigidbody(
+        &self,
+        file: &PmxFile,
+        rigidbody: &PmxRigidbody,
+    ) -> Result<RigidBodyAsset, PmxLoadError<A::Error>> {
+        let bone = match rigidbody.related_bone_index.0 {
+            Some(index) if index >= file.bones.len() => {
+                return Err(PmxLoadError::BadRigidbodyBone(
+                    rigidbody.rigidbody_name_local.clone(),
+                ))
+            }
+            index => index.map(|index| self.bone_id(index)),
+        };
+        let [x, y, z] = rigidbody.shape_size;
+        Ok(RigidBodyAsset {
+            name: Some(rigidbody.rigidbody_name_local.clone()),
+            bone,
+            group: rigidbody.group_id.clamp(0, 15) as u8,
+            collision_mask: rigidbody.no_collision_group as u16,
+            shape: match rigidbody.shape {
+                format::PmxShapeType::Sphere => RigidBodyShape::Sphere { radius: x },
+                format::PmxShapeType::Box => RigidBodyShape::Box {
+                    half_extents: Vec3::new(x, y, z),
+                },
+                format::PmxShapeType::Capsule => RigidBodyShape::Capsule {
+                    radius: x,
+                    height: y,
+                },
+            },
+            position: Vec3::from_array(rigidbody.shape_position),
+            rotation: euler_rotation(rigidbody.shape_rotation),
+            mass: rigidbody.mass,
+            linear_damping: rigidbody.move_attenuation,
+            angular_damping: rigidbody.rotation_damping,
+            restitution: rigidbody.repulsion,
+            friction: rigidbody.friction_force,
+            mode: match rigidbody.physics_mode {
+                format::PmxPhysicsMode::FollowBone => RigidBodyMode::FollowBone,
+                format::PmxPhysicsMode::Physics => RigidBodyMode::Physics,
+                format::PmxPhysicsMode::PhysicsAndBone => RigidBodyMode::PhysicsWithBone,
+            },
         })
     }
+
+    /// Every joint type is simulated as a spring 6-DOF joint, the only one
+    /// PMX 2.0 has.
+    fn load_joint(
+        &self,
+        file: &PmxFile,
+        joint: &PmxJoint,
+    ) -> Result<JointAsset, PmxLoadError<A::Error>> {
+        let body = |index: &format::PmxIndex| {
+            index
+                .0
+                .filter(|index| *index < file.rigidbodies.len())
+                .ok_or_else(|| PmxLoadError::BadJointRigidbody(joint.joint_name_local.clone()))
+        };
+        Ok(JointAsset {
+            name: Some(joint.joint_name_local.clone()),
+            body_a: body(&joint.rigidbody_index_a)?,
+            body_b: body(&joint.rigidbody_index_b)?,
+            position: Vec3::from_array(joint.position),
+            rotation: euler_rotation(joint.rotation),
+            linear_lower: Vec3::from_array(joint.position_minimum),
+            linear_upper: Vec3::from_array(joint.position_maximum),
+            angular_lower: Vec3::from_array(joint.rotation_minimum),
+            angular_upper: Vec3::from_array(joint.rotation_maximum),
+            linear_stiffness: Vec3::from_array(joint.position_spring),
+            angular_stiffness: Vec3::from_array(joint.rotation_spring),
+        })
+    }
+
+    fn load_physics(&self, file: &PmxFile) -> Result<PhysicsAsset, PmxLoadError<A::Error>> {
+        Ok(PhysicsAsset {
+            rigid_bodies: file
+                .rigidbodies
+                .iter()
+                .map(|rigidbody| self.load_rigidbody(file, rigidbody))
+                .collect::<Result<_, _>>()?,
+            joints: file
+                .joints
+                .iter()
+                .map(|joint| self.load_joint(file, joint))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn load_file(&mut self, file: PmxFile) -> Result<PmxLoadResult, PmxLoadError<A::Error>> {
+        let surfaces = self.load_surfaces(&file)?;
+        let physics = self.load_physics(&file)?;
+        Ok((
+            SceneAsset {
+                name: None,
+                nodes: surfaces,
+            },
+            physics,
+        ))
+    }
 }
 
 pub fn load_bundle<T, A: Archive<T>>(
     id: BundleIndex,
     bundle: &mut A,
     params: AssetLoadParams,
-) -> Result<SceneAsset, PmxLoadError<A::Error>> {
+) -> Result<PmxLoadResult, PmxLoadError<A::Error>> {
     let file_name = params.bundle_model_filename("pmx");
 
     let mut file_entry = bundle
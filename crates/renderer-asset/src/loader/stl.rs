@@ -0,0 +1,279 @@
+//! Stereolithography files, as exported by CAD software.
+//!
+//! Facets keep their own vertices and normals, so the mesh is flat shaded.
+//! Facet normals of zero length are computed from the vertex winding.
+
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    str,
+};
+
+use glam::Vec3;
+
+use crate::{
+    archive::{Archive, Entry},
+    index::BundleIndex,
+    mesh::MeshAsset,
+    normal::flat_normals,
+    primitive::{PrimitiveAsset, PrimitiveAssetAttributes, PrimitiveAssetMode},
+};
+
+use super::{default_material, AssetLoadParams};
+
+const HEADER_SIZE: usize = 84;
+const FACET_SIZE: usize = 50;
+
+#[derive(Debug)]
+pub enum StlLoadError<E> {
+    Io(E),
+    ModelNotFound(String),
+    Truncated,
+    // Line number, starting from 1
+    Syntax(usize),
+}
+
+impl<E: Display> Display for StlLoadError<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            StlLoadError::Io(err) => Display::fmt(err, f),
+            StlLoadError::ModelNotFound(file_name) => {
+                write!(f, "File {} not found in bundle", file_name)
+            }
+            StlLoadError::Truncated => write!(f, "STL file is truncated"),
+            StlLoadError::Syntax(line) => write!(f, "STL syntax error at line {}", line),
+        }
+    }
+}
+
+impl<E: Error> Error for StlLoadError<E> {}
+
+/// Name, and the normal and corners of each triangle.
+type Facets = (Option<String>, Vec<([f32; 3], [[f32; 3]; 3])>);
+
+fn is_binary(data: &[u8]) -> bool {
+    // Binary headers may start with "solid" as well, so the size and the
+    // content decide
+    let Some(count) = data.get(80..HEADER_SIZE) else {
+        return !data.starts_with(b"solid");
+    };
+    let count = u32::from_le_bytes(count.try_into().unwrap()) as usize;
+    let size = count
+        .checked_mul(FACET_SIZE)
+        .and_then(|size| size.checked_add(HEADER_SIZE));
+    size == Some(data.len()) || !data.starts_with(b"solid") || data.contains(&0)
+}
+
+fn parse_binary<E>(data: &[u8]) -> Result<Facets, StlLoadError<E>> {
+    let count = data
+        .get(80..HEADER_SIZE)
+        .ok_or(StlLoadError::Truncated)?
+        .try_into()
+        .unwrap();
+    let count = u32::from_le_bytes(count) as usize;
+    let facets = data[HEADER_SIZE..].chunks_exact(FACET_SIZE);
+    if facets.len() < count {
+        return Err(StlLoadError::Truncated);
+    }
+    let facets = facets
+        .take(count)
+        .map(|facet| {
+            let vec3 = |index: usize| {
+                [0, 1, 2].map(|axis| {
+                    let offset = (index * 3 + axis) * 4;
+                    f32::from_le_bytes(facet[offset..offset + 4].try_into().unwrap())
+                })
+            };
+            (vec3(0), [vec3(1), vec3(2), vec3(3)])
+        })
+        .collect();
+    Ok((None, facets))
+}
+
+fn parse_ascii<E>(data: &[u8]) -> Result<Facets, StlLoadError<E>> {
+    let mut name = None;
+    let mut facets = Vec::new();
+    let mut normal = None;
+    let mut corners = Vec::new();
+    for (index, line) in data.split(|byte| *byte == b'\n').enumerate() {
+        let syntax_error = || StlLoadError::Syntax(index + 1);
+        let line = str::from_utf8(line).map_err(|_| syntax_error())?;
+        let vec3 = |tokens: &[&str]| -> Result<[f32; 3], StlLoadError<E>> {
+            let [x, y, z] = tokens else {
+                return Err(syntax_error());
+            };
+            let parse = |token: &str| token.parse().map_err(|_| syntax_error());
+            Ok([parse(x)?, parse(y)?, parse(z)?])
+        };
+        let tokens = line.split_ascii_whitespace().collect::<Vec<_>>();
+        match tokens[..] {
+            [] | ["outer", "loop"] | ["endloop"] | ["endsolid", ..] => (),
+            ["solid", ..] => {
+                let solid_name = line.trim().strip_prefix("solid").unwrap().trim();
+                if name.is_none() && !solid_name.is_empty() {
+                    name = Some(String::from(solid_name));
+                }
+            }
+            ["facet", "normal", ref values @ ..] => normal = Some(vec3(values)?),
+            ["vertex", ref values @ ..] => corners.push(vec3(values)?),
+            ["endfacet"] => {
+                let normal = normal.take().ok_or_else(syntax_error)?;
+                if corners.len() < 3 {
+                    return Err(syntax_error());
+                }
+                // Some exporters write polygons, which are split as fans
+                for corner in 1..corners.len() - 1 {
+                    facets.push((normal, [corners[0], corners[corner], corners[corner + 1]]));
+                }
+                corners.clear();
+            }
+            _ => return Err(syntax_error()),
+        }
+    }
+    if normal.is_some() {
+        return Err(StlLoadError::Truncated);
+    }
+    Ok((name, facets))
+}
+
+fn load_from_buffer<E>(id: BundleIndex, data: &[u8]) -> Result<MeshAsset, StlLoadError<E>> {
+    let (name, facets) = if is_binary(data) {
+        parse_binary(data)?
+    } else {
+        parse_ascii(data)?
+    };
+
+    let position = facets
+        .iter()
+        .flat_map(|(_, corners)| *corners)
+        .collect::<Vec<_>>();
+    let generated = flat_normals(PrimitiveAssetMode::TriangleList, &position, None);
+    let normal = facets
+        .iter()
+        .zip(generated.chunks_exact(3))
+        .flat_map(|((normal, _), generated)| {
+            let normal = Vec3::from_array(*normal).normalize_or_zero();
+            if normal == Vec3::ZERO {
+                [generated[0]; 3]
+            } else {
+                [normal.to_array(); 3]
+            }
+        })
+        .collect();
+
+    let mut primitive = PrimitiveAsset {
+        attributes: PrimitiveAssetAttributes {
+            position,
+            normal,
+            tangent: Vec::new(),
+            tex_coord: Vec::new(),
+            color: Vec::new(),
+            joints: Vec::new(),
+            weights: Vec::new(),
+        },
+        indices: None,
+        material: Some(default_material(id)),
+        mode: PrimitiveAssetMode::TriangleList,
+        targets: Vec::new(),
+    };
+    primitive.generate_tangents();
+    Ok(MeshAsset {
+        name,
+        primitives: vec![primitive],
+        weights: Vec::new(),
+    })
+}
+
+pub fn load_bundle<T, A: Archive<T>>(
+    id: BundleIndex,
+    bundle: &mut A,
+    params: &AssetLoadParams,
+) -> Result<MeshAsset, StlLoadError<A::Error>> {
+    let file_name = params.bundle_model_filename("stl");
+
+    let mut file_entry = bundle
+        .by_path(&file_name)
+        .map_err(StlLoadError::Io)?
+        .ok_or_else(|| StlLoadError::ModelNotFound(file_name))?;
+    let file = file_entry.unpack().map_err(StlLoadError::Io)?;
+    drop(file_entry);
+
+    load_from_buffer(id, &file)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+
+    use super::{load_from_buffer, StlLoadError};
+    use crate::index::BundleIndex;
+
+    #[test]
+    fn test_ascii() {
+        let data = b"solid part
+  facet normal 0 0 0
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 1 0
+    endloop
+  endfacet
+  facet normal 0 0 -2
+    outer loop
+      vertex 0 0 0
+      vertex 0 1 0
+      vertex 1 0 0
+    endloop
+  endfacet
+endsolid part
+";
+        let mesh = load_from_buffer::<io::Error>(BundleIndex([0; 32]), data).unwrap();
+        assert_eq!(mesh.name.as_deref(), Some("part"));
+        let primitive = &mesh.primitives[0];
+        assert_eq!(primitive.attributes.position.len(), 6);
+        // The first normal is computed, the second one normalized
+        assert_eq!(
+            primitive.attributes.normal,
+            [
+                [0.0, 0.0, 1.0],
+                [0.0, 0.0, 1.0],
+                [0.0, 0.0, 1.0],
+                [0.0, 0.0, -1.0],
+                [0.0, 0.0, -1.0],
+                [0.0, 0.0, -1.0]
+            ]
+        );
+
+        assert!(matches!(
+            load_from_buffer::<io::Error>(BundleIndex([0; 32]), b"solid\nfacet normal 0 0\n"),
+            Err(StlLoadError::Syntax(2))
+        ));
+    }
+
+    #[test]
+    fn test_binary() {
+        // Binary headers starting with "solid" are common
+        let mut data = b"solid binary".to_vec();
+        data.resize(80, 0);
+        data.extend(1u32.to_le_bytes());
+        for value in [
+            0.0f32, 0.0, 1.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 2.0, 0.0,
+        ] {
+            data.extend(value.to_le_bytes());
+        }
+        data.extend([0, 0]);
+
+        let mesh = load_from_buffer::<io::Error>(BundleIndex([0; 32]), &data).unwrap();
+        let primitive = &mesh.primitives[0];
+        assert_eq!(
+            primitive.attributes.position,
+            [[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [0.0, 2.0, 0.0]]
+        );
+        assert_eq!(primitive.attributes.normal, [[0.0, 0.0, 1.0]; 3]);
+
+        assert!(matches!(
+            load_from_buffer::<io::Error>(BundleIndex([0; 32]), &data[..100]),
+            Err(StlLoadError::Truncated)
+        ));
+    }
+}
@@ -133,6 +133,20 @@ impl TextureLoader {
         Ok(texture)
     }
 
+    pub fn load_from_buffer(
+        &mut self,
+        id: AssetIndex,
+        buffer: &[u8],
+        sampler: SamplerAsset,
+    ) -> Result<Arc<TextureAsset>, ImageError> {
+        if let Some(texture) = self.texture_cache.get(&id) {
+            return Ok(texture.clone());
+        }
+        let texture = self.load_from_buffer_uncached(id.clone(), buffer, sampler)?;
+        self.texture_cache.insert(id, texture.clone());
+        Ok(texture)
+    }
+
     pub fn load_from_archive<T, A: Archive<T>, P: AsRef<Path>>(
         &mut self,
         id: AssetIndex,
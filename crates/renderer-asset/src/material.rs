@@ -21,6 +21,51 @@ pub enum PmxToonReference {
     Internal { index: u8 },
 }
 
+/// GLTF KHR_materials_clearcoat.
+#[derive(Debug, Clone)]
+pub struct PbrClearcoat {
+    pub factor: f32,
+    pub texture: Option<TextureInfo>,
+    pub roughness_factor: f32,
+    pub roughness_texture: Option<TextureInfo>,
+    pub normal_texture: Option<NormalTextureInfo>,
+}
+
+/// GLTF KHR_materials_transmission.
+#[derive(Debug, Clone)]
+pub struct PbrTransmission {
+    pub factor: f32,
+    pub texture: Option<TextureInfo>,
+}
+
+/// GLTF KHR_materials_sheen.
+#[derive(Debug, Clone)]
+pub struct PbrSheen {
+    pub color_factor: [f32; 3],
+    pub color_texture: Option<TextureInfo>,
+    pub roughness_factor: f32,
+    pub roughness_texture: Option<TextureInfo>,
+}
+
+/// GLTF KHR_materials_specular.
+#[derive(Debug, Clone)]
+pub struct PbrSpecular {
+    pub factor: f32,
+    pub texture: Option<TextureInfo>,
+    pub color_factor: [f32; 3],
+    pub color_texture: Option<TextureInfo>,
+}
+
+/// GLTF KHR_materials_volume.
+#[derive(Debug, Clone)]
+pub struct PbrVolume {
+    pub thickness_factor: f32,
+    pub thickness_texture: Option<TextureInfo>,
+    // Infinity when the medium doesn't absorb light
+    pub attenuation_distance: f32,
+    pub attenuation_color: [f32; 3],
+}
+
 /// Define lighting parameters for the material.
 #[derive(Debug, Clone)]
 pub enum MaterialAssetData {
@@ -31,6 +76,13 @@ pub enum MaterialAssetData {
         metallic_factor: f32,
         roughness_factor: f32,
         metallic_roughness_texture: Option<TextureInfo>,
+        /// Index of refraction, 1.5 unless specified by KHR_materials_ior.
+        ior: f32,
+        clearcoat: Option<Box<PbrClearcoat>>,
+        transmission: Option<Box<PbrTransmission>>,
+        sheen: Option<Box<PbrSheen>>,
+        specular: Option<Box<PbrSpecular>>,
+        volume: Option<Box<PbrVolume>>,
     },
     /// Materials in MTL file. Basic blinn-phone lighting model.
     BlinnPhong {
@@ -130,7 +182,136 @@ pub struct MaterialAsset {
     pub occlusion_texture: Option<OcclusionTextureInfo>,
     pub emissive_texture: Option<TextureInfo>,
     pub emissive_factor: [f32; 3],
+    // Multiplier of emissive factor, from KHR_materials_emissive_strength
+    pub emissive_strength: f32,
     pub alpha_mode: MaterialAlphaMode,
     pub double_sided: bool,
     pub uv_animation: Option<UvAnimation>,
 }
+
+impl MaterialAsset {
+    /// All textures used by the material, with the name of their slot.
+    pub fn textures(&self) -> Vec<(&'static str, &Arc<TextureAsset>)> {
+        fn info<'a>(
+            textures: &mut Vec<(&'static str, &'a Arc<TextureAsset>)>,
+            slot: &'static str,
+            info: &'a Option<TextureInfo>,
+        ) {
+            if let Some(info) = info {
+                textures.push((slot, &info.texture));
+            }
+        }
+
+        let mut textures = Vec::new();
+        match &self.data {
+            MaterialAssetData::Pbr {
+                base_color_texture,
+                metallic_roughness_texture,
+                clearcoat,
+                transmission,
+                sheen,
+                specular,
+                volume,
+                ..
+            } => {
+                info(&mut textures, "base color", base_color_texture);
+                info(
+                    &mut textures,
+                    "metallic roughness",
+                    metallic_roughness_texture,
+                );
+                if let Some(clearcoat) = clearcoat {
+                    info(&mut textures, "clearcoat", &clearcoat.texture);
+                    info(
+                        &mut textures,
+                        "clearcoat roughness",
+                        &clearcoat.roughness_texture,
+                    );
+                    if let Some(normal) = &clearcoat.normal_texture {
+                        textures.push(("clearcoat normal", &normal.texture));
+                    }
+                }
+                if let Some(transmission) = transmission {
+                    info(&mut textures, "transmission", &transmission.texture);
+                }
+                if let Some(sheen) = sheen {
+                    info(&mut textures, "sheen color", &sheen.color_texture);
+                    info(&mut textures, "sheen roughness", &sheen.roughness_texture);
+                }
+                if let Some(specular) = specular {
+                    info(&mut textures, "specular", &specular.texture);
+                    info(&mut textures, "specular color", &specular.color_texture);
+                }
+                if let Some(volume) = volume {
+                    info(&mut textures, "thickness", &volume.thickness_texture);
+                }
+            }
+            MaterialAssetData::BlinnPhong {
+                ambient_texture,
+                diffuse_texture,
+                specular_texture,
+                shininess_texture,
+                dissolve_texture,
+                ..
+            } => {
+                info(&mut textures, "ambient", ambient_texture);
+                info(&mut textures, "diffuse", diffuse_texture);
+                info(&mut textures, "specular", specular_texture);
+                info(&mut textures, "shininess", shininess_texture);
+                info(&mut textures, "dissolve", dissolve_texture);
+            }
+            MaterialAssetData::Unlit {
+                base_color_texture, ..
+            } => info(&mut textures, "base color", base_color_texture),
+            MaterialAssetData::MTone {
+                base_color_texture,
+                shade_multiply_texture,
+                shading_shift_texture,
+                matcap_texture,
+                rim_multiply_texture,
+                outline_width_multiply_texture,
+                ..
+            } => {
+                info(&mut textures, "base color", base_color_texture);
+                info(&mut textures, "shade multiply", shade_multiply_texture);
+                if let Some(shading_shift) = shading_shift_texture {
+                    textures.push(("shading shift", &shading_shift.texture));
+                }
+                info(&mut textures, "matcap", matcap_texture);
+                info(&mut textures, "rim multiply", rim_multiply_texture);
+                info(
+                    &mut textures,
+                    "outline width multiply",
+                    outline_width_multiply_texture,
+                );
+            }
+            MaterialAssetData::Pmx {
+                texture,
+                environment,
+                toon_reference,
+                ..
+            } => {
+                info(&mut textures, "texture", texture);
+                info(&mut textures, "environment", environment);
+                if let PmxToonReference::Texture(texture) = toon_reference {
+                    textures.push(("toon", texture));
+                }
+            }
+        }
+        if let Some(normal) = &self.normal_texture {
+            textures.push(("normal", &normal.texture));
+        }
+        if let Some(occlusion) = &self.occlusion_texture {
+            textures.push(("occlusion", &occlusion.texture));
+        }
+        info(&mut textures, "emissive", &self.emissive_texture);
+        if let Some(texture) = self
+            .uv_animation
+            .as_ref()
+            .and_then(|uv_animation| uv_animation.mask_texture.as_ref())
+        {
+            textures.push(("UV animation mask", texture));
+        }
+        textures
+    }
+}
@@ -0,0 +1,130 @@
+use glam::Vec3;
+
+use super::primitive::{Normal, PrimitiveAssetMode};
+
+fn triangle_positions(positions: &[[f32; 3]], triangle: [usize; 3]) -> [Vec3; 3] {
+    triangle.map(|index| Vec3::from_array(positions[index]))
+}
+
+fn face_normal([pnt_0, pnt_1, pnt_2]: [Vec3; 3]) -> Vec3 {
+    (pnt_1 - pnt_0).cross(pnt_2 - pnt_0).normalize_or_zero()
+}
+
+/// Normal of the face each vertex belongs to. A vertex shared by several
+/// faces takes the normal of the last one, so split vertices first for
+/// faceted shading. Vertices outside of triangles get a zero normal.
+pub fn flat_normals(
+    mode: PrimitiveAssetMode,
+    positions: &[[f32; 3]],
+    indices: Option<&[u32]>,
+) -> Normal {
+    let mut buffer = vec![[0.0; 3]; positions.len()];
+    for triangle in mode.triangles(positions.len(), indices) {
+        let normal = face_normal(triangle_positions(positions, triangle)).to_array();
+        for index in triangle {
+            buffer[index] = normal;
+        }
+    }
+    buffer
+}
+
+/// Average of the normals of the faces around each vertex, weighted by the
+/// angle of the face at that vertex, so the result doesn't depend on how
+/// the surface is split into triangles. Vertices outside of triangles get a
+/// zero normal.
+pub fn smooth_normals(
+    mode: PrimitiveAssetMode,
+    positions: &[[f32; 3]],
+    indices: Option<&[u32]>,
+) -> Normal {
+    let mut buffer = vec![Vec3::ZERO; positions.len()];
+    for triangle in mode.triangles(positions.len(), indices) {
+        let points = triangle_positions(positions, triangle);
+        let normal = face_normal(points);
+        for corner in 0..3 {
+            let point = points[corner];
+            let edge_a = points[(corner + 1) % 3] - point;
+            let edge_b = points[(corner + 2) % 3] - point;
+            let angle = edge_a.angle_between(edge_b);
+            // Zero-length edges give NaN
+            if angle.is_finite() {
+                buffer[triangle[corner]] += normal * angle;
+            }
+        }
+    }
+    buffer
+        .into_iter()
+        .map(|normal| normal.normalize_or_zero().to_array())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::f32::consts::FRAC_1_SQRT_2;
+
+    use glam::Vec3;
+
+    use super::{flat_normals, smooth_normals};
+    use crate::primitive::PrimitiveAssetMode;
+
+    // Two quads folded 90 degrees along the y axis, the left one facing +z
+    // and the right one facing +x
+    const POSITIONS: [[f32; 3]; 6] = [
+        [-1.0, 0.0, 0.0],
+        [0.0, 0.0, 0.0],
+        [0.0, 0.0, -1.0],
+        [-1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [0.0, 1.0, -1.0],
+    ];
+    const INDICES: [u32; 12] = [0, 1, 4, 0, 4, 3, 1, 2, 5, 1, 5, 4];
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+        assert!(
+            Vec3::from_array(actual).abs_diff_eq(Vec3::from_array(expected), 1e-5),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_flat_normals() {
+        let positions: Vec<[f32; 3]> = INDICES
+            .iter()
+            .map(|index| POSITIONS[*index as usize])
+            .collect();
+        let normals = flat_normals(PrimitiveAssetMode::TriangleList, &positions, None);
+        for (index, normal) in normals.into_iter().enumerate() {
+            let expected = if index < 6 {
+                [0.0, 0.0, 1.0]
+            } else {
+                [1.0, 0.0, 0.0]
+            };
+            assert_close(normal, expected);
+        }
+
+        // Lines have no faces
+        let normals = flat_normals(PrimitiveAssetMode::LineList, &positions, None);
+        assert!(normals.iter().all(|normal| *normal == [0.0; 3]));
+    }
+
+    #[test]
+    fn test_smooth_normals() {
+        let normals = smooth_normals(PrimitiveAssetMode::TriangleList, &POSITIONS, Some(&INDICES));
+        assert_close(normals[0], [0.0, 0.0, 1.0]);
+        assert_close(normals[2], [1.0, 0.0, 0.0]);
+        // Vertex 1 has two triangles on the right quad but one on the left,
+        // both covering 90 degrees
+        assert_close(normals[1], [FRAC_1_SQRT_2, 0.0, FRAC_1_SQRT_2]);
+        assert_close(normals[4], [FRAC_1_SQRT_2, 0.0, FRAC_1_SQRT_2]);
+
+        // The same fold as a strip, zigzagging between the top and bottom
+        let strip = [3, 0, 4, 1, 5, 2].map(|index| POSITIONS[index]);
+        let normals = smooth_normals(PrimitiveAssetMode::TriangleStrip, &strip, None);
+        assert_close(normals[1], [0.0, 0.0, 1.0]);
+        assert_close(normals[2], [FRAC_1_SQRT_2, 0.0, FRAC_1_SQRT_2]);
+        assert_close(normals[3], [FRAC_1_SQRT_2, 0.0, FRAC_1_SQRT_2]);
+        assert_close(normals[5], [1.0, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,1017 @@
+//! Rigid body physics of PMX models, and its simulation.
+//!
+//! Rigid bodies are attached to bones. Bodies following their bone are moved
+//! by the animation, while the others fall under gravity, held together by
+//! spring joints and pushed out of each other, and then move their bone.
+//!
+//! The simulation is position based: constraints are solved by moving the
+//! bodies directly over small substeps, which keeps stiff joint chains
+//! stable without a global solver.
+
+use glam::{Mat4, Quat, Vec3};
+
+use crate::index::AssetIndex;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RigidBodyShape {
+    Sphere {
+        radius: f32,
+    },
+    Box {
+        half_extents: Vec3,
+    },
+    /// Along the y axis, `height` not counting the caps.
+    Capsule {
+        radius: f32,
+        height: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RigidBodyMode {
+    /// Moved with the bone and not simulated.
+    FollowBone,
+    /// Simulated, and moves the bone.
+    Physics,
+    /// Simulated, but keeps the position of the bone and only rotates it.
+    PhysicsWithBone,
+}
+
+#[derive(Debug, Clone)]
+pub struct RigidBodyAsset {
+    pub name: Option<String>,
+    pub bone: Option<AssetIndex>,
+    /// Collision group, from 0 to 15.
+    pub group: u8,
+    /// Bit `n` is set to collide with group `n`.
+    pub collision_mask: u16,
+    pub shape: RigidBodyShape,
+    /// Rest transform in model space.
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub mass: f32,
+    /// Fraction of the velocity lost per second.
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    /// Kept for completeness, but not simulated.
+    pub restitution: f32,
+    pub friction: f32,
+    pub mode: RigidBodyMode,
+}
+
+/// 6-DOF joint, with springs pulling `body_b` back to its rest transform
+/// relative to `body_a`.
+#[derive(Debug, Clone)]
+pub struct JointAsset {
+    pub name: Option<String>,
+    /// Indices into [`PhysicsAsset::rigid_bodies`].
+    pub body_a: usize,
+    pub body_b: usize,
+    /// Rest transform in model space.
+    pub position: Vec3,
+    pub rotation: Quat,
+    /// Limits of the movement along the axes of the joint. An axis with the
+    /// lower limit above the upper one is free.
+    pub linear_lower: Vec3,
+    pub linear_upper: Vec3,
+    /// Limits of the rotation around the axes of the joint, in radians.
+    pub angular_lower: Vec3,
+    pub angular_upper: Vec3,
+    pub linear_stiffness: Vec3,
+    pub angular_stiffness: Vec3,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PhysicsAsset {
+    pub rigid_bodies: Vec<RigidBodyAsset>,
+    pub joints: Vec<JointAsset>,
+}
+
+/// Bone transforms the simulation reads and drives.
+pub trait PhysicsPose {
+    fn parent(&self, node: &AssetIndex) -> Option<AssetIndex>;
+
+    fn world_transform(&self, node: &AssetIndex) -> Mat4;
+
+    fn local_transform(&self, node: &AssetIndex) -> Mat4;
+
+    /// Replace the transform of the node. The world transforms of the node
+    /// and its descendants must follow.
+    fn set_local_transform(&mut self, node: &AssetIndex, transform: Mat4);
+}
+
+/// Gravity MMD uses, in model units per second squared.
+pub const DEFAULT_GRAVITY: Vec3 = Vec3::new(0.0, -98.0, 0.0);
+
+const SUBSTEP: f32 = 1.0 / 240.0;
+const MAX_SUBSTEPS: usize = 32;
+const ITERATIONS: usize = 4;
+const EPSILON: f32 = 1e-6;
+
+#[derive(Debug, Clone)]
+struct BodyState {
+    bone: Option<AssetIndex>,
+    mode: RigidBodyMode,
+    /// Transform of the body in the space of the bone.
+    offset: Mat4,
+    rest: (Vec3, Quat),
+    inverse_mass: f32,
+    /// In the space of the body.
+    inverse_inertia: Vec3,
+    linear_damping: f32,
+    angular_damping: f32,
+    group: u16,
+    collision_mask: u16,
+    shape: RigidBodyShape,
+    position: Vec3,
+    rotation: Quat,
+    prev_position: Vec3,
+    prev_rotation: Quat,
+    velocity: Vec3,
+    angular_velocity: Vec3,
+    /// Transforms following the bone at the start and the end of the step.
+    from: (Vec3, Quat),
+    to: (Vec3, Quat),
+}
+
+#[derive(Debug, Clone)]
+struct JointState {
+    body_a: usize,
+    body_b: usize,
+    /// Joint frame in the space of each body.
+    frame_a: (Vec3, Quat),
+    frame_b: (Vec3, Quat),
+    linear_lower: Vec3,
+    linear_upper: Vec3,
+    angular_lower: Vec3,
+    angular_upper: Vec3,
+    linear_stiffness: Vec3,
+    angular_stiffness: Vec3,
+}
+
+#[derive(Debug, Clone)]
+pub struct PhysicsSimulation {
+    bodies: Vec<BodyState>,
+    joints: Vec<JointState>,
+    /// Bodies moving their bone, parents first.
+    driving: Vec<usize>,
+    initial_local_transforms: Vec<(AssetIndex, Mat4)>,
+    gravity: Vec3,
+}
+
+/// Diagonal of the inertia tensor, along the axes of the shape.
+fn inertia(shape: RigidBodyShape, mass: f32) -> Vec3 {
+    match shape {
+        RigidBodyShape::Sphere { radius } => Vec3::splat(0.4 * mass * radius * radius),
+        RigidBodyShape::Box { half_extents } => {
+            let squared = half_extents * half_extents;
+            Vec3::new(
+                squared.y + squared.z,
+                squared.x + squared.z,
+                squared.x + squared.y,
+            ) * mass
+                / 3.0
+        }
+        RigidBodyShape::Capsule { radius, height } => {
+            // As a cylinder covering the caps
+            let length = height + 2.0 * radius;
+            let side = mass * (3.0 * radius * radius + length * length) / 12.0;
+            Vec3::new(side, mass * radius * radius / 2.0, side)
+        }
+    }
+}
+
+/// Capsule around the shape, as its axis, half length and radius. Boxes are
+/// rounded along their longest axis.
+fn bounding_capsule(shape: RigidBodyShape) -> (Vec3, f32, f32) {
+    match shape {
+        RigidBodyShape::Sphere { radius } => (Vec3::Y, 0.0, radius),
+        RigidBodyShape::Capsule { radius, height } => (Vec3::Y, height / 2.0, radius),
+        RigidBodyShape::Box { half_extents } => {
+            let axis = if half_extents.x >= half_extents.y && half_extents.x >= half_extents.z {
+                Vec3::X
+            } else if half_extents.y >= half_extents.z {
+                Vec3::Y
+            } else {
+                Vec3::Z
+            };
+            let length = half_extents.dot(axis);
+            let radius = (half_extents - axis * length).max_element();
+            (axis, (length - radius).max(0.0), radius)
+        }
+    }
+}
+
+fn invert_or_zero(value: f32) -> f32 {
+    if value > EPSILON {
+        value.recip()
+    } else {
+        0.0
+    }
+}
+
+fn closest_point_on_segment(point: Vec3, head: Vec3, tail: Vec3) -> Vec3 {
+    let segment = tail - head;
+    let length_squared = segment.length_squared();
+    if length_squared <= EPSILON {
+        return head;
+    }
+    let t = ((point - head).dot(segment) / length_squared).clamp(0.0, 1.0);
+    head + segment * t
+}
+
+/// Closest points between the segments `head_a`-`tail_a` and
+/// `head_b`-`tail_b`.
+fn closest_points(head_a: Vec3, tail_a: Vec3, head_b: Vec3, tail_b: Vec3) -> (Vec3, Vec3) {
+    let segment_a = tail_a - head_a;
+    let segment_b = tail_b - head_b;
+    let between = head_a - head_b;
+    let length_a = segment_a.length_squared();
+    let length_b = segment_b.length_squared();
+    let along_b = segment_b.dot(between);
+    let (s, t) = if length_a <= EPSILON && length_b <= EPSILON {
+        (0.0, 0.0)
+    } else if length_a <= EPSILON {
+        (0.0, (along_b / length_b).clamp(0.0, 1.0))
+    } else {
+        let along_a = segment_a.dot(between);
+        if length_b <= EPSILON {
+            ((-along_a / length_a).clamp(0.0, 1.0), 0.0)
+        } else {
+            let cross = segment_a.dot(segment_b);
+            let denominator = length_a * length_b - cross * cross;
+            let s = if denominator > EPSILON {
+                ((cross * along_b - along_a * length_b) / denominator).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let t = (cross * s + along_b) / length_b;
+            if t < 0.0 {
+                ((-along_a / length_a).clamp(0.0, 1.0), 0.0)
+            } else if t > 1.0 {
+                (((cross - along_a) / length_a).clamp(0.0, 1.0), 1.0)
+            } else {
+                (s, t)
+            }
+        }
+    };
+    (head_a + segment_a * s, head_b + segment_b * t)
+}
+
+/// Contact of a capsule around `head`-`tail` in the space of a box, as the
+/// normal out of the box, the deepest points on the box and on the capsule,
+/// and the depth. The closest points are found by projecting back and forth
+/// between the box and the segment, which converges for convex shapes.
+fn box_contact(
+    half_extents: Vec3,
+    head: Vec3,
+    tail: Vec3,
+    radius: f32,
+) -> Option<(Vec3, Vec3, Vec3, f32)> {
+    let mut point = closest_point_on_segment(Vec3::ZERO, head, tail);
+    for _ in 0..8 {
+        point = closest_point_on_segment(point.clamp(-half_extents, half_extents), head, tail);
+    }
+    let surface = point.clamp(-half_extents, half_extents);
+    let distance = point.distance(surface);
+    if distance > EPSILON {
+        if distance >= radius {
+            return None;
+        }
+        let normal = (point - surface) / distance;
+        return Some((normal, surface, point - normal * radius, radius - distance));
+    }
+    // Inside the box, leave through the nearest face
+    let room = half_extents - point.abs();
+    let axis = if room.x <= room.y && room.x <= room.z {
+        0
+    } else if room.y <= room.z {
+        1
+    } else {
+        2
+    };
+    let normal = Vec3::AXES[axis] * if point[axis] < 0.0 { -1.0 } else { 1.0 };
+    let mut surface = point;
+    surface[axis] = normal[axis] * half_extents[axis];
+    Some((
+        normal,
+        surface,
+        point - normal * radius,
+        room[axis] + radius,
+    ))
+}
+
+/// Part of `value` outside of the limits, per axis.
+fn limit_excess(value: Vec3, lower: Vec3, upper: Vec3) -> Vec3 {
+    let mut excess = Vec3::ZERO;
+    for axis in 0..3 {
+        if lower[axis] <= upper[axis] {
+            excess[axis] = value[axis] - value[axis].clamp(lower[axis], upper[axis]);
+        }
+    }
+    excess
+}
+
+/// Contact between two bodies, as the normal pushing `a` out of `b`, the
+/// deepest points on both and the depth. Between two boxes, the smaller one
+/// is rounded to a capsule.
+fn contact(a: &BodyState, b: &BodyState) -> Option<(Vec3, Vec3, Vec3, f32)> {
+    let box_extents = |body: &BodyState| match body.shape {
+        RigidBodyShape::Box { half_extents } => Some(half_extents),
+        _ => None,
+    };
+    let box_a = box_extents(a);
+    let box_b = box_extents(b);
+    let against_box = |boxed: &BodyState, half_extents: Vec3, other: &BodyState| {
+        let to_box = boxed.rotation.inverse();
+        let (head, tail, radius) = other.capsule();
+        let (normal, surface, point, depth) = box_contact(
+            half_extents,
+            to_box * (head - boxed.position),
+            to_box * (tail - boxed.position),
+            radius,
+        )?;
+        let to_world = |point: Vec3| boxed.position + boxed.rotation * point;
+        Some((
+            boxed.rotation * normal,
+            to_world(surface),
+            to_world(point),
+            depth,
+        ))
+    };
+    match (box_a, box_b) {
+        (Some(extents_a), Some(extents_b)) if extents_a.max_element() < extents_b.max_element() => {
+            let (normal, point_b, point_a, depth) = against_box(b, extents_b, a)?;
+            Some((normal, point_a, point_b, depth))
+        }
+        (Some(extents_a), _) => {
+            let (normal, point_a, point_b, depth) = against_box(a, extents_a, b)?;
+            Some((-normal, point_a, point_b, depth))
+        }
+        (None, Some(extents_b)) => {
+            let (normal, point_b, point_a, depth) = against_box(b, extents_b, a)?;
+            Some((normal, point_a, point_b, depth))
+        }
+        (None, None) => {
+            let (head_a, tail_a, radius_a) = a.capsule();
+            let (head_b, tail_b, radius_b) = b.capsule();
+            let (point_a, point_b) = closest_points(head_a, tail_a, head_b, tail_b);
+            let distance = point_a.distance(point_b);
+            let depth = radius_a + radius_b - distance;
+            if depth <= 0.0 {
+                return None;
+            }
+            let normal = (point_a - point_b)
+                .try_normalize()
+                .or_else(|| (a.position - b.position).try_normalize())
+                .unwrap_or(Vec3::Y);
+            Some((
+                normal,
+                point_a - normal * radius_a,
+                point_b + normal * radius_b,
+                depth,
+            ))
+        }
+    }
+}
+
+impl BodyState {
+    fn transform(&self) -> Mat4 {
+        Mat4::from_rotation_translation(self.rotation, self.position)
+    }
+
+    /// Core segment of the shape, for the shape as a capsule, with its
+    /// radius.
+    fn capsule(&self) -> (Vec3, Vec3, f32) {
+        let (axis, half_length, radius) = bounding_capsule(self.shape);
+        let half = self.rotation * axis * half_length;
+        (self.position - half, self.position + half, radius)
+    }
+
+    fn is_dynamic(&self) -> bool {
+        self.inverse_mass > 0.0 || self.inverse_inertia != Vec3::ZERO
+    }
+
+    fn apply_inverse_inertia(&self, vector: Vec3) -> Vec3 {
+        self.rotation * (self.inverse_inertia * (self.rotation.inverse() * vector))
+    }
+
+    /// Inverse mass felt by a push along `normal` at `arm` from the center.
+    fn generalized_inverse_mass(&self, arm: Vec3, normal: Vec3) -> f32 {
+        let torque = arm.cross(normal);
+        self.inverse_mass + torque.dot(self.apply_inverse_inertia(torque))
+    }
+
+    fn rotate(&mut self, rotation: Vec3) {
+        let spin = Quat::from_xyzw(rotation.x, rotation.y, rotation.z, 0.0) * self.rotation;
+        self.rotation = (self.rotation + spin * 0.5).normalize();
+    }
+
+    fn push(&mut self, arm: Vec3, impulse: Vec3) {
+        self.position += impulse * self.inverse_mass;
+        self.rotate(self.apply_inverse_inertia(arm.cross(impulse)));
+    }
+
+    /// Where the bone puts the body. Bodies without a bone stay at rest.
+    fn target(&self, pose: &impl PhysicsPose) -> (Vec3, Quat) {
+        let Some(bone) = &self.bone else {
+            return self.rest;
+        };
+        let (_, rotation, position) =
+            (pose.world_transform(bone) * self.offset).to_scale_rotation_translation();
+        (position, rotation)
+    }
+}
+
+/// Move the points at `arm_a` of body `a` and `arm_b` of body `b` by
+/// `correction` towards each other, as much as their masses and the
+/// `compliance` allow.
+fn solve_position(
+    bodies: &mut [BodyState],
+    (a, arm_a): (usize, Vec3),
+    (b, arm_b): (usize, Vec3),
+    correction: Vec3,
+    compliance: f32,
+) {
+    let length = correction.length();
+    if length <= EPSILON {
+        return;
+    }
+    let normal = correction / length;
+    let weight = bodies[a].generalized_inverse_mass(arm_a, normal)
+        + bodies[b].generalized_inverse_mass(arm_b, normal)
+        + compliance;
+    if weight <= EPSILON {
+        return;
+    }
+    let impulse = normal * (length / weight);
+    bodies[a].push(arm_a, impulse);
+    bodies[b].push(arm_b, -impulse);
+}
+
+/// Rotate body `a` by `correction` and body `b` by its opposite, as much as
+/// their inertia and the `compliance` allow.
+fn solve_rotation(bodies: &mut [BodyState], a: usize, b: usize, correction: Vec3, compliance: f32) {
+    let angle = correction.length();
+    if angle <= EPSILON {
+        return;
+    }
+    let axis = correction / angle;
+    let weight = axis.dot(bodies[a].apply_inverse_inertia(axis))
+        + axis.dot(bodies[b].apply_inverse_inertia(axis))
+        + compliance;
+    if weight <= EPSILON {
+        return;
+    }
+    let impulse = axis * (angle / weight);
+    let rotation_a = bodies[a].apply_inverse_inertia(impulse);
+    let rotation_b = bodies[b].apply_inverse_inertia(-impulse);
+    bodies[a].rotate(rotation_a);
+    bodies[b].rotate(rotation_b);
+}
+
+fn stiffness_compliance(stiffness: f32, dt: f32) -> Option<f32> {
+    (stiffness > 0.0).then(|| 1.0 / (stiffness * dt * dt))
+}
+
+impl JointState {
+    /// Anchors on both bodies and the frame of the joint on body `a`.
+    fn anchors(&self, bodies: &[BodyState]) -> (Vec3, Vec3, Quat) {
+        let (a, b) = (&bodies[self.body_a], &bodies[self.body_b]);
+        let arm_a = a.rotation * self.frame_a.0;
+        let arm_b = b.rotation * self.frame_b.0;
+        (arm_a, arm_b, a.rotation * self.frame_a.1)
+    }
+
+    /// Position of `b` relative to `a` in the frame of the joint.
+    fn offset(&self, bodies: &[BodyState]) -> (Vec3, Vec3, Vec3, Quat) {
+        let (arm_a, arm_b, frame) = self.anchors(bodies);
+        let anchor_a = bodies[self.body_a].position + arm_a;
+        let anchor_b = bodies[self.body_b].position + arm_b;
+        (frame.inverse() * (anchor_b - anchor_a), arm_a, arm_b, frame)
+    }
+
+    /// Rotation of `b` relative to `a` in the frame of the joint, as a
+    /// rotation vector. Its components match the euler angles of the
+    /// limits for small rotations.
+    fn angles(&self, bodies: &[BodyState]) -> (Vec3, Quat) {
+        let frame_a = bodies[self.body_a].rotation * self.frame_a.1;
+        let frame_b = bodies[self.body_b].rotation * self.frame_b.1;
+        let mut relative = frame_a.inverse() * frame_b;
+        if relative.w < 0.0 {
+            relative = -relative;
+        }
+        (relative.to_scaled_axis(), frame_a)
+    }
+
+    fn solve(&self, bodies: &mut [BodyState], dt: f32) {
+        let (a, b) = (self.body_a, self.body_b);
+
+        let (offset, arm_a, arm_b, frame) = self.offset(bodies);
+        let excess = limit_excess(offset, self.linear_lower, self.linear_upper);
+        solve_position(bodies, (a, arm_a), (b, arm_b), frame * excess, 0.0);
+        for axis in 0..3 {
+            if let Some(compliance) = stiffness_compliance(self.linear_stiffness[axis], dt) {
+                let (offset, arm_a, arm_b, frame) = self.offset(bodies);
+                let correction = frame * (Vec3::AXES[axis] * offset[axis]);
+                solve_position(bodies, (a, arm_a), (b, arm_b), correction, compliance);
+            }
+        }
+
+        let (angles, frame) = self.angles(bodies);
+        let excess = limit_excess(angles, self.angular_lower, self.angular_upper);
+        solve_rotation(bodies, a, b, frame * excess, 0.0);
+        for axis in 0..3 {
+            if let Some(compliance) = stiffness_compliance(self.angular_stiffness[axis], dt) {
+                let (angles, frame) = self.angles(bodies);
+                let correction = frame * (Vec3::AXES[axis] * angles[axis]);
+                solve_rotation(bodies, a, b, correction, compliance);
+            }
+        }
+    }
+}
+
+impl PhysicsSimulation {
+    /// Start the simulation from the current pose, taken as the rest pose.
+    /// Joints between missing bodies are ignored.
+    pub fn new(asset: &PhysicsAsset, pose: &impl PhysicsPose) -> Self {
+        let bodies: Vec<BodyState> = asset
+            .rigid_bodies
+            .iter()
+            .map(|body| {
+                let rest = Mat4::from_rotation_translation(body.rotation, body.position);
+                let offset = body
+                    .bone
+                    .as_ref()
+                    .map(|bone| pose.world_transform(bone).inverse() * rest)
+                    .unwrap_or(Mat4::IDENTITY);
+                let simulated = body.mode != RigidBodyMode::FollowBone && body.mass > 0.0;
+                let (inverse_mass, inverse_inertia) = if simulated {
+                    (
+                        body.mass.recip(),
+                        inertia(body.shape, body.mass).map(invert_or_zero),
+                    )
+                } else {
+                    (0.0, Vec3::ZERO)
+                };
+                BodyState {
+                    bone: body.bone.clone(),
+                    mode: body.mode,
+                    offset,
+                    rest: (body.position, body.rotation),
+                    inverse_mass,
+                    inverse_inertia,
+                    linear_damping: body.linear_damping.clamp(0.0, 1.0),
+                    angular_damping: body.angular_damping.clamp(0.0, 1.0),
+                    group: 1 << body.group.min(15),
+                    collision_mask: body.collision_mask,
+                    shape: body.shape,
+                    position: body.position,
+                    rotation: body.rotation,
+                    prev_position: body.position,
+                    prev_rotation: body.rotation,
+                    velocity: Vec3::ZERO,
+                    angular_velocity: Vec3::ZERO,
+                    from: (body.position, body.rotation),
+                    to: (body.position, body.rotation),
+                }
+            })
+            .collect();
+
+        let joints = asset
+            .joints
+            .iter()
+            .filter(|joint| joint.body_a < bodies.len() && joint.body_b < bodies.len())
+            .map(|joint| {
+                let frame = |body: &RigidBodyAsset| {
+                    let inverse = body.rotation.inverse();
+                    (
+                        inverse * (joint.position - body.position),
+                        inverse * joint.rotation,
+                    )
+                };
+                JointState {
+                    body_a: joint.body_a,
+                    body_b: joint.body_b,
+                    frame_a: frame(&asset.rigid_bodies[joint.body_a]),
+                    frame_b: frame(&asset.rigid_bodies[joint.body_b]),
+                    linear_lower: joint.linear_lower,
+                    linear_upper: joint.linear_upper,
+                    angular_lower: joint.angular_lower,
+                    angular_upper: joint.angular_upper,
+                    linear_stiffness: joint.linear_stiffness,
+                    angular_stiffness: joint.angular_stiffness,
+                }
+            })
+            .collect();
+
+        let depth = |bone: &AssetIndex| {
+            let mut depth = 0;
+            let mut node = pose.parent(bone);
+            while let Some(parent) = node {
+                depth += 1;
+                node = pose.parent(&parent);
+            }
+            depth
+        };
+        let mut driving: Vec<(usize, usize)> = bodies
+            .iter()
+            .enumerate()
+            .filter(|(_, body)| body.is_dynamic())
+            .filter_map(|(index, body)| body.bone.as_ref().map(|bone| (depth(bone), index)))
+            .collect();
+        driving.sort();
+        let driving: Vec<usize> = driving.into_iter().map(|(_, index)| index).collect();
+        let initial_local_transforms = driving
+            .iter()
+            .filter_map(|index| bodies[*index].bone.clone())
+            .map(|bone| {
+                let transform = pose.local_transform(&bone);
+                (bone, transform)
+            })
+            .collect();
+
+        Self {
+            bodies,
+            joints,
+            driving,
+            initial_local_transforms,
+            gravity: DEFAULT_GRAVITY,
+        }
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vec3) {
+        self.gravity = gravity;
+    }
+
+    /// Current transform of a rigid body in model space.
+    pub fn rigid_body_transform(&self, index: usize) -> Option<Mat4> {
+        self.bodies.get(index).map(BodyState::transform)
+    }
+
+    fn integrate(&mut self, t: f32, dt: f32) {
+        for body in &mut self.bodies {
+            body.prev_position = body.position;
+            body.prev_rotation = body.rotation;
+            let (from_position, from_rotation) = body.from;
+            let (to_position, to_rotation) = body.to;
+            if body.inverse_mass > 0.0 {
+                body.velocity += self.gravity * dt;
+                body.velocity *= (1.0 - body.linear_damping).powf(dt);
+                body.position += body.velocity * dt;
+            } else {
+                body.position = from_position.lerp(to_position, t);
+            }
+            if body.inverse_inertia != Vec3::ZERO {
+                body.angular_velocity *= (1.0 - body.angular_damping).powf(dt);
+                body.rotate(body.angular_velocity * dt);
+            } else {
+                body.rotation = from_rotation.slerp(to_rotation, t);
+            }
+        }
+    }
+
+    fn collide(&mut self, jointed: &[(usize, usize)]) {
+        for a in 0..self.bodies.len() {
+            for b in a + 1..self.bodies.len() {
+                let (body_a, body_b) = (&self.bodies[a], &self.bodies[b]);
+                if !(body_a.is_dynamic() || body_b.is_dynamic())
+                    || body_a.collision_mask & body_b.group == 0
+                    || body_b.collision_mask & body_a.group == 0
+                    || jointed.contains(&(a, b))
+                {
+                    continue;
+                }
+                let Some((normal, point_a, point_b, penetration)) = contact(body_a, body_b) else {
+                    continue;
+                };
+                let arm_a = point_a - body_a.position;
+                let arm_b = point_b - body_b.position;
+                solve_position(
+                    &mut self.bodies,
+                    (a, arm_a),
+                    (b, arm_b),
+                    normal * penetration,
+                    0.0,
+                );
+            }
+        }
+    }
+
+    fn update_velocities(&mut self, dt: f32) {
+        for body in &mut self.bodies {
+            body.velocity = (body.position - body.prev_position) / dt;
+            let mut spin = body.rotation * body.prev_rotation.inverse();
+            if spin.w < 0.0 {
+                spin = -spin;
+            }
+            body.angular_velocity = Vec3::new(spin.x, spin.y, spin.z) * 2.0 / dt;
+        }
+    }
+
+    /// Move the bones of the simulated bodies to them.
+    fn drive(&self, pose: &mut impl PhysicsPose) {
+        for index in &self.driving {
+            let body = &self.bodies[*index];
+            let Some(bone) = &body.bone else {
+                continue;
+            };
+            let local = pose.local_transform(bone);
+            let parent = pose.world_transform(bone) * local.inverse();
+            let target = parent.inverse() * body.transform() * body.offset.inverse();
+            let (scale, _, translation) = local.to_scale_rotation_translation();
+            let (_, rotation, target_translation) = target.to_scale_rotation_translation();
+            let translation = match body.mode {
+                RigidBodyMode::Physics => target_translation,
+                _ => translation,
+            };
+            pose.set_local_transform(
+                bone,
+                Mat4::from_scale_rotation_translation(scale, rotation, translation),
+            );
+        }
+    }
+
+    /// Advance the simulation by `dt` seconds, with bodies following their
+    /// bone moved to the current pose, and move the bones of the simulated
+    /// bodies in the pose.
+    pub fn step(&mut self, pose: &mut impl PhysicsPose, dt: f32) {
+        if dt <= 0.0 {
+            return;
+        }
+        for body in &mut self.bodies {
+            body.from = body.to;
+            body.to = body.target(pose);
+        }
+        let jointed: Vec<(usize, usize)> = self
+            .joints
+            .iter()
+            .map(|joint| {
+                (
+                    joint.body_a.min(joint.body_b),
+                    joint.body_a.max(joint.body_b),
+                )
+            })
+            .collect();
+
+        let substeps = ((dt / SUBSTEP).ceil() as usize).clamp(1, MAX_SUBSTEPS);
+        let substep = dt / substeps as f32;
+        for index in 0..substeps {
+            self.integrate((index + 1) as f32 / substeps as f32, substep);
+            for _ in 0..ITERATIONS {
+                for joint in &self.joints {
+                    joint.solve(&mut self.bodies, substep);
+                }
+                self.collide(&jointed);
+            }
+            self.update_velocities(substep);
+        }
+        self.drive(pose);
+    }
+
+    /// Put the bones of the simulated bodies back to rest, and every body
+    /// back on its bone.
+    pub fn reset(&mut self, pose: &mut impl PhysicsPose) {
+        for (bone, transform) in &self.initial_local_transforms {
+            pose.set_local_transform(bone, *transform);
+        }
+        for body in &mut self.bodies {
+            let (position, rotation) = body.target(pose);
+            body.position = position;
+            body.rotation = rotation;
+            body.prev_position = position;
+            body.prev_rotation = rotation;
+            body.velocity = Vec3::ZERO;
+            body.angular_velocity = Vec3::ZERO;
+            body.from = (position, rotation);
+            body.to = (position, rotation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::f32::consts::PI;
+
+    use glam::{Mat4, Quat, Vec3};
+
+    use super::{
+        JointAsset, PhysicsAsset, PhysicsPose, PhysicsSimulation, RigidBodyAsset, RigidBodyMode,
+        RigidBodyShape,
+    };
+    use crate::index::{AssetIndex, BundleAssetType, BundleIndex};
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn bone(index: usize) -> AssetIndex {
+        AssetIndex::BundleTypeIndex(BundleIndex([0; 32]), BundleAssetType::Bone, index)
+    }
+
+    fn index(bone: &AssetIndex) -> usize {
+        match bone {
+            AssetIndex::BundleTypeIndex(_, _, index) => *index,
+            _ => unreachable!(),
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Pose {
+        bones: Vec<(Option<usize>, Mat4)>,
+    }
+
+    impl PhysicsPose for Pose {
+        fn parent(&self, node: &AssetIndex) -> Option<AssetIndex> {
+            self.bones[index(node)].0.map(bone)
+        }
+
+        fn world_transform(&self, node: &AssetIndex) -> Mat4 {
+            let parent = self
+                .parent(node)
+                .map(|parent| self.world_transform(&parent))
+                .unwrap_or(Mat4::IDENTITY);
+            parent * self.local_transform(node)
+        }
+
+        fn local_transform(&self, node: &AssetIndex) -> Mat4 {
+            self.bones[index(node)].1
+        }
+
+        fn set_local_transform(&mut self, node: &AssetIndex, transform: Mat4) {
+            self.bones[index(node)].1 = transform;
+        }
+    }
+
+    /// A root bone, with a child at the same place swinging the body.
+    fn pose() -> Pose {
+        Pose {
+            bones: vec![(None, Mat4::IDENTITY), (Some(0), Mat4::IDENTITY)],
+        }
+    }
+
+    fn body(bone_index: usize, position: Vec3, mode: RigidBodyMode) -> RigidBodyAsset {
+        RigidBodyAsset {
+            name: None,
+            bone: Some(bone(bone_index)),
+            group: 0,
+            collision_mask: 0,
+            shape: RigidBodyShape::Sphere { radius: 0.1 },
+            position,
+            rotation: Quat::IDENTITY,
+            mass: 1.0,
+            linear_damping: 0.9,
+            angular_damping: 0.9,
+            restitution: 0.0,
+            friction: 0.5,
+            mode,
+        }
+    }
+
+    /// A body on the root following it, and a body one unit along +x hinged
+    /// to it at the origin, free to rotate around z.
+    fn pendulum(mode: RigidBodyMode) -> PhysicsAsset {
+        PhysicsAsset {
+            rigid_bodies: vec![
+                body(0, Vec3::ZERO, RigidBodyMode::FollowBone),
+                body(1, Vec3::X, mode),
+            ],
+            joints: vec![JointAsset {
+                name: None,
+                body_a: 0,
+                body_b: 1,
+                position: Vec3::ZERO,
+                rotation: Quat::IDENTITY,
+                linear_lower: Vec3::ZERO,
+                linear_upper: Vec3::ZERO,
+                angular_lower: Vec3::new(0.0, 0.0, -PI),
+                angular_upper: Vec3::new(0.0, 0.0, PI),
+                linear_stiffness: Vec3::ZERO,
+                angular_stiffness: Vec3::ZERO,
+            }],
+        }
+    }
+
+    fn simulate(asset: &PhysicsAsset, pose: &mut Pose, steps: usize) -> PhysicsSimulation {
+        let mut simulation = PhysicsSimulation::new(asset, pose);
+        simulation.set_gravity(Vec3::new(0.0, -9.8, 0.0));
+        for _ in 0..steps {
+            simulation.step(pose, DT);
+        }
+        simulation
+    }
+
+    fn body_position(simulation: &PhysicsSimulation, index: usize) -> Vec3 {
+        simulation
+            .rigid_body_transform(index)
+            .unwrap()
+            .w_axis
+            .truncate()
+    }
+
+    fn assert_close(actual: Vec3, expected: Vec3, tolerance: f32) {
+        assert!(
+            actual.abs_diff_eq(expected, tolerance),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_follow_bone() {
+        let asset = PhysicsAsset {
+            rigid_bodies: vec![body(0, Vec3::X, RigidBodyMode::FollowBone)],
+            joints: vec![],
+        };
+        let mut pose = pose();
+        let mut simulation = simulate(&asset, &mut pose, 10);
+        assert_close(body_position(&simulation, 0), Vec3::X, 1e-6);
+
+        pose.bones[0].1 = Mat4::from_rotation_translation(Quat::from_rotation_z(PI / 2.0), Vec3::Z);
+        simulation.step(&mut pose, DT);
+        assert_close(
+            body_position(&simulation, 0),
+            Vec3::new(0.0, 1.0, 1.0),
+            1e-5,
+        );
+        assert_eq!(pose.bones[0].1.w_axis.truncate(), Vec3::Z);
+    }
+
+    #[test]
+    fn test_pendulum() {
+        let asset = pendulum(RigidBodyMode::Physics);
+        let mut pose = pose();
+        let mut simulation = simulate(&asset, &mut pose, 600);
+        // Hanging straight down, the bone turned with the body
+        assert_close(body_position(&simulation, 1), Vec3::NEG_Y, 1e-2);
+        let swing = pose.world_transform(&bone(1));
+        assert_close(swing.transform_vector3(Vec3::X), Vec3::NEG_Y, 1e-2);
+        assert_close(swing.w_axis.truncate(), Vec3::ZERO, 1e-2);
+
+        // Deterministic
+        let mut other_pose = self::pose();
+        simulate(&asset, &mut other_pose, 600);
+        assert_eq!(pose, other_pose);
+
+        // Keeping the position of the bone gives the same swing
+        let mut with_bone = self::pose();
+        simulate(
+            &pendulum(RigidBodyMode::PhysicsWithBone),
+            &mut with_bone,
+            600,
+        );
+        let with_bone = with_bone.world_transform(&bone(1));
+        assert_close(with_bone.transform_vector3(Vec3::X), Vec3::NEG_Y, 1e-2);
+        assert_eq!(with_bone.w_axis.truncate(), Vec3::ZERO);
+
+        simulation.reset(&mut pose);
+        assert_eq!(pose, self::pose());
+        assert_close(body_position(&simulation, 1), Vec3::X, 1e-6);
+    }
+
+    #[test]
+    fn test_joint_limits() {
+        let mut asset = pendulum(RigidBodyMode::Physics);
+        asset.joints[0].angular_lower.z = -0.5;
+        let mut pose = pose();
+        let simulation = simulate(&asset, &mut pose, 600);
+        assert_close(
+            body_position(&simulation, 1),
+            Vec3::new(0.5f32.cos(), -0.5f32.sin(), 0.0),
+            1e-2,
+        );
+
+        // A spring holds it up part of the way
+        let mut asset = pendulum(RigidBodyMode::Physics);
+        asset.joints[0].angular_stiffness.z = 10.0;
+        let mut pose = self::pose();
+        let simulation = simulate(&asset, &mut pose, 600);
+        let position = body_position(&simulation, 1);
+        assert!(position.x > 0.1 && position.y < -0.1, "{:?}", position);
+        assert_close(position.length() * Vec3::ONE, Vec3::ONE, 1e-2);
+    }
+
+    #[test]
+    fn test_collision() {
+        let mut asset = pendulum(RigidBodyMode::Physics);
+        asset.rigid_bodies.push(RigidBodyAsset {
+            shape: RigidBodyShape::Box {
+                half_extents: Vec3::new(2.0, 0.1, 0.5),
+            },
+            ..body(0, Vec3::new(0.0, -0.5, 0.0), RigidBodyMode::FollowBone)
+        });
+
+        // Masked out, it swings through the box
+        let mut pose = pose();
+        let simulation = simulate(&asset, &mut pose, 600);
+        assert_close(body_position(&simulation, 1), Vec3::NEG_Y, 1e-2);
+
+        // Resting on top of the box
+        for body in &mut asset.rigid_bodies {
+            body.collision_mask = 1;
+        }
+        let mut pose = self::pose();
+        let simulation = simulate(&asset, &mut pose, 600);
+        let position = body_position(&simulation, 1);
+        assert_close(position, Vec3::new(0.91f32.sqrt(), -0.3, 0.0), 1e-2);
+    }
+}
@@ -1,6 +1,10 @@
 use std::sync::Arc;
 
-use super::material::MaterialAsset;
+use super::{
+    material::MaterialAsset,
+    normal::{flat_normals, smooth_normals},
+    tangent::calculate_tangents,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PrimitiveAssetMode {
@@ -11,6 +15,53 @@ pub enum PrimitiveAssetMode {
     TriangleList,
 }
 
+impl PrimitiveAssetMode {
+    pub fn is_triangles(&self) -> bool {
+        matches!(
+            self,
+            PrimitiveAssetMode::TriangleStrip | PrimitiveAssetMode::TriangleList
+        )
+    }
+
+    /// Vertex indices of each triangle, counter-clockwise like the source.
+    /// Empty for points and lines. Degenerate strip triangles, and triangles
+    /// with an index past `vertex_count`, are skipped.
+    pub fn triangles(&self, vertex_count: usize, indices: Option<&[u32]>) -> Vec<[usize; 3]> {
+        let index = |index: usize| match indices {
+            Some(indices) => indices[index] as usize,
+            None => index,
+        };
+        let count = indices.map_or(vertex_count, |indices| indices.len());
+        let mut triangles: Vec<[usize; 3]> = match self {
+            PrimitiveAssetMode::Points
+            | PrimitiveAssetMode::LineStrip
+            | PrimitiveAssetMode::LineList => Vec::new(),
+            PrimitiveAssetMode::TriangleList => (0..count / 3)
+                .map(|triangle| {
+                    [
+                        index(triangle * 3),
+                        index(triangle * 3 + 1),
+                        index(triangle * 3 + 2),
+                    ]
+                })
+                .collect(),
+            PrimitiveAssetMode::TriangleStrip => (0..count.saturating_sub(2))
+                .map(|triangle| {
+                    // Every other triangle flips to keep the winding order
+                    if triangle % 2 == 0 {
+                        [index(triangle), index(triangle + 1), index(triangle + 2)]
+                    } else {
+                        [index(triangle), index(triangle + 2), index(triangle + 1)]
+                    }
+                })
+                .filter(|[a, b, c]| a != b && b != c && a != c)
+                .collect(),
+        };
+        triangles.retain(|triangle| triangle.iter().all(|index| *index < vertex_count));
+        triangles
+    }
+}
+
 pub type Position = Vec<[f32; 3]>;
 pub type Normal = Vec<[f32; 3]>;
 pub type Tangent = Vec<[f32; 4]>;
@@ -45,3 +96,132 @@ pub struct PrimitiveAsset {
     pub mode: PrimitiveAssetMode,
     pub targets: Vec<PrimitiveAssetMorphTarget>,
 }
+
+fn unindex_attribute<T: Copy>(data: &mut Vec<T>, vertex_count: usize, indices: &[usize]) {
+    // Attributes like morph target normals may be absent, and ones missing
+    // vertices are dropped
+    if data.len() == vertex_count {
+        *data = indices.iter().map(|index| data[*index]).collect();
+    } else {
+        data.clear();
+    }
+}
+
+impl PrimitiveAsset {
+    /// Give every triangle its own vertices, turning strips into lists.
+    /// Points and lines are left untouched.
+    pub fn unindex(&mut self) {
+        if !self.mode.is_triangles() {
+            return;
+        }
+        let vertex_count = self.attributes.position.len();
+        let indices: Vec<usize> = self
+            .mode
+            .triangles(vertex_count, self.indices.as_deref())
+            .into_iter()
+            .flatten()
+            .collect();
+        let attributes = &mut self.attributes;
+        unindex_attribute(&mut attributes.position, vertex_count, &indices);
+        unindex_attribute(&mut attributes.normal, vertex_count, &indices);
+        unindex_attribute(&mut attributes.tangent, vertex_count, &indices);
+        for tex_coord in &mut attributes.tex_coord {
+            unindex_attribute(tex_coord, vertex_count, &indices);
+        }
+        for color in &mut attributes.color {
+            unindex_attribute(color, vertex_count, &indices);
+        }
+        for joints in &mut attributes.joints {
+            unindex_attribute(joints, vertex_count, &indices);
+        }
+        for weights in &mut attributes.weights {
+            unindex_attribute(weights, vertex_count, &indices);
+        }
+        for target in &mut self.targets {
+            unindex_attribute(&mut target.position, vertex_count, &indices);
+            unindex_attribute(&mut target.normal, vertex_count, &indices);
+            unindex_attribute(&mut target.tangent, vertex_count, &indices);
+        }
+        self.indices = None;
+        self.mode = PrimitiveAssetMode::TriangleList;
+    }
+
+    /// Replace normals with face normals. Vertices are split first so faces
+    /// don't share them.
+    pub fn generate_flat_normals(&mut self) {
+        self.unindex();
+        self.attributes.normal = flat_normals(self.mode, &self.attributes.position, None);
+    }
+
+    /// Replace normals with angle-weighted averages of the adjacent faces.
+    pub fn generate_smooth_normals(&mut self) {
+        self.attributes.normal = smooth_normals(
+            self.mode,
+            &self.attributes.position,
+            self.indices.as_deref(),
+        );
+    }
+
+    /// Replace tangents with MikkTSpace ones. The texture coordinate set is
+    /// the one of the normal texture, if there is any.
+    pub fn generate_tangents(&mut self) {
+        let tex_coord = self
+            .material
+            .as_ref()
+            .and_then(|material| material.normal_texture.as_ref())
+            .map_or(0, |texture| texture.tex_coord);
+        let attributes = &self.attributes;
+        let tex_coord = attributes
+            .tex_coord
+            .get(tex_coord)
+            .map_or(&[][..], Vec::as_slice);
+        self.attributes.tangent = calculate_tangents(
+            self.mode,
+            &attributes.position,
+            &attributes.normal,
+            tex_coord,
+            self.indices.as_deref(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PrimitiveAsset, PrimitiveAssetAttributes, PrimitiveAssetMode};
+
+    #[test]
+    fn test_out_of_range_indices() {
+        let indices = [0, 1, 2, 2, 1, 3];
+        let triangles = PrimitiveAssetMode::TriangleList.triangles(3, Some(&indices));
+        assert_eq!(triangles, [[0, 1, 2]]);
+
+        let indices = [0, 1, 2, 7];
+        let triangles = PrimitiveAssetMode::TriangleStrip.triangles(3, Some(&indices));
+        assert_eq!(triangles, [[0, 1, 2]]);
+    }
+
+    #[test]
+    fn test_unindex_short_attributes() {
+        let mut primitive = PrimitiveAsset {
+            attributes: PrimitiveAssetAttributes {
+                position: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+                normal: vec![[0.0, 0.0, 1.0]],
+                tangent: Vec::new(),
+                tex_coord: vec![vec![[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]],
+                color: vec![vec![[1.0; 4]; 2]],
+                joints: Vec::new(),
+                weights: Vec::new(),
+            },
+            indices: Some(vec![2, 1, 0]),
+            material: None,
+            mode: PrimitiveAssetMode::TriangleList,
+            targets: Vec::new(),
+        };
+        primitive.unindex();
+        let attributes = &primitive.attributes;
+        assert_eq!(attributes.position[0], [0.0, 1.0, 0.0]);
+        assert_eq!(attributes.tex_coord[0][0], [0.0, 1.0]);
+        assert!(attributes.normal.is_empty());
+        assert!(attributes.color[0].is_empty());
+    }
+}
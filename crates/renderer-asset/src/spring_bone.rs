@@ -0,0 +1,471 @@
+//! VRM spring bones, from the `VRMC_springBone` glTF extension, and their
+//! simulation.
+//!
+//! Each spring is a chain of joints swinging behind the animated skeleton
+//! under stiffness, gravity and drag, pushed out of colliders. Every joint
+//! but the last is rotated to point at the simulated position of the next.
+
+use glam::{Mat4, Quat, Vec3};
+
+use crate::index::AssetIndex;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColliderShape {
+    Sphere {
+        offset: Vec3,
+        radius: f32,
+    },
+    Capsule {
+        offset: Vec3,
+        radius: f32,
+        tail: Vec3,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct ColliderAsset {
+    pub node: AssetIndex,
+    /// Shape in the space of the node.
+    pub shape: ColliderShape,
+}
+
+#[derive(Debug, Clone)]
+pub struct ColliderGroupAsset {
+    pub name: Option<String>,
+    /// Indices into [`SpringBoneAsset::colliders`].
+    pub colliders: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SpringJointAsset {
+    pub node: AssetIndex,
+    pub hit_radius: f32,
+    pub stiffness: f32,
+    pub gravity_power: f32,
+    pub gravity_dir: Vec3,
+    pub drag_force: f32,
+}
+
+impl SpringJointAsset {
+    /// Joint with the default parameters of the specification.
+    pub fn new(node: AssetIndex) -> Self {
+        Self {
+            node,
+            hit_radius: 0.0,
+            stiffness: 1.0,
+            gravity_power: 0.0,
+            gravity_dir: Vec3::NEG_Y,
+            drag_force: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SpringAsset {
+    pub name: Option<String>,
+    /// Joints from the root of the chain. The last one is only a tail and is
+    /// not rotated.
+    pub joints: Vec<SpringJointAsset>,
+    /// Indices into [`SpringBoneAsset::collider_groups`].
+    pub collider_groups: Vec<usize>,
+    /// Node whose movement doesn't count as inertia, like the hips.
+    pub center: Option<AssetIndex>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SpringBoneAsset {
+    pub colliders: Vec<ColliderAsset>,
+    pub collider_groups: Vec<ColliderGroupAsset>,
+    pub springs: Vec<SpringAsset>,
+}
+
+/// Node transforms the simulation reads and drives.
+pub trait SpringBonePose {
+    fn world_transform(&self, node: &AssetIndex) -> Mat4;
+
+    fn local_transform(&self, node: &AssetIndex) -> Mat4;
+
+    /// Replace the rotation of the node. The world transforms of the node
+    /// and its descendants must follow.
+    fn set_local_rotation(&mut self, node: &AssetIndex, rotation: Quat);
+}
+
+#[derive(Debug, Clone)]
+struct JointState {
+    node: AssetIndex,
+    hit_radius: f32,
+    stiffness: f32,
+    gravity: Vec3,
+    drag_force: f32,
+    initial_local_rotation: Quat,
+    /// Direction to the next joint in the space of this joint.
+    bone_axis: Vec3,
+    bone_length: f32,
+    /// Tail positions in the space of the center.
+    current_tail: Vec3,
+    prev_tail: Vec3,
+}
+
+#[derive(Debug, Clone)]
+struct SpringState {
+    joints: Vec<JointState>,
+    colliders: Vec<usize>,
+    center: Option<AssetIndex>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SpringBoneSimulation {
+    colliders: Vec<ColliderAsset>,
+    springs: Vec<SpringState>,
+}
+
+fn center_transform(pose: &impl SpringBonePose, center: Option<&AssetIndex>) -> Mat4 {
+    center
+        .map(|center| pose.world_transform(center))
+        .unwrap_or(Mat4::IDENTITY)
+}
+
+fn closest_point_on_segment(point: Vec3, head: Vec3, tail: Vec3) -> Vec3 {
+    let segment = tail - head;
+    let length_squared = segment.length_squared();
+    if length_squared == 0.0 {
+        return head;
+    }
+    let t = ((point - head).dot(segment) / length_squared).clamp(0.0, 1.0);
+    head + segment * t
+}
+
+impl SpringBoneSimulation {
+    /// Start the simulation from the current pose, taken as the rest pose.
+    pub fn new(asset: &SpringBoneAsset, pose: &impl SpringBonePose) -> Self {
+        let springs = asset
+            .springs
+            .iter()
+            .map(|spring| {
+                let to_center = center_transform(pose, spring.center.as_ref()).inverse();
+                let joints = spring
+                    .joints
+                    .windows(2)
+                    .map(|joints| {
+                        let (joint, next) = (&joints[0], &joints[1]);
+                        let local = pose.local_transform(&joint.node);
+                        let (_, initial_local_rotation, _) = local.to_scale_rotation_translation();
+                        let next_local = pose.local_transform(&next.node).w_axis.truncate();
+                        let head = pose.world_transform(&joint.node).w_axis.truncate();
+                        let tail = pose.world_transform(&next.node).w_axis.truncate();
+                        let bone_length = head.distance(tail);
+                        let tail = to_center.transform_point3(tail);
+                        JointState {
+                            node: joint.node.clone(),
+                            hit_radius: joint.hit_radius,
+                            stiffness: joint.stiffness,
+                            gravity: joint.gravity_dir.normalize_or_zero() * joint.gravity_power,
+                            drag_force: joint.drag_force,
+                            initial_local_rotation,
+                            bone_axis: next_local.normalize_or(Vec3::Y),
+                            bone_length,
+                            current_tail: tail,
+                            prev_tail: tail,
+                        }
+                    })
+                    .collect();
+                let colliders = spring
+                    .collider_groups
+                    .iter()
+                    .filter_map(|group| asset.collider_groups.get(*group))
+                    .flat_map(|group| group.colliders.iter().copied())
+                    .filter(|collider| *collider < asset.colliders.len())
+                    .collect();
+                SpringState {
+                    joints,
+                    colliders,
+                    center: spring.center.clone(),
+                }
+            })
+            .collect();
+        Self {
+            colliders: asset.colliders.clone(),
+            springs,
+        }
+    }
+
+    /// Push a tail with `radius` out of the collider.
+    fn collide(&self, pose: &impl SpringBonePose, collider: usize, tail: &mut Vec3, radius: f32) {
+        let collider = &self.colliders[collider];
+        let transform = pose.world_transform(&collider.node);
+        let (center, collider_radius) = match collider.shape {
+            ColliderShape::Sphere { offset, radius } => {
+                (transform.transform_point3(offset), radius)
+            }
+            ColliderShape::Capsule {
+                offset,
+                radius,
+                tail: capsule_tail,
+            } => {
+                let head = transform.transform_point3(offset);
+                let capsule_tail = transform.transform_point3(capsule_tail);
+                (closest_point_on_segment(*tail, head, capsule_tail), radius)
+            }
+        };
+        let distance = radius + collider_radius;
+        let offset = *tail - center;
+        if offset.length_squared() < distance * distance {
+            *tail = center + offset.normalize_or(Vec3::Y) * distance;
+        }
+    }
+
+    /// Advance the simulation by `dt` seconds and rotate the joints in the
+    /// pose. Joints are processed from the root of each chain, so every
+    /// joint sees the movement of the ones before it.
+    pub fn step(&mut self, pose: &mut impl SpringBonePose, dt: f32) {
+        for spring_index in 0..self.springs.len() {
+            let center = center_transform(pose, self.springs[spring_index].center.as_ref());
+            let to_center = center.inverse();
+            for joint_index in 0..self.springs[spring_index].joints.len() {
+                let spring = &self.springs[spring_index];
+                let joint = &spring.joints[joint_index];
+                let world = pose.world_transform(&joint.node);
+                let local = pose.local_transform(&joint.node);
+                let parent_rotation = (world * local.inverse()).to_scale_rotation_translation().1;
+                let head = world.w_axis.truncate();
+                let rest_rotation = parent_rotation * joint.initial_local_rotation;
+
+                let current_tail = center.transform_point3(joint.current_tail);
+                let prev_tail = center.transform_point3(joint.prev_tail);
+                let inertia = (current_tail - prev_tail) * (1.0 - joint.drag_force);
+                let stiffness = rest_rotation * joint.bone_axis * joint.stiffness * dt;
+                let external = joint.gravity * dt;
+                let mut next_tail = current_tail + inertia + stiffness + external;
+                let constrain = |tail: Vec3| {
+                    head + (tail - head).normalize_or(rest_rotation * joint.bone_axis)
+                        * joint.bone_length
+                };
+                next_tail = constrain(next_tail);
+                for collider in &spring.colliders {
+                    self.collide(pose, *collider, &mut next_tail, joint.hit_radius);
+                    next_tail = constrain(next_tail);
+                }
+
+                let direction = rest_rotation.inverse() * (next_tail - head);
+                let rotation = joint.initial_local_rotation
+                    * Quat::from_rotation_arc(
+                        joint.bone_axis,
+                        direction.normalize_or(joint.bone_axis),
+                    );
+                let node = joint.node.clone();
+                let joint = &mut self.springs[spring_index].joints[joint_index];
+                joint.prev_tail = joint.current_tail;
+                joint.current_tail = to_center.transform_point3(next_tail);
+                pose.set_local_rotation(&node, rotation);
+            }
+        }
+    }
+
+    /// Put the joints back to rest and stop them.
+    pub fn reset(&mut self, pose: &mut impl SpringBonePose) {
+        for spring in &mut self.springs {
+            for joint in &spring.joints {
+                pose.set_local_rotation(&joint.node, joint.initial_local_rotation);
+            }
+        }
+        for spring in &mut self.springs {
+            let to_center = center_transform(pose, spring.center.as_ref()).inverse();
+            for joint in &mut spring.joints {
+                let world = pose.world_transform(&joint.node);
+                let (_, rotation, head) = world.to_scale_rotation_translation();
+                let tail = head + rotation * joint.bone_axis * joint.bone_length;
+                joint.current_tail = to_center.transform_point3(tail);
+                joint.prev_tail = joint.current_tail;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::{Mat4, Quat, Vec3};
+
+    use super::{
+        ColliderAsset, ColliderGroupAsset, ColliderShape, SpringAsset, SpringBoneAsset,
+        SpringBonePose, SpringBoneSimulation, SpringJointAsset,
+    };
+    use crate::index::{AssetIndex, BundleAssetType, BundleIndex};
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn node(index: usize) -> AssetIndex {
+        AssetIndex::BundleTypeIndex(BundleIndex([0; 32]), BundleAssetType::Node, index)
+    }
+
+    fn index(node: &AssetIndex) -> usize {
+        match node {
+            AssetIndex::BundleTypeIndex(_, _, index) => *index,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Nodes with translation and rotation, each the child of the previous
+    /// one except for the ones with no parent.
+    #[derive(Debug, Clone, PartialEq)]
+    struct Pose {
+        nodes: Vec<(Option<usize>, Vec3, Quat)>,
+    }
+
+    impl SpringBonePose for Pose {
+        fn world_transform(&self, node: &AssetIndex) -> Mat4 {
+            let (parent, _, _) = self.nodes[index(node)];
+            let parent = parent
+                .map(|parent| self.world_transform(&self::node(parent)))
+                .unwrap_or(Mat4::IDENTITY);
+            parent * self.local_transform(node)
+        }
+
+        fn local_transform(&self, node: &AssetIndex) -> Mat4 {
+            let (_, translation, rotation) = self.nodes[index(node)];
+            Mat4::from_rotation_translation(rotation, translation)
+        }
+
+        fn set_local_rotation(&mut self, node: &AssetIndex, rotation: Quat) {
+            self.nodes[index(node)].2 = rotation;
+        }
+    }
+
+    /// A root with a horizontal chain of three bones along +x.
+    fn chain() -> Pose {
+        Pose {
+            nodes: vec![
+                (None, Vec3::ZERO, Quat::IDENTITY),
+                (Some(0), Vec3::ZERO, Quat::IDENTITY),
+                (Some(1), Vec3::X, Quat::IDENTITY),
+                (Some(2), Vec3::X, Quat::IDENTITY),
+            ],
+        }
+    }
+
+    fn spring(
+        joint: impl Fn(AssetIndex) -> SpringJointAsset,
+        collider_groups: Vec<usize>,
+    ) -> SpringAsset {
+        SpringAsset {
+            name: None,
+            joints: (1..4).map(|index| joint(node(index))).collect(),
+            collider_groups,
+            center: None,
+        }
+    }
+
+    fn heavy(node: AssetIndex) -> SpringJointAsset {
+        SpringJointAsset {
+            stiffness: 0.0,
+            gravity_power: 1.0,
+            ..SpringJointAsset::new(node)
+        }
+    }
+
+    fn position(pose: &Pose, index: usize) -> Vec3 {
+        pose.world_transform(&node(index)).w_axis.truncate()
+    }
+
+    #[test]
+    fn test_rest() {
+        let asset = SpringBoneAsset {
+            springs: vec![spring(SpringJointAsset::new, vec![])],
+            ..Default::default()
+        };
+        let mut pose = chain();
+        let mut simulation = SpringBoneSimulation::new(&asset, &pose);
+        for _ in 0..60 {
+            simulation.step(&mut pose, DT);
+        }
+        assert!(position(&pose, 3).abs_diff_eq(Vec3::new(2.0, 0.0, 0.0), 1e-4));
+
+        // The chain lags behind its moving parent, then settles back
+        pose.nodes[0].2 = Quat::from_rotation_z(1.0);
+        simulation.step(&mut pose, DT);
+        let rest = pose
+            .world_transform(&node(0))
+            .transform_point3(Vec3::new(2.0, 0.0, 0.0));
+        assert!(position(&pose, 3).distance(rest) > 0.1);
+        for _ in 0..600 {
+            simulation.step(&mut pose, DT);
+        }
+        assert!(position(&pose, 3).abs_diff_eq(rest, 1e-3));
+    }
+
+    #[test]
+    fn test_gravity() {
+        let asset = SpringBoneAsset {
+            springs: vec![spring(heavy, vec![])],
+            ..Default::default()
+        };
+        let mut pose = chain();
+        let mut simulation = SpringBoneSimulation::new(&asset, &pose);
+        for _ in 0..600 {
+            simulation.step(&mut pose, DT);
+        }
+        // Hanging straight down, bone lengths kept
+        assert!(position(&pose, 2).abs_diff_eq(Vec3::new(0.0, -1.0, 0.0), 1e-2));
+        assert!(position(&pose, 3).abs_diff_eq(Vec3::new(0.0, -2.0, 0.0), 1e-2));
+
+        // Deterministic
+        let mut other_pose = chain();
+        let mut other = SpringBoneSimulation::new(&asset, &other_pose);
+        for _ in 0..600 {
+            other.step(&mut other_pose, DT);
+        }
+        assert_eq!(pose, other_pose);
+
+        simulation.reset(&mut pose);
+        assert_eq!(pose, chain());
+    }
+
+    #[test]
+    fn test_colliders() {
+        let simulate = |shape| {
+            let asset = SpringBoneAsset {
+                colliders: vec![ColliderAsset {
+                    node: node(0),
+                    shape,
+                }],
+                collider_groups: vec![ColliderGroupAsset {
+                    name: None,
+                    colliders: vec![0],
+                }],
+                springs: vec![spring(
+                    |node| SpringJointAsset {
+                        hit_radius: 0.1,
+                        ..heavy(node)
+                    },
+                    vec![0],
+                )],
+            };
+            let mut pose = chain();
+            let mut simulation = SpringBoneSimulation::new(&asset, &pose);
+            for _ in 0..600 {
+                simulation.step(&mut pose, DT);
+            }
+            pose
+        };
+
+        let pose = simulate(ColliderShape::Sphere {
+            offset: Vec3::new(0.5, -1.0, 0.0),
+            radius: 0.5,
+        });
+        // Draped over the sphere instead of hanging straight down. Keeping
+        // the bone length after a push may leave tails slightly inside.
+        let tail = position(&pose, 2);
+        assert!(tail.distance(Vec3::new(0.5, -1.0, 0.0)) >= 0.6 - 1e-2);
+        assert!(tail.x > 0.1);
+
+        // The chain stays in the z = 0 plane, where this capsule looks like
+        // the sphere
+        let capsule = simulate(ColliderShape::Capsule {
+            offset: Vec3::new(0.5, -1.0, -1.0),
+            radius: 0.5,
+            tail: Vec3::new(0.5, -1.0, 1.0),
+        });
+        for index in 2..4 {
+            assert!(position(&capsule, index).abs_diff_eq(position(&pose, index), 1e-4));
+        }
+    }
+}
@@ -1,58 +1,173 @@
+use bevy_mikktspace::Geometry;
 use glam::Vec3;
 
-use super::primitive::PrimitiveAssetMode;
+use super::primitive::{PrimitiveAssetMode, Tangent};
 
-fn calculate_triangle_tangent(positions: &[&[f32; 3]; 3]) -> Vec3 {
-    let pnt_0 = Vec3::from_array(*positions[0]);
-    let pnt_1 = Vec3::from_array(*positions[1]);
-    let pnt_2 = Vec3::from_array(*positions[2]);
-    let vec_a = pnt_2 - pnt_1;
-    let vec_b = pnt_0 - pnt_1;
-    vec_a.cross(vec_b).normalize()
+struct MikkTSpaceGeometry<'a> {
+    triangles: Vec<[usize; 3]>,
+    positions: &'a [[f32; 3]],
+    normals: &'a [[f32; 3]],
+    tex_coords: &'a [[f32; 2]],
+    tangents: Tangent,
 }
 
-// TODO: replace with mikktspace
-pub fn calculate_tangent(
+impl Geometry for MikkTSpaceGeometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.triangles.len()
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.positions[self.triangles[face][vert]]
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.normals[self.triangles[face][vert]]
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.tex_coords[self.triangles[face][vert]]
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[self.triangles[face][vert]] = tangent;
+    }
+}
+
+/// Any tangent perpendicular to the normal, for when there are no texture
+/// coordinates to align it with.
+fn fallback_tangent(normal: &[f32; 3]) -> [f32; 4] {
+    let normal = Vec3::from_array(*normal);
+    let tangent = if normal == Vec3::ZERO {
+        Vec3::X
+    } else {
+        normal.normalize().any_orthonormal_vector()
+    };
+    tangent.extend(1.0).to_array()
+}
+
+/// MikkTSpace tangents, with the bitangent sign in `w`. Vertices shared by
+/// faces that disagree take the tangent of the last one. Without texture
+/// coordinates, or for points and lines, tangents are just perpendicular to
+/// the normals.
+pub fn calculate_tangents(
     mode: PrimitiveAssetMode,
     positions: &[[f32; 3]],
+    normals: &[[f32; 3]],
+    tex_coords: &[[f32; 2]],
     indices: Option<&[u32]>,
-) -> Vec<[f32; 3]> {
-    let mut buffer = vec![Vec3::ZERO; positions.len()];
-    match mode {
-        // Points and lines don't have normals
-        PrimitiveAssetMode::Points
-        | PrimitiveAssetMode::LineStrip
-        | PrimitiveAssetMode::LineList => {}
-        PrimitiveAssetMode::TriangleStrip => todo!(),
-        PrimitiveAssetMode::TriangleList => {
-            if let Some(indices) = indices {
-                let triangles = indices.len() / 3;
-                (0..triangles).for_each(|triangle_index| {
-                    let indices: Vec<usize> = (triangle_index * 3..triangle_index * 3 + 3)
-                        .map(|index| indices[index] as usize)
-                        .collect();
-                    let positions: Vec<&[f32; 3]> =
-                        indices.iter().map(|index| &positions[*index]).collect();
-                    let positions: [&[f32; 3]; 3] = positions.try_into().unwrap();
-                    let normal = calculate_triangle_tangent(&positions);
-                    indices.iter().for_each(|index| buffer[*index] += normal);
-                });
-            } else {
-                let triangles = positions.len() / 3;
-                (0..triangles).for_each(|triangle_index| {
-                    let indices: Vec<usize> =
-                        (triangle_index * 3..triangle_index * 3 + 3).collect();
-                    let positions: Vec<&[f32; 3]> =
-                        indices.iter().map(|index| &positions[*index]).collect();
-                    let positions: [&[f32; 3]; 3] = positions.try_into().unwrap();
-                    let normal = calculate_triangle_tangent(&positions);
-                    indices.iter().for_each(|index| buffer[*index] += normal);
-                });
-            }
-        }
+) -> Tangent {
+    let tangents: Tangent = normals.iter().map(fallback_tangent).collect();
+    if tex_coords.len() != positions.len() || normals.len() != positions.len() {
+        return tangents;
+    }
+    let mut geometry = MikkTSpaceGeometry {
+        triangles: mode.triangles(positions.len(), indices),
+        positions,
+        normals,
+        tex_coords,
+        tangents,
     };
-    for point_normal in &mut buffer {
-        *point_normal = point_normal.normalize();
+    // Only fails on degenerate input, which may be partly written by then
+    if !geometry.triangles.is_empty() && !bevy_mikktspace::generate_tangents(&mut geometry) {
+        return normals.iter().map(fallback_tangent).collect();
+    }
+    geometry.tangents
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec4;
+
+    use super::calculate_tangents;
+    use crate::primitive::PrimitiveAssetMode;
+
+    // A quad facing +z, as two triangles
+    const POSITIONS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+    const NORMALS: [[f32; 3]; 4] = [[0.0, 0.0, 1.0]; 4];
+    const INDICES: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+    fn assert_tangents(tangents: &[[f32; 4]], expected: [f32; 4]) {
+        for tangent in tangents {
+            assert!(
+                Vec4::from_array(*tangent).abs_diff_eq(Vec4::from_array(expected), 1e-5),
+                "{:?} != {:?}",
+                tangent,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_tangents() {
+        let tex_coords = POSITIONS.map(|[x, y, _z]| [x, y]);
+        let tangents = calculate_tangents(
+            PrimitiveAssetMode::TriangleList,
+            &POSITIONS,
+            &NORMALS,
+            &tex_coords,
+            Some(&INDICES),
+        );
+        assert_tangents(&tangents, [1.0, 0.0, 0.0, 1.0]);
+
+        // Flipping v mirrors the bitangent
+        let flipped = POSITIONS.map(|[x, y, _z]| [x, 1.0 - y]);
+        let tangents = calculate_tangents(
+            PrimitiveAssetMode::TriangleList,
+            &POSITIONS,
+            &NORMALS,
+            &flipped,
+            Some(&INDICES),
+        );
+        assert_tangents(&tangents, [1.0, 0.0, 0.0, -1.0]);
+
+        // U running along y
+        let rotated = POSITIONS.map(|[x, y, _z]| [y, 1.0 - x]);
+        let positions = INDICES.map(|index| POSITIONS[index as usize]);
+        let tex_coords = INDICES.map(|index| rotated[index as usize]);
+        let tangents = calculate_tangents(
+            PrimitiveAssetMode::TriangleList,
+            &positions,
+            &[[0.0, 0.0, 1.0]; 6],
+            &tex_coords,
+            None,
+        );
+        assert_tangents(&tangents, [0.0, 1.0, 0.0, 1.0]);
+
+        let strip = [1, 2, 0, 3].map(|index: usize| POSITIONS[index]);
+        let tex_coords = strip.map(|[x, y, _z]| [x, y]);
+        let tangents = calculate_tangents(
+            PrimitiveAssetMode::TriangleStrip,
+            &strip,
+            &NORMALS,
+            &tex_coords,
+            None,
+        );
+        assert_tangents(&tangents, [1.0, 0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_fallback_tangents() {
+        let tangents = calculate_tangents(
+            PrimitiveAssetMode::TriangleList,
+            &POSITIONS,
+            &NORMALS,
+            &[],
+            Some(&INDICES),
+        );
+        for tangent in tangents {
+            let tangent = Vec4::from_array(tangent);
+            assert!(tangent.truncate().is_normalized());
+            assert_eq!(tangent.z, 0.0);
+            assert_eq!(tangent.w, 1.0);
+        }
     }
-    buffer.into_iter().map(|normal| normal.to_array()).collect()
 }
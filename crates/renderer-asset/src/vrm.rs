@@ -0,0 +1,527 @@
+//! VRM 1.0 avatar data, from the `VRMC_vrm` glTF extension.
+
+use std::collections::BTreeMap;
+
+use glam::Vec3;
+
+use crate::{index::AssetIndex, spring_bone::SpringBoneAsset};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HumanBone {
+    Hips,
+    Spine,
+    Chest,
+    UpperChest,
+    Neck,
+    Head,
+    LeftEye,
+    RightEye,
+    Jaw,
+    LeftUpperLeg,
+    LeftLowerLeg,
+    LeftFoot,
+    LeftToes,
+    RightUpperLeg,
+    RightLowerLeg,
+    RightFoot,
+    RightToes,
+    LeftShoulder,
+    LeftUpperArm,
+    LeftLowerArm,
+    LeftHand,
+    RightShoulder,
+    RightUpperArm,
+    RightLowerArm,
+    RightHand,
+    LeftThumbMetacarpal,
+    LeftThumbProximal,
+    LeftThumbDistal,
+    LeftIndexProximal,
+    LeftIndexIntermediate,
+    LeftIndexDistal,
+    LeftMiddleProximal,
+    LeftMiddleIntermediate,
+    LeftMiddleDistal,
+    LeftRingProximal,
+    LeftRingIntermediate,
+    LeftRingDistal,
+    LeftLittleProximal,
+    LeftLittleIntermediate,
+    LeftLittleDistal,
+    RightThumbMetacarpal,
+    RightThumbProximal,
+    RightThumbDistal,
+    RightIndexProximal,
+    RightIndexIntermediate,
+    RightIndexDistal,
+    RightMiddleProximal,
+    RightMiddleIntermediate,
+    RightMiddleDistal,
+    RightRingProximal,
+    RightRingIntermediate,
+    RightRingDistal,
+    RightLittleProximal,
+    RightLittleIntermediate,
+    RightLittleDistal,
+}
+
+impl HumanBone {
+    pub const ALL: [HumanBone; 55] = [
+        HumanBone::Hips,
+        HumanBone::Spine,
+        HumanBone::Chest,
+        HumanBone::UpperChest,
+        HumanBone::Neck,
+        HumanBone::Head,
+        HumanBone::LeftEye,
+        HumanBone::RightEye,
+        HumanBone::Jaw,
+        HumanBone::LeftUpperLeg,
+        HumanBone::LeftLowerLeg,
+        HumanBone::LeftFoot,
+        HumanBone::LeftToes,
+        HumanBone::RightUpperLeg,
+        HumanBone::RightLowerLeg,
+        HumanBone::RightFoot,
+        HumanBone::RightToes,
+        HumanBone::LeftShoulder,
+        HumanBone::LeftUpperArm,
+        HumanBone::LeftLowerArm,
+        HumanBone::LeftHand,
+        HumanBone::RightShoulder,
+        HumanBone::RightUpperArm,
+        HumanBone::RightLowerArm,
+        HumanBone::RightHand,
+        HumanBone::LeftThumbMetacarpal,
+        HumanBone::LeftThumbProximal,
+        HumanBone::LeftThumbDistal,
+        HumanBone::LeftIndexProximal,
+        HumanBone::LeftIndexIntermediate,
+        HumanBone::LeftIndexDistal,
+        HumanBone::LeftMiddleProximal,
+        HumanBone::LeftMiddleIntermediate,
+        HumanBone::LeftMiddleDistal,
+        HumanBone::LeftRingProximal,
+        HumanBone::LeftRingIntermediate,
+        HumanBone::LeftRingDistal,
+        HumanBone::LeftLittleProximal,
+        HumanBone::LeftLittleIntermediate,
+        HumanBone::LeftLittleDistal,
+        HumanBone::RightThumbMetacarpal,
+        HumanBone::RightThumbProximal,
+        HumanBone::RightThumbDistal,
+        HumanBone::RightIndexProximal,
+        HumanBone::RightIndexIntermediate,
+        HumanBone::RightIndexDistal,
+        HumanBone::RightMiddleProximal,
+        HumanBone::RightMiddleIntermediate,
+        HumanBone::RightMiddleDistal,
+        HumanBone::RightRingProximal,
+        HumanBone::RightRingIntermediate,
+        HumanBone::RightRingDistal,
+        HumanBone::RightLittleProximal,
+        HumanBone::RightLittleIntermediate,
+        HumanBone::RightLittleDistal,
+    ];
+
+    /// Bones every VRM humanoid must have.
+    pub const REQUIRED: [HumanBone; 15] = [
+        HumanBone::Hips,
+        HumanBone::Spine,
+        HumanBone::Head,
+        HumanBone::LeftUpperLeg,
+        HumanBone::LeftLowerLeg,
+        HumanBone::LeftFoot,
+        HumanBone::RightUpperLeg,
+        HumanBone::RightLowerLeg,
+        HumanBone::RightFoot,
+        HumanBone::LeftUpperArm,
+        HumanBone::LeftLowerArm,
+        HumanBone::LeftHand,
+        HumanBone::RightUpperArm,
+        HumanBone::RightLowerArm,
+        HumanBone::RightHand,
+    ];
+
+    /// Name of the bone in the VRM specification.
+    pub fn name(&self) -> &'static str {
+        match self {
+            HumanBone::Hips => "hips",
+            HumanBone::Spine => "spine",
+            HumanBone::Chest => "chest",
+            HumanBone::UpperChest => "upperChest",
+            HumanBone::Neck => "neck",
+            HumanBone::Head => "head",
+            HumanBone::LeftEye => "leftEye",
+            HumanBone::RightEye => "rightEye",
+            HumanBone::Jaw => "jaw",
+            HumanBone::LeftUpperLeg => "leftUpperLeg",
+            HumanBone::LeftLowerLeg => "leftLowerLeg",
+            HumanBone::LeftFoot => "leftFoot",
+            HumanBone::LeftToes => "leftToes",
+            HumanBone::RightUpperLeg => "rightUpperLeg",
+            HumanBone::RightLowerLeg => "rightLowerLeg",
+            HumanBone::RightFoot => "rightFoot",
+            HumanBone::RightToes => "rightToes",
+            HumanBone::LeftShoulder => "leftShoulder",
+            HumanBone::LeftUpperArm => "leftUpperArm",
+            HumanBone::LeftLowerArm => "leftLowerArm",
+            HumanBone::LeftHand => "leftHand",
+            HumanBone::RightShoulder => "rightShoulder",
+            HumanBone::RightUpperArm => "rightUpperArm",
+            HumanBone::RightLowerArm => "rightLowerArm",
+            HumanBone::RightHand => "rightHand",
+            HumanBone::LeftThumbMetacarpal => "leftThumbMetacarpal",
+            HumanBone::LeftThumbProximal => "leftThumbProximal",
+            HumanBone::LeftThumbDistal => "leftThumbDistal",
+            HumanBone::LeftIndexProximal => "leftIndexProximal",
+            HumanBone::LeftIndexIntermediate => "leftIndexIntermediate",
+            HumanBone::LeftIndexDistal => "leftIndexDistal",
+            HumanBone::LeftMiddleProximal => "leftMiddleProximal",
+            HumanBone::LeftMiddleIntermediate => "leftMiddleIntermediate",
+            HumanBone::LeftMiddleDistal => "leftMiddleDistal",
+            HumanBone::LeftRingProximal => "leftRingProximal",
+            HumanBone::LeftRingIntermediate => "leftRingIntermediate",
+            HumanBone::LeftRingDistal => "leftRingDistal",
+            HumanBone::LeftLittleProximal => "leftLittleProximal",
+            HumanBone::LeftLittleIntermediate => "leftLittleIntermediate",
+            HumanBone::LeftLittleDistal => "leftLittleDistal",
+            HumanBone::RightThumbMetacarpal => "rightThumbMetacarpal",
+            HumanBone::RightThumbProximal => "rightThumbProximal",
+            HumanBone::RightThumbDistal => "rightThumbDistal",
+            HumanBone::RightIndexProximal => "rightIndexProximal",
+            HumanBone::RightIndexIntermediate => "rightIndexIntermediate",
+            HumanBone::RightIndexDistal => "rightIndexDistal",
+            HumanBone::RightMiddleProximal => "rightMiddleProximal",
+            HumanBone::RightMiddleIntermediate => "rightMiddleIntermediate",
+            HumanBone::RightMiddleDistal => "rightMiddleDistal",
+            HumanBone::RightRingProximal => "rightRingProximal",
+            HumanBone::RightRingIntermediate => "rightRingIntermediate",
+            HumanBone::RightRingDistal => "rightRingDistal",
+            HumanBone::RightLittleProximal => "rightLittleProximal",
+            HumanBone::RightLittleIntermediate => "rightLittleIntermediate",
+            HumanBone::RightLittleDistal => "rightLittleDistal",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|bone| bone.name() == name)
+    }
+}
+
+/// Human bones mapped to the nodes driving them.
+#[derive(Debug, Clone, Default)]
+pub struct HumanoidAsset {
+    pub bones: BTreeMap<HumanBone, AssetIndex>,
+}
+
+impl HumanoidAsset {
+    pub fn node(&self, bone: HumanBone) -> Option<&AssetIndex> {
+        self.bones.get(&bone)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ExpressionPreset {
+    Happy,
+    Angry,
+    Sad,
+    Relaxed,
+    Surprised,
+    Aa,
+    Ih,
+    Ou,
+    Ee,
+    Oh,
+    Blink,
+    BlinkLeft,
+    BlinkRight,
+    LookUp,
+    LookDown,
+    LookLeft,
+    LookRight,
+    Neutral,
+}
+
+impl ExpressionPreset {
+    pub const ALL: [ExpressionPreset; 18] = [
+        ExpressionPreset::Happy,
+        ExpressionPreset::Angry,
+        ExpressionPreset::Sad,
+        ExpressionPreset::Relaxed,
+        ExpressionPreset::Surprised,
+        ExpressionPreset::Aa,
+        ExpressionPreset::Ih,
+        ExpressionPreset::Ou,
+        ExpressionPreset::Ee,
+        ExpressionPreset::Oh,
+        ExpressionPreset::Blink,
+        ExpressionPreset::BlinkLeft,
+        ExpressionPreset::BlinkRight,
+        ExpressionPreset::LookUp,
+        ExpressionPreset::LookDown,
+        ExpressionPreset::LookLeft,
+        ExpressionPreset::LookRight,
+        ExpressionPreset::Neutral,
+    ];
+
+    /// Name of the preset in the VRM specification.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExpressionPreset::Happy => "happy",
+            ExpressionPreset::Angry => "angry",
+            ExpressionPreset::Sad => "sad",
+            ExpressionPreset::Relaxed => "relaxed",
+            ExpressionPreset::Surprised => "surprised",
+            ExpressionPreset::Aa => "aa",
+            ExpressionPreset::Ih => "ih",
+            ExpressionPreset::Ou => "ou",
+            ExpressionPreset::Ee => "ee",
+            ExpressionPreset::Oh => "oh",
+            ExpressionPreset::Blink => "blink",
+            ExpressionPreset::BlinkLeft => "blinkLeft",
+            ExpressionPreset::BlinkRight => "blinkRight",
+            ExpressionPreset::LookUp => "lookUp",
+            ExpressionPreset::LookDown => "lookDown",
+            ExpressionPreset::LookLeft => "lookLeft",
+            ExpressionPreset::LookRight => "lookRight",
+            ExpressionPreset::Neutral => "neutral",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|preset| preset.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ExpressionName {
+    Preset(ExpressionPreset),
+    Custom(String),
+}
+
+/// How an expression treats the blink, look-at or mouth expressions while
+/// it is active.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExpressionOverride {
+    #[default]
+    None,
+    /// Disable them while the expression has any weight.
+    Block,
+    /// Scale them down by the weight of the expression.
+    Blend,
+}
+
+/// Morph target weight driven by an expression.
+#[derive(Debug, Clone)]
+pub struct MorphTargetBind {
+    /// Node holding the mesh.
+    pub node: AssetIndex,
+    /// Morph target index in the mesh.
+    pub index: usize,
+    /// Weight of the target when the expression is fully applied.
+    pub weight: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaterialColorType {
+    Color,
+    EmissionColor,
+    ShadeColor,
+    MatcapColor,
+    RimColor,
+    OutlineColor,
+}
+
+#[derive(Debug, Clone)]
+pub struct MaterialColorBind {
+    pub material: AssetIndex,
+    pub kind: MaterialColorType,
+    pub target_value: [f32; 4],
+}
+
+#[derive(Debug, Clone)]
+pub struct TextureTransformBind {
+    pub material: AssetIndex,
+    pub scale: [f32; 2],
+    pub offset: [f32; 2],
+}
+
+#[derive(Debug, Clone)]
+pub struct ExpressionAsset {
+    pub name: ExpressionName,
+    pub morph_target_binds: Vec<MorphTargetBind>,
+    pub material_color_binds: Vec<MaterialColorBind>,
+    pub texture_transform_binds: Vec<TextureTransformBind>,
+    /// Round the weight to 0 or 1.
+    pub is_binary: bool,
+    pub override_blink: ExpressionOverride,
+    pub override_look_at: ExpressionOverride,
+    pub override_mouth: ExpressionOverride,
+}
+
+impl ExpressionAsset {
+    /// Morph target weights for the expression at `weight`, as node,
+    /// target index and target weight.
+    pub fn morph_target_weights(
+        &self,
+        weight: f32,
+    ) -> impl Iterator<Item = (&AssetIndex, usize, f32)> {
+        let weight = if self.is_binary {
+            weight.round()
+        } else {
+            weight
+        };
+        self.morph_target_binds
+            .iter()
+            .map(move |bind| (&bind.node, bind.index, bind.weight * weight))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LookAtType {
+    /// Rotate the eye bones.
+    Bone,
+    /// Apply the look expressions.
+    Expression,
+}
+
+/// Map from a gaze angle in degrees to a bone angle in degrees, or to an
+/// expression weight.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LookAtRangeMap {
+    pub input_max_value: f32,
+    pub output_scale: f32,
+}
+
+impl Default for LookAtRangeMap {
+    fn default() -> Self {
+        Self {
+            input_max_value: 90.0,
+            output_scale: 10.0,
+        }
+    }
+}
+
+impl LookAtRangeMap {
+    pub fn map(&self, angle: f32) -> f32 {
+        if self.input_max_value <= 0.0 {
+            return 0.0;
+        }
+        angle.abs().min(self.input_max_value) / self.input_max_value * self.output_scale
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LookAtAsset {
+    /// Position of the eyes relative to the head bone.
+    pub offset_from_head_bone: Vec3,
+    pub kind: LookAtType,
+    pub range_map_horizontal_inner: LookAtRangeMap,
+    pub range_map_horizontal_outer: LookAtRangeMap,
+    pub range_map_vertical_down: LookAtRangeMap,
+    pub range_map_vertical_up: LookAtRangeMap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FirstPersonType {
+    /// Hide parts weighted to the head in first person.
+    #[default]
+    Auto,
+    Both,
+    ThirdPersonOnly,
+    FirstPersonOnly,
+}
+
+#[derive(Debug, Clone)]
+pub struct MeshAnnotation {
+    pub node: AssetIndex,
+    pub kind: FirstPersonType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AvatarPermission {
+    #[default]
+    OnlyAuthor,
+    OnlySeparatelyLicensedPerson,
+    Everyone,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CommercialUsage {
+    #[default]
+    PersonalNonProfit,
+    PersonalProfit,
+    Corporation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CreditNotation {
+    #[default]
+    Required,
+    Unnecessary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Modification {
+    #[default]
+    Prohibited,
+    AllowModification,
+    AllowModificationRedistribution,
+}
+
+/// Avatar information and licence. Defaults are the most restrictive.
+#[derive(Debug, Clone, Default)]
+pub struct VrmMeta {
+    pub name: String,
+    pub version: Option<String>,
+    pub authors: Vec<String>,
+    pub copyright_information: Option<String>,
+    pub contact_information: Option<String>,
+    pub references: Vec<String>,
+    pub third_party_licenses: Option<String>,
+    pub license_url: String,
+    pub avatar_permission: AvatarPermission,
+    pub allow_excessively_violent_usage: bool,
+    pub allow_excessively_sexual_usage: bool,
+    pub commercial_usage: CommercialUsage,
+    pub allow_political_or_religious_usage: bool,
+    pub allow_antisocial_or_hate_usage: bool,
+    pub credit_notation: CreditNotation,
+    pub allow_redistribution: bool,
+    pub modification: Modification,
+    pub other_license_url: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct VrmAsset {
+    pub spec_version: String,
+    pub meta: VrmMeta,
+    pub humanoid: HumanoidAsset,
+    pub expressions: Vec<ExpressionAsset>,
+    pub look_at: Option<LookAtAsset>,
+    pub mesh_annotations: Vec<MeshAnnotation>,
+    pub spring_bone: Option<SpringBoneAsset>,
+}
+
+impl VrmAsset {
+    pub fn expression(&self, name: &ExpressionName) -> Option<&ExpressionAsset> {
+        self.expressions
+            .iter()
+            .find(|expression| expression.name == *name)
+    }
+
+    pub fn preset(&self, preset: ExpressionPreset) -> Option<&ExpressionAsset> {
+        self.expression(&ExpressionName::Preset(preset))
+    }
+
+    /// How a mesh node shows in first person. Nodes without annotation are
+    /// [`FirstPersonType::Auto`].
+    pub fn first_person_type(&self, node: &AssetIndex) -> FirstPersonType {
+        self.mesh_annotations
+            .iter()
+            .find(|annotation| annotation.node == *node)
+            .map(|annotation| annotation.kind)
+            .unwrap_or_default()
+    }
+}
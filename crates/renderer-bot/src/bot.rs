@@ -0,0 +1,266 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    pin::{pin, Pin},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bytes::BytesMut;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use log::{debug, warn};
+use renderer_protocol::{
+    compression::{CompressionConfig, CompressionError, FrameCodec, COMPRESSION_HEADER},
+    input::{PlayerEntityInput, PlayerMove},
+    message::{ClientMessage, ServerMessage},
+    traffic::TrafficCounters,
+    version::VersionData,
+};
+use tokio::{
+    select,
+    time::{interval, sleep_until, MissedTickBehavior},
+};
+use tokio_serde::{Deserializer, Serializer};
+use tokio_tungstenite::tungstenite::{self, client::IntoClientRequest, http::HeaderValue, Message};
+
+use crate::movement::{MovementPattern, Mover};
+
+#[derive(Debug, Clone)]
+pub struct BotConfig {
+    pub url: String,
+    /// How long each bot stays connected after syncing the world.
+    pub duration: Duration,
+    pub movement: MovementPattern,
+    /// Player inputs sent per second.
+    pub input_rate: f64,
+    pub compression: CompressionConfig,
+}
+
+#[derive(Debug)]
+pub enum BotError {
+    WebSocket(tungstenite::Error),
+    Compression(CompressionError),
+    Codec(Box<dyn Error + Send + Sync>),
+    UnexpectedMessage(&'static str),
+    Closed,
+}
+
+impl Display for BotError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BotError::WebSocket(err) => Display::fmt(err, f),
+            BotError::Compression(err) => Display::fmt(err, f),
+            BotError::Codec(err) => Display::fmt(err, f),
+            BotError::UnexpectedMessage(expected) => {
+                write!(f, "Unexpected message, expected {}", expected)
+            }
+            BotError::Closed => write!(f, "Connection closed by the server"),
+        }
+    }
+}
+
+impl Error for BotError {}
+
+impl From<tungstenite::Error> for BotError {
+    fn from(err: tungstenite::Error) -> Self {
+        BotError::WebSocket(err)
+    }
+}
+
+impl From<CompressionError> for BotError {
+    fn from(err: CompressionError) -> Self {
+        BotError::Compression(err)
+    }
+}
+
+/// What one bot observed. Times are in milliseconds, sizes in bytes.
+#[derive(Debug, Default)]
+pub struct BotReport {
+    /// From opening the connection to receiving the world.
+    pub connect_time: Option<f64>,
+    /// Time between consecutive tick outputs.
+    pub tick_intervals: Vec<f64>,
+    /// Tick time reported by the server.
+    pub tick_durations: Vec<f64>,
+    /// Size of each received message on the wire.
+    pub message_sizes: Vec<f64>,
+    pub error: Option<BotError>,
+}
+
+async fn send<K, E, SE>(
+    sink: &mut K,
+    frame_codec: &FrameCodec,
+    encoder: Pin<&mut E>,
+    message: ClientMessage,
+) -> Result<(), BotError>
+where
+    K: Sink<Message, Error = tungstenite::Error> + Unpin,
+    E: Serializer<ClientMessage, Error = SE>,
+    SE: Error + Send + Sync + 'static,
+{
+    let payload = encoder
+        .serialize(&message)
+        .map_err(|err| BotError::Codec(Box::new(err)))?;
+    sink.send(Message::binary(frame_codec.encode(&payload)?))
+        .await?;
+    Ok(())
+}
+
+/// Next server message, with its size on the wire.
+async fn receive<S, D, SE>(
+    stream: &mut S,
+    frame_codec: &FrameCodec,
+    mut decoder: Pin<&mut D>,
+) -> Result<(ServerMessage, usize), BotError>
+where
+    S: Stream<Item = Result<Message, tungstenite::Error>> + Unpin,
+    D: Deserializer<ServerMessage, Error = SE>,
+    SE: Error + Send + Sync + 'static,
+{
+    loop {
+        let Some(message) = stream.next().await else {
+            return Err(BotError::Closed);
+        };
+        let frame = match message? {
+            Message::Binary(data) => data,
+            Message::Text(text) => text.into_bytes(),
+            Message::Close(_) => return Err(BotError::Closed),
+            Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => continue,
+        };
+        let payload = frame_codec.decode(&frame)?;
+        let message = decoder
+            .as_mut()
+            .deserialize(&BytesMut::from(payload.as_slice()))
+            .map_err(|err| BotError::Codec(Box::new(err)))?;
+        return Ok((message, frame.len()));
+    }
+}
+
+/// Connect, follow the movement pattern until the configured duration is
+/// over, and report what was measured. Traffic is added to `traffic`.
+pub async fn run<Codec, SE>(
+    index: usize,
+    config: Arc<BotConfig>,
+    traffic: Arc<TrafficCounters>,
+) -> BotReport
+where
+    Codec:
+        Deserializer<ServerMessage, Error = SE> + Serializer<ClientMessage, Error = SE> + Default,
+    SE: Error + Send + Sync + 'static,
+{
+    let mut report = BotReport::default();
+    if let Err(err) = run_inner::<Codec, SE>(index, &config, traffic, &mut report).await {
+        warn!("Bot {} failed: {}", index, err);
+        report.error = Some(err);
+    }
+    report
+}
+
+async fn run_inner<Codec, SE>(
+    index: usize,
+    config: &BotConfig,
+    traffic: Arc<TrafficCounters>,
+    report: &mut BotReport,
+) -> Result<(), BotError>
+where
+    Codec:
+        Deserializer<ServerMessage, Error = SE> + Serializer<ClientMessage, Error = SE> + Default,
+    SE: Error + Send + Sync + 'static,
+{
+    // Separate halves, so that sending doesn't wait for a message to arrive
+    let mut encoder = pin!(Codec::default());
+    let mut decoder = pin!(Codec::default());
+    let connect_start = Instant::now();
+
+    let mut request = config.url.as_str().into_client_request()?;
+    if let Some(offer) = config.compression.offer() {
+        if let Ok(offer) = HeaderValue::from_str(&offer) {
+            request.headers_mut().insert(COMPRESSION_HEADER, offer);
+        }
+    }
+    let (stream, response) = tokio_tungstenite::connect_async(request).await?;
+    let compression = response
+        .headers()
+        .get(COMPRESSION_HEADER)
+        .and_then(|answer| answer.to_str().ok())
+        .and_then(|answer| config.compression.accepted(answer));
+    debug!("Bot {} compression: {:?}", index, compression);
+    let frame_codec = FrameCodec::new(compression, config.compression.threshold, traffic);
+    let (mut sink, mut stream) = stream.split();
+
+    send(
+        &mut sink,
+        &frame_codec,
+        encoder.as_mut(),
+        ClientMessage::Handshake {
+            version: VersionData::current(),
+            admin_token: None,
+        },
+    )
+    .await?;
+    let (ServerMessage::Handshake { version }, _) =
+        receive(&mut stream, &frame_codec, decoder.as_mut()).await?
+    else {
+        return Err(BotError::UnexpectedMessage("handshake"));
+    };
+    debug!("Bot {} connected to server {:?}", index, version);
+    let (ServerMessage::SyncWorld { player_id, .. }, _) =
+        receive(&mut stream, &frame_codec, decoder.as_mut()).await?
+    else {
+        return Err(BotError::UnexpectedMessage("world sync"));
+    };
+    report.connect_time = Some(connect_start.elapsed().as_secs_f64() * 1000.0);
+    debug!("Bot {} joined as {}", index, player_id);
+
+    let deadline = tokio::time::Instant::now() + config.duration;
+    let input_period = Duration::from_secs_f64(1.0 / config.input_rate);
+    let mut input_interval = interval(input_period);
+    input_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut mover = Mover::new(config.movement, index);
+    let mut sequence = 0u32;
+    let mut last_tick: Option<Instant> = None;
+
+    loop {
+        select! {
+            biased;
+            _ = sleep_until(deadline) => break,
+            _ = input_interval.tick() => {
+                let direction = mover.step(input_period.as_secs_f32());
+                let input = vec![PlayerEntityInput::Move(PlayerMove {
+                    sequence,
+                    direction,
+                    jump: false,
+                })];
+                sequence = sequence.wrapping_add(1);
+                send(
+                    &mut sink,
+                    &frame_codec,
+                    encoder.as_mut(),
+                    ClientMessage::PlayerInput(input),
+                )
+                .await?;
+            }
+            received = receive(&mut stream, &frame_codec, decoder.as_mut()) => {
+                let (message, size) = received?;
+                let ServerMessage::TickOutput(output) = message else {
+                    // Chat rejections and the like don't matter here
+                    continue;
+                };
+                let now = Instant::now();
+                if let Some(last_tick) = last_tick {
+                    report
+                        .tick_intervals
+                        .push((now - last_tick).as_secs_f64() * 1000.0);
+                }
+                last_tick = Some(now);
+                report
+                    .tick_durations
+                    .push(output.tick_duration_micros as f64 / 1000.0);
+                report.message_sizes.push(size as f64);
+            }
+        }
+    }
+
+    let _ = sink.close().await;
+    Ok(())
+}
@@ -0,0 +1,229 @@
+mod bot;
+mod movement;
+mod stats;
+
+use std::{env, sync::Arc, time::Duration};
+
+use renderer_protocol::{
+    codec::Compact,
+    compression::CompressionConfig,
+    message::{ClientMessage, ServerMessage},
+    traffic::TrafficCounters,
+};
+use tokio::{task::JoinSet, time::Instant};
+use tokio_serde::formats::{Bincode, Json};
+
+use crate::{
+    bot::{BotConfig, BotReport},
+    movement::MovementPattern,
+    stats::Summary,
+};
+
+#[derive(Debug, Clone, Copy, Default)]
+enum SerializeType {
+    #[default]
+    Json,
+    Bincode,
+    Compact,
+}
+
+#[derive(Debug)]
+struct Args {
+    url: String,
+    bots: usize,
+    duration: Duration,
+    serialize_type: SerializeType,
+    movement: MovementPattern,
+    input_rate: f64,
+    /// Delay between starting two bots.
+    ramp_up: Duration,
+    compression: bool,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            url: String::from("ws://127.0.0.1:12345"),
+            bots: 100,
+            duration: Duration::from_secs(30),
+            serialize_type: SerializeType::default(),
+            movement: MovementPattern::default(),
+            input_rate: 20.0,
+            ramp_up: Duration::from_millis(10),
+            compression: true,
+        }
+    }
+}
+
+fn value(args: &mut impl Iterator<Item = String>, name: &str) -> String {
+    args.next()
+        .unwrap_or_else(|| panic!("Missing value for {}", name))
+}
+
+impl Args {
+    fn parse() -> Self {
+        let mut args = env::args().skip(1);
+        let mut parsed = Self::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--url" => parsed.url = value(&mut args, &arg),
+                "--bots" => parsed.bots = value(&mut args, &arg).parse().expect("Bad bot count"),
+                "--duration" => {
+                    let seconds: f64 = value(&mut args, &arg).parse().expect("Bad duration");
+                    parsed.duration = Duration::from_secs_f64(seconds);
+                }
+                "--codec" => {
+                    parsed.serialize_type = match value(&mut args, &arg).as_str() {
+                        "json" => SerializeType::Json,
+                        "bincode" => SerializeType::Bincode,
+                        "compact" => SerializeType::Compact,
+                        other => panic!("Unknown codec: {:?}", other),
+                    }
+                }
+                "--movement" => {
+                    let name = value(&mut args, &arg);
+                    parsed.movement = MovementPattern::from_name(&name)
+                        .unwrap_or_else(|| panic!("Unknown movement: {:?}", name));
+                }
+                "--input-rate" => {
+                    parsed.input_rate = value(&mut args, &arg).parse().expect("Bad input rate");
+                    assert!(parsed.input_rate > 0.0, "Input rate must be positive");
+                }
+                "--ramp-up" => {
+                    let millis = value(&mut args, &arg).parse().expect("Bad ramp up");
+                    parsed.ramp_up = Duration::from_millis(millis);
+                }
+                "--no-compression" => parsed.compression = false,
+                _ => panic!("Unknown argument: {}", arg),
+            }
+        }
+        parsed
+    }
+}
+
+async fn run_bots<Codec, SE>(
+    args: &Args,
+    config: Arc<BotConfig>,
+    traffic: Arc<TrafficCounters>,
+) -> Vec<BotReport>
+where
+    Codec: tokio_serde::Deserializer<ServerMessage, Error = SE>
+        + tokio_serde::Serializer<ClientMessage, Error = SE>
+        + Default
+        + Send
+        + 'static,
+    SE: std::error::Error + Send + Sync + 'static,
+{
+    let mut bots = JoinSet::new();
+    for index in 0..args.bots {
+        if index > 0 && !args.ramp_up.is_zero() {
+            tokio::time::sleep(args.ramp_up).await;
+        }
+        bots.spawn(bot::run::<Codec, SE>(
+            index,
+            config.clone(),
+            traffic.clone(),
+        ));
+    }
+    let mut reports = Vec::with_capacity(args.bots);
+    while let Some(report) = bots.join_next().await {
+        reports.push(report.expect("Bot crashed"));
+    }
+    reports
+}
+
+fn print_summary(name: &str, unit: &str, samples: &[f64]) {
+    match Summary::new(samples) {
+        Some(summary) => println!("{:<20} {} ({})", name, summary, unit),
+        None => println!("{:<20} no samples", name),
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+    let args = Args::parse();
+    let config = Arc::new(BotConfig {
+        url: args.url.clone(),
+        duration: args.duration,
+        movement: args.movement,
+        input_rate: args.input_rate,
+        compression: if args.compression {
+            CompressionConfig::default()
+        } else {
+            CompressionConfig::disabled()
+        },
+    });
+    let traffic = Arc::new(TrafficCounters::default());
+
+    println!(
+        "Running {} bots against {} for {:.1} s",
+        args.bots,
+        args.url,
+        args.duration.as_secs_f64()
+    );
+    let start = Instant::now();
+    let reports = match args.serialize_type {
+        SerializeType::Json => {
+            run_bots::<Json<ServerMessage, ClientMessage>, _>(&args, config, traffic.clone()).await
+        }
+        SerializeType::Bincode => {
+            run_bots::<Bincode<ServerMessage, ClientMessage>, _>(&args, config, traffic.clone())
+                .await
+        }
+        SerializeType::Compact => {
+            run_bots::<Compact<ServerMessage, ClientMessage>, _>(&args, config, traffic.clone())
+                .await
+        }
+    };
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let mut connect_times = Vec::new();
+    let mut tick_intervals = Vec::new();
+    let mut jitter = Vec::new();
+    let mut tick_durations = Vec::new();
+    let mut message_sizes = Vec::new();
+    let mut failed = 0;
+    for report in &reports {
+        if let Some(err) = &report.error {
+            println!("Bot failed: {}", err);
+            failed += 1;
+        }
+        connect_times.extend(report.connect_time);
+        tick_intervals.extend_from_slice(&report.tick_intervals);
+        // Jitter is measured against the mean interval the bot saw
+        if let Some(summary) = Summary::new(&report.tick_intervals) {
+            jitter.extend(
+                report
+                    .tick_intervals
+                    .iter()
+                    .map(|interval| (interval - summary.mean).abs()),
+            );
+        }
+        tick_durations.extend_from_slice(&report.tick_durations);
+        message_sizes.extend_from_slice(&report.message_sizes);
+    }
+
+    println!();
+    println!("Bots: {} connected, {} failed", connect_times.len(), failed);
+    print_summary("Connect time", "ms", &connect_times);
+    print_summary("Tick interval", "ms", &tick_intervals);
+    print_summary("Tick jitter", "ms", &jitter);
+    print_summary("Server tick time", "ms", &tick_durations);
+    print_summary("Message size", "bytes", &message_sizes);
+
+    let stats = traffic.stats();
+    println!(
+        "Sent: {} messages, {} bytes ({:.1} KiB/s)",
+        stats.sent.messages,
+        stats.sent.bytes,
+        stats.sent.bytes as f64 / 1024.0 / elapsed
+    );
+    println!(
+        "Received: {} messages, {} bytes, {} uncompressed ({:.1} KiB/s)",
+        stats.received.messages,
+        stats.received.bytes,
+        stats.received.uncompressed_bytes,
+        stats.received.bytes as f64 / 1024.0 / elapsed
+    );
+}
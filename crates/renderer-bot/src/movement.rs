@@ -0,0 +1,91 @@
+use std::f32::consts::TAU;
+
+use glam::Vec3;
+use renderer_protocol::movement::WALK_SPEED as SPEED;
+
+/// Random walkers turn back beyond this distance from the origin.
+const WALK_RADIUS: f32 = 50.0;
+const EYE_HEIGHT: f32 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MovementPattern {
+    /// Stay in place, still sending input.
+    Idle,
+    #[default]
+    RandomWalk,
+    Circle,
+}
+
+impl MovementPattern {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "idle" => Some(MovementPattern::Idle),
+            "random-walk" => Some(MovementPattern::RandomWalk),
+            "circle" => Some(MovementPattern::Circle),
+            _ => None,
+        }
+    }
+}
+
+/// Moves one bot around. Seeded by the bot index, so runs are repeatable.
+#[derive(Debug)]
+pub struct Mover {
+    pattern: MovementPattern,
+    seed: u64,
+    position: Vec3,
+    heading: f32,
+    center: Vec3,
+    radius: f32,
+}
+
+impl Mover {
+    pub fn new(pattern: MovementPattern, index: usize) -> Self {
+        let mut mover = Self {
+            pattern,
+            seed: 0x9e37_79b9_7f4a_7c15 ^ (index as u64 + 1).wrapping_mul(0xbf58_476d_1ce4_e5b9),
+            position: Vec3::ZERO,
+            heading: 0.0,
+            center: Vec3::ZERO,
+            radius: 0.0,
+        };
+        let angle = mover.random() * TAU;
+        let distance = mover.random() * WALK_RADIUS;
+        mover.position = Vec3::new(angle.cos() * distance, EYE_HEIGHT, angle.sin() * distance);
+        mover.heading = mover.random() * TAU;
+        mover.center = mover.position;
+        mover.radius = 2.0 + mover.random() * 8.0;
+        mover
+    }
+
+    /// Xorshift, in `0..1`.
+    fn random(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        (self.seed >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Move by `dt` seconds, returning the direction to walk in. The server
+    /// owns the bot position, so this only tracks where it means to go.
+    pub fn step(&mut self, dt: f32) -> Vec3 {
+        let previous = self.position;
+        match self.pattern {
+            MovementPattern::Idle => {}
+            MovementPattern::RandomWalk => {
+                self.heading += (self.random() - 0.5) * 2.0 * dt;
+                if self.position.length() > WALK_RADIUS {
+                    // Head back toward the origin
+                    self.heading = (-self.position.z).atan2(-self.position.x);
+                }
+                self.position +=
+                    Vec3::new(self.heading.cos(), 0.0, self.heading.sin()) * SPEED * dt;
+            }
+            MovementPattern::Circle => {
+                self.heading += SPEED / self.radius * dt;
+                self.position = self.center
+                    + Vec3::new(self.heading.cos(), 0.0, self.heading.sin()) * self.radius;
+            }
+        }
+        ((self.position - previous) / (SPEED * dt)).clamp_length_max(1.0)
+    }
+}
@@ -0,0 +1,66 @@
+use std::fmt::{self, Display, Formatter};
+
+/// Distribution of a set of samples.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub min: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+impl Summary {
+    pub fn new(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let percentile = |p: f64| {
+            let index = ((sorted.len() - 1) as f64 * p).round() as usize;
+            sorted[index]
+        };
+        Some(Self {
+            count: sorted.len(),
+            min: sorted[0],
+            mean: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "min {:.2}, mean {:.2}, p50 {:.2}, p95 {:.2}, p99 {:.2}, max {:.2} ({} samples)",
+            self.min, self.mean, self.p50, self.p95, self.p99, self.max, self.count
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Summary;
+
+    #[test]
+    fn test_summary() {
+        assert_eq!(Summary::new(&[]), None);
+
+        let samples: Vec<f64> = (1..=100).rev().map(f64::from).collect();
+        let summary = Summary::new(&samples).unwrap();
+        assert_eq!(summary.count, 100);
+        assert_eq!(summary.min, 1.0);
+        assert_eq!(summary.max, 100.0);
+        assert_eq!(summary.mean, 50.5);
+        assert_eq!(summary.p50, 51.0);
+        assert_eq!(summary.p95, 95.0);
+        assert_eq!(summary.p99, 99.0);
+    }
+}
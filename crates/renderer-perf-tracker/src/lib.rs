@@ -1,14 +1,46 @@
 use std::{
-    collections::VecDeque,
+    collections::{BTreeMap, VecDeque},
     time::{Duration, Instant},
 };
 
+/// Frame time distribution over the sample window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameTimeStats {
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// Frame times counted into buckets of equal width. The last bucket also
+/// counts every longer frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    pub bucket_width: Duration,
+    pub counts: Vec<usize>,
+}
+
+/// A named span of a frame. Timers started while another is running are
+/// nested in it, one level deeper.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimerSample {
+    pub name: &'static str,
+    pub depth: usize,
+    pub duration: Duration,
+}
+
 #[derive(Debug)]
 pub struct PerformanceTracker {
     frame_time_samples: usize,
     frame_time: VecDeque<Duration>,
     frame_timestamp: VecDeque<Instant>,
     frame_time_sum: Duration,
+    /// Started timers of the current frame, by index into `frame_timers`.
+    running_timers: Vec<(usize, Instant)>,
+    frame_timers: Vec<TimerSample>,
+    last_frame_timers: Vec<TimerSample>,
+    /// Total time of each timer over the recent frames.
+    timer_history: BTreeMap<&'static str, VecDeque<Duration>>,
 }
 
 impl PerformanceTracker {
@@ -18,6 +50,10 @@ impl PerformanceTracker {
             frame_time: VecDeque::new(),
             frame_timestamp: VecDeque::new(),
             frame_time_sum: Duration::ZERO,
+            running_timers: Vec::new(),
+            frame_timers: Vec::new(),
+            last_frame_timers: Vec::new(),
+            timer_history: BTreeMap::new(),
         }
     }
 
@@ -34,7 +70,7 @@ impl PerformanceTracker {
     }
 
     pub fn last_frame_time(&self) -> Option<&Duration> {
-        self.frame_time.front()
+        self.frame_time.back()
     }
 
     pub fn add_sample(&mut self, frame_time: Duration, frame_timestamp: Instant) {
@@ -51,6 +87,96 @@ impl PerformanceTracker {
         }
 
         self.frame_time_sum += frame_time;
+        self.finish_frame_timers();
+    }
+
+    /// Frame time at `percentile`, between 0 and 1, by nearest rank.
+    pub fn percentile(&self, percentile: f32) -> Option<Duration> {
+        let sorted = self.sorted_frame_time();
+        percentile_of(&sorted, percentile)
+    }
+
+    pub fn stats(&self) -> Option<FrameTimeStats> {
+        let sorted = self.sorted_frame_time();
+        Some(FrameTimeStats {
+            p50: percentile_of(&sorted, 0.5)?,
+            p95: percentile_of(&sorted, 0.95)?,
+            p99: percentile_of(&sorted, 0.99)?,
+            max: *sorted.last()?,
+        })
+    }
+
+    fn sorted_frame_time(&self) -> Vec<Duration> {
+        let mut sorted: Vec<Duration> = self.frame_time.iter().copied().collect();
+        sorted.sort();
+        sorted
+    }
+
+    pub fn histogram(&self, bucket_width: Duration, bucket_count: usize) -> Histogram {
+        let mut counts = vec![0; bucket_count];
+        if bucket_count > 0 && !bucket_width.is_zero() {
+            for frame_time in &self.frame_time {
+                let bucket = (frame_time.as_nanos() / bucket_width.as_nanos()) as usize;
+                counts[bucket.min(bucket_count - 1)] += 1;
+            }
+        }
+        Histogram {
+            bucket_width,
+            counts,
+        }
+    }
+
+    /// Start a named timer, nested in the running one if any. Timers
+    /// still running when the frame sample is added are discarded.
+    pub fn start_timer(&mut self, name: &'static str) {
+        self.running_timers
+            .push((self.frame_timers.len(), Instant::now()));
+        self.frame_timers.push(TimerSample {
+            name,
+            depth: self.running_timers.len() - 1,
+            duration: Duration::ZERO,
+        });
+    }
+
+    /// Stop the most recently started timer.
+    pub fn stop_timer(&mut self) {
+        if let Some((index, start)) = self.running_timers.pop() {
+            self.frame_timers[index].duration = start.elapsed();
+        }
+    }
+
+    /// Timers of the last finished frame, in the order they started.
+    pub fn last_frame_timers(&self) -> &[TimerSample] {
+        &self.last_frame_timers
+    }
+
+    /// Total time of each timer over the recent frames, oldest first. A
+    /// timer that was not started in a frame counts as zero there.
+    pub fn timer_history(&self) -> &BTreeMap<&'static str, VecDeque<Duration>> {
+        &self.timer_history
+    }
+
+    fn finish_frame_timers(&mut self) {
+        // Discard the timers still running, and the ones nested in them
+        if let Some((first_running, _)) = self.running_timers.first() {
+            self.frame_timers.truncate(*first_running);
+        }
+        self.running_timers.clear();
+
+        let mut totals: BTreeMap<&'static str, Duration> = BTreeMap::new();
+        for timer in &self.frame_timers {
+            *totals.entry(timer.name).or_default() += timer.duration;
+        }
+        for name in totals.keys() {
+            self.timer_history.entry(name).or_default();
+        }
+        for (name, history) in self.timer_history.iter_mut() {
+            history.push_back(totals.get(name).copied().unwrap_or_default());
+            while history.len() > self.frame_time_samples {
+                history.pop_front();
+            }
+        }
+        self.last_frame_timers = std::mem::take(&mut self.frame_timers);
     }
 
     pub fn fps(&self) -> Option<f32> {
@@ -67,3 +193,101 @@ impl PerformanceTracker {
             })
     }
 }
+
+/// Nearest rank: the smallest sample with at least `percentile` of the
+/// samples less than or equal to it.
+fn percentile_of(sorted: &[Duration], percentile: f32) -> Option<Duration> {
+    let rank = (percentile.clamp(0.0, 1.0) * sorted.len() as f32).ceil() as usize;
+    sorted.get(rank.max(1) - 1).copied()
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::PerformanceTracker;
+
+    #[test]
+    fn test_stats() {
+        let mut tracker = PerformanceTracker::new(100);
+        let now = Instant::now();
+        // Older samples fall out of the window
+        for millis in [1000, 1000].into_iter().chain(1..=100) {
+            tracker.add_sample(Duration::from_millis(millis), now);
+        }
+        let stats = tracker.stats().unwrap();
+        assert_eq!(stats.p50, Duration::from_millis(50));
+        assert_eq!(stats.p95, Duration::from_millis(95));
+        assert_eq!(stats.p99, Duration::from_millis(99));
+        assert_eq!(stats.max, Duration::from_millis(100));
+
+        let histogram = tracker.histogram(Duration::from_millis(25), 3);
+        assert_eq!(histogram.counts, [24, 25, 51]);
+    }
+
+    #[test]
+    fn test_percentile() {
+        let mut tracker = PerformanceTracker::new(100);
+        let now = Instant::now();
+        assert_eq!(tracker.percentile(0.5), None);
+        for millis in (1..=100).rev() {
+            tracker.add_sample(Duration::from_millis(millis), now);
+        }
+        let percentile = |percentile| tracker.percentile(percentile).unwrap().as_millis();
+        assert_eq!(percentile(0.0), 1);
+        assert_eq!(percentile(0.5), 50);
+        assert_eq!(percentile(0.95), 95);
+        assert_eq!(percentile(0.99), 99);
+        assert_eq!(percentile(1.0), 100);
+
+        let mut tracker = PerformanceTracker::new(4);
+        for millis in [40, 10, 30, 20] {
+            tracker.add_sample(Duration::from_millis(millis), now);
+        }
+        let percentile = |percentile| tracker.percentile(percentile).unwrap().as_millis();
+        assert_eq!(percentile(0.25), 10);
+        assert_eq!(percentile(0.5), 20);
+        assert_eq!(percentile(0.51), 30);
+        assert_eq!(percentile(0.99), 40);
+    }
+
+    #[test]
+    fn test_last_frame_time() {
+        let mut tracker = PerformanceTracker::new(2);
+        let now = Instant::now();
+        assert_eq!(tracker.last_frame_time(), None);
+        for millis in 1..=3 {
+            tracker.add_sample(Duration::from_millis(millis), now);
+        }
+        assert_eq!(tracker.last_frame_time(), Some(&Duration::from_millis(3)));
+    }
+
+    #[test]
+    fn test_timers() {
+        let mut tracker = PerformanceTracker::new(2);
+        let now = Instant::now();
+        tracker.start_timer("tick");
+        tracker.start_timer("physics");
+        tracker.stop_timer();
+        tracker.stop_timer();
+        tracker.start_timer("render");
+        tracker.add_sample(Duration::ZERO, now);
+
+        // The running render timer is discarded
+        let timers: Vec<_> = tracker
+            .last_frame_timers()
+            .iter()
+            .map(|timer| (timer.name, timer.depth))
+            .collect();
+        assert_eq!(timers, [("tick", 0), ("physics", 1)]);
+
+        tracker.start_timer("render");
+        tracker.stop_timer();
+        tracker.add_sample(Duration::ZERO, now);
+        assert_eq!(tracker.last_frame_timers().len(), 1);
+        let history = tracker.timer_history();
+        assert_eq!(history["tick"].len(), 2);
+        assert_eq!(history["tick"][1], Duration::ZERO);
+        assert_eq!(history["render"].len(), 1);
+    }
+}
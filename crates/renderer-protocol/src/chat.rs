@@ -0,0 +1,59 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Longest chat message accepted by default, in characters.
+pub const MAX_CHAT_MESSAGE_LENGTH: usize = 256;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChatMessage {
+    pub sender: Uuid,
+    /// Milliseconds since the Unix epoch, stamped by the server.
+    pub timestamp: u64,
+    /// Only the target and the sender receive the message if set.
+    pub target: Option<Uuid>,
+    pub text: String,
+}
+
+impl ChatMessage {
+    pub fn visible_to(&self, player_id: Uuid) -> bool {
+        match self.target {
+            Some(target) => target == player_id || self.sender == player_id,
+            None => true,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChatRejectReason {
+    Empty,
+    TooLong { max_length: usize },
+    RateLimited,
+    UnknownTarget,
+}
+
+impl Display for ChatRejectReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ChatRejectReason::Empty => write!(f, "Empty chat message"),
+            ChatRejectReason::TooLong { max_length } => {
+                write!(f, "Chat message is longer than {} characters", max_length)
+            }
+            ChatRejectReason::RateLimited => write!(f, "Sending chat messages too fast"),
+            ChatRejectReason::UnknownTarget => write!(f, "Whisper target is not online"),
+        }
+    }
+}
+
+impl Error for ChatRejectReason {}
+
+/// A chat message the world refused, reported back to its sender only.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChatRejection {
+    pub sender: Uuid,
+    pub reason: ChatRejectReason,
+}
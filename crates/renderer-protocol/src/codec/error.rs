@@ -0,0 +1,57 @@
+use std::{
+    error::Error as StdError,
+    fmt::{self, Display, Formatter},
+};
+
+use serde::{de, ser};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    Message(String),
+    UnexpectedEof,
+    UnsupportedVersion(u8),
+    VarintOverflow,
+    IntegerOutOfRange,
+    InvalidBool(u8),
+    InvalidChar(u32),
+    InvalidUtf8,
+    InvalidOption(u8),
+    LengthRequired,
+    AnyNotSupported,
+    TrailingBytes(usize),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Message(message) => write!(f, "{}", message),
+            Error::UnexpectedEof => write!(f, "Unexpected end of input"),
+            Error::UnsupportedVersion(version) => {
+                write!(f, "Unsupported format version {}", version)
+            }
+            Error::VarintOverflow => write!(f, "Varint is too long"),
+            Error::IntegerOutOfRange => write!(f, "Integer is out of range"),
+            Error::InvalidBool(value) => write!(f, "Invalid bool value {}", value),
+            Error::InvalidChar(value) => write!(f, "Invalid char value {:#x}", value),
+            Error::InvalidUtf8 => write!(f, "Invalid UTF-8 string"),
+            Error::InvalidOption(value) => write!(f, "Invalid option tag {}", value),
+            Error::LengthRequired => write!(f, "Sequence length is required"),
+            Error::AnyNotSupported => write!(f, "Format is not self-describing"),
+            Error::TrailingBytes(length) => write!(f, "{} trailing bytes after value", length),
+        }
+    }
+}
+
+impl StdError for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
@@ -0,0 +1,759 @@
+//! Compact binary encoding for protocol messages.
+//!
+//! Every frame starts with a format version byte. Integers are LEB128
+//! varints (signed ones zigzag encoded), floats are little endian, and
+//! strings, byte arrays and sequences are prefixed with their length.
+//!
+//! Structs and enums are encoded so that peers can evolve independently:
+//!
+//! - Struct fields are written as `tag, length, payload`, where the tag is the
+//!   field index plus one, and a zero tag ends the struct. Decoders skip
+//!   fields they don't know, so new fields can be appended freely. Fields
+//!   missing from older peers need `#[serde(default)]`.
+//! - Enum variants are written as `index, length, payload`. New variants must
+//!   be appended at the end; older decoders reject them with an error instead
+//!   of misreading the rest of the frame.
+//!
+//! Fields must never be reordered or removed, as indices are the only thing
+//! identifying them on the wire.
+
+mod de;
+mod error;
+pub mod position;
+mod ser;
+#[cfg(feature = "tokio-serde")]
+mod tokio;
+
+pub use de::{from_slice, Deserializer};
+pub use error::Error;
+pub use ser::{to_vec, Serializer};
+#[cfg(feature = "tokio-serde")]
+pub use tokio::Compact;
+
+pub const FORMAT_VERSION: u8 = 1;
+
+const MAX_VARINT_LENGTH: usize = 10;
+
+fn write_varint(output: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        output.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<u64, Error> {
+    let mut value: u64 = 0;
+    for index in 0..MAX_VARINT_LENGTH {
+        let (byte, rest) = input.split_first().ok_or(Error::UnexpectedEof)?;
+        *input = rest;
+        let shift = index * 7;
+        let bits = (*byte & 0x7f) as u64;
+        if shift == 63 && bits > 1 {
+            return Err(Error::VarintOverflow);
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::VarintOverflow)
+}
+
+fn zigzag_encode(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn zigzag_decode(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fmt::Debug;
+
+    use glam::Vec3;
+    use renderer_asset::index::BundleIndex;
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    use crate::{
+        chat::{ChatMessage, ChatRejectReason, ChatRejection},
+        entity::{
+            BaseEntityData, EntitiesIds, EntitiesOutputs, EntityData, EntityKind,
+            EntityResourceData, EntityStates, LightEntityOutput, LightEntityState, LightSource,
+            ObjectEntityOutput, ObjectEntityState, ObjectPermissions, PlayerEntityOutput,
+        },
+        environment::{Environment, LightParam},
+        input::{PlayerEntityInput, PlayerMove},
+        intent::{IntentRejectReason, IntentRejection, ObjectIntent, Role},
+        message::{ClientMessage, ServerMessage},
+        tick::TickOutput,
+        version::VersionData,
+    };
+
+    use super::{from_slice, position::POSITION_SCALE, to_vec, Error, FORMAT_VERSION};
+
+    const ITERATIONS: usize = 256;
+
+    /// Xorshift generator, so failures can be reproduced from the seed.
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: u64) -> u64 {
+            self.next() % bound
+        }
+
+        fn uuid(&mut self) -> Uuid {
+            Uuid::from_u64_pair(self.next(), self.next())
+        }
+
+        fn float(&mut self) -> f32 {
+            (self.below(2_000_000) as f32 - 1_000_000.0) / 1000.0
+        }
+
+        /// Positions sitting on the quantization grid, so quantized round
+        /// trips are exact.
+        fn position(&mut self) -> Vec3 {
+            let mut component = || (self.below(2_000_000) as f32 - 1_000_000.0) / POSITION_SCALE;
+            Vec3::new(component(), component(), component())
+        }
+
+        fn vec3(&mut self) -> Vec3 {
+            Vec3::new(self.float(), self.float(), self.float())
+        }
+
+        fn string(&mut self) -> String {
+            let length = self.below(16);
+            (0..length)
+                .map(|_| char::from_u32(self.below(0x3000) as u32).unwrap_or('?'))
+                .collect()
+        }
+
+        fn vec<T>(&mut self, max_length: u64, mut f: impl FnMut(&mut Self) -> T) -> Vec<T> {
+            let length = self.below(max_length);
+            (0..length).map(|_| f(self)).collect()
+        }
+
+        fn version(&mut self) -> VersionData {
+            VersionData::current()
+        }
+
+        fn base(&mut self) -> BaseEntityData {
+            BaseEntityData {
+                id: self.uuid(),
+                position: self.vec3(),
+            }
+        }
+
+        fn resource(&mut self) -> EntityResourceData {
+            match self.below(3) {
+                0 => EntityResourceData::Box,
+                1 => EntityResourceData::Crosshair,
+                _ => {
+                    let mut bundle_index = [0u8; 32];
+                    bundle_index.fill_with(|| self.next() as u8);
+                    EntityResourceData::External {
+                        bundle_index: BundleIndex(bundle_index),
+                        link: self.string(),
+                    }
+                }
+            }
+        }
+
+        fn option<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> Option<T> {
+            if self.below(2) == 0 {
+                None
+            } else {
+                Some(f(self))
+            }
+        }
+
+        fn chat_message(&mut self) -> ChatMessage {
+            ChatMessage {
+                sender: self.uuid(),
+                timestamp: self.next(),
+                target: self.option(Self::uuid),
+                text: self.string(),
+            }
+        }
+
+        fn chat_reject_reason(&mut self) -> ChatRejectReason {
+            match self.below(4) {
+                0 => ChatRejectReason::Empty,
+                1 => ChatRejectReason::TooLong {
+                    max_length: self.below(1024) as usize,
+                },
+                2 => ChatRejectReason::RateLimited,
+                _ => ChatRejectReason::UnknownTarget,
+            }
+        }
+
+        fn permissions(&mut self) -> ObjectPermissions {
+            ObjectPermissions(self.below(4) as u8)
+        }
+
+        fn role(&mut self) -> Role {
+            match self.below(2) {
+                0 => Role::Player,
+                _ => Role::Admin,
+            }
+        }
+
+        fn object_intent(&mut self) -> ObjectIntent {
+            match self.below(3) {
+                0 => ObjectIntent::Spawn {
+                    id: self.uuid(),
+                    position: self.vec3(),
+                    resource: self.resource(),
+                    permissions: self.permissions(),
+                },
+                1 => ObjectIntent::Move {
+                    id: self.uuid(),
+                    position: self.vec3(),
+                },
+                _ => ObjectIntent::Delete { id: self.uuid() },
+            }
+        }
+
+        fn intent_reject_reason(&mut self) -> IntentRejectReason {
+            match self.below(8) {
+                0 => IntentRejectReason::UnknownObject,
+                1 => IntentRejectReason::AlreadyExists,
+                2 => IntentRejectReason::NotPermitted,
+                3 => IntentRejectReason::SpawnDisabled,
+                4 => IntentRejectReason::TooManyObjects {
+                    max_objects: self.below(1024) as usize,
+                },
+                5 => IntentRejectReason::ResourceNotAllowed,
+                6 => IntentRejectReason::BadPosition,
+                _ => IntentRejectReason::RateLimited,
+            }
+        }
+
+        fn light_source(&mut self) -> LightSource {
+            match self.below(3) {
+                0 => LightSource::Point {
+                    color: self.vec3(),
+                    constant: self.float(),
+                    linear: self.float(),
+                    quadratic: self.float(),
+                },
+                1 => LightSource::Directional {
+                    color: self.vec3(),
+                    direction: self.vec3(),
+                    constant: self.float(),
+                    linear: self.float(),
+                    quadratic: self.float(),
+                    range_inner: self.float(),
+                    range_outer: self.float(),
+                },
+                _ => LightSource::Parallel {
+                    direction: self.vec3(),
+                    color: self.vec3(),
+                    strength: self.float(),
+                },
+            }
+        }
+
+        fn environment(&mut self) -> Environment {
+            Environment {
+                background_color: self.vec3(),
+                light_param: LightParam {
+                    start_strength: self.float(),
+                    stop_strength: self.float(),
+                    max_strength: self.float(),
+                    border_start_strength: self.float(),
+                    border_stop_strength: self.float(),
+                    border_max_strength: self.float(),
+                    ambient_strength: self.float(),
+                },
+            }
+        }
+
+        fn entity_states(&mut self) -> EntityStates {
+            let mut states = EntityStates::default();
+            for _ in 0..self.below(8) {
+                let state = ObjectEntityState {
+                    base: self.base(),
+                    resource: self.resource(),
+                    owner: self.option(Self::uuid),
+                    permissions: self.permissions(),
+                };
+                states.push(&EntityKind::OBJECT, EntityData::encode(&state).unwrap());
+            }
+            for _ in 0..self.below(8) {
+                let state = self.base();
+                states.push(&EntityKind::PLAYER, EntityData::encode(&state).unwrap());
+            }
+            for _ in 0..self.below(4) {
+                let state = LightEntityState {
+                    base: self.base(),
+                    source: self.light_source(),
+                };
+                states.push(&EntityKind::LIGHT, EntityData::encode(&state).unwrap());
+            }
+            states
+        }
+
+        fn tick_output(&mut self) -> TickOutput {
+            let mut entity_outputs = EntitiesOutputs::default();
+            for _ in 0..self.below(16) {
+                let output = ObjectEntityOutput::NewPosition(self.position());
+                let output = (self.uuid(), EntityData::encode(&output).unwrap());
+                entity_outputs.push(&EntityKind::OBJECT, output);
+            }
+            for _ in 0..self.below(16) {
+                let output = match self.below(2) {
+                    0 => PlayerEntityOutput::NewPosition(self.position()),
+                    _ => PlayerEntityOutput::Acknowledged {
+                        sequence: self.next() as u32,
+                        position: self.vec3(),
+                        velocity: self.vec3(),
+                    },
+                };
+                let output = (self.uuid(), EntityData::encode(&output).unwrap());
+                entity_outputs.push(&EntityKind::PLAYER, output);
+            }
+            for _ in 0..self.below(4) {
+                let output = match self.below(2) {
+                    0 => LightEntityOutput::NewPosition(self.position()),
+                    _ => LightEntityOutput::NewSource(self.light_source()),
+                };
+                let output = (self.uuid(), EntityData::encode(&output).unwrap());
+                entity_outputs.push(&EntityKind::LIGHT, output);
+            }
+            let mut removed_entity_uuids = EntitiesIds::default();
+            for kind in [EntityKind::OBJECT, EntityKind::PLAYER] {
+                for _ in 0..self.below(8) {
+                    removed_entity_uuids.push(&kind, self.uuid());
+                }
+            }
+            TickOutput {
+                new_entity_states: self.entity_states(),
+                entity_outputs,
+                removed_entity_uuids,
+                chat_messages: self.vec(4, Self::chat_message),
+                tick_duration_micros: self.below(1_000_000),
+                tick: self.next(),
+                environment: self.option(Self::environment),
+                chat_rejections: self.vec(4, |random| ChatRejection {
+                    sender: random.uuid(),
+                    reason: random.chat_reject_reason(),
+                }),
+                intent_rejections: self.vec(4, |random| IntentRejection {
+                    sender: random.uuid(),
+                    intent: random.object_intent(),
+                    reason: random.intent_reject_reason(),
+                }),
+            }
+        }
+
+        fn server_message(&mut self, variant: u64) -> ServerMessage {
+            match variant {
+                0 => ServerMessage::Handshake {
+                    version: self.version(),
+                },
+                1 => ServerMessage::SyncWorld {
+                    player_id: self.uuid(),
+                    entity_states: self.entity_states(),
+                    environment: self.environment(),
+                    role: self.role(),
+                },
+                2 => ServerMessage::TickOutput(self.tick_output()),
+                3 => ServerMessage::ChatRejected(self.chat_reject_reason()),
+                4 => ServerMessage::IntentRejected {
+                    intent: self.object_intent(),
+                    reason: self.intent_reject_reason(),
+                },
+                _ => unreachable!(),
+            }
+        }
+
+        fn client_message(&mut self, variant: u64) -> ClientMessage {
+            match variant {
+                0 => ClientMessage::Handshake {
+                    version: self.version(),
+                    admin_token: self.option(Self::string),
+                },
+                1 => ClientMessage::PlayerInput(self.vec(8, |random| match random.below(2) {
+                    0 => PlayerEntityInput::NewPosition(random.vec3()),
+                    _ => PlayerEntityInput::Move(PlayerMove {
+                        sequence: random.next() as u32,
+                        direction: random.vec3(),
+                        jump: random.below(2) == 0,
+                    }),
+                })),
+                2 => ClientMessage::Chat {
+                    text: self.string(),
+                    target: self.option(Self::uuid),
+                },
+                3 => ClientMessage::ObjectIntent(self.object_intent()),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    const SERVER_MESSAGE_VARIANTS: u64 = 5;
+    const CLIENT_MESSAGE_VARIANTS: u64 = 4;
+
+    fn round_trip<T>(value: &T) -> T
+    where
+        T: Serialize + for<'a> Deserialize<'a>,
+    {
+        let encoded = to_vec(value).unwrap();
+        assert_eq!(encoded[0], FORMAT_VERSION);
+        from_slice(&encoded).unwrap()
+    }
+
+    fn assert_round_trip<T>(value: T)
+    where
+        T: Serialize + for<'a> Deserialize<'a> + PartialEq + Debug,
+    {
+        assert_eq!(round_trip(&value), value);
+    }
+
+    #[test]
+    fn test_varint() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut buffer = Vec::new();
+            super::write_varint(&mut buffer, value);
+            let mut input = buffer.as_slice();
+            assert_eq!(super::read_varint(&mut input), Ok(value));
+            assert!(input.is_empty());
+        }
+        let mut overflow: &[u8] = &[0xff; 11];
+        assert_eq!(
+            super::read_varint(&mut overflow),
+            Err(Error::VarintOverflow)
+        );
+        for value in [0, 1, -1, i64::MIN, i64::MAX] {
+            assert_eq!(super::zigzag_decode(super::zigzag_encode(value)), value);
+        }
+    }
+
+    #[test]
+    fn test_primitives() {
+        assert_round_trip(true);
+        assert_round_trip(-12345i32);
+        assert_round_trip(u64::MAX);
+        assert_round_trip(i128::MIN);
+        assert_round_trip(1.5f32);
+        assert_round_trip('字');
+        assert_round_trip(String::from("hello"));
+        assert_round_trip(Some(3u8));
+        assert_round_trip(Option::<u8>::None);
+        assert_round_trip((1u8, -2i16, 3u32));
+        assert_round_trip(vec![Vec3::X, Vec3::Y]);
+    }
+
+    #[test]
+    fn test_server_message_round_trip() {
+        for variant in 0..SERVER_MESSAGE_VARIANTS {
+            let mut random = Random(0x9e3779b97f4a7c15 ^ variant);
+            for _ in 0..ITERATIONS {
+                assert_round_trip(random.server_message(variant));
+            }
+        }
+    }
+
+    #[test]
+    fn test_client_message_round_trip() {
+        for variant in 0..CLIENT_MESSAGE_VARIANTS {
+            let mut random = Random(0xd1b54a32d192ed03 ^ variant);
+            for _ in 0..ITERATIONS {
+                assert_round_trip(random.client_message(variant));
+            }
+        }
+    }
+
+    #[test]
+    fn test_quantized_position() {
+        let position = Vec3::new(1.0001, -20.5, 300.25);
+        let output = PlayerEntityOutput::NewPosition(position);
+        let PlayerEntityOutput::NewPosition(decoded) = round_trip(&output) else {
+            unreachable!();
+        };
+        assert!((decoded - position).abs().max_element() <= 0.5 / POSITION_SCALE);
+
+        // Quantized positions should be much smaller than three floats
+        let encoded = to_vec(&ObjectEntityOutput::NewPosition(Vec3::new(1.0, 2.0, 3.0))).unwrap();
+        assert!(encoded.len() < 1 + 2 + 3 * 4, "{:?}", encoded);
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        let mut random = Random(0x2545f4914f6cdd1d);
+        for variant in 0..SERVER_MESSAGE_VARIANTS {
+            for _ in 0..ITERATIONS {
+                let encoded = to_vec(&random.server_message(variant)).unwrap();
+                let length = random.below(encoded.len() as u64) as usize;
+                let mut corrupted = encoded.clone();
+                corrupted.truncate(length.max(1));
+                assert!(from_slice::<ServerMessage>(&corrupted).is_err());
+
+                let position = 1 + random.below(encoded.len() as u64 - 1) as usize;
+                let mut corrupted = encoded;
+                corrupted[position] ^= 1 << random.below(8);
+                // Flipped bits may still decode, but must never panic
+                let _ = from_slice::<ServerMessage>(&corrupted);
+            }
+        }
+
+        let mut unsupported = to_vec(&ClientMessage::PlayerInput(Vec::new())).unwrap();
+        unsupported[0] = FORMAT_VERSION + 1;
+        assert_eq!(
+            from_slice::<ClientMessage>(&unsupported).unwrap_err(),
+            Error::UnsupportedVersion(FORMAT_VERSION + 1)
+        );
+
+        let mut trailing = to_vec(&ClientMessage::PlayerInput(Vec::new())).unwrap();
+        trailing.push(0);
+        assert_eq!(
+            from_slice::<ClientMessage>(&trailing).unwrap_err(),
+            Error::TrailingBytes(1)
+        );
+    }
+
+    /// A hypothetical newer revision of the messages, with a field appended
+    /// to every variant and a new variant at the end.
+    mod next {
+        use serde::{Deserialize, Serialize};
+        use uuid::Uuid;
+
+        use crate::{
+            chat::{ChatMessage, ChatRejectReason, ChatRejection},
+            entity::{EntitiesIds, EntitiesOutputs, EntityStates},
+            environment::Environment,
+            input::PlayerEntityInput,
+            intent::{IntentRejectReason, IntentRejection, ObjectIntent, Role},
+            version::VersionData,
+        };
+
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+        pub struct TickOutput {
+            pub new_entity_states: EntityStates,
+            pub entity_outputs: EntitiesOutputs,
+            pub removed_entity_uuids: EntitiesIds,
+            pub chat_messages: Vec<ChatMessage>,
+            #[serde(default)]
+            pub tick_duration_micros: u64,
+            #[serde(default)]
+            pub tick: u64,
+            #[serde(default)]
+            pub environment: Option<Environment>,
+            #[serde(default)]
+            pub chat_rejections: Vec<ChatRejection>,
+            #[serde(default)]
+            pub intent_rejections: Vec<IntentRejection>,
+            #[serde(default)]
+            pub server_time_millis: u64,
+        }
+
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+        pub enum ServerMessage {
+            Handshake {
+                version: VersionData,
+                #[serde(default)]
+                features: Vec<String>,
+            },
+            SyncWorld {
+                player_id: Uuid,
+                entity_states: EntityStates,
+                #[serde(default)]
+                environment: Environment,
+                #[serde(default)]
+                role: Role,
+                #[serde(default)]
+                world_name: Option<String>,
+            },
+            TickOutput(TickOutput),
+            ChatRejected(ChatRejectReason),
+            IntentRejected {
+                intent: ObjectIntent,
+                reason: IntentRejectReason,
+            },
+            Kick {
+                reason: String,
+            },
+        }
+
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+        pub enum ClientMessage {
+            Handshake {
+                version: VersionData,
+                #[serde(default)]
+                admin_token: Option<String>,
+                #[serde(default)]
+                features: Vec<String>,
+            },
+            PlayerInput(Vec<PlayerEntityInput>),
+            Chat {
+                text: String,
+                target: Option<Uuid>,
+            },
+            ObjectIntent(ObjectIntent),
+            Ping(u64),
+        }
+    }
+
+    fn server_message_to_next(message: ServerMessage, random: &mut Random) -> next::ServerMessage {
+        match message {
+            ServerMessage::Handshake { version } => next::ServerMessage::Handshake {
+                version,
+                features: random.vec(4, Random::string),
+            },
+            ServerMessage::SyncWorld {
+                player_id,
+                entity_states,
+                environment,
+                role,
+            } => next::ServerMessage::SyncWorld {
+                player_id,
+                entity_states,
+                environment,
+                role,
+                world_name: Some(random.string()),
+            },
+            ServerMessage::TickOutput(output) => {
+                next::ServerMessage::TickOutput(next::TickOutput {
+                    new_entity_states: output.new_entity_states,
+                    entity_outputs: output.entity_outputs,
+                    removed_entity_uuids: output.removed_entity_uuids,
+                    chat_messages: output.chat_messages,
+                    tick_duration_micros: output.tick_duration_micros,
+                    tick: output.tick,
+                    environment: output.environment,
+                    chat_rejections: output.chat_rejections,
+                    intent_rejections: output.intent_rejections,
+                    server_time_millis: random.next(),
+                })
+            }
+            ServerMessage::ChatRejected(reason) => next::ServerMessage::ChatRejected(reason),
+            ServerMessage::IntentRejected { intent, reason } => {
+                next::ServerMessage::IntentRejected { intent, reason }
+            }
+        }
+    }
+
+    fn server_message_from_next(message: next::ServerMessage) -> ServerMessage {
+        match message {
+            next::ServerMessage::Handshake { version, .. } => ServerMessage::Handshake { version },
+            next::ServerMessage::SyncWorld {
+                player_id,
+                entity_states,
+                environment,
+                role,
+                ..
+            } => ServerMessage::SyncWorld {
+                player_id,
+                entity_states,
+                environment,
+                role,
+            },
+            next::ServerMessage::TickOutput(output) => ServerMessage::TickOutput(TickOutput {
+                new_entity_states: output.new_entity_states,
+                entity_outputs: output.entity_outputs,
+                removed_entity_uuids: output.removed_entity_uuids,
+                chat_messages: output.chat_messages,
+                tick_duration_micros: output.tick_duration_micros,
+                tick: output.tick,
+                environment: output.environment,
+                chat_rejections: output.chat_rejections,
+                intent_rejections: output.intent_rejections,
+            }),
+            next::ServerMessage::ChatRejected(reason) => ServerMessage::ChatRejected(reason),
+            next::ServerMessage::IntentRejected { intent, reason } => {
+                ServerMessage::IntentRejected { intent, reason }
+            }
+            next::ServerMessage::Kick { .. } => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_server_message_cross_version() {
+        for variant in 0..SERVER_MESSAGE_VARIANTS {
+            let mut random = Random(0xbf58476d1ce4e5b9 ^ variant);
+            for _ in 0..ITERATIONS {
+                // Newer peer reading an older message
+                let message = random.server_message(variant);
+                let decoded: next::ServerMessage = from_slice(&to_vec(&message).unwrap()).unwrap();
+                assert_eq!(server_message_from_next(decoded), message);
+
+                // Older peer reading a newer message
+                let newer = server_message_to_next(message.clone(), &mut random);
+                let decoded: ServerMessage = from_slice(&to_vec(&newer).unwrap()).unwrap();
+                assert_eq!(decoded, message);
+            }
+        }
+
+        let kick = next::ServerMessage::Kick {
+            reason: String::from("bye"),
+        };
+        assert!(from_slice::<ServerMessage>(&to_vec(&kick).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_client_message_cross_version() {
+        for variant in 0..CLIENT_MESSAGE_VARIANTS {
+            let mut random = Random(0x94d049bb133111eb ^ variant);
+            for _ in 0..ITERATIONS {
+                let message = random.client_message(variant);
+                let newer = match message.clone() {
+                    ClientMessage::Handshake {
+                        version,
+                        admin_token,
+                    } => next::ClientMessage::Handshake {
+                        version,
+                        admin_token,
+                        features: random.vec(4, Random::string),
+                    },
+                    ClientMessage::PlayerInput(input) => next::ClientMessage::PlayerInput(input),
+                    ClientMessage::Chat { text, target } => {
+                        next::ClientMessage::Chat { text, target }
+                    }
+                    ClientMessage::ObjectIntent(intent) => {
+                        next::ClientMessage::ObjectIntent(intent)
+                    }
+                };
+
+                let decoded: ClientMessage = from_slice(&to_vec(&newer).unwrap()).unwrap();
+                assert_eq!(decoded, message);
+
+                let decoded: next::ClientMessage = from_slice(&to_vec(&message).unwrap()).unwrap();
+                let decoded = match decoded {
+                    next::ClientMessage::Handshake {
+                        version,
+                        admin_token,
+                        features,
+                    } => {
+                        assert!(features.is_empty());
+                        ClientMessage::Handshake {
+                            version,
+                            admin_token,
+                        }
+                    }
+                    next::ClientMessage::PlayerInput(input) => ClientMessage::PlayerInput(input),
+                    next::ClientMessage::Chat { text, target } => {
+                        ClientMessage::Chat { text, target }
+                    }
+                    next::ClientMessage::ObjectIntent(intent) => {
+                        ClientMessage::ObjectIntent(intent)
+                    }
+                    next::ClientMessage::Ping(_) => unreachable!(),
+                };
+                assert_eq!(decoded, message);
+            }
+        }
+
+        let ping = next::ClientMessage::Ping(42);
+        assert!(from_slice::<ClientMessage>(&to_vec(&ping).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,43 @@
+//! Quantized position encoding.
+//!
+//! Use with `#[serde(with = "crate::codec::position")]`. Human readable
+//! formats keep the plain `Vec3`, binary formats store each component as a
+//! fixed-point integer with [`POSITION_SCALE`] steps per unit, which the
+//! compact codec writes as a zigzag varint.
+
+use glam::Vec3;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Quantization steps per world unit, a bit finer than one millimetre.
+pub const POSITION_SCALE: f32 = 1024.0;
+/// Components of this magnitude or more saturate when quantized, about
+/// two million units.
+pub const POSITION_LIMIT: f32 = i32::MAX as f32 / POSITION_SCALE;
+
+pub fn quantize(position: Vec3) -> [i32; 3] {
+    // Float to integer casts saturate, so out-of-range positions clamp.
+    (position * POSITION_SCALE)
+        .round()
+        .to_array()
+        .map(|value| value as i32)
+}
+
+pub fn dequantize(quantized: [i32; 3]) -> Vec3 {
+    Vec3::from_array(quantized.map(|value| value as f32)) / POSITION_SCALE
+}
+
+pub fn serialize<S: Serializer>(position: &Vec3, serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        position.serialize(serializer)
+    } else {
+        quantize(*position).serialize(serializer)
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec3, D::Error> {
+    if deserializer.is_human_readable() {
+        Vec3::deserialize(deserializer)
+    } else {
+        <[i32; 3]>::deserialize(deserializer).map(dequantize)
+    }
+}
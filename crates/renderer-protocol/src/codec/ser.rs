@@ -0,0 +1,403 @@
+use std::mem;
+
+use serde::{
+    ser::{
+        self, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+        SerializeTupleStruct, SerializeTupleVariant,
+    },
+    Serialize,
+};
+
+use super::{write_varint, zigzag_encode, Error, FORMAT_VERSION};
+
+/// Encode a value into a frame, prefixed with the format version.
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    let mut serializer = Serializer {
+        output: vec![FORMAT_VERSION],
+    };
+    value.serialize(&mut serializer)?;
+    Ok(serializer.output)
+}
+
+#[derive(Debug, Default)]
+pub struct Serializer {
+    output: Vec<u8>,
+}
+
+impl Serializer {
+    pub fn into_inner(self) -> Vec<u8> {
+        self.output
+    }
+
+    fn write_varint(&mut self, value: u64) {
+        write_varint(&mut self.output, value);
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_varint(bytes.len() as u64);
+        self.output.extend_from_slice(bytes);
+    }
+
+    /// Run `f` against an empty buffer, then write its output with a length
+    /// prefix so that decoders can skip it without knowing its schema.
+    fn write_nested<F>(&mut self, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>,
+    {
+        let outer = mem::take(&mut self.output);
+        let result = f(self);
+        let inner = mem::replace(&mut self.output, outer);
+        result?;
+        self.write_bytes(&inner);
+        Ok(())
+    }
+
+    fn begin_variant(&mut self, variant_index: u32) -> Result<VariantSerializer<'_>, Error> {
+        self.write_varint(variant_index as u64);
+        let outer = mem::take(&mut self.output);
+        Ok(VariantSerializer {
+            serializer: self,
+            outer,
+            next_field: 0,
+        })
+    }
+}
+
+impl<'a> ser::Serializer for &'a mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = VariantSerializer<'a>;
+    type SerializeMap = Self;
+    type SerializeStruct = StructSerializer<'a>;
+    type SerializeStructVariant = VariantSerializer<'a>;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.output.push(v as u8);
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.write_varint(zigzag_encode(v));
+        Ok(())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), Error> {
+        self.output.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.output.push(v);
+        Ok(())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.serialize_u64(v as u64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.write_varint(v);
+        Ok(())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), Error> {
+        self.output.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.output.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.output.extend_from_slice(&v.to_le_bytes());
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.serialize_u32(v as u32)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.write_bytes(v.as_bytes());
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.write_bytes(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.output.push(0);
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        self.output.push(1);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), Error> {
+        self.write_varint(variant_index as u64);
+        self.write_varint(0);
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.write_varint(variant_index as u64);
+        self.write_nested(|serializer| value.serialize(serializer))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self, Error> {
+        let len = len.ok_or(Error::LengthRequired)?;
+        self.write_varint(len as u64);
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<VariantSerializer<'a>, Error> {
+        self.begin_variant(variant_index)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self, Error> {
+        let len = len.ok_or(Error::LengthRequired)?;
+        self.write_varint(len as u64);
+        Ok(self)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<StructSerializer<'a>, Error> {
+        Ok(StructSerializer {
+            serializer: self,
+            next_field: 0,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<VariantSerializer<'a>, Error> {
+        self.begin_variant(variant_index)
+    }
+}
+
+impl SerializeSeq for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl SerializeTuple for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl SerializeTupleStruct for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl SerializeMap for &mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Write one tagged field: the tag is the field index plus one, since zero
+/// marks the end of the struct.
+fn write_field<T: Serialize + ?Sized>(
+    serializer: &mut Serializer,
+    next_field: &mut u64,
+    value: &T,
+) -> Result<(), Error> {
+    *next_field += 1;
+    serializer.write_varint(*next_field);
+    serializer.write_nested(|serializer| value.serialize(serializer))
+}
+
+pub struct StructSerializer<'a> {
+    serializer: &'a mut Serializer,
+    next_field: u64,
+}
+
+impl SerializeStruct for StructSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        write_field(self.serializer, &mut self.next_field, value)
+    }
+
+    fn skip_field(&mut self, _key: &'static str) -> Result<(), Error> {
+        self.next_field += 1;
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.serializer.write_varint(0);
+        Ok(())
+    }
+}
+
+/// Serializer for tuple and struct variants. The payload is buffered so it
+/// can be written with a length prefix after the variant index.
+pub struct VariantSerializer<'a> {
+    serializer: &'a mut Serializer,
+    outer: Vec<u8>,
+    next_field: u64,
+}
+
+impl VariantSerializer<'_> {
+    fn finish(self) {
+        let inner = mem::replace(&mut self.serializer.output, self.outer);
+        self.serializer.write_bytes(&inner);
+    }
+}
+
+impl SerializeTupleVariant for VariantSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(&mut *self.serializer)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish();
+        Ok(())
+    }
+}
+
+impl SerializeStructVariant for VariantSerializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        write_field(self.serializer, &mut self.next_field, value)
+    }
+
+    fn skip_field(&mut self, _key: &'static str) -> Result<(), Error> {
+        self.next_field += 1;
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.serializer.write_varint(0);
+        self.finish();
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+use std::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    pin::Pin,
+};
+
+use bytes::{Bytes, BytesMut};
+use serde::{Deserialize, Serialize};
+use tokio_serde::{Deserializer, Serializer};
+
+use super::{from_slice, to_vec, Error};
+
+/// `tokio_serde` codec for the compact binary format.
+pub struct Compact<Item, SinkItem> {
+    ghost: PhantomData<(Item, SinkItem)>,
+}
+
+impl<Item, SinkItem> Default for Compact<Item, SinkItem> {
+    fn default() -> Self {
+        Self { ghost: PhantomData }
+    }
+}
+
+impl<Item, SinkItem> Debug for Compact<Item, SinkItem> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Compact").finish()
+    }
+}
+
+impl<Item, SinkItem> Deserializer<Item> for Compact<Item, SinkItem>
+where
+    for<'a> Item: Deserialize<'a>,
+{
+    type Error = Error;
+
+    fn deserialize(self: Pin<&mut Self>, src: &BytesMut) -> Result<Item, Self::Error> {
+        from_slice(src)
+    }
+}
+
+impl<Item, SinkItem> Serializer<SinkItem> for Compact<Item, SinkItem>
+where
+    SinkItem: Serialize,
+{
+    type Error = Error;
+
+    fn serialize(self: Pin<&mut Self>, item: &SinkItem) -> Result<Bytes, Self::Error> {
+        to_vec(item).map(Bytes::from)
+    }
+}
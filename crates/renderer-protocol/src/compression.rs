@@ -0,0 +1,341 @@
+//! Optional per-message compression on top of the message codec.
+//!
+//! The client offers the algorithms it supports in the [`COMPRESSION_HEADER`]
+//! of the WebSocket upgrade request, and the server answers with the one it
+//! picked in the same response header. Without an answer, frames are sent
+//! untouched. Otherwise each frame starts with a flag byte telling whether
+//! the rest is compressed, so small messages below the threshold skip the
+//! compressor entirely.
+
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io::{self, Read, Write},
+    sync::Arc,
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder};
+use serde::{Deserialize, Serialize};
+
+use crate::traffic::TrafficCounters;
+
+pub const COMPRESSION_HEADER: &str = "x-renderer-compression";
+
+/// Largest payload a compressed frame may expand to.
+pub const MAX_DECOMPRESSED_SIZE: usize = 64 * 1024 * 1024;
+
+const FRAME_RAW: u8 = 0;
+const FRAME_COMPRESSED: u8 = 1;
+
+const ZSTD_LEVEL: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CompressionType {
+    Zstd,
+    Deflate,
+}
+
+impl CompressionType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CompressionType::Zstd => "zstd",
+            CompressionType::Deflate => "deflate",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim() {
+            name if name.eq_ignore_ascii_case("zstd") => Some(CompressionType::Zstd),
+            name if name.eq_ignore_ascii_case("deflate") => Some(CompressionType::Deflate),
+            _ => None,
+        }
+    }
+
+    fn compress(&self, payload: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+        match self {
+            CompressionType::Zstd => zstd::stream::copy_encode(payload, output, ZSTD_LEVEL),
+            CompressionType::Deflate => {
+                let mut encoder = DeflateEncoder::new(output, flate2::Compression::fast());
+                encoder.write_all(payload)?;
+                encoder.finish()?;
+                Ok(())
+            }
+        }
+    }
+
+    fn decompress(&self, payload: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        let limit = MAX_DECOMPRESSED_SIZE as u64 + 1;
+        let mut output = Vec::new();
+        match self {
+            CompressionType::Zstd => {
+                zstd::stream::Decoder::new(payload)?
+                    .take(limit)
+                    .read_to_end(&mut output)?;
+            }
+            CompressionType::Deflate => {
+                DeflateDecoder::new(payload)
+                    .take(limit)
+                    .read_to_end(&mut output)?;
+            }
+        }
+        if output.len() > MAX_DECOMPRESSED_SIZE {
+            return Err(CompressionError::FrameTooLarge);
+        }
+        Ok(output)
+    }
+}
+
+impl Display for CompressionType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressionConfig {
+    /// Supported algorithms, the preferred one first.
+    pub types: Vec<CompressionType>,
+    /// Messages smaller than this many bytes are sent uncompressed.
+    pub threshold: usize,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            types: vec![CompressionType::Zstd, CompressionType::Deflate],
+            threshold: 1024,
+        }
+    }
+}
+
+impl CompressionConfig {
+    pub fn disabled() -> Self {
+        Self {
+            types: Vec::new(),
+            ..Default::default()
+        }
+    }
+
+    /// Header value for the client to offer its algorithms.
+    pub fn offer(&self) -> Option<String> {
+        if self.types.is_empty() {
+            return None;
+        }
+        let names: Vec<&str> = self.types.iter().map(CompressionType::name).collect();
+        Some(names.join(", "))
+    }
+
+    /// Pick the algorithm the server prefers among the client's offer.
+    pub fn accept(&self, offer: &str) -> Option<CompressionType> {
+        let offered: Vec<CompressionType> = offer
+            .split(',')
+            .filter_map(CompressionType::from_name)
+            .collect();
+        self.types
+            .iter()
+            .copied()
+            .find(|compression_type| offered.contains(compression_type))
+    }
+
+    /// Check the server's answer against what the client offered.
+    pub fn accepted(&self, answer: &str) -> Option<CompressionType> {
+        CompressionType::from_name(answer).filter(|answer| self.types.contains(answer))
+    }
+}
+
+#[derive(Debug)]
+pub enum CompressionError {
+    Io(io::Error),
+    EmptyFrame,
+    BadFrameFlag(u8),
+    FrameTooLarge,
+}
+
+impl Display for CompressionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CompressionError::Io(error) => Display::fmt(error, f),
+            CompressionError::EmptyFrame => write!(f, "Empty frame"),
+            CompressionError::BadFrameFlag(flag) => write!(f, "Bad frame flag {}", flag),
+            CompressionError::FrameTooLarge => write!(
+                f,
+                "Frame expands to more than {} bytes",
+                MAX_DECOMPRESSED_SIZE
+            ),
+        }
+    }
+}
+
+impl Error for CompressionError {}
+
+impl From<io::Error> for CompressionError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameCodec {
+    compression: Option<CompressionType>,
+    threshold: usize,
+    counters: Arc<TrafficCounters>,
+}
+
+impl FrameCodec {
+    pub fn new(
+        compression: Option<CompressionType>,
+        threshold: usize,
+        counters: Arc<TrafficCounters>,
+    ) -> Self {
+        Self {
+            compression,
+            threshold,
+            counters,
+        }
+    }
+
+    pub fn compression(&self) -> Option<CompressionType> {
+        self.compression
+    }
+
+    pub fn counters(&self) -> &Arc<TrafficCounters> {
+        &self.counters
+    }
+
+    pub fn encode(&self, payload: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        let frame = match self.compression {
+            None => payload.to_vec(),
+            Some(compression) if payload.len() >= self.threshold => {
+                let mut frame = Vec::with_capacity(payload.len() / 2 + 1);
+                frame.push(FRAME_COMPRESSED);
+                compression.compress(payload, &mut frame)?;
+                // Incompressible data would only grow
+                if frame.len() > payload.len() {
+                    frame.clear();
+                    frame.push(FRAME_RAW);
+                    frame.extend_from_slice(payload);
+                }
+                frame
+            }
+            Some(_) => {
+                let mut frame = Vec::with_capacity(payload.len() + 1);
+                frame.push(FRAME_RAW);
+                frame.extend_from_slice(payload);
+                frame
+            }
+        };
+        self.counters.add_sent(frame.len(), payload.len());
+        Ok(frame)
+    }
+
+    pub fn decode(&self, frame: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        let payload = match self.compression {
+            None => frame.to_vec(),
+            Some(compression) => {
+                let (flag, payload) = frame.split_first().ok_or(CompressionError::EmptyFrame)?;
+                match *flag {
+                    FRAME_RAW => payload.to_vec(),
+                    FRAME_COMPRESSED => compression.decompress(payload)?,
+                    flag => return Err(CompressionError::BadFrameFlag(flag)),
+                }
+            }
+        };
+        self.counters.add_received(frame.len(), payload.len());
+        Ok(payload)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use crate::traffic::TrafficCounters;
+
+    use super::{
+        CompressionConfig, CompressionError, CompressionType, FrameCodec, MAX_DECOMPRESSED_SIZE,
+    };
+
+    fn codec(compression: Option<CompressionType>, threshold: usize) -> FrameCodec {
+        FrameCodec::new(compression, threshold, Arc::new(TrafficCounters::default()))
+    }
+
+    #[test]
+    fn test_negotiation() {
+        let client = CompressionConfig::default();
+        let server = CompressionConfig {
+            types: vec![CompressionType::Deflate, CompressionType::Zstd],
+            ..Default::default()
+        };
+        let offer = client.offer().unwrap();
+        assert_eq!(offer, "zstd, deflate");
+        let answer = server.accept(&offer).unwrap();
+        assert_eq!(answer, CompressionType::Deflate);
+        assert_eq!(client.accepted(answer.name()), Some(answer));
+
+        assert_eq!(CompressionConfig::disabled().offer(), None);
+        assert_eq!(CompressionConfig::disabled().accept(&offer), None);
+        assert_eq!(server.accept("brotli, lz4"), None);
+        assert_eq!(
+            CompressionConfig {
+                types: vec![CompressionType::Zstd],
+                ..Default::default()
+            }
+            .accepted("deflate"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let payload: Vec<u8> = (0..16384u32).map(|index| (index % 7) as u8).collect();
+        for compression in [CompressionType::Zstd, CompressionType::Deflate] {
+            let sender = codec(Some(compression), 64);
+            let receiver = codec(Some(compression), 64);
+
+            let frame = sender.encode(&payload).unwrap();
+            assert!(frame.len() < payload.len() / 4);
+            assert_eq!(receiver.decode(&frame).unwrap(), payload);
+
+            let small = sender.encode(b"tiny").unwrap();
+            assert_eq!(small, b"\0tiny");
+            assert_eq!(receiver.decode(&small).unwrap(), b"tiny");
+
+            let sent = sender.counters().stats().sent;
+            assert_eq!(sent.messages, 2);
+            assert_eq!(sent.uncompressed_bytes, payload.len() as u64 + 4);
+            assert_eq!(sent.bytes, (frame.len() + small.len()) as u64);
+            assert_eq!(receiver.counters().stats().received, sent);
+        }
+    }
+
+    #[test]
+    fn test_uncompressed() {
+        let codec = codec(None, 0);
+        assert_eq!(codec.encode(b"plain").unwrap(), b"plain");
+        assert_eq!(codec.decode(b"plain").unwrap(), b"plain");
+    }
+
+    #[test]
+    fn test_bad_frames() {
+        let codec = codec(Some(CompressionType::Deflate), 0);
+        assert!(matches!(
+            codec.decode(b""),
+            Err(CompressionError::EmptyFrame)
+        ));
+        assert!(matches!(
+            codec.decode(b"\x07data"),
+            Err(CompressionError::BadFrameFlag(7))
+        ));
+        assert!(codec.decode(b"\x01not deflate").is_err());
+
+        let bomb = vec![0u8; MAX_DECOMPRESSED_SIZE + 1];
+        for compression in [CompressionType::Zstd, CompressionType::Deflate] {
+            let codec = self::codec(Some(compression), 0);
+            let frame = codec.encode(&bomb).unwrap();
+            assert!(matches!(
+                codec.decode(&frame),
+                Err(CompressionError::FrameTooLarge)
+            ));
+        }
+    }
+}
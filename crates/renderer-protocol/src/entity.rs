@@ -1,15 +1,123 @@
+//! Entity data on the wire.
+//!
+//! Entity kinds are not fixed by the protocol. Each kind is identified by an
+//! [`EntityKind`] tag, and its states and outputs travel as [`EntityData`],
+//! which holds the kind specific type encoded with the [compact
+//! codec](crate::codec). Peers decode the kinds they know, and skip the rest.
+
+use std::{
+    borrow::Cow,
+    collections::{btree_map, BTreeMap},
+    fmt::{self, Display, Formatter},
+    ops::BitOr,
+};
+
 use glam::Vec3;
 use renderer_asset::index::BundleIndex;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::codec;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct EntityKind(pub Cow<'static, str>);
+
+impl EntityKind {
+    pub const OBJECT: EntityKind = EntityKind::new("object");
+    pub const PLAYER: EntityKind = EntityKind::new("player");
+    pub const LIGHT: EntityKind = EntityKind::new("light");
+
+    pub const fn new(name: &'static str) -> Self {
+        Self(Cow::Borrowed(name))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for EntityKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A kind specific state or output, encoded with the compact codec.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct EntityData(pub Vec<u8>);
+
+impl EntityData {
+    pub fn encode<T: Serialize>(value: &T) -> Result<Self, codec::Error> {
+        codec::to_vec(value).map(Self)
+    }
+
+    pub fn decode<T: for<'a> Deserialize<'a>>(&self) -> Result<T, codec::Error> {
+        codec::from_slice(&self.0)
+    }
+}
+
+/// Entries grouped by their entity kind.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ByKind<T>(pub BTreeMap<EntityKind, Vec<T>>);
+
+impl<T> Default for ByKind<T> {
+    fn default() -> Self {
+        Self(BTreeMap::new())
+    }
+}
+
+impl<T> ByKind<T> {
+    pub fn push(&mut self, kind: &EntityKind, item: T) {
+        match self.0.get_mut(kind) {
+            Some(items) => items.push(item),
+            None => {
+                self.0.insert(kind.clone(), vec![item]);
+            }
+        }
+    }
+
+    pub fn get(&self, kind: &EntityKind) -> &[T] {
+        self.0.get(kind).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.values().all(Vec::is_empty)
+    }
+
+    pub fn iter(&self) -> btree_map::Iter<'_, EntityKind, Vec<T>> {
+        self.0.iter()
+    }
+}
+
+impl<T> IntoIterator for ByKind<T> {
+    type Item = (EntityKind, Vec<T>);
+    type IntoIter = btree_map::IntoIter<EntityKind, Vec<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<T> FromIterator<(EntityKind, T)> for ByKind<T> {
+    fn from_iter<I: IntoIterator<Item = (EntityKind, T)>>(iter: I) -> Self {
+        let mut by_kind = Self::default();
+        for (kind, item) in iter {
+            by_kind.push(&kind, item);
+        }
+        by_kind
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BaseEntityData {
     pub id: Uuid,
     pub position: Vec3,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum EntityResourceData {
     Box,
     Crosshair,
@@ -19,36 +127,96 @@ pub enum EntityResourceData {
     },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// What players other than the owner may do with an object. Owners and
+/// admins may always move and delete it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ObjectPermissions(pub u8);
+
+impl ObjectPermissions {
+    pub const NONE: Self = Self(0);
+    pub const MOVE: Self = Self(1 << 0);
+    pub const DELETE: Self = Self(1 << 1);
+    pub const ALL: Self = Self(Self::MOVE.0 | Self::DELETE.0);
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for ObjectPermissions {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ObjectEntityState {
     pub base: BaseEntityData,
     pub resource: EntityResourceData,
+    /// Player who spawned the object, none for objects of the server.
+    #[serde(default)]
+    pub owner: Option<Uuid>,
+    #[serde(default)]
+    pub permissions: ObjectPermissions,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
-pub struct EntityStates {
-    pub object: Vec<ObjectEntityState>,
-    pub player: Vec<BaseEntityData>,
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum PlayerEntityOutput {
-    NewPosition(Vec3),
+    NewPosition(#[serde(with = "crate::codec::position")] Vec3),
+    /// State after the latest move of the player's own client was applied,
+    /// unquantized so that the client can replay its later moves from it.
+    Acknowledged {
+        sequence: u32,
+        position: Vec3,
+        velocity: Vec3,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum ObjectEntityOutput {
-    NewPosition(Vec3),
+    NewPosition(#[serde(with = "crate::codec::position")] Vec3),
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
-pub struct EntitiesOutputs {
-    pub object: Vec<(Uuid, ObjectEntityOutput)>,
-    pub player: Vec<(Uuid, PlayerEntityOutput)>,
+/// How a light entity shines. Point and directional lights shine from the
+/// entity position, parallel lights from infinitely far away.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum LightSource {
+    Point {
+        color: Vec3,
+        constant: f32,
+        linear: f32,
+        quadratic: f32,
+    },
+    Directional {
+        color: Vec3,
+        direction: Vec3,
+        constant: f32,
+        linear: f32,
+        quadratic: f32,
+        range_inner: f32,
+        range_outer: f32,
+    },
+    Parallel {
+        direction: Vec3,
+        color: Vec3,
+        strength: f32,
+    },
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
-pub struct EntitiesIds {
-    pub object: Vec<Uuid>,
-    pub player: Vec<Uuid>,
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LightEntityState {
+    pub base: BaseEntityData,
+    pub source: LightSource,
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum LightEntityOutput {
+    NewPosition(#[serde(with = "crate::codec::position")] Vec3),
+    NewSource(LightSource),
+}
+
+pub type EntityStates = ByKind<EntityData>;
+pub type EntitiesOutputs = ByKind<(Uuid, EntityData)>;
+pub type EntitiesIds = ByKind<Uuid>;
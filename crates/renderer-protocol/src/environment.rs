@@ -0,0 +1,45 @@
+//! World wide rendering state, owned by the server.
+
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+/// Strengths of the lighting terms, applied to every light.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct LightParam {
+    pub start_strength: f32,
+    pub stop_strength: f32,
+    pub max_strength: f32,
+    pub border_start_strength: f32,
+    pub border_stop_strength: f32,
+    pub border_max_strength: f32,
+    pub ambient_strength: f32,
+}
+
+impl Default for LightParam {
+    fn default() -> Self {
+        Self {
+            start_strength: 0.30,
+            stop_strength: 1.00,
+            max_strength: 0.80,
+            border_start_strength: 0.40,
+            border_stop_strength: 0.80,
+            border_max_strength: 0.20,
+            ambient_strength: 0.60,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Environment {
+    pub background_color: Vec3,
+    pub light_param: LightParam,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self {
+            background_color: Vec3::new(0.8, 0.8, 1.0),
+            light_param: LightParam::default(),
+        }
+    }
+}
@@ -1,7 +1,21 @@
 use glam::Vec3;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum PlayerEntityInput {
+    /// Ignored, as the server owns player positions. Kept so that inputs of
+    /// older clients still decode.
     NewPosition(Vec3),
+    Move(PlayerMove),
+}
+
+/// Movement wanted by a client for one of its frames.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct PlayerMove {
+    /// Counts up by one per move, and is acknowledged back by the server so
+    /// that the client knows which moves it still has to replay.
+    pub sequence: u32,
+    /// Horizontal walking direction, of length up to one.
+    pub direction: Vec3,
+    pub jump: bool,
 }
@@ -0,0 +1,106 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::entity::{EntityResourceData, ObjectPermissions};
+
+/// What a player is allowed to do, granted at handshake.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Role {
+    #[default]
+    Player,
+    /// Allowed to change any object, whatever its permissions.
+    Admin,
+}
+
+/// A request from a client to change an object entity, checked against the
+/// object permissions before it is applied.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ObjectIntent {
+    /// Spawn an object owned by the player. The id is picked by the client,
+    /// so it can tell its objects apart before the server confirms them.
+    Spawn {
+        id: Uuid,
+        position: Vec3,
+        resource: EntityResourceData,
+        permissions: ObjectPermissions,
+    },
+    Move {
+        id: Uuid,
+        position: Vec3,
+    },
+    Delete {
+        id: Uuid,
+    },
+}
+
+impl ObjectIntent {
+    pub fn id(&self) -> Uuid {
+        match self {
+            ObjectIntent::Spawn { id, .. }
+            | ObjectIntent::Move { id, .. }
+            | ObjectIntent::Delete { id } => *id,
+        }
+    }
+
+    pub fn action(&self) -> &'static str {
+        match self {
+            ObjectIntent::Spawn { .. } => "spawn",
+            ObjectIntent::Move { .. } => "move",
+            ObjectIntent::Delete { .. } => "delete",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntentRejectReason {
+    UnknownObject,
+    AlreadyExists,
+    /// The object doesn't allow the action to this player.
+    NotPermitted,
+    /// Spawning is reserved to admins.
+    SpawnDisabled,
+    TooManyObjects {
+        max_objects: usize,
+    },
+    /// Only boxes and the external models the server allows can be spawned.
+    ResourceNotAllowed,
+    /// The position is not a finite vector.
+    BadPosition,
+    /// Too many intents in a short time.
+    RateLimited,
+}
+
+impl Display for IntentRejectReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            IntentRejectReason::UnknownObject => write!(f, "No such object"),
+            IntentRejectReason::AlreadyExists => write!(f, "An entity with this id already exists"),
+            IntentRejectReason::NotPermitted => write!(f, "Not permitted on this object"),
+            IntentRejectReason::SpawnDisabled => write!(f, "Only admins can spawn objects"),
+            IntentRejectReason::TooManyObjects { max_objects } => {
+                write!(f, "Already owning the maximum of {} objects", max_objects)
+            }
+            IntentRejectReason::ResourceNotAllowed => {
+                write!(f, "This resource can't be spawned")
+            }
+            IntentRejectReason::BadPosition => write!(f, "Bad position"),
+            IntentRejectReason::RateLimited => write!(f, "Too many requests, slow down"),
+        }
+    }
+}
+
+impl Error for IntentRejectReason {}
+
+/// An intent the server rejected while applying it in a tick.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct IntentRejection {
+    pub sender: Uuid,
+    pub intent: ObjectIntent,
+    pub reason: IntentRejectReason,
+}
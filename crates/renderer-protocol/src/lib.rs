@@ -1,5 +1,14 @@
+pub mod chat;
+pub mod codec;
+#[cfg(feature = "compression")]
+pub mod compression;
 pub mod entity;
+pub mod environment;
 pub mod input;
+pub mod intent;
 pub mod message;
+pub mod movement;
+pub mod replay;
 pub mod tick;
+pub mod traffic;
 pub mod version;
@@ -2,10 +2,16 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::{
-    entity::EntityStates, input::PlayerEntityInput, tick::TickOutput, version::VersionData,
+    chat::ChatRejectReason,
+    entity::EntityStates,
+    environment::Environment,
+    input::PlayerEntityInput,
+    intent::{IntentRejectReason, ObjectIntent, Role},
+    tick::TickOutput,
+    version::VersionData,
 };
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum ServerMessage {
     Handshake {
         version: VersionData,
@@ -13,12 +19,31 @@ pub enum ServerMessage {
     SyncWorld {
         player_id: Uuid,
         entity_states: EntityStates,
+        #[serde(default)]
+        environment: Environment,
+        #[serde(default)]
+        role: Role,
     },
     TickOutput(TickOutput),
+    ChatRejected(ChatRejectReason),
+    IntentRejected {
+        intent: ObjectIntent,
+        reason: IntentRejectReason,
+    },
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub enum ClientMessage {
-    Handshake { version: VersionData },
+    Handshake {
+        version: VersionData,
+        /// Grants the admin role when it matches the server token.
+        #[serde(default)]
+        admin_token: Option<String>,
+    },
     PlayerInput(Vec<PlayerEntityInput>),
+    Chat {
+        text: String,
+        target: Option<Uuid>,
+    },
+    ObjectIntent(ObjectIntent),
 }
@@ -0,0 +1,189 @@
+//! Player movement rules, shared by the server simulating players and by
+//! clients predicting their own player until the server confirms it.
+
+use std::collections::VecDeque;
+
+use glam::Vec3;
+
+use crate::input::PlayerMove;
+
+pub const PLAYER_RADIUS: f32 = 0.3;
+/// Half the length of the segment of the player capsule.
+pub const PLAYER_HALF_HEIGHT: f32 = 0.6;
+/// Horizontal speed of a walking player, in meters per second.
+pub const WALK_SPEED: f32 = 4.0;
+/// Upward speed a jump starts with, in meters per second.
+pub const JUMP_SPEED: f32 = 5.0;
+pub const GRAVITY: Vec3 = Vec3::new(0.0, -9.81, 0.0);
+/// Height of the ground clients predict against, the server default.
+pub const GROUND_HEIGHT: f32 = 0.0;
+
+/// Moves kept for replaying while the server doesn't acknowledge them.
+const MAX_PENDING_MOVES: usize = 256;
+
+/// Velocity of a player after applying `input`. Jumping only works on the
+/// ground, and bad directions are treated as standing still.
+pub fn apply_move(velocity: Vec3, input: &PlayerMove, grounded: bool) -> Vec3 {
+    let direction = Vec3::new(input.direction.x, 0.0, input.direction.z);
+    let direction = if direction.is_finite() {
+        direction.clamp_length_max(1.0)
+    } else {
+        Vec3::ZERO
+    };
+    let mut velocity = Vec3::new(
+        direction.x * WALK_SPEED,
+        velocity.y,
+        direction.z * WALK_SPEED,
+    );
+    if input.jump && grounded {
+        velocity.y = JUMP_SPEED;
+    }
+    velocity
+}
+
+/// Local player position, predicted from the moves sent to the server and
+/// corrected whenever it acknowledges one of them.
+///
+/// Only the ground is predicted, bumping into other bodies is left for the
+/// server to correct.
+#[derive(Debug, Clone)]
+pub struct Prediction {
+    position: Vec3,
+    velocity: Vec3,
+    /// Moves sent but not acknowledged yet, with how long each one lasted.
+    pending: VecDeque<(PlayerMove, f32)>,
+    next_sequence: u32,
+}
+
+impl Prediction {
+    pub fn new(position: Vec3) -> Self {
+        Self {
+            position,
+            velocity: Vec3::ZERO,
+            pending: VecDeque::new(),
+            next_sequence: 0,
+        }
+    }
+
+    pub fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    pub fn velocity(&self) -> Vec3 {
+        self.velocity
+    }
+
+    fn step(position: &mut Vec3, velocity: &mut Vec3, input: &PlayerMove, dt: f32) {
+        let bottom = PLAYER_HALF_HEIGHT + PLAYER_RADIUS;
+        let grounded = position.y - bottom <= GROUND_HEIGHT + 1e-4;
+        *velocity = apply_move(*velocity, input, grounded);
+        *velocity += GRAVITY * dt;
+        *position += *velocity * dt;
+        if position.y - bottom < GROUND_HEIGHT {
+            position.y = GROUND_HEIGHT + bottom;
+            velocity.y = velocity.y.max(0.0);
+        }
+    }
+
+    /// Predict a move lasting `dt` seconds, returning the input to send.
+    pub fn push(&mut self, direction: Vec3, jump: bool, dt: f32) -> PlayerMove {
+        let input = PlayerMove {
+            sequence: self.next_sequence,
+            direction,
+            jump,
+        };
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+        Self::step(&mut self.position, &mut self.velocity, &input, dt);
+        if self.pending.len() == MAX_PENDING_MOVES {
+            self.pending.pop_front();
+        }
+        self.pending.push_back((input, dt));
+        input
+    }
+
+    /// Position after a move lasting `dt` seconds, without predicting it.
+    pub fn preview(&self, direction: Vec3, jump: bool, dt: f32) -> Vec3 {
+        let input = PlayerMove {
+            sequence: self.next_sequence,
+            direction,
+            jump,
+        };
+        let (mut position, mut velocity) = (self.position, self.velocity);
+        Self::step(&mut position, &mut velocity, &input, dt);
+        position
+    }
+
+    /// Restart from the state the server reached with the move `sequence`,
+    /// replaying the moves it hasn't applied yet.
+    pub fn reconcile(&mut self, sequence: u32, position: Vec3, velocity: Vec3) {
+        while let Some((input, _)) = self.pending.front() {
+            // Sequences wrap around, so compare their distance
+            if sequence.wrapping_sub(input.sequence) >= u32::MAX / 2 {
+                break;
+            }
+            self.pending.pop_front();
+        }
+        self.position = position;
+        self.velocity = velocity;
+        for (input, dt) in &self.pending {
+            Self::step(&mut self.position, &mut self.velocity, input, *dt);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glam::Vec3;
+
+    use super::{Prediction, JUMP_SPEED, PLAYER_HALF_HEIGHT, PLAYER_RADIUS, WALK_SPEED};
+
+    const STANDING: f32 = PLAYER_HALF_HEIGHT + PLAYER_RADIUS;
+
+    #[test]
+    fn test_walk_and_jump() {
+        let dt = 1.0 / 60.0;
+        let mut prediction = Prediction::new(Vec3::new(0.0, STANDING, 0.0));
+        for _ in 0..60 {
+            prediction.push(Vec3::X * 2.0, false, dt);
+        }
+        // Directions are capped to walking speed, and the player stays on
+        // the ground
+        let position = prediction.position();
+        assert!((position.x - WALK_SPEED).abs() < 1e-3, "{:?}", position);
+        assert_eq!(position.y, STANDING);
+
+        // Previews leave the prediction as is
+        let preview = prediction.preview(Vec3::ZERO, true, dt);
+        assert!(preview.y > STANDING);
+        assert_eq!(prediction.position().y, STANDING);
+        prediction.push(Vec3::ZERO, true, dt);
+        assert_eq!(prediction.position(), preview);
+        // No jumping again in the air
+        prediction.push(Vec3::ZERO, true, dt);
+        assert!(prediction.velocity().y < JUMP_SPEED);
+        for _ in 0..120 {
+            prediction.push(Vec3::ZERO, false, dt);
+        }
+        assert_eq!(prediction.position().y, STANDING);
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let dt = 0.1;
+        let mut prediction = Prediction::new(Vec3::new(0.0, STANDING, 0.0));
+        let first = prediction.push(Vec3::X, false, dt);
+        prediction.push(Vec3::X, false, dt);
+        prediction.push(Vec3::Z, false, dt);
+
+        // The server stopped the player on something after the first move,
+        // so the two later moves are replayed from there
+        let blocked = Vec3::new(0.1, STANDING, 0.0);
+        prediction.reconcile(first.sequence, blocked, Vec3::ZERO);
+        let expected = blocked + Vec3::new(WALK_SPEED * dt, 0.0, WALK_SPEED * dt);
+        assert!(prediction.position().abs_diff_eq(expected, 1e-5));
+
+        // Acknowledging every move leaves the server state as is
+        prediction.reconcile(first.sequence + 2, blocked, Vec3::ZERO);
+        assert_eq!(prediction.position(), blocked);
+    }
+}
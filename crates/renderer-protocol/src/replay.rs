@@ -0,0 +1,369 @@
+//! Recorded server sessions.
+//!
+//! A replay file starts with [`REPLAY_MAGIC`] and a header frame, followed by
+//! one frame per tick. Frames are compact encoded and prefixed with their
+//! length as a little endian `u32`.
+
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+use glam::Vec3;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    chat::ChatMessage,
+    codec,
+    entity::EntityStates,
+    environment::Environment,
+    input::PlayerEntityInput,
+    intent::{ObjectIntent, Role},
+    tick::TickOutput,
+    version::VersionData,
+};
+
+pub const REPLAY_MAGIC: [u8; 4] = *b"RRPL";
+
+/// Frames larger than this are treated as corrupted.
+const MAX_FRAME_LENGTH: u32 = 64 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplayHeader {
+    pub version: VersionData,
+    /// World state before the first recorded tick.
+    pub entity_states: EntityStates,
+    #[serde(default)]
+    pub environment: Environment,
+    /// Scripts attached to entities of the header.
+    #[serde(default)]
+    pub scripts: Vec<ReplayScript>,
+}
+
+/// Source of a script, so that replays don't depend on the script files.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplayScript {
+    pub path: String,
+    pub source: String,
+    /// Entities running the script.
+    pub entities: Vec<Uuid>,
+}
+
+/// Something that changed the world between two ticks.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ReplayEvent {
+    PlayerJoined {
+        id: Uuid,
+        position: Vec3,
+    },
+    PlayerLeft(Uuid),
+    Input(Uuid, PlayerEntityInput),
+    Chat(ChatMessage),
+    /// An intent of a player, with its role. Rejected ones are recorded too,
+    /// as their rejections are part of the output.
+    ObjectIntent {
+        player: Uuid,
+        role: Role,
+        intent: ObjectIntent,
+    },
+    /// A script file changed, and was recompiled from `source`.
+    ScriptReloaded {
+        path: String,
+        source: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplayTick {
+    pub tick: u64,
+    #[serde(with = "nanos")]
+    pub dt: Duration,
+    /// Events applied before the tick ran, in order.
+    pub events: Vec<ReplayEvent>,
+    pub output: TickOutput,
+}
+
+/// Durations as whole nanoseconds, as the compact codec can't decode the
+/// field names of the default representation.
+mod nanos {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_nanos() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_nanos)
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Codec(codec::Error),
+    BadMagic,
+    FrameTooLarge(u32),
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "Replay I/O error: {}", err),
+            ReplayError::Codec(err) => write!(f, "Bad replay frame: {}", err),
+            ReplayError::BadMagic => write!(f, "Not a replay file"),
+            ReplayError::FrameTooLarge(length) => {
+                write!(f, "Replay frame of {} bytes is too large", length)
+            }
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(err: io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
+
+impl From<codec::Error> for ReplayError {
+    fn from(err: codec::Error) -> Self {
+        ReplayError::Codec(err)
+    }
+}
+
+#[derive(Debug)]
+pub struct ReplayWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> ReplayWriter<W> {
+    pub fn new(mut writer: W, header: &ReplayHeader) -> Result<Self, ReplayError> {
+        writer.write_all(&REPLAY_MAGIC)?;
+        let mut replay_writer = Self { writer };
+        replay_writer.write_frame(header)?;
+        Ok(replay_writer)
+    }
+
+    fn write_frame<T: Serialize>(&mut self, value: &T) -> Result<(), ReplayError> {
+        let frame = codec::to_vec(value)?;
+        self.writer.write_all(&(frame.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&frame)?;
+        Ok(())
+    }
+
+    pub fn write_tick(&mut self, tick: &ReplayTick) -> Result<(), ReplayError> {
+        self.write_frame(tick)
+    }
+
+    pub fn flush(&mut self) -> Result<(), ReplayError> {
+        Ok(self.writer.flush()?)
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads the header on creation, then iterates over ticks.
+#[derive(Debug)]
+pub struct ReplayReader<R: Read> {
+    reader: R,
+    header: ReplayHeader,
+}
+
+fn read_frame<R: Read, T: DeserializeOwned>(reader: &mut R) -> Result<Option<T>, ReplayError> {
+    let mut length = [0; 4];
+    // A clean end of file can only happen between frames
+    match reader.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+    let length = u32::from_le_bytes(length);
+    if length > MAX_FRAME_LENGTH {
+        return Err(ReplayError::FrameTooLarge(length));
+    }
+    let mut frame = vec![0; length as usize];
+    reader.read_exact(&mut frame)?;
+    Ok(Some(codec::from_slice(&frame)?))
+}
+
+impl<R: Read> ReplayReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, ReplayError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != REPLAY_MAGIC {
+            return Err(ReplayError::BadMagic);
+        }
+        let header = read_frame(&mut reader)?
+            .ok_or_else(|| ReplayError::Io(io::ErrorKind::UnexpectedEof.into()))?;
+        Ok(Self { reader, header })
+    }
+
+    pub fn header(&self) -> &ReplayHeader {
+        &self.header
+    }
+}
+
+impl<R: Read> Iterator for ReplayReader<R> {
+    type Item = Result<ReplayTick, ReplayError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        read_frame(&mut self.reader).transpose()
+    }
+}
+
+/// Whether two tick outputs describe the same changes. Entities of a kind
+/// are produced in no particular order, so outputs are compared per entity,
+/// and timings are ignored.
+pub fn outputs_match(a: &TickOutput, b: &TickOutput) -> bool {
+    fn normalize(output: &TickOutput) -> TickOutput {
+        let mut output = output.clone();
+        output.tick_duration_micros = 0;
+        for (_, outputs) in output.entity_outputs.0.iter_mut() {
+            // Stable, so changes of one entity stay in order
+            outputs.sort_by_key(|(id, _)| *id);
+        }
+        for (_, ids) in output.removed_entity_uuids.0.iter_mut() {
+            ids.sort();
+        }
+        for (_, states) in output.new_entity_states.0.iter_mut() {
+            states.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        output
+    }
+    normalize(a) == normalize(b)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use glam::Vec3;
+    use uuid::Uuid;
+
+    use crate::{
+        entity::{EntityData, EntityKind, EntityStates},
+        environment::Environment,
+        input::PlayerEntityInput,
+        tick::TickOutput,
+        version::VersionData,
+    };
+
+    use super::{
+        outputs_match, ReplayError, ReplayEvent, ReplayHeader, ReplayReader, ReplayScript,
+        ReplayTick, ReplayWriter,
+    };
+
+    #[test]
+    fn test_replay_round_trip() {
+        let header = ReplayHeader {
+            version: VersionData::current(),
+            entity_states: EntityStates::default(),
+            environment: Environment::default(),
+            scripts: vec![ReplayScript {
+                path: String::from("scripts/spin.rhai"),
+                source: String::from("fn on_tick(dt) {}"),
+                entities: vec![Uuid::from_u128(8)],
+            }],
+        };
+        let id = Uuid::from_u128(7);
+        let mut output = TickOutput::default();
+        output
+            .entity_outputs
+            .push(&EntityKind::PLAYER, (id, EntityData(vec![1, 2, 3])));
+        let ticks = vec![
+            ReplayTick {
+                tick: 0,
+                dt: Duration::from_millis(50),
+                events: vec![
+                    ReplayEvent::PlayerJoined {
+                        id,
+                        position: Vec3::ZERO,
+                    },
+                    ReplayEvent::Input(id, PlayerEntityInput::NewPosition(Vec3::ONE)),
+                ],
+                output,
+            },
+            ReplayTick {
+                tick: 1,
+                dt: Duration::from_millis(50),
+                events: vec![
+                    ReplayEvent::PlayerLeft(id),
+                    ReplayEvent::ScriptReloaded {
+                        path: String::from("scripts/spin.rhai"),
+                        source: String::from("fn on_tick(dt) { this.position.y += dt; }"),
+                    },
+                ],
+                output: TickOutput::default(),
+            },
+        ];
+
+        let mut writer = ReplayWriter::new(Vec::new(), &header).unwrap();
+        for tick in &ticks {
+            writer.write_tick(tick).unwrap();
+        }
+        let data = writer.into_inner();
+
+        let reader = ReplayReader::new(data.as_slice()).unwrap();
+        assert_eq!(reader.header(), &header);
+        let read: Vec<ReplayTick> = reader.map(Result::unwrap).collect();
+        assert_eq!(read, ticks);
+
+        // Truncated in the middle of a frame
+        let mut reader = ReplayReader::new(&data[..data.len() - 1]).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        assert!(matches!(reader.next(), Some(Err(ReplayError::Io(_)))));
+
+        assert!(matches!(
+            ReplayReader::new(&b"nope"[..]),
+            Err(ReplayError::BadMagic)
+        ));
+    }
+
+    #[test]
+    fn test_outputs_match() {
+        let (a, b) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let data = |byte| EntityData(vec![byte]);
+        let mut first = TickOutput::default();
+        first.entity_outputs.push(&EntityKind::OBJECT, (a, data(1)));
+        first.entity_outputs.push(&EntityKind::OBJECT, (b, data(2)));
+        first.entity_outputs.push(&EntityKind::OBJECT, (a, data(3)));
+        let mut second = TickOutput::default();
+        second
+            .entity_outputs
+            .push(&EntityKind::OBJECT, (b, data(2)));
+        second
+            .entity_outputs
+            .push(&EntityKind::OBJECT, (a, data(1)));
+        second
+            .entity_outputs
+            .push(&EntityKind::OBJECT, (a, data(3)));
+        assert!(outputs_match(&first, &second));
+
+        let mut third = TickOutput::default();
+        third.entity_outputs.push(&EntityKind::OBJECT, (a, data(3)));
+        third.entity_outputs.push(&EntityKind::OBJECT, (a, data(1)));
+        third.entity_outputs.push(&EntityKind::OBJECT, (b, data(2)));
+        assert!(!outputs_match(&first, &third));
+
+        // Entities inserted together have no order
+        let mut fourth = TickOutput::default();
+        fourth.new_entity_states.push(&EntityKind::OBJECT, data(1));
+        fourth.new_entity_states.push(&EntityKind::OBJECT, data(2));
+        let mut fifth = TickOutput::default();
+        fifth.new_entity_states.push(&EntityKind::OBJECT, data(2));
+        fifth.new_entity_states.push(&EntityKind::OBJECT, data(1));
+        assert!(outputs_match(&fourth, &fifth));
+    }
+}
@@ -2,13 +2,38 @@ use std::mem;
 
 use serde::{Deserialize, Serialize};
 
-use crate::entity::{EntitiesIds, EntitiesOutputs, EntityStates};
+use crate::{
+    chat::{ChatMessage, ChatRejection},
+    entity::{EntitiesIds, EntitiesOutputs, EntityStates},
+    environment::Environment,
+    intent::IntentRejection,
+};
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct TickOutput {
     pub new_entity_states: EntityStates,
     pub entity_outputs: EntitiesOutputs,
     pub removed_entity_uuids: EntitiesIds,
+    #[serde(default)]
+    pub chat_messages: Vec<ChatMessage>,
+    /// Time the server spent producing this output, in microseconds.
+    #[serde(default)]
+    pub tick_duration_micros: u64,
+    /// Number of the tick that produced this output, counting up from zero
+    /// by one.
+    #[serde(default)]
+    pub tick: u64,
+    /// Set when the environment changed in this tick.
+    #[serde(default)]
+    pub environment: Option<Environment>,
+    /// Chat messages rejected in this tick. Connections turn these into
+    /// `ChatRejected` messages for the sender, so clients never see them.
+    #[serde(default)]
+    pub chat_rejections: Vec<ChatRejection>,
+    /// Intents rejected in this tick, turned into `IntentRejected` messages
+    /// the same way.
+    #[serde(default)]
+    pub intent_rejections: Vec<IntentRejection>,
 }
 
 impl TickOutput {
@@ -17,6 +42,12 @@ impl TickOutput {
             new_entity_states: mem::take(&mut self.new_entity_states),
             entity_outputs: mem::take(&mut self.entity_outputs),
             removed_entity_uuids: mem::take(&mut self.removed_entity_uuids),
+            chat_messages: mem::take(&mut self.chat_messages),
+            tick_duration_micros: mem::take(&mut self.tick_duration_micros),
+            tick: mem::take(&mut self.tick),
+            environment: mem::take(&mut self.environment),
+            chat_rejections: mem::take(&mut self.chat_rejections),
+            intent_rejections: mem::take(&mut self.intent_rejections),
         }
     }
 }
@@ -0,0 +1,64 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DirectionStats {
+    pub messages: u64,
+    /// Bytes on the wire.
+    pub bytes: u64,
+    /// Bytes before compression, as seen by the message codec.
+    pub uncompressed_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrafficStats {
+    pub sent: DirectionStats,
+    pub received: DirectionStats,
+}
+
+#[derive(Debug, Default)]
+struct DirectionCounters {
+    messages: AtomicU64,
+    bytes: AtomicU64,
+    uncompressed_bytes: AtomicU64,
+}
+
+impl DirectionCounters {
+    fn add(&self, bytes: usize, uncompressed_bytes: usize) {
+        self.messages.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.uncompressed_bytes
+            .fetch_add(uncompressed_bytes as u64, Ordering::Relaxed);
+    }
+
+    fn stats(&self) -> DirectionStats {
+        DirectionStats {
+            messages: self.messages.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            uncompressed_bytes: self.uncompressed_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Byte counters, which can be shared between many connections.
+#[derive(Debug, Default)]
+pub struct TrafficCounters {
+    sent: DirectionCounters,
+    received: DirectionCounters,
+}
+
+impl TrafficCounters {
+    pub fn add_sent(&self, bytes: usize, uncompressed_bytes: usize) {
+        self.sent.add(bytes, uncompressed_bytes);
+    }
+
+    pub fn add_received(&self, bytes: usize, uncompressed_bytes: usize) {
+        self.received.add(bytes, uncompressed_bytes);
+    }
+
+    pub fn stats(&self) -> TrafficStats {
+        TrafficStats {
+            sent: self.sent.stats(),
+            received: self.received.stats(),
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use glam::Vec3;
+use renderer_protocol::entity::EntityKind;
+use renderer_server::world::spatial::SpatialIndex;
+use uuid::Uuid;
+
+const WORLD_SIZE: f32 = 1000.0;
+const QUERY_RADIUS: f32 = 32.0;
+
+/// Deterministic positions spread over the world, so runs are comparable.
+fn positions(count: usize) -> Vec<(Uuid, Vec3)> {
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    let mut next = || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed >> 40) as f32 / (1u64 << 24) as f32 * WORLD_SIZE
+    };
+    (0..count)
+        .map(|i| {
+            (
+                Uuid::from_u128(i as u128),
+                Vec3::new(next(), next(), next()),
+            )
+        })
+        .collect()
+}
+
+fn linear_radius(entities: &HashMap<Uuid, Vec3>, center: Vec3, radius: f32) -> Vec<Uuid> {
+    entities
+        .iter()
+        .filter(|(_, position)| position.distance_squared(center) <= radius * radius)
+        .map(|(id, _)| *id)
+        .collect()
+}
+
+fn query_radius(c: &mut Criterion) {
+    let mut group = c.benchmark_group("query_radius");
+    for count in [1000, 5000, 20000] {
+        let positions = positions(count);
+        let linear: HashMap<Uuid, Vec3> = positions.iter().copied().collect();
+        let mut index = SpatialIndex::default();
+        for (id, position) in &positions {
+            index.insert(EntityKind::OBJECT, *id, *position);
+        }
+        let centers: Vec<Vec3> = positions.iter().take(64).map(|(_, p)| *p).collect();
+
+        group.bench_with_input(BenchmarkId::new("linear", count), &count, |b, _| {
+            b.iter(|| {
+                for center in &centers {
+                    black_box(linear_radius(&linear, *center, QUERY_RADIUS));
+                }
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("grid", count), &count, |b, _| {
+            b.iter(|| {
+                for center in &centers {
+                    black_box(index.query_radius(*center, QUERY_RADIUS));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn update(c: &mut Criterion) {
+    let positions = positions(20000);
+    let mut index = SpatialIndex::default();
+    for (id, position) in &positions {
+        index.insert(EntityKind::OBJECT, *id, *position);
+    }
+    c.bench_function("update_20000", |b| {
+        let mut offset = Vec3::ZERO;
+        b.iter(|| {
+            offset += Vec3::splat(0.5);
+            for (id, position) in &positions {
+                index.update(*id, *position + offset);
+            }
+        })
+    });
+}
+
+criterion_group!(benches, query_radius, update);
+criterion_main!(benches);
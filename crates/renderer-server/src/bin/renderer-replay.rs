@@ -0,0 +1,37 @@
+use std::{env, fs::File, io::BufReader, process::ExitCode};
+
+use renderer_protocol::replay::ReplayReader;
+use renderer_server::replay::verify;
+
+fn main() -> ExitCode {
+    env_logger::init();
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("Usage: renderer-replay <replay file>");
+        return ExitCode::FAILURE;
+    };
+
+    let report = File::open(&path)
+        .map_err(Into::into)
+        .and_then(|file| ReplayReader::new(BufReader::new(file)))
+        .and_then(verify);
+    let report = match report {
+        Ok(report) => report,
+        Err(err) => {
+            eprintln!("Failed to replay {}: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match report.divergence {
+        None => {
+            println!("{} ticks replayed, no divergence", report.ticks);
+            ExitCode::SUCCESS
+        }
+        Some(divergence) => {
+            println!("Diverged at tick {}", divergence.tick);
+            println!("Recorded: {:#?}", divergence.expected);
+            println!("Replayed: {:#?}", divergence.actual);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::{any::Any, collections::VecDeque, fmt::Debug};
+
+use renderer_protocol::entity::EntityKind;
+use uuid::Uuid;
+
+use super::Entity;
+
+/// A message on its way to another entity, or to a later tick.
+#[derive(Debug)]
+pub struct Envelope {
+    pub kind: EntityKind,
+    pub target: Uuid,
+    /// Tick at which the message is delivered.
+    pub deliver_at: u64,
+    pub message: Box<dyn AnyMessage>,
+}
+
+pub trait AnyMessage: Any + Debug + Send + Sync {
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: Any + Debug + Send + Sync> AnyMessage for T {
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+/// Handed to an entity while it handles a message or updates, to send
+/// messages to itself and to other entities.
+pub struct EntityContext<'a, E: Entity + ?Sized> {
+    id: Uuid,
+    tick: u64,
+    pending_messages: &'a mut VecDeque<E::Message>,
+    outbox: &'a mut Vec<Envelope>,
+}
+
+impl<'a, E: Entity + ?Sized> EntityContext<'a, E> {
+    pub(crate) fn new(
+        id: Uuid,
+        tick: u64,
+        pending_messages: &'a mut VecDeque<E::Message>,
+        outbox: &'a mut Vec<Envelope>,
+    ) -> Self {
+        Self {
+            id,
+            tick,
+            pending_messages,
+            outbox,
+        }
+    }
+
+    /// Id of the entity being processed.
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+
+    /// Number of the current tick, starting from zero.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Handle a message later in this tick.
+    pub fn send_self(&mut self, message: E::Message) {
+        self.pending_messages.push_back(message);
+    }
+
+    /// Handle a message `delay` ticks later. Zero is the same as
+    /// [`send_self`](Self::send_self).
+    pub fn schedule_self(&mut self, delay: u64, message: E::Message) {
+        if delay == 0 {
+            self.send_self(message);
+        } else {
+            self.outbox.push(Envelope {
+                kind: E::KIND,
+                target: self.id,
+                deliver_at: self.tick + delay,
+                message: Box::new(message),
+            });
+        }
+    }
+
+    /// Send a message to another entity, handled in this tick.
+    pub fn send<T: Entity>(&mut self, target: Uuid, message: T::Message) {
+        self.schedule::<T>(target, 0, message);
+    }
+
+    /// Send a message to another entity, handled `delay` ticks later.
+    pub fn schedule<T: Entity>(&mut self, target: Uuid, delay: u64, message: T::Message) {
+        self.outbox.push(Envelope {
+            kind: T::KIND,
+            target,
+            deliver_at: self.tick + delay,
+            message: Box::new(message),
+        });
+    }
+}
@@ -0,0 +1,83 @@
+use glam::Vec3;
+use renderer_protocol::entity::{EntityKind, LightEntityOutput, LightEntityState, LightSource};
+use serde::{Deserialize, Serialize};
+
+use super::{context::EntityContext, BaseEntityData, Entity, Message, Output, State};
+
+#[derive(Debug, Clone)]
+pub struct LightEntity {
+    base: BaseEntityData,
+    source: LightSource,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum LightEntityMessage {
+    NewPosition(Vec3),
+    NewSource(LightSource),
+}
+
+impl Message for LightEntityMessage {}
+
+impl Output for LightEntityOutput {}
+
+impl State for LightEntityState {
+    fn id(&self) -> uuid::Uuid {
+        self.base.id
+    }
+
+    fn position(&self) -> Vec3 {
+        self.base.position
+    }
+}
+
+impl From<LightEntityState> for LightEntity {
+    fn from(state: LightEntityState) -> Self {
+        Self {
+            base: state.base,
+            source: state.source,
+        }
+    }
+}
+
+impl LightEntity {
+    pub fn source(&self) -> &LightSource {
+        &self.source
+    }
+}
+
+impl Entity for LightEntity {
+    const KIND: EntityKind = EntityKind::LIGHT;
+
+    type Message = LightEntityMessage;
+    type Output = LightEntityOutput;
+    type State = LightEntityState;
+
+    fn base_data(&self) -> &BaseEntityData {
+        &self.base
+    }
+
+    fn clone_state(&self) -> Self::State {
+        Self::State {
+            base: self.base.clone(),
+            source: self.source.clone(),
+        }
+    }
+
+    fn process_message(
+        &mut self,
+        message: Self::Message,
+        _context: &mut EntityContext<Self>,
+        mut on_change: impl FnMut(Self::Output),
+    ) {
+        match message {
+            LightEntityMessage::NewPosition(new_position) => {
+                self.base.position = new_position;
+                on_change(LightEntityOutput::NewPosition(new_position));
+            }
+            LightEntityMessage::NewSource(source) => {
+                self.source = source.clone();
+                on_change(LightEntityOutput::NewSource(source));
+            }
+        }
+    }
+}
@@ -1,14 +1,23 @@
-use std::{collections::VecDeque, fmt::Debug};
+use std::{fmt::Debug, time::Duration};
+
+use context::EntityContext;
+
+use crate::world::physics::Body;
 
 use glam::Vec3;
-use renderer_protocol::entity::BaseEntityData;
+use renderer_protocol::entity::{BaseEntityData, EntityKind};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+pub mod context;
+pub mod light;
 pub mod object;
 pub mod player;
 
-pub trait Message: Serialize + for<'a> Deserialize<'a> + Debug + Clone + Send + Sync {}
+pub trait Message:
+    Serialize + for<'a> Deserialize<'a> + Debug + Clone + Send + Sync + 'static
+{
+}
 
 pub trait Output: Serialize + for<'a> Deserialize<'a> + Debug + Clone + Send + Sync {}
 
@@ -21,7 +30,10 @@ impl Message for () {}
 
 impl Output for () {}
 
-pub trait Entity: Debug {
+pub trait Entity: Debug + Send + Sync + 'static {
+    /// Tag of this entity kind on the wire.
+    const KIND: EntityKind;
+
     type Message: Message;
     type Output: Output;
     type State: State;
@@ -39,9 +51,34 @@ pub trait Entity: Debug {
     fn process_message(
         &mut self,
         message: Self::Message,
-        pending_messages: &mut VecDeque<Self::Message>,
+        context: &mut EntityContext<Self>,
         on_change: impl FnMut(Self::Output),
     );
+
+    /// Called once per tick before messages are processed, with the time
+    /// since the previous tick.
+    fn update(
+        &mut self,
+        _dt: Duration,
+        _context: &mut EntityContext<Self>,
+        _on_change: impl FnMut(Self::Output),
+    ) {
+    }
+
+    /// Physical body, for entities taking part in physics.
+    fn body(&self) -> Option<&Body> {
+        None
+    }
+
+    /// Called after each physics step with the new position and body, for
+    /// entities returning a body.
+    fn apply_physics(
+        &mut self,
+        _position: Vec3,
+        _body: Body,
+        _on_change: impl FnMut(Self::Output),
+    ) {
+    }
 }
 
 impl State for BaseEntityData {
@@ -1,20 +1,40 @@
-use std::collections::VecDeque;
+use std::time::Duration;
 
 use glam::Vec3;
-use renderer_protocol::entity::{EntityResourceData, ObjectEntityOutput, ObjectEntityState};
+use renderer_protocol::{
+    entity::{
+        EntityKind, EntityResourceData, ObjectEntityOutput, ObjectEntityState, ObjectPermissions,
+    },
+    intent::Role,
+};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-use super::{BaseEntityData, Entity, Message, Output, State};
+use crate::{
+    script::{EntityScript, ScriptEffects},
+    world::{intent::check_position, physics::Body},
+};
+
+use super::{context::EntityContext, BaseEntityData, Entity, Message, Output, State};
 
 #[derive(Debug, Clone)]
 pub struct ObjectEntity {
     base: BaseEntityData,
     resource: EntityResourceData,
+    owner: Option<Uuid>,
+    permissions: ObjectPermissions,
+    body: Option<Body>,
+    script: Option<EntityScript>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ObjectEntityMessage {
     NewPosition(Vec3),
+    /// Text for the attached script, ignored without one.
+    Script {
+        sender: Uuid,
+        message: String,
+    },
 }
 
 impl Message for ObjectEntityMessage {}
@@ -36,11 +56,64 @@ impl From<ObjectEntityState> for ObjectEntity {
         Self {
             base: state.base,
             resource: state.resource,
+            owner: state.owner,
+            permissions: state.permissions,
+            body: None,
+            script: None,
+        }
+    }
+}
+
+impl ObjectEntity {
+    pub fn with_body(mut self, body: Body) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    pub fn with_script(mut self, script: EntityScript) -> Self {
+        self.script = Some(script);
+        self
+    }
+
+    pub fn script(&self) -> Option<&EntityScript> {
+        self.script.as_ref()
+    }
+
+    pub fn owner(&self) -> Option<Uuid> {
+        self.owner
+    }
+
+    /// Whether `player` may do what `permission` covers with this object.
+    pub fn permits(&self, player: Uuid, role: Role, permission: ObjectPermissions) -> bool {
+        role == Role::Admin || self.owner == Some(player) || self.permissions.contains(permission)
+    }
+
+    fn apply_script(
+        &mut self,
+        effects: ScriptEffects,
+        context: &mut EntityContext<Self>,
+        mut on_change: impl FnMut(ObjectEntityOutput),
+    ) {
+        if check_position(effects.position).is_err() {
+            if let Some(script) = &mut self.script {
+                script.fail(self.base.id, "position out of range");
+            }
+            return;
+        }
+        if effects.position != self.base.position {
+            self.base.position = effects.position;
+            on_change(ObjectEntityOutput::NewPosition(effects.position));
+        }
+        let sender = self.base.id;
+        for (target, message) in effects.messages {
+            context.send::<Self>(target, ObjectEntityMessage::Script { sender, message });
         }
     }
 }
 
 impl Entity for ObjectEntity {
+    const KIND: EntityKind = EntityKind::OBJECT;
+
     type Message = ObjectEntityMessage;
     type Output = ObjectEntityOutput;
     type State = ObjectEntityState;
@@ -53,13 +126,15 @@ impl Entity for ObjectEntity {
         Self::State {
             base: self.base.clone(),
             resource: self.resource.clone(),
+            owner: self.owner,
+            permissions: self.permissions,
         }
     }
 
     fn process_message(
         &mut self,
         message: Self::Message,
-        _pending_messages: &mut VecDeque<Self::Message>,
+        context: &mut EntityContext<Self>,
         mut on_change: impl FnMut(Self::Output),
     ) {
         match message {
@@ -67,6 +142,47 @@ impl Entity for ObjectEntity {
                 self.base.position = new_position;
                 on_change(ObjectEntityOutput::NewPosition(new_position));
             }
+            ObjectEntityMessage::Script { sender, message } => {
+                let (id, position) = (self.base.id, self.base.position);
+                let Some(script) = &mut self.script else {
+                    return;
+                };
+                if let Some(effects) = script.on_message(id, position, sender, message) {
+                    self.apply_script(effects, context, on_change);
+                }
+            }
+        }
+    }
+
+    fn update(
+        &mut self,
+        dt: Duration,
+        context: &mut EntityContext<Self>,
+        on_change: impl FnMut(Self::Output),
+    ) {
+        let (id, position) = (self.base.id, self.base.position);
+        let Some(script) = &mut self.script else {
+            return;
+        };
+        if let Some(effects) = script.on_tick(id, position, dt) {
+            self.apply_script(effects, context, on_change);
+        }
+    }
+
+    fn body(&self) -> Option<&Body> {
+        self.body.as_ref()
+    }
+
+    fn apply_physics(
+        &mut self,
+        position: Vec3,
+        body: Body,
+        mut on_change: impl FnMut(Self::Output),
+    ) {
+        self.body = Some(body);
+        if position != self.base.position {
+            self.base.position = position;
+            on_change(ObjectEntityOutput::NewPosition(position));
         }
     }
 }
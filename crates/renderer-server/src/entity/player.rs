@@ -1,20 +1,34 @@
 use std::collections::VecDeque;
 
 use glam::Vec3;
-use renderer_protocol::{entity::PlayerEntityOutput, input::PlayerEntityInput};
+use log::warn;
+use renderer_protocol::{
+    entity::{EntityKind, PlayerEntityOutput},
+    input::{PlayerEntityInput, PlayerMove},
+    movement::{apply_move, PLAYER_HALF_HEIGHT, PLAYER_RADIUS},
+};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use super::{BaseEntityData, Entity, Message, Output};
+use crate::world::physics::{Body, Motion, Shape};
+
+use super::{context::EntityContext, BaseEntityData, Entity, Message, Output};
+
+const PLAYER_MASS: f32 = 70.0;
 
 #[derive(Debug, Clone)]
 pub struct PlayerEntity {
     base_data: BaseEntityData,
+    body: Body,
+    /// Latest move applied this tick, acknowledged once physics ran.
+    last_move: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum PlayerEntityMessage {
+    /// Teleport, for the server side only.
     NewPosition(Vec3),
+    Move(PlayerMove),
 }
 
 impl Message for PlayerEntityMessage {}
@@ -22,6 +36,8 @@ impl Message for PlayerEntityMessage {}
 impl Output for PlayerEntityOutput {}
 
 impl Entity for PlayerEntity {
+    const KIND: EntityKind = EntityKind::PLAYER;
+
     type Message = PlayerEntityMessage;
     type Output = PlayerEntityOutput;
     type State = BaseEntityData;
@@ -37,7 +53,7 @@ impl Entity for PlayerEntity {
     fn process_message(
         &mut self,
         message: Self::Message,
-        _pending_messages: &mut VecDeque<Self::Message>,
+        _context: &mut EntityContext<Self>,
         mut on_change: impl FnMut(Self::Output),
     ) {
         match message {
@@ -45,6 +61,38 @@ impl Entity for PlayerEntity {
                 self.base_data.position = new_position;
                 on_change(PlayerEntityOutput::NewPosition(new_position));
             }
+            PlayerEntityMessage::Move(input) => {
+                if let Motion::Dynamic { velocity, .. } = &mut self.body.motion {
+                    *velocity = apply_move(*velocity, &input, self.body.grounded);
+                    // Only one jump per landing
+                    self.body.grounded &= !input.jump;
+                }
+                self.last_move = Some(input.sequence);
+            }
+        }
+    }
+
+    fn body(&self) -> Option<&Body> {
+        Some(&self.body)
+    }
+
+    fn apply_physics(
+        &mut self,
+        position: Vec3,
+        body: Body,
+        mut on_change: impl FnMut(Self::Output),
+    ) {
+        self.body = body;
+        if position != self.base_data.position {
+            self.base_data.position = position;
+            on_change(PlayerEntityOutput::NewPosition(position));
+        }
+        if let Some(sequence) = self.last_move.take() {
+            on_change(PlayerEntityOutput::Acknowledged {
+                sequence,
+                position,
+                velocity: body.velocity(),
+            });
         }
     }
 }
@@ -53,6 +101,17 @@ impl PlayerEntity {
     pub fn new(id: Uuid, position: Vec3) -> Self {
         Self {
             base_data: BaseEntityData { id, position },
+            body: Body::new(
+                Shape::Capsule {
+                    radius: PLAYER_RADIUS,
+                    half_height: PLAYER_HALF_HEIGHT,
+                },
+                Motion::Dynamic {
+                    velocity: Vec3::ZERO,
+                    mass: PLAYER_MASS,
+                },
+            ),
+            last_move: None,
         }
     }
 
@@ -62,8 +121,11 @@ impl PlayerEntity {
         pending_messages: &mut VecDeque<PlayerEntityMessage>,
     ) {
         match input {
-            PlayerEntityInput::NewPosition(new_position) => {
-                pending_messages.push_back(PlayerEntityMessage::NewPosition(new_position));
+            PlayerEntityInput::NewPosition(_) => {
+                warn!("Ignored position sent by player {}", self.base_data.id);
+            }
+            PlayerEntityInput::Move(input) => {
+                pending_messages.push_back(PlayerEntityMessage::Move(input));
             }
         }
     }
@@ -0,0 +1,5 @@
+pub mod entity;
+pub mod replay;
+pub mod script;
+pub mod server;
+pub mod world;
@@ -1,29 +1,229 @@
 use std::{
+    env,
+    fs::{self, File},
     net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
     sync::Arc,
+    time::Duration,
 };
 
-use server::{websocket::WebSocketServer, Server};
-use tokio_serde::formats::Json;
+use glam::Vec3;
+use log::warn;
+use renderer_protocol::{
+    codec::Compact,
+    entity::{BaseEntityData, EntityResourceData, ObjectEntityState, ObjectPermissions},
+};
+use renderer_server::{
+    entity::object::ObjectEntity,
+    script::EntityScript,
+    server::{
+        interest::InterestConfig,
+        metrics::MetricsServer,
+        scheduler::{CatchUp, TickConfig},
+        websocket::WebSocketServer,
+        Server, ServerConfig,
+    },
+};
+use tokio_serde::formats::{Bincode, Json};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Default)]
+enum SerializeType {
+    #[default]
+    Json,
+    Bincode,
+    Compact,
+}
+
+#[derive(Debug, Default)]
+struct Args {
+    serialize_type: SerializeType,
+    /// Replay file to record the session to.
+    record: Option<PathBuf>,
+    /// Address to serve metrics on.
+    metrics: Option<SocketAddr>,
+    /// Seconds between metrics summaries in the log.
+    metrics_log: Option<f64>,
+    tick: TickConfig,
+    interest: InterestConfig,
+    /// Scripts to spawn an object entity for, one object each.
+    scripts: Vec<PathBuf>,
+    /// Reload changed script files while running.
+    hot_reload: bool,
+    admin_token: Option<String>,
+    /// Links of the external models clients may spawn.
+    spawn_links: Vec<String>,
+}
+
+/// Environment variable holding the admin token, so that it doesn't show
+/// in the process list.
+const ADMIN_TOKEN_VAR: &str = "RENDERER_ADMIN_TOKEN";
 
-pub mod entity;
-pub mod server;
-pub mod world;
+impl Args {
+    fn parse() -> Self {
+        let mut args = env::args().skip(1);
+        let mut parsed = Self::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--codec" => {
+                    parsed.serialize_type = match args.next().as_deref() {
+                        Some("json") => SerializeType::Json,
+                        Some("bincode") => SerializeType::Bincode,
+                        Some("compact") => SerializeType::Compact,
+                        other => panic!("Unknown codec: {:?}", other),
+                    }
+                }
+                "--record" => {
+                    let path = args.next().expect("Missing replay file path");
+                    parsed.record = Some(PathBuf::from(path));
+                }
+                "--metrics" => {
+                    let address = args.next().expect("Missing metrics address");
+                    parsed.metrics = Some(address.parse().expect("Bad metrics address"));
+                }
+                "--metrics-log" => {
+                    let seconds = args.next().expect("Missing metrics log interval");
+                    parsed.metrics_log = Some(seconds.parse().expect("Bad metrics log interval"));
+                }
+                "--tick-rate" => {
+                    let rate = args.next().expect("Missing tick rate");
+                    parsed.tick.rate = rate.parse().expect("Bad tick rate");
+                    assert!(parsed.tick.rate > 0, "Tick rate must be positive");
+                }
+                "--catch-up" => {
+                    let policy = args.next().expect("Missing catch up policy");
+                    parsed.tick.catch_up = match policy.split_once(':') {
+                        None if policy == "skip" => CatchUp::Skip,
+                        Some(("burst", max_ticks)) => CatchUp::Burst {
+                            max_ticks: max_ticks.parse().expect("Bad burst tick count"),
+                        },
+                        _ => panic!("Unknown catch up policy: {:?}", policy),
+                    }
+                }
+                "--interest-radius" => {
+                    let radius = args.next().expect("Missing interest radius");
+                    parsed.interest.radius = match radius.as_str() {
+                        "none" => None,
+                        radius => Some(radius.parse().expect("Bad interest radius")),
+                    };
+                }
+                "--script" => {
+                    let path = args.next().expect("Missing script path");
+                    parsed.scripts.push(PathBuf::from(path));
+                }
+                "--hot-reload" => parsed.hot_reload = true,
+                "--admin-token" => {
+                    parsed.admin_token = Some(args.next().expect("Missing admin token"));
+                }
+                "--admin-token-file" => {
+                    let path = args.next().expect("Missing admin token file path");
+                    let token = fs::read_to_string(path).expect("Failed to read admin token file");
+                    parsed.admin_token = Some(token.trim_end().to_owned());
+                }
+                "--spawn-link" => {
+                    parsed
+                        .spawn_links
+                        .push(args.next().expect("Missing spawn link"));
+                }
+                _ => panic!("Unknown argument: {}", arg),
+            }
+        }
+        if parsed.admin_token.is_none() {
+            parsed.admin_token = env::var(ADMIN_TOKEN_VAR).ok();
+        }
+        parsed
+    }
+}
 
 #[tokio::main]
 async fn main() {
     env_logger::init();
-    let server = Arc::new(Server::default());
+    let Args {
+        serialize_type,
+        record,
+        metrics,
+        metrics_log,
+        tick,
+        interest,
+        scripts,
+        hot_reload,
+        admin_token,
+        spawn_links,
+    } = Args::parse();
+    let mut config = ServerConfig {
+        tick,
+        interest,
+        admin_token,
+        ..Default::default()
+    };
+    config.metrics.log_interval = metrics_log.map(Duration::from_secs_f64);
+    let server = Arc::new(Server::new(config));
+
+    {
+        let mut state = server.state.write().await;
+        let world = &mut state.world;
+        world.scripts.hot_reload = hot_reload;
+        world.intents.spawn_links = spawn_links;
+        for path in scripts {
+            let script = world.scripts.load(&path).expect("Failed to load script");
+            let object = ObjectEntity::from(ObjectEntityState {
+                base: BaseEntityData {
+                    id: Uuid::new_v4(),
+                    position: Vec3::ZERO,
+                },
+                resource: EntityResourceData::Box,
+                owner: None,
+                permissions: ObjectPermissions::NONE,
+            })
+            .with_script(EntityScript::new(script));
+            world
+                .entities
+                .insert(object, &mut world.tick_output)
+                .expect("Failed to spawn scripted object");
+        }
+    }
+
+    if let Some(path) = record {
+        let file = File::create(&path).expect("Failed to create replay file");
+        server
+            .start_recording(file)
+            .await
+            .expect("Failed to start recording");
+    }
 
     let serve = {
         let server = server.clone();
         tokio::spawn(async move {
-            let websocket_werver =
-                WebSocketServer::new(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 12345));
-            websocket_werver.serve(server, Json::default).await
+            let listen_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 12345);
+            match serialize_type {
+                SerializeType::Json => {
+                    WebSocketServer::new(listen_addr)
+                        .serve(server, Json::default)
+                        .await
+                }
+                SerializeType::Bincode => {
+                    WebSocketServer::new(listen_addr)
+                        .serve(server, Bincode::default)
+                        .await
+                }
+                SerializeType::Compact => {
+                    WebSocketServer::new(listen_addr)
+                        .serve(server, Compact::default)
+                        .await
+                }
+            }
         })
     };
 
+    if let Some(address) = metrics {
+        let server = server.clone();
+        tokio::spawn(async move {
+            if let Err(err) = MetricsServer::new(address).serve(server).await {
+                warn!("Metrics server failed: {}", err);
+            }
+        });
+    }
+
     let run = {
         let server = server.clone();
         tokio::spawn(async move { server.run().await })
@@ -0,0 +1,395 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    mem,
+    sync::Arc,
+    time::Duration,
+};
+
+use log::warn;
+use renderer_protocol::{
+    entity::{BaseEntityData, EntityKind, LightEntityState, ObjectEntityState},
+    replay::{
+        outputs_match, ReplayError, ReplayEvent, ReplayHeader, ReplayReader, ReplayScript,
+        ReplayTick, ReplayWriter,
+    },
+    tick::TickOutput,
+    version::VersionData,
+};
+use uuid::Uuid;
+
+use crate::{
+    entity::{light::LightEntity, object::ObjectEntity, player::PlayerEntity, Entity},
+    script::{EntityScript, Script},
+    world::World,
+};
+
+/// Sources of the scripts the objects of `world` run.
+fn scripts_of(world: &World) -> Vec<ReplayScript> {
+    let mut scripts: Vec<ReplayScript> = Vec::new();
+    let Some(objects) = world.entities.items::<ObjectEntity>() else {
+        return scripts;
+    };
+    for object in objects.iter() {
+        let Some(script) = object.script() else {
+            continue;
+        };
+        let path = script.script().path().to_string_lossy().into_owned();
+        match scripts.iter_mut().find(|recorded| recorded.path == path) {
+            Some(recorded) => recorded.entities.push(object.id()),
+            None => scripts.push(ReplayScript {
+                path,
+                source: script.script().source(),
+                entities: vec![object.id()],
+            }),
+        }
+    }
+    scripts
+}
+
+/// Writes the events applied to a world and the outputs of its ticks.
+#[derive(Debug)]
+pub struct Recorder<W: Write> {
+    writer: ReplayWriter<W>,
+    events: Vec<ReplayEvent>,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Start recording a world that has not ticked yet.
+    pub fn new(writer: W, world: &World) -> Result<Self, ReplayError> {
+        let header = ReplayHeader {
+            version: VersionData::current(),
+            entity_states: world.entities.state(),
+            environment: world.environment().clone(),
+            scripts: scripts_of(world),
+        };
+        Ok(Self {
+            writer: ReplayWriter::new(writer, &header)?,
+            events: Vec::new(),
+        })
+    }
+
+    pub fn record(&mut self, event: ReplayEvent) {
+        self.events.push(event);
+    }
+
+    /// Write a tick with the events recorded since the previous one.
+    pub fn write_tick(
+        &mut self,
+        tick: u64,
+        dt: Duration,
+        output: &TickOutput,
+    ) -> Result<(), ReplayError> {
+        self.writer.write_tick(&ReplayTick {
+            tick,
+            dt,
+            events: std::mem::take(&mut self.events),
+            output: output.clone(),
+        })?;
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+}
+
+impl Recorder<Vec<u8>> {
+    /// Take what was written so far, to be saved without holding the world.
+    pub fn take_written(&mut self) -> Vec<u8> {
+        mem::take(self.writer.get_mut())
+    }
+}
+
+/// Event recording that a script now runs its latest version.
+pub fn script_reloaded(script: &Script) -> ReplayEvent {
+    ReplayEvent::ScriptReloaded {
+        path: script.path().to_string_lossy().into_owned(),
+        source: script.source(),
+    }
+}
+
+/// Apply a recorded event the way the server did.
+pub fn apply_event(world: &mut World, event: ReplayEvent) {
+    match event {
+        ReplayEvent::PlayerJoined { id, position } => {
+            if let Err(err) = world.insert_player(PlayerEntity::new(id, position)) {
+                warn!("Replayed player {} failed to join: {}", id, err);
+            }
+        }
+        ReplayEvent::PlayerLeft(id) => world.entities.queue_remove::<PlayerEntity>(id),
+        ReplayEvent::Input(id, input) => world.entities.process_player_inputs(id, input),
+        ReplayEvent::Chat(message) => world.push_chat_message(message),
+        ReplayEvent::ObjectIntent {
+            player,
+            role,
+            intent,
+        } => world.push_intent(player, role, intent),
+        ReplayEvent::ScriptReloaded { path, source } => {
+            if let Err(err) = world.scripts.load_source(&path, source) {
+                warn!("Replayed script failed to reload: {}", err);
+            }
+        }
+    }
+}
+
+/// The world as it was when the recording started.
+fn seed_world(header: &ReplayHeader) -> Result<World, ReplayError> {
+    let mut world = World::default().with_environment(header.environment.clone());
+    let mut scripts: HashMap<Uuid, Arc<Script>> = HashMap::new();
+    for script in &header.scripts {
+        let source = script.source.clone();
+        match world.scripts.load_source(&script.path, source) {
+            Ok(loaded) => {
+                for id in &script.entities {
+                    scripts.insert(*id, loaded.clone());
+                }
+            }
+            Err(err) => warn!("Replayed script failed to load: {}", err),
+        }
+    }
+
+    // Entities are new in the first tick, as recordings start before it
+    let states = &header.entity_states;
+    let mut output = mem::take(&mut world.tick_output);
+    for state in states.get(&EntityKind::OBJECT) {
+        let state: ObjectEntityState = state.decode()?;
+        let script = scripts.get(&state.base.id).cloned();
+        let mut object = ObjectEntity::from(state);
+        if let Some(script) = script {
+            object = object.with_script(EntityScript::new(script));
+        }
+        insert(&mut world, object, &mut output);
+    }
+    for state in states.get(&EntityKind::LIGHT) {
+        let state: LightEntityState = state.decode()?;
+        insert(&mut world, LightEntity::from(state), &mut output);
+    }
+    for state in states.get(&EntityKind::PLAYER) {
+        let BaseEntityData { id, position } = state.decode()?;
+        insert(&mut world, PlayerEntity::new(id, position), &mut output);
+    }
+    world.tick_output = output;
+    Ok(world)
+}
+
+/// Insert an entity of the header, skipping the ones every world starts
+/// with.
+fn insert<E: Entity>(world: &mut World, entity: E, output: &mut TickOutput) {
+    let id = entity.id();
+    if world.entities.contains::<E>(id) {
+        return;
+    }
+    if let Err(err) = world.entities.insert(entity, output) {
+        warn!("Replayed entity {} failed to insert: {}", id, err);
+    }
+}
+
+#[derive(Debug)]
+pub struct Divergence {
+    pub tick: u64,
+    pub expected: TickOutput,
+    pub actual: TickOutput,
+}
+
+#[derive(Debug)]
+pub struct VerifyReport {
+    pub ticks: u64,
+    /// The first tick whose output differs from the recording.
+    pub divergence: Option<Divergence>,
+}
+
+/// Re-run a recording on a fresh world, stopping at the first tick whose
+/// output differs.
+pub fn verify<R: Read>(reader: ReplayReader<R>) -> Result<VerifyReport, ReplayError> {
+    let mut world = seed_world(reader.header())?;
+    let mut ticks = 0;
+    for tick in reader {
+        let tick = tick?;
+        for event in tick.events {
+            apply_event(&mut world, event);
+        }
+        let output = world.tick(tick.dt);
+        ticks += 1;
+        if !outputs_match(&output, &tick.output) {
+            return Ok(VerifyReport {
+                ticks,
+                divergence: Some(Divergence {
+                    tick: tick.tick,
+                    expected: tick.output,
+                    actual: output,
+                }),
+            });
+        }
+    }
+    Ok(VerifyReport {
+        ticks,
+        divergence: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs, time::Duration};
+
+    use glam::Vec3;
+    use renderer_protocol::{
+        chat::ChatMessage,
+        entity::{
+            BaseEntityData, EntityData, EntityKind, EntityResourceData, LightEntityState,
+            LightSource, ObjectEntityState, ObjectPermissions,
+        },
+        environment::Environment,
+        input::{PlayerEntityInput, PlayerMove},
+        replay::{ReplayEvent, ReplayReader, ReplayWriter},
+    };
+    use uuid::Uuid;
+
+    use crate::{
+        entity::{light::LightEntity, object::ObjectEntity},
+        script::EntityScript,
+        world::World,
+    };
+
+    use super::{apply_event, verify, Recorder};
+
+    const DT: Duration = Duration::from_millis(50);
+
+    fn walk(sequence: u32, direction: Vec3) -> PlayerMove {
+        PlayerMove {
+            sequence,
+            direction,
+            jump: false,
+        }
+    }
+
+    fn record(mut world: World, events: &[Vec<ReplayEvent>]) -> Vec<u8> {
+        let mut recorder = Recorder::new(Vec::new(), &world).unwrap();
+        for events in events {
+            for event in events {
+                recorder.record(event.clone());
+                apply_event(&mut world, event.clone());
+            }
+            let tick = world.current_tick();
+            let output = world.tick(DT);
+            recorder.write_tick(tick, DT, &output).unwrap();
+        }
+        recorder.into_inner()
+    }
+
+    #[test]
+    fn test_verify() {
+        let (a, b) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let events = vec![
+            vec![
+                ReplayEvent::PlayerJoined {
+                    id: a,
+                    position: Vec3::ZERO,
+                },
+                ReplayEvent::PlayerJoined {
+                    id: b,
+                    position: Vec3::ZERO,
+                },
+            ],
+            vec![
+                ReplayEvent::Input(a, PlayerEntityInput::Move(walk(0, Vec3::X))),
+                ReplayEvent::Input(b, PlayerEntityInput::Move(walk(0, Vec3::NEG_Z))),
+            ],
+            vec![ReplayEvent::Chat(ChatMessage {
+                sender: a,
+                timestamp: 0,
+                target: None,
+                text: String::from("Hello"),
+            })],
+            vec![ReplayEvent::PlayerLeft(b)],
+            vec![],
+        ];
+        let data = record(World::default(), &events);
+        let report = verify(ReplayReader::new(data.as_slice()).unwrap()).unwrap();
+        assert_eq!(report.ticks, 5);
+        assert!(report.divergence.is_none());
+
+        // Tamper with the output of the second tick
+        let reader = ReplayReader::new(data.as_slice()).unwrap();
+        let header = reader.header().clone();
+        let mut writer = ReplayWriter::new(Vec::new(), &header).unwrap();
+        for tick in reader {
+            let mut tick = tick.unwrap();
+            if tick.tick == 1 {
+                for (_, outputs) in tick.output.entity_outputs.0.iter_mut() {
+                    outputs[0].1 = EntityData(vec![0]);
+                }
+            }
+            writer.write_tick(&tick).unwrap();
+        }
+        let data = writer.into_inner();
+        let report = verify(ReplayReader::new(data.as_slice()).unwrap()).unwrap();
+        assert_eq!(report.ticks, 2);
+        assert_eq!(report.divergence.unwrap().tick, 1);
+    }
+
+    #[test]
+    fn test_verify_seeded() {
+        let path = env::temp_dir().join(format!("rise-{}.rhai", Uuid::new_v4()));
+        fs::write(
+            &path,
+            "fn on_tick(dt) { this.position += vec3(0.0, dt, 0.0); }",
+        )
+        .unwrap();
+        let environment = Environment {
+            background_color: Vec3::new(0.1, 0.2, 0.3),
+            ..Default::default()
+        };
+        let mut world = World::default().with_environment(environment.clone());
+        let script = world.scripts.load(&path).unwrap();
+        let object = ObjectEntity::from(ObjectEntityState {
+            base: BaseEntityData {
+                id: Uuid::from_u128(1),
+                position: Vec3::new(0.0, 5.0, 0.0),
+            },
+            resource: EntityResourceData::Box,
+            owner: None,
+            permissions: ObjectPermissions::NONE,
+        })
+        .with_script(EntityScript::new(script));
+        world
+            .entities
+            .insert(object, &mut world.tick_output)
+            .unwrap();
+        let light = LightEntity::from(LightEntityState {
+            base: BaseEntityData {
+                id: Uuid::from_u128(2),
+                position: Vec3::Y,
+            },
+            source: LightSource::Parallel {
+                direction: Vec3::NEG_Y,
+                color: Vec3::ONE,
+                strength: 1.0,
+            },
+        });
+        world
+            .entities
+            .insert(light, &mut world.tick_output)
+            .unwrap();
+
+        let reloaded = ReplayEvent::ScriptReloaded {
+            path: path.to_string_lossy().into_owned(),
+            source: String::from("fn on_tick(dt) { this.position += vec3(dt, 0.0, 0.0); }"),
+        };
+        let data = record(world, &[vec![], vec![], vec![reloaded], vec![]]);
+        // Scripts run from the sources in the recording
+        fs::remove_file(&path).unwrap();
+
+        let reader = ReplayReader::new(data.as_slice()).unwrap();
+        assert_eq!(reader.header().environment, environment);
+        assert_eq!(reader.header().scripts[0].entities, [Uuid::from_u128(1)]);
+        let ticks = reader.map(Result::unwrap).collect::<Vec<_>>();
+        let new_objects = ticks[0].output.new_entity_states.get(&EntityKind::OBJECT);
+        assert_eq!(new_objects.len(), 1);
+        assert!(!ticks[3].output.entity_outputs.is_empty());
+
+        let report = verify(ReplayReader::new(data.as_slice()).unwrap()).unwrap();
+        assert_eq!(report.ticks, 4);
+        assert!(report.divergence.is_none(), "{:?}", report.divergence);
+    }
+}
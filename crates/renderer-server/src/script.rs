@@ -0,0 +1,562 @@
+//! Object behaviour written in Rhai.
+//!
+//! A script defines optional callbacks, called with `this` bound to the
+//! entity:
+//!
+//! ```rhai
+//! fn on_tick(dt) {
+//!     this.position += vec3(0.0, dt, 0.0);
+//! }
+//!
+//! fn on_message(sender, message) {
+//!     this.state.received = message;
+//!     this.send(sender, "pong");
+//! }
+//! ```
+//!
+//! Scripts only see the entity they are attached to: its id, position and
+//! a `state` map kept between calls. They can send text messages to other
+//! object entities, but have no access to files, modules or `eval`, and
+//! run with bounded operations and memory.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display, Formatter},
+    fs, io, mem,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use glam::Vec3;
+use log::{debug, info, warn};
+use rhai::{
+    module_resolvers::DummyModuleResolver, CallFnOptions, Dynamic, Engine, EvalAltResult, FuncArgs,
+    Map, ParseError, Scope, AST, FLOAT,
+};
+use uuid::Uuid;
+
+/// Operations a single callback may run before it is stopped.
+const MAX_OPERATIONS: u64 = 100_000;
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Io(PathBuf, io::Error),
+    Compile(PathBuf, ParseError),
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Io(path, err) => write!(f, "Failed to read {}: {}", path.display(), err),
+            ScriptError::Compile(path, err) => {
+                write!(f, "Failed to compile {}: {}", path.display(), err)
+            }
+        }
+    }
+}
+
+impl Error for ScriptError {}
+
+/// What a script sees as `this`.
+#[derive(Debug, Clone)]
+struct ScriptEntity {
+    id: Uuid,
+    position: Vec3,
+    state: Map,
+    outbox: Vec<(Uuid, String)>,
+}
+
+fn sandboxed_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_module_resolver(DummyModuleResolver::new())
+        .disable_symbol("eval")
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(32)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(4096)
+        .set_max_array_size(1024)
+        .set_max_map_size(256)
+        .on_print(|text| info!("Script: {}", text))
+        .on_debug(|text, _source, position| debug!("Script at {}: {}", position, text));
+
+    engine
+        .register_type_with_name::<Vec3>("Vec3")
+        .register_fn("vec3", |x: FLOAT, y: FLOAT, z: FLOAT| {
+            Vec3::new(x as f32, y as f32, z as f32)
+        })
+        .register_get_set(
+            "x",
+            |v: &mut Vec3| v.x as FLOAT,
+            |v: &mut Vec3, x: FLOAT| v.x = x as f32,
+        )
+        .register_get_set(
+            "y",
+            |v: &mut Vec3| v.y as FLOAT,
+            |v: &mut Vec3, y: FLOAT| v.y = y as f32,
+        )
+        .register_get_set(
+            "z",
+            |v: &mut Vec3| v.z as FLOAT,
+            |v: &mut Vec3, z: FLOAT| v.z = z as f32,
+        )
+        .register_fn("+", |a: Vec3, b: Vec3| a + b)
+        .register_fn("-", |a: Vec3, b: Vec3| a - b)
+        .register_fn("*", |v: Vec3, s: FLOAT| v * s as f32)
+        .register_fn("*", |s: FLOAT, v: Vec3| v * s as f32)
+        .register_fn("length", |v: &mut Vec3| v.length() as FLOAT)
+        .register_fn("to_string", |v: &mut Vec3| v.to_string())
+        .register_fn("to_debug", |v: &mut Vec3| format!("{:?}", v));
+
+    engine
+        .register_type_with_name::<ScriptEntity>("Entity")
+        .register_get("id", |entity: &mut ScriptEntity| entity.id.to_string())
+        .register_get_set(
+            "position",
+            |entity: &mut ScriptEntity| entity.position,
+            |entity: &mut ScriptEntity, position: Vec3| entity.position = position,
+        )
+        .register_get_set(
+            "state",
+            |entity: &mut ScriptEntity| entity.state.clone(),
+            |entity: &mut ScriptEntity, state: Map| entity.state = state,
+        )
+        .register_fn(
+            "send",
+            |entity: &mut ScriptEntity,
+             target: &str,
+             message: &str|
+             -> Result<(), Box<EvalAltResult>> {
+                let target = Uuid::parse_str(target)
+                    .map_err(|err| format!("Bad entity id {:?}: {}", target, err))?;
+                entity.outbox.push((target, message.to_owned()));
+                Ok(())
+            },
+        );
+    engine
+}
+
+#[derive(Debug)]
+struct Compiled {
+    ast: Arc<AST>,
+    source: String,
+    modified: Option<SystemTime>,
+    /// Bumped on every reload.
+    version: u64,
+    has_tick: bool,
+    has_message: bool,
+}
+
+impl Compiled {
+    fn new(ast: AST, source: String, modified: Option<SystemTime>, version: u64) -> Self {
+        let has_function = |name| ast.iter_functions().any(|function| function.name == name);
+        Self {
+            has_tick: has_function("on_tick"),
+            has_message: has_function("on_message"),
+            ast: Arc::new(ast),
+            source,
+            modified,
+            version,
+        }
+    }
+}
+
+/// A compiled script file, shared by the entities running it.
+#[derive(Debug)]
+pub struct Script {
+    path: PathBuf,
+    engine: Arc<Engine>,
+    compiled: RwLock<Compiled>,
+}
+
+impl Script {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn version(&self) -> u64 {
+        self.compiled.read().unwrap().version
+    }
+
+    /// Source of the running version.
+    pub fn source(&self) -> String {
+        self.compiled.read().unwrap().source.clone()
+    }
+
+    fn compile(engine: &Engine, path: &Path, source: &str) -> Result<AST, ScriptError> {
+        engine
+            .compile(source)
+            .map_err(|err| ScriptError::Compile(path.to_owned(), err))
+    }
+
+    fn read(
+        engine: &Engine,
+        path: &Path,
+    ) -> Result<(AST, String, Option<SystemTime>), ScriptError> {
+        let error = |err| ScriptError::Io(path.to_owned(), err);
+        let modified = fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        let source = fs::read_to_string(path).map_err(error)?;
+        let ast = Self::compile(engine, path, &source)?;
+        Ok((ast, source, modified))
+    }
+
+    /// Recompile if the file changed since it was last read. A script
+    /// that fails to compile keeps running its previous version.
+    fn reload(&self) -> Result<bool, ScriptError> {
+        let modified = fs::metadata(&self.path)
+            .and_then(|meta| meta.modified())
+            .map_err(|err| ScriptError::Io(self.path.clone(), err))?;
+        if self.compiled.read().unwrap().modified == Some(modified) {
+            return Ok(false);
+        }
+        let result = Self::read(&self.engine, &self.path);
+        let mut compiled = self.compiled.write().unwrap();
+        // Don't retry a broken file until it changes again
+        compiled.modified = Some(modified);
+        let (ast, source, modified) = result?;
+        *compiled = Compiled::new(ast, source, modified, compiled.version + 1);
+        Ok(true)
+    }
+
+    /// Run `source` from now on, as if the file changed to it.
+    fn replace(&self, source: String) -> Result<(), ScriptError> {
+        let ast = Self::compile(&self.engine, &self.path, &source)?;
+        let mut compiled = self.compiled.write().unwrap();
+        *compiled = Compiled::new(ast, source, compiled.modified, compiled.version + 1);
+        Ok(())
+    }
+}
+
+/// Loaded scripts by path, with one sandboxed engine for all of them.
+#[derive(Debug)]
+pub struct Scripts {
+    engine: Arc<Engine>,
+    loaded: HashMap<PathBuf, Arc<Script>>,
+    /// Check script files for changes every tick.
+    pub hot_reload: bool,
+}
+
+impl Default for Scripts {
+    fn default() -> Self {
+        Self {
+            engine: Arc::new(sandboxed_engine()),
+            loaded: HashMap::new(),
+            hot_reload: false,
+        }
+    }
+}
+
+impl Scripts {
+    /// Load and compile a script file, or get it if it is already loaded.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<Arc<Script>, ScriptError> {
+        let path = path.as_ref();
+        if let Some(script) = self.loaded.get(path) {
+            return Ok(script.clone());
+        }
+        let (ast, source, modified) = Script::read(&self.engine, path)?;
+        let script = Arc::new(Script {
+            path: path.to_owned(),
+            engine: self.engine.clone(),
+            compiled: RwLock::new(Compiled::new(ast, source, modified, 0)),
+        });
+        self.loaded.insert(path.to_owned(), script.clone());
+        Ok(script)
+    }
+
+    /// Load a script from `source` instead of its file, as replays do. A
+    /// script already loaded from `path` switches to the new source.
+    pub fn load_source(
+        &mut self,
+        path: impl AsRef<Path>,
+        source: String,
+    ) -> Result<Arc<Script>, ScriptError> {
+        let path = path.as_ref();
+        if let Some(script) = self.loaded.get(path) {
+            script.replace(source)?;
+            return Ok(script.clone());
+        }
+        let ast = Script::compile(&self.engine, path, &source)?;
+        let script = Arc::new(Script {
+            path: path.to_owned(),
+            engine: self.engine.clone(),
+            compiled: RwLock::new(Compiled::new(ast, source, None, 0)),
+        });
+        self.loaded.insert(path.to_owned(), script.clone());
+        Ok(script)
+    }
+
+    /// Recompile the scripts whose files changed, returning them. Entities
+    /// pick up the new version on their next callback.
+    pub fn reload_changed(&self) -> Vec<Arc<Script>> {
+        let mut reloaded = Vec::new();
+        for script in self.loaded.values() {
+            match script.reload() {
+                Ok(true) => {
+                    info!("Reloaded script {}", script.path.display());
+                    reloaded.push(script.clone());
+                }
+                Ok(false) => (),
+                Err(err) => warn!("{}", err),
+            }
+        }
+        reloaded
+    }
+}
+
+/// What a callback changed, to be applied by the entity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptEffects {
+    pub position: Vec3,
+    /// Messages to other object entities.
+    pub messages: Vec<(Uuid, String)>,
+}
+
+/// A script attached to one entity, with the state it keeps between calls.
+#[derive(Debug, Clone)]
+pub struct EntityScript {
+    script: Arc<Script>,
+    state: Map,
+    /// Version of the script that failed on this entity. Its callbacks are
+    /// skipped until the script is reloaded.
+    failed: Option<u64>,
+}
+
+impl EntityScript {
+    pub fn new(script: Arc<Script>) -> Self {
+        Self {
+            script,
+            state: Map::new(),
+            failed: None,
+        }
+    }
+
+    pub fn script(&self) -> &Arc<Script> {
+        &self.script
+    }
+
+    pub fn has_failed(&self) -> bool {
+        self.failed == Some(self.script.version())
+    }
+
+    /// Skip the callbacks of this version, for effects the entity rejected.
+    pub fn fail(&mut self, id: Uuid, reason: &str) {
+        warn!(
+            "Script {} failed on entity {}: {}",
+            self.script.path.display(),
+            id,
+            reason
+        );
+        self.failed = Some(self.script.version());
+    }
+
+    pub fn on_tick(&mut self, id: Uuid, position: Vec3, dt: Duration) -> Option<ScriptEffects> {
+        let args = (dt.as_secs_f64() as FLOAT,);
+        self.call(id, position, |compiled| compiled.has_tick, "on_tick", args)
+    }
+
+    pub fn on_message(
+        &mut self,
+        id: Uuid,
+        position: Vec3,
+        sender: Uuid,
+        message: String,
+    ) -> Option<ScriptEffects> {
+        let args = (sender.to_string(), message);
+        self.call(
+            id,
+            position,
+            |compiled| compiled.has_message,
+            "on_message",
+            args,
+        )
+    }
+
+    fn call(
+        &mut self,
+        id: Uuid,
+        position: Vec3,
+        defined: impl FnOnce(&Compiled) -> bool,
+        name: &str,
+        args: impl FuncArgs,
+    ) -> Option<ScriptEffects> {
+        let (ast, version) = {
+            let compiled = self.script.compiled.read().unwrap();
+            if !defined(&compiled) {
+                return None;
+            }
+            (compiled.ast.clone(), compiled.version)
+        };
+        if self.failed == Some(version) {
+            return None;
+        }
+
+        let mut this = Dynamic::from(ScriptEntity {
+            id,
+            position,
+            state: mem::take(&mut self.state),
+            outbox: Vec::new(),
+        });
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut this);
+        let result = self.script.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut Scope::new(),
+            &ast,
+            name,
+            args,
+        );
+        let Some(entity) = this.try_cast::<ScriptEntity>() else {
+            warn!(
+                "Script {} replaced `this` on entity {}",
+                self.script.path.display(),
+                id
+            );
+            self.failed = Some(version);
+            return None;
+        };
+        self.state = entity.state;
+        if let Err(err) = result {
+            warn!(
+                "Script {} failed in {} on entity {}: {}",
+                self.script.path.display(),
+                name,
+                id,
+                err
+            );
+            self.failed = Some(version);
+            return None;
+        }
+        Some(ScriptEffects {
+            position: entity.position,
+            messages: entity.outbox,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        env, fs,
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    use glam::Vec3;
+    use uuid::Uuid;
+
+    use super::{EntityScript, Scripts};
+
+    fn write_script(name: &str, source: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("{}-{}.rhai", name, Uuid::new_v4()));
+        fs::write(&path, source).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_callbacks() {
+        let path = write_script(
+            "callbacks",
+            r#"
+            fn on_tick(dt) {
+                this.position += vec3(0.0, dt, 0.0);
+                this.state.ticks = (this.state.ticks ?? 0) + 1;
+            }
+
+            fn on_message(sender, message) {
+                this.send(sender, message + " " + this.state.ticks);
+            }
+            "#,
+        );
+        let mut scripts = Scripts::default();
+        let mut script = EntityScript::new(scripts.load(&path).unwrap());
+        let (id, sender) = (Uuid::from_u128(1), Uuid::from_u128(2));
+
+        let effects = script
+            .on_tick(id, Vec3::ZERO, Duration::from_millis(500))
+            .unwrap();
+        assert_eq!(effects.position, Vec3::new(0.0, 0.5, 0.0));
+        assert!(effects.messages.is_empty());
+        script
+            .on_tick(id, effects.position, Duration::ZERO)
+            .unwrap();
+
+        let effects = script
+            .on_message(id, Vec3::ZERO, sender, String::from("ticks"))
+            .unwrap();
+        assert_eq!(effects.messages, [(sender, String::from("ticks 2"))]);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_errors_and_reload() {
+        let path = write_script("errors", "fn on_tick(dt) { loop {} }");
+        let mut scripts = Scripts::default();
+        let loaded = scripts.load(&path).unwrap();
+        let mut script = EntityScript::new(loaded.clone());
+        let mut other = EntityScript::new(loaded);
+        let id = Uuid::from_u128(1);
+
+        // Stopped by the operation limit, and disabled for this entity only
+        assert!(script.on_tick(id, Vec3::ZERO, Duration::ZERO).is_none());
+        assert!(script.has_failed());
+        assert!(!other.has_failed());
+        // No callback, so nothing to fail
+        assert!(script
+            .on_message(id, Vec3::ZERO, id, String::new())
+            .is_none());
+
+        // The file system may not tell writes apart within its timestamp
+        // resolution, so move the timestamp explicitly
+        fs::write(
+            &path,
+            "fn on_tick(dt) { this.position = vec3(1.0, 2.0, 3.0); }",
+        )
+        .unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        scripts.reload_changed();
+        assert!(!script.has_failed());
+        let effects = script.on_tick(id, Vec3::ZERO, Duration::ZERO).unwrap();
+        assert_eq!(effects.position, Vec3::new(1.0, 2.0, 3.0));
+        assert!(other.on_tick(id, Vec3::ZERO, Duration::ZERO).is_some());
+
+        // A broken file keeps the last working version
+        fs::write(&path, "fn on_tick(dt) {").unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(20))
+            .unwrap();
+        scripts.reload_changed();
+        assert!(script.on_tick(id, Vec3::ZERO, Duration::ZERO).is_some());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let path = write_script(
+            "sandbox",
+            r#"
+            fn on_tick(dt) { eval("1") }
+            fn on_message(sender, message) { this.send("not an id", message); }
+            "#,
+        );
+        let mut scripts = Scripts::default();
+        assert!(scripts.load(&path).is_err());
+        fs::write(
+            &path,
+            r#"fn on_message(sender, message) { this.send("not an id", message); }"#,
+        )
+        .unwrap();
+        let mut scripts = Scripts::default();
+        let mut script = EntityScript::new(scripts.load(&path).unwrap());
+        let id = Uuid::from_u128(1);
+        assert!(script
+            .on_message(id, Vec3::ZERO, id, String::new())
+            .is_none());
+        assert!(script.has_failed());
+        fs::remove_file(path).unwrap();
+    }
+}
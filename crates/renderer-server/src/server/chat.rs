@@ -0,0 +1,117 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use renderer_protocol::chat::{ChatRejectReason, MAX_CHAT_MESSAGE_LENGTH};
+use serde::{Deserialize, Serialize};
+
+use super::limiter::{RateLimit, TokenBucket};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatConfig {
+    /// Longest accepted message, in characters.
+    pub max_length: usize,
+    /// Messages a player may send in a row before being limited.
+    pub burst: u32,
+    /// Time for one message of the burst to become available again.
+    pub refill_interval: Duration,
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            max_length: MAX_CHAT_MESSAGE_LENGTH,
+            burst: 5,
+            refill_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Per-connection checks of chat messages.
+#[derive(Debug)]
+pub struct ChatLimiter {
+    config: ChatConfig,
+    bucket: TokenBucket,
+}
+
+impl ChatLimiter {
+    pub fn new(config: ChatConfig, now: Instant) -> Self {
+        let limit = RateLimit {
+            burst: config.burst,
+            refill_interval: config.refill_interval,
+        };
+        Self {
+            config,
+            bucket: TokenBucket::new(limit, now),
+        }
+    }
+
+    /// Validate a message, returning its trimmed text.
+    pub fn check<'a>(&mut self, text: &'a str, now: Instant) -> Result<&'a str, ChatRejectReason> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(ChatRejectReason::Empty);
+        }
+        if text.chars().count() > self.config.max_length {
+            return Err(ChatRejectReason::TooLong {
+                max_length: self.config.max_length,
+            });
+        }
+        if !self.bucket.take(now) {
+            return Err(ChatRejectReason::RateLimited);
+        }
+        Ok(text)
+    }
+}
+
+pub fn timestamp_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use renderer_protocol::chat::ChatRejectReason;
+
+    use super::{ChatConfig, ChatLimiter};
+
+    #[test]
+    fn test_chat_limiter() {
+        let start = Instant::now();
+        let config = ChatConfig {
+            max_length: 8,
+            burst: 2,
+            refill_interval: Duration::from_secs(1),
+        };
+        let mut limiter = ChatLimiter::new(config, start);
+
+        assert_eq!(limiter.check("  hi  ", start), Ok("hi"));
+        assert_eq!(limiter.check(" \n", start), Err(ChatRejectReason::Empty));
+        assert_eq!(
+            limiter.check("too long message", start),
+            Err(ChatRejectReason::TooLong { max_length: 8 })
+        );
+        assert_eq!(limiter.check("two", start), Ok("two"));
+        assert_eq!(
+            limiter.check("three", start),
+            Err(ChatRejectReason::RateLimited)
+        );
+
+        let later = start + Duration::from_millis(1500);
+        assert_eq!(limiter.check("again", later), Ok("again"));
+        assert_eq!(
+            limiter.check("again", later),
+            Err(ChatRejectReason::RateLimited)
+        );
+
+        let much_later = start + Duration::from_secs(60);
+        assert_eq!(limiter.check("one", much_later), Ok("one"));
+        assert_eq!(limiter.check("two", much_later), Ok("two"));
+        assert_eq!(
+            limiter.check("three", much_later),
+            Err(ChatRejectReason::RateLimited)
+        );
+    }
+}
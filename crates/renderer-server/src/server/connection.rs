@@ -1,25 +1,44 @@
 use std::{
     error::Error,
     fmt::{self, Debug, Display, Formatter},
+    mem,
     pin::Pin,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use futures::{Sink, SinkExt, Stream, StreamExt, TryStreamExt};
 use glam::Vec3;
 use log::{info, trace};
 use renderer_protocol::{
+    chat::ChatMessage,
+    intent::{IntentRejectReason, Role},
     message::{ClientMessage, ServerMessage},
+    replay::ReplayEvent,
     version::VersionData,
 };
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
 use tokio::{select, sync::mpsc, time::sleep};
 use uuid::Uuid;
 
 use crate::{
     entity::{player::PlayerEntity, Entity},
-    server::Server,
+    server::{
+        chat::{timestamp_millis, ChatLimiter},
+        limiter::TokenBucket,
+        Server,
+    },
 };
 
+/// Compare admin tokens in constant time, so that timing doesn't tell how
+/// much of a guess was right. Their digests are compared, as comparing
+/// tokens of different lengths would return early and tell the length.
+fn token_matches(expected: &str, token: &str) -> bool {
+    let expected = Sha256::digest(expected.as_bytes());
+    let token = Sha256::digest(token.as_bytes());
+    expected.as_slice().ct_eq(token.as_slice()).into()
+}
+
 #[derive(Debug)]
 pub struct HandshakeData {
     pub version: VersionData,
@@ -125,11 +144,22 @@ where
             }
         };
         let message = message.map_err(ConnectionError::ReceiveError)?;
-        let client_version = match message {
-            ClientMessage::Handshake { version } => version,
+        let (client_version, admin_token) = match message {
+            ClientMessage::Handshake {
+                version,
+                admin_token,
+            } => (version, admin_token),
             _ => return Err(ConnectionError::BadMessage(message)),
         };
         info!("Client version: {:?}", client_version);
+        let role = match (&self.server.config.admin_token, admin_token) {
+            (Some(expected), Some(token)) if token_matches(expected, &token) => Role::Admin,
+            (_, Some(_)) => {
+                info!("Client sent a wrong admin token");
+                Role::Player
+            }
+            _ => Role::Player,
+        };
 
         // Lock server state
         let mut state = self.server.state.write().await;
@@ -143,27 +173,40 @@ where
             return Err(ConnectionError::OutputChannelAlreadyExists);
         }
 
-        let player = PlayerEntity::new(player_id, Vec3::ZERO);
+        let position = Vec3::ZERO;
+        let player = PlayerEntity::new(player_id, position);
         info!(
-            "Player {} logged in at {:?}",
+            "Player {} logged in at {:?} as {:?}",
             player.id(),
-            player.position()
+            player.position(),
+            role
         );
         if state.world.insert_player(player).is_err() {
             state.remove_channel(player_id);
             return Err(ConnectionError::PlayerAlreadyExists);
         }
+        state.record(ReplayEvent::PlayerJoined {
+            id: player_id,
+            position,
+        });
 
         // Copy entity state, and send them to client
-        let entity_states = state.world.entities.state();
+        let entity_states = state.sync_entity_states(player_id, &self.server.config.interest);
+        let environment = state.world.environment().clone();
 
         drop(state);
 
+        let mut chat_limiter = ChatLimiter::new(self.server.config.chat.clone(), Instant::now());
+        let mut intent_limiter =
+            TokenBucket::new(self.server.config.intent_limit.clone(), Instant::now());
+
         let run_result = async move {
             transport
                 .send(ServerMessage::SyncWorld {
                     player_id,
                     entity_states,
+                    environment,
+                    role,
                 })
                 .await
                 .map_err(ConnectionError::SendError)?;
@@ -184,14 +227,68 @@ where
                                     self.server.input_queue.push((player_id, input));
                                 }
                             }
+                            ClientMessage::Chat { text, target } => {
+                                match chat_limiter.check(&text, Instant::now()) {
+                                    Ok(text) => {
+                                        self.server.chat_queue.push(ChatMessage {
+                                            sender: player_id,
+                                            timestamp: timestamp_millis(),
+                                            target,
+                                            text: text.to_string(),
+                                        });
+                                    }
+                                    Err(reason) => {
+                                        info!("Chat from {} rejected: {}", player_id, reason);
+                                        transport
+                                            .send(ServerMessage::ChatRejected(reason))
+                                            .await
+                                            .map_err(ConnectionError::SendError)?;
+                                    }
+                                }
+                            }
+                            ClientMessage::ObjectIntent(intent) => {
+                                if intent_limiter.take(Instant::now()) {
+                                    self.server.intent_queue.push((player_id, role, intent));
+                                } else {
+                                    info!("Intent from {} rate limited", player_id);
+                                    let reason = IntentRejectReason::RateLimited;
+                                    transport
+                                        .send(ServerMessage::IntentRejected { intent, reason })
+                                        .await
+                                        .map_err(ConnectionError::SendError)?;
+                                }
+                            }
                         }
                     }
                     output = output_rx.recv() => {
                         let Some(output) = output else {
                             return Err(ConnectionError::OutputChannelDestroyed);
                         };
+                        let mut output = (*output).clone();
+                        output
+                            .chat_messages
+                            .retain(|message| message.visible_to(player_id));
+                        for rejection in mem::take(&mut output.chat_rejections) {
+                            if rejection.sender == player_id {
+                                transport
+                                    .send(ServerMessage::ChatRejected(rejection.reason))
+                                    .await
+                                    .map_err(ConnectionError::SendError)?;
+                            }
+                        }
+                        for rejection in mem::take(&mut output.intent_rejections) {
+                            if rejection.sender == player_id {
+                                transport
+                                    .send(ServerMessage::IntentRejected {
+                                        intent: rejection.intent,
+                                        reason: rejection.reason,
+                                    })
+                                    .await
+                                    .map_err(ConnectionError::SendError)?;
+                            }
+                        }
                         transport
-                            .send(ServerMessage::TickOutput((*output).clone()))
+                            .send(ServerMessage::TickOutput(output))
                             .await
                             .map_err(ConnectionError::SendError)?;
                     }
@@ -204,10 +301,24 @@ where
 
         // Queue removal of player
         let mut state = self.server.state.write().await;
-        state.world.entities.queue_remove_player(player_id);
+        state.record(ReplayEvent::PlayerLeft(player_id));
+        state.world.entities.queue_remove::<PlayerEntity>(player_id);
         // Remove output queue
         state.output_queue.remove(&player_id);
 
         run_result
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::token_matches;
+
+    #[test]
+    fn test_token_matches() {
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secret", "secreT"));
+        assert!(!token_matches("secret", "secret2"));
+        assert!(!token_matches("secret", ""));
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashSet;
+
+use renderer_protocol::{
+    entity::{EntitiesIds, EntitiesOutputs, EntityKind, EntityStates},
+    tick::TickOutput,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::world::Entities;
+
+/// Kinds sent to every client wherever they are, as lights affect the
+/// whole scene.
+const GLOBAL_KINDS: &[EntityKind] = &[EntityKind::LIGHT];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InterestConfig {
+    /// Distance from a player within which entities are sent to it, none to
+    /// send every entity.
+    pub radius: Option<f32>,
+}
+
+impl Default for InterestConfig {
+    fn default() -> Self {
+        Self {
+            radius: Some(128.0),
+        }
+    }
+}
+
+/// Entities a client was told about, so that it only receives entities
+/// near its player.
+#[derive(Debug, Default)]
+pub struct Interest {
+    known: HashSet<Uuid>,
+}
+
+impl Interest {
+    /// Local entities within `radius` of the player, or none when the
+    /// player isn't in the world.
+    fn visible(entities: &Entities, player: Uuid, radius: f32) -> Option<HashSet<Uuid>> {
+        let spatial = entities.spatial();
+        let center = spatial.position(player)?;
+        Some(
+            spatial
+                .query_radius(center, radius)
+                .into_iter()
+                .filter(|id| {
+                    !spatial
+                        .kind(*id)
+                        .is_some_and(|kind| GLOBAL_KINDS.contains(kind))
+                })
+                .collect(),
+        )
+    }
+
+    /// States to send to a joining player, which the interest then tracks.
+    pub fn sync(&mut self, entities: &Entities, player: Uuid, radius: f32) -> EntityStates {
+        let Some(visible) = Self::visible(entities, player, radius) else {
+            return entities.state();
+        };
+        let mut states = entities.state_of(visible.iter().copied());
+        let all = entities.state();
+        for kind in GLOBAL_KINDS {
+            for state in all.get(kind) {
+                states.push(kind, state.clone());
+            }
+        }
+        self.known = visible;
+        states
+    }
+
+    /// Output of a tick for this client. Entities coming within range are
+    /// sent with their whole state, and ones going out of range are removed.
+    pub fn filter(
+        &mut self,
+        entities: &Entities,
+        player: Uuid,
+        radius: f32,
+        output: &TickOutput,
+    ) -> TickOutput {
+        let Some(visible) = Self::visible(entities, player, radius) else {
+            return output.clone();
+        };
+        let is_global = |kind: &EntityKind| GLOBAL_KINDS.contains(kind);
+
+        // Entering entities get their current state, which already holds
+        // this tick's outputs
+        let mut new_entity_states = entities.state_of(visible.difference(&self.known).copied());
+        for (kind, states) in output.new_entity_states.iter() {
+            if is_global(kind) {
+                for state in states {
+                    new_entity_states.push(kind, state.clone());
+                }
+            }
+        }
+
+        let mut entity_outputs = EntitiesOutputs::default();
+        for (kind, outputs) in output.entity_outputs.iter() {
+            for (id, data) in outputs {
+                if is_global(kind) || (self.known.contains(id) && visible.contains(id)) {
+                    entity_outputs.push(kind, (*id, data.clone()));
+                }
+            }
+        }
+
+        let mut removed_entity_uuids = EntitiesIds::default();
+        for (kind, ids) in output.removed_entity_uuids.iter() {
+            for id in ids {
+                if is_global(kind) || self.known.contains(id) {
+                    removed_entity_uuids.push(kind, *id);
+                }
+            }
+        }
+        for id in self.known.difference(&visible) {
+            // Entities removed from the world are already listed above
+            if let Some(kind) = entities.spatial().kind(*id) {
+                removed_entity_uuids.push(kind, *id);
+            }
+        }
+
+        self.known = visible;
+        TickOutput {
+            new_entity_states,
+            entity_outputs,
+            removed_entity_uuids,
+            ..output.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use glam::Vec3;
+    use renderer_protocol::{
+        entity::{
+            BaseEntityData, EntityData, EntityKind, EntityResourceData, LightEntityState,
+            LightSource, ObjectEntityState, ObjectPermissions,
+        },
+        tick::TickOutput,
+    };
+    use uuid::Uuid;
+
+    use crate::{
+        entity::{
+            light::LightEntity,
+            object::ObjectEntity,
+            player::{PlayerEntity, PlayerEntityMessage},
+        },
+        world::World,
+    };
+
+    use super::Interest;
+
+    fn object_ids(states: &[EntityData]) -> Vec<Uuid> {
+        states
+            .iter()
+            .map(|state| state.decode::<ObjectEntityState>().unwrap().base.id)
+            .collect()
+    }
+
+    #[test]
+    fn test_interest() {
+        let dt = Duration::from_millis(50);
+        let radius = 10.0;
+        let mut world = World::default();
+        let player = Uuid::from_u128(1);
+        let near = Uuid::from_u128(2);
+        let far = Uuid::from_u128(3);
+        let light = Uuid::from_u128(4);
+        world
+            .insert_player(PlayerEntity::new(player, Vec3::ZERO))
+            .unwrap();
+        let mut output = TickOutput::default();
+        for (id, position) in [(near, Vec3::X), (far, Vec3::X * 100.0)] {
+            let object = ObjectEntity::from(ObjectEntityState {
+                base: BaseEntityData { id, position },
+                resource: EntityResourceData::Crosshair,
+                owner: None,
+                permissions: ObjectPermissions::default(),
+            });
+            world.entities.insert(object, &mut output).unwrap();
+        }
+        let source = LightSource::Parallel {
+            direction: Vec3::NEG_Y,
+            color: Vec3::ONE,
+            strength: 1.0,
+        };
+        let light_state = LightEntityState {
+            base: BaseEntityData {
+                id: light,
+                position: Vec3::X * 100.0,
+            },
+            source,
+        };
+        world
+            .entities
+            .insert(LightEntity::from(light_state), &mut output)
+            .unwrap();
+        let _ = world.tick(dt);
+
+        let mut interest = Interest::default();
+        let states = interest.sync(&world.entities, player, radius);
+        let objects = object_ids(states.get(&EntityKind::OBJECT));
+        assert!(objects.contains(&near));
+        assert!(!objects.contains(&far));
+        // Lights are sent however far they are
+        let lights = states.get(&EntityKind::LIGHT);
+        assert_eq!(
+            lights[0].decode::<LightEntityState>().unwrap().base.id,
+            light
+        );
+
+        // Walking to the far object swaps which one the client knows
+        world.send::<PlayerEntity>(player, PlayerEntityMessage::NewPosition(Vec3::X * 100.0));
+        let output = world.tick(dt);
+        let output = interest.filter(&world.entities, player, radius, &output);
+        assert_eq!(
+            object_ids(output.new_entity_states.get(&EntityKind::OBJECT)),
+            [far]
+        );
+        assert!(output
+            .removed_entity_uuids
+            .get(&EntityKind::OBJECT)
+            .contains(&near));
+        // The player's own movement is still sent
+        let outputs = output.entity_outputs.get(&EntityKind::PLAYER);
+        assert!(!outputs.is_empty() && outputs.iter().all(|(id, _)| *id == player));
+
+        // Removing an unknown entity isn't sent
+        world.entities.queue_remove::<ObjectEntity>(near);
+        let output = world.tick(dt);
+        let output = interest.filter(&world.entities, player, radius, &output);
+        assert!(output.removed_entity_uuids.is_empty());
+    }
+}
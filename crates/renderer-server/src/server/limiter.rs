@@ -0,0 +1,83 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// Actions allowed in a row before being limited.
+    pub burst: u32,
+    /// Time for one action of the burst to become available again.
+    pub refill_interval: Duration,
+}
+
+/// Per-connection token bucket.
+#[derive(Debug)]
+pub struct TokenBucket {
+    limit: RateLimit,
+    tokens: u32,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: limit.burst,
+            limit,
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if self.limit.refill_interval.is_zero() {
+            self.tokens = self.limit.burst;
+            return;
+        }
+        while self.tokens < self.limit.burst
+            && now.duration_since(self.last_refill) >= self.limit.refill_interval
+        {
+            self.tokens += 1;
+            self.last_refill += self.limit.refill_interval;
+        }
+        if self.tokens == self.limit.burst {
+            self.last_refill = now;
+        }
+    }
+
+    /// Take a token, returning whether one was left.
+    pub fn take(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens == 0 {
+            return false;
+        }
+        self.tokens -= 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::{RateLimit, TokenBucket};
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let limit = RateLimit {
+            burst: 2,
+            refill_interval: Duration::from_secs(1),
+        };
+        let mut bucket = TokenBucket::new(limit, start);
+        assert!(bucket.take(start));
+        assert!(bucket.take(start));
+        assert!(!bucket.take(start));
+
+        // Refills one token per interval, up to the burst
+        assert!(bucket.take(start + Duration::from_millis(1500)));
+        assert!(!bucket.take(start + Duration::from_millis(1500)));
+        let much_later = start + Duration::from_secs(60);
+        assert!(bucket.take(much_later));
+        assert!(bucket.take(much_later));
+        assert!(!bucket.take(much_later));
+    }
+}
@@ -0,0 +1,541 @@
+//! Server statistics, exposed in the Prometheus text format.
+//!
+//! The tick loop hands a [`TickSnapshot`] to [`Metrics`] after every tick,
+//! and [`MetricsServer`] answers `GET /metrics` on a local address with the
+//! rendered result.
+
+use std::{
+    fmt::{Display, Write as _},
+    io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use log::{debug, info, warn};
+use renderer_perf_tracker::PerformanceTracker;
+use renderer_protocol::{entity::EntityKind, traffic::TrafficStats};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+
+use super::Server;
+
+/// Traffic rates are measured over at least this long.
+const RATE_INTERVAL: Duration = Duration::from_secs(1);
+const QUANTILES: [f32; 3] = [0.5, 0.95, 0.99];
+/// Requests with a longer header are rejected.
+const MAX_REQUEST_LENGTH: usize = 8 * 1024;
+/// Connections not sending a whole request header in time are closed.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsConfig {
+    /// Number of recent ticks the tick duration quantiles are computed over.
+    pub tick_window: usize,
+    /// Log a summary this often.
+    pub log_interval: Option<Duration>,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            // A minute at the default tick rate
+            tick_window: 1200,
+            log_interval: None,
+        }
+    }
+}
+
+/// World state after a tick.
+#[derive(Debug, Clone, Default)]
+pub struct TickSnapshot {
+    pub entity_counts: Vec<(EntityKind, usize)>,
+    /// Inputs waiting when the tick started.
+    pub input_queue: usize,
+    /// Chat messages waiting when the tick started.
+    pub chat_queue: usize,
+    /// Object intents waiting when the tick started.
+    pub intent_queue: usize,
+    pub scheduled_messages: usize,
+}
+
+/// Per second rates, over the last [`RATE_INTERVAL`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TrafficRates {
+    pub sent_messages: f64,
+    pub sent_bytes: f64,
+    pub received_messages: f64,
+    pub received_bytes: f64,
+}
+
+impl TrafficRates {
+    fn between(previous: &TrafficStats, current: &TrafficStats, elapsed: Duration) -> Self {
+        let rate = |previous: u64, current: u64| {
+            current.saturating_sub(previous) as f64 / elapsed.as_secs_f64()
+        };
+        Self {
+            sent_messages: rate(previous.sent.messages, current.sent.messages),
+            sent_bytes: rate(previous.sent.bytes, current.sent.bytes),
+            received_messages: rate(previous.received.messages, current.received.messages),
+            received_bytes: rate(previous.received.bytes, current.received.bytes),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TickMetrics {
+    window: PerformanceTracker,
+    count: u64,
+    sum: Duration,
+    snapshot: TickSnapshot,
+    rate_sample: Option<(Instant, TrafficStats)>,
+    rates: TrafficRates,
+    last_log: Option<Instant>,
+}
+
+impl TickMetrics {
+    fn new(tick_window: usize) -> Self {
+        Self {
+            window: PerformanceTracker::new(tick_window.max(1)),
+            count: 0,
+            sum: Duration::ZERO,
+            snapshot: TickSnapshot::default(),
+            rate_sample: None,
+            rates: TrafficRates::default(),
+            last_log: None,
+        }
+    }
+
+    /// Quantiles of the tick duration window, and its maximum.
+    fn quantiles(&self) -> Option<([Duration; QUANTILES.len()], Duration)> {
+        let max = self.window.percentile(1.0)?;
+        let quantiles = QUANTILES.map(|quantile| self.window.percentile(quantile).unwrap_or(max));
+        Some((quantiles, max))
+    }
+}
+
+#[derive(Debug)]
+pub struct Metrics {
+    config: MetricsConfig,
+    connections: AtomicU64,
+    connections_total: AtomicU64,
+    skipped_ticks: AtomicU64,
+    ticks: Mutex<TickMetrics>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new(MetricsConfig::default())
+    }
+}
+
+impl Metrics {
+    pub fn new(config: MetricsConfig) -> Self {
+        Self {
+            ticks: Mutex::new(TickMetrics::new(config.tick_window)),
+            config,
+            connections: AtomicU64::new(0),
+            connections_total: AtomicU64::new(0),
+            skipped_ticks: AtomicU64::new(0),
+        }
+    }
+
+    pub fn connection_opened(&self) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+        self.connections_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_closed(&self) {
+        self.connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn record_skipped_ticks(&self, count: u64) {
+        self.skipped_ticks.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn connections(&self) -> u64 {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    pub fn traffic_rates(&self) -> TrafficRates {
+        self.ticks.lock().unwrap().rates
+    }
+
+    /// Record a finished tick, and log a summary when it is due.
+    pub fn record_tick(
+        &self,
+        duration: Duration,
+        snapshot: TickSnapshot,
+        traffic: TrafficStats,
+        now: Instant,
+    ) {
+        let mut ticks = self.ticks.lock().unwrap();
+        ticks.window.add_sample(duration, now);
+        ticks.count += 1;
+        ticks.sum += duration;
+        ticks.snapshot = snapshot;
+
+        match ticks.rate_sample {
+            Some((time, previous)) if now - time >= RATE_INTERVAL => {
+                ticks.rates = TrafficRates::between(&previous, &traffic, now - time);
+                ticks.rate_sample = Some((now, traffic));
+            }
+            Some(_) => {}
+            None => ticks.rate_sample = Some((now, traffic)),
+        }
+
+        let Some(log_interval) = self.config.log_interval else {
+            return;
+        };
+        match ticks.last_log {
+            Some(last_log) if now - last_log < log_interval => {}
+            Some(_) => {
+                ticks.last_log = Some(now);
+                self.log(&ticks);
+            }
+            None => ticks.last_log = Some(now),
+        }
+    }
+
+    fn log(&self, ticks: &TickMetrics) {
+        let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let (quantiles, max) = ticks.quantiles().unwrap_or_default();
+        let entities: Vec<String> = ticks
+            .snapshot
+            .entity_counts
+            .iter()
+            .map(|(kind, count)| format!("{} {}", count, kind))
+            .collect();
+        info!(
+            "Tick p50 {:.2} ms, p99 {:.2} ms, max {:.2} ms; {} connections; entities: {}; \
+            sent {:.0} msg/s {:.1} KiB/s; received {:.0} msg/s {:.1} KiB/s",
+            millis(quantiles[0]),
+            millis(quantiles[2]),
+            millis(max),
+            self.connections(),
+            entities.join(", "),
+            ticks.rates.sent_messages,
+            ticks.rates.sent_bytes / 1024.0,
+            ticks.rates.received_messages,
+            ticks.rates.received_bytes / 1024.0,
+        );
+    }
+
+    /// Render every metric in the Prometheus text format.
+    pub fn render(&self, traffic: TrafficStats) -> String {
+        let ticks = self.ticks.lock().unwrap();
+        let mut out = Exposition::default();
+
+        out.header(
+            "tick_duration_seconds",
+            "summary",
+            "Time spent producing a tick, over recent ticks.",
+        );
+        let quantiles = ticks.quantiles();
+        if let Some((quantiles, _)) = quantiles {
+            for (quantile, value) in QUANTILES.iter().zip(quantiles) {
+                out.sample(
+                    "tick_duration_seconds",
+                    &[("quantile", &quantile.to_string())],
+                    value.as_secs_f64(),
+                );
+            }
+        }
+        out.sample("tick_duration_seconds_sum", &[], ticks.sum.as_secs_f64());
+        out.sample("tick_duration_seconds_count", &[], ticks.count);
+        if let Some((_, max)) = quantiles {
+            out.gauge(
+                "tick_duration_max_seconds",
+                "Longest recent tick.",
+                max.as_secs_f64(),
+            );
+        }
+
+        out.counter(
+            "skipped_ticks_total",
+            "Ticks dropped to catch up after running late.",
+            self.skipped_ticks.load(Ordering::Relaxed),
+        );
+
+        out.header("entities", "gauge", "Entities in the world, by kind.");
+        for (kind, count) in &ticks.snapshot.entity_counts {
+            out.sample("entities", &[("kind", kind.name())], count);
+        }
+
+        out.gauge(
+            "connections",
+            "Open client connections.",
+            self.connections(),
+        );
+        out.counter(
+            "connections_total",
+            "Client connections accepted.",
+            self.connections_total.load(Ordering::Relaxed),
+        );
+
+        for (direction, stats, messages_rate, bytes_rate) in [
+            (
+                "sent",
+                traffic.sent,
+                ticks.rates.sent_messages,
+                ticks.rates.sent_bytes,
+            ),
+            (
+                "received",
+                traffic.received,
+                ticks.rates.received_messages,
+                ticks.rates.received_bytes,
+            ),
+        ] {
+            out.counter(
+                &format!("{}_messages_total", direction),
+                &format!("Messages {} over all connections.", direction),
+                stats.messages,
+            );
+            out.counter(
+                &format!("{}_bytes_total", direction),
+                &format!("Bytes {} on the wire.", direction),
+                stats.bytes,
+            );
+            out.counter(
+                &format!("{}_uncompressed_bytes_total", direction),
+                &format!("Bytes {} before compression.", direction),
+                stats.uncompressed_bytes,
+            );
+            out.gauge(
+                &format!("{}_messages_per_second", direction),
+                &format!("Messages {} per second.", direction),
+                messages_rate,
+            );
+            out.gauge(
+                &format!("{}_bytes_per_second", direction),
+                &format!("Bytes {} on the wire per second.", direction),
+                bytes_rate,
+            );
+        }
+
+        out.gauge(
+            "input_queue_depth",
+            "Player inputs waiting when the last tick started.",
+            ticks.snapshot.input_queue,
+        );
+        out.gauge(
+            "chat_queue_depth",
+            "Chat messages waiting when the last tick started.",
+            ticks.snapshot.chat_queue,
+        );
+        out.gauge(
+            "intent_queue_depth",
+            "Object intents waiting when the last tick started.",
+            ticks.snapshot.intent_queue,
+        );
+        out.gauge(
+            "scheduled_messages",
+            "Entity messages waiting for a later tick.",
+            ticks.snapshot.scheduled_messages,
+        );
+
+        out.text
+    }
+}
+
+/// Writes metrics in the Prometheus text format, prefixing their names.
+#[derive(Debug, Default)]
+struct Exposition {
+    text: String,
+}
+
+impl Exposition {
+    fn header(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.text, "# HELP renderer_{} {}", name, help);
+        let _ = writeln!(self.text, "# TYPE renderer_{} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        let _ = write!(self.text, "renderer_{}", name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(label, value)| format!("{}=\"{}\"", label, escape_label(value)))
+                .collect();
+            let _ = write!(self.text, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.text, " {}", value);
+    }
+
+    fn gauge(&mut self, name: &str, help: &str, value: impl Display) {
+        self.header(name, "gauge", help);
+        self.sample(name, &[], value);
+    }
+
+    fn counter(&mut self, name: &str, help: &str, value: impl Display) {
+        self.header(name, "counter", help);
+        self.sample(name, &[], value);
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Minimal HTTP server answering `GET /metrics`.
+#[derive(Debug)]
+pub struct MetricsServer {
+    listen_addr: SocketAddr,
+}
+
+impl MetricsServer {
+    pub fn new(listen_addr: SocketAddr) -> Self {
+        Self { listen_addr }
+    }
+
+    pub async fn serve(&self, server: Arc<Server>) -> io::Result<()> {
+        let listener = TcpListener::bind(self.listen_addr).await?;
+        info!("Metrics on http://{}/metrics", self.listen_addr);
+        loop {
+            let (stream, address) = listener.accept().await?;
+            let server = server.clone();
+            tokio::spawn(async move {
+                if let Err(err) = respond(stream, &server).await {
+                    debug!("Metrics request from {} failed: {}", address, err);
+                }
+            });
+        }
+    }
+}
+
+/// Read a request up to the end of its header, none if the client closed
+/// the connection or sent too much.
+async fn read_request(stream: &mut TcpStream) -> io::Result<Option<Vec<u8>>> {
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        request.extend_from_slice(&buffer[..read]);
+        if request.len() > MAX_REQUEST_LENGTH {
+            warn!("Metrics request header too long");
+            return Ok(None);
+        }
+    }
+    Ok(Some(request))
+}
+
+async fn respond(mut stream: TcpStream, server: &Server) -> io::Result<()> {
+    let request = timeout(REQUEST_TIMEOUT, read_request(&mut stream))
+        .await
+        .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+    let Some(request) = request else {
+        return Ok(());
+    };
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+    let (status, content_type, body) = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some("/metrics")) => (
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            server.metrics.render(server.traffic.stats()),
+        ),
+        (Some("GET"), _) => ("404 Not Found", "text/plain", String::from("Not found\n")),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            String::from("Method not allowed\n"),
+        ),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use renderer_protocol::{
+        entity::EntityKind,
+        traffic::{DirectionStats, TrafficStats},
+    };
+
+    use super::{Metrics, MetricsConfig, TickSnapshot};
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::new(MetricsConfig {
+            tick_window: 100,
+            log_interval: None,
+        });
+        metrics.connection_opened();
+        metrics.connection_opened();
+        metrics.connection_closed();
+
+        let start = Instant::now();
+        let traffic = |messages| TrafficStats {
+            sent: DirectionStats {
+                messages,
+                bytes: messages * 100,
+                uncompressed_bytes: messages * 200,
+            },
+            received: DirectionStats::default(),
+        };
+        // Only the last 100 of these count towards the quantiles
+        for i in 0..200 {
+            let snapshot = TickSnapshot {
+                entity_counts: vec![(EntityKind::PLAYER, 3)],
+                input_queue: 5,
+                ..Default::default()
+            };
+            let now = start + Duration::from_millis(50 * i);
+            metrics.record_tick(
+                Duration::from_millis(i % 100 + 1),
+                snapshot,
+                traffic(i),
+                now,
+            );
+        }
+        let rates = metrics.traffic_rates();
+        assert_eq!(rates.sent_messages, 20.0);
+        assert_eq!(rates.sent_bytes, 2000.0);
+
+        let text = metrics.render(traffic(200));
+        for line in [
+            "# TYPE renderer_tick_duration_seconds summary",
+            "renderer_tick_duration_seconds{quantile=\"0.5\"} 0.05",
+            "renderer_tick_duration_seconds{quantile=\"0.99\"} 0.099",
+            "renderer_tick_duration_seconds_count 200",
+            "renderer_tick_duration_max_seconds 0.1",
+            "renderer_entities{kind=\"player\"} 3",
+            "renderer_connections 1",
+            "renderer_connections_total 2",
+            "renderer_sent_messages_total 200",
+            "renderer_sent_uncompressed_bytes_total 40000",
+            "renderer_sent_bytes_per_second 2000",
+            "renderer_input_queue_depth 5",
+        ] {
+            assert!(
+                text.lines().any(|other| other == line),
+                "Missing {:?}",
+                line
+            );
+        }
+    }
+}
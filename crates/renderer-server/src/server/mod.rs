@@ -2,50 +2,100 @@ use std::{
     collections::{hash_map::Entry, HashMap},
     error::Error,
     fmt::{self, Debug, Display, Formatter},
+    fs::File,
     sync::Arc,
     time::{Duration, Instant},
 };
 
+use chat::ChatConfig;
 use connection::{Connection, ConnectionError};
 use crossbeam::queue::SegQueue;
 use futures::SinkExt;
+use interest::{Interest, InterestConfig};
+use limiter::RateLimit;
 use log::{trace, warn};
+use metrics::{Metrics, MetricsConfig, TickSnapshot};
 use renderer_perf_tracker::PerformanceTracker;
-use renderer_protocol::{input::PlayerEntityInput, tick::TickOutput};
+use renderer_protocol::{
+    chat::ChatMessage,
+    compression::CompressionConfig,
+    entity::EntityStates,
+    input::PlayerEntityInput,
+    intent::{ObjectIntent, Role},
+    replay::{ReplayError, ReplayEvent},
+    tick::TickOutput,
+    traffic::TrafficCounters,
+};
+use scheduler::{TickConfig, TickScheduler};
 use serde::{Deserialize, Serialize};
 use serve::Serve;
 use tokio::{
+    fs,
+    io::{self, AsyncWriteExt, BufWriter},
     sync::{mpsc, Mutex, RwLock},
-    time::sleep,
+    time::sleep_until,
 };
 use uuid::Uuid;
 
-use crate::world::World;
+use crate::{
+    replay::{script_reloaded, Recorder},
+    world::World,
+};
 
+pub mod chat;
 pub mod connection;
+pub mod interest;
+pub mod limiter;
+pub mod metrics;
+pub mod scheduler;
 pub mod serve;
 pub mod websocket;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub handshake_timeout: Duration,
+    pub compression: CompressionConfig,
+    pub chat: ChatConfig,
+    pub metrics: MetricsConfig,
+    pub tick: TickConfig,
+    pub interest: InterestConfig,
+    /// Object intents a connection may send.
+    pub intent_limit: RateLimit,
+    /// Token granting the admin role to clients sending it, none to have
+    /// no admins.
+    pub admin_token: Option<String>,
 }
 
 impl Default for ServerConfig {
     fn default() -> Self {
         Self {
             handshake_timeout: Duration::from_secs(10),
+            compression: CompressionConfig::default(),
+            chat: ChatConfig::default(),
+            metrics: MetricsConfig::default(),
+            tick: TickConfig::default(),
+            interest: InterestConfig::default(),
+            intent_limit: RateLimit {
+                burst: 10,
+                refill_interval: Duration::from_millis(100),
+            },
+            admin_token: None,
         }
     }
 }
 
-// Number from a legendary game
-const TARGET_TICK_RATE: usize = 20;
+#[derive(Debug)]
+struct OutputChannel {
+    sender: mpsc::UnboundedSender<Arc<TickOutput>>,
+    interest: Interest,
+}
 
 #[derive(Debug, Default)]
 pub struct ServerState {
     pub world: World,
-    output_queue: HashMap<Uuid, mpsc::UnboundedSender<Arc<TickOutput>>>,
+    output_queue: HashMap<Uuid, OutputChannel>,
+    /// Recorded ticks, buffered until the tick loop saves them.
+    recorder: Option<Recorder<Vec<u8>>>,
 }
 
 #[derive(Debug)]
@@ -68,14 +118,55 @@ impl ServerState {
         match self.output_queue.entry(id) {
             Entry::Occupied(_) => Err(ChannelAlreadyExists),
             Entry::Vacant(entry) => {
-                entry.insert(channel);
+                entry.insert(OutputChannel {
+                    sender: channel,
+                    interest: Interest::default(),
+                });
                 Ok(())
             }
         }
     }
 
     pub fn remove_channel(&mut self, id: Uuid) -> Option<mpsc::UnboundedSender<Arc<TickOutput>>> {
-        self.output_queue.remove(&id)
+        self.output_queue.remove(&id).map(|channel| channel.sender)
+    }
+
+    /// States of the entities a joining player should know about, tracked
+    /// from now on for the outputs sent to it.
+    pub fn sync_entity_states(&mut self, id: Uuid, config: &InterestConfig) -> EntityStates {
+        match (config.radius, self.output_queue.get_mut(&id)) {
+            (Some(radius), Some(channel)) => {
+                channel.interest.sync(&self.world.entities, id, radius)
+            }
+            _ => self.world.entities.state(),
+        }
+    }
+
+    fn start_recording(&mut self) -> Result<(), ReplayError> {
+        self.recorder = Some(Recorder::new(Vec::new(), &self.world)?);
+        Ok(())
+    }
+
+    fn stop_recording(&mut self) {
+        self.recorder = None;
+    }
+
+    /// Record an event about to be applied to the world.
+    pub fn record(&mut self, event: ReplayEvent) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(event);
+        }
+    }
+
+    /// Record a tick, returning what is left to save to the replay file.
+    fn record_tick(&mut self, tick: u64, dt: Duration, output: &TickOutput) -> Option<Vec<u8>> {
+        let recorder = self.recorder.as_mut()?;
+        if let Err(err) = recorder.write_tick(tick, dt, output) {
+            warn!("Recording stopped: {}", err);
+            self.recorder = None;
+            return None;
+        }
+        Some(recorder.take_written())
     }
 }
 
@@ -83,8 +174,14 @@ impl ServerState {
 pub struct Server {
     run_lock: Mutex<()>,
     pub input_queue: SegQueue<(Uuid, PlayerEntityInput)>,
+    pub chat_queue: SegQueue<ChatMessage>,
+    pub intent_queue: SegQueue<(Uuid, Role, ObjectIntent)>,
     pub config: ServerConfig,
     pub state: RwLock<ServerState>,
+    replay_file: Mutex<Option<BufWriter<fs::File>>>,
+    /// Bytes sent and received over all connections.
+    pub traffic: Arc<TrafficCounters>,
+    pub metrics: Metrics,
 }
 
 pub enum ServeError<S: Serve> {
@@ -120,55 +217,156 @@ impl Server {
         Self {
             run_lock: Mutex::new(()),
             input_queue: SegQueue::new(),
-            config,
+            chat_queue: SegQueue::new(),
+            intent_queue: SegQueue::new(),
             state: RwLock::new(ServerState::default()),
+            replay_file: Mutex::new(None),
+            traffic: Arc::default(),
+            metrics: Metrics::new(config.metrics.clone()),
+            config,
         }
     }
 
     pub async fn run(&self) -> ! {
         let _lock = self.run_lock.lock().await;
-        let mut performance_tracker = PerformanceTracker::new(TARGET_TICK_RATE);
-        let target_frame_time = Duration::from_secs(1) / TARGET_TICK_RATE as u32;
+        let mut performance_tracker = PerformanceTracker::new(self.config.tick.rate as usize);
+        let mut scheduler = TickScheduler::new(&self.config.tick, Instant::now());
+        // Fixed, so that ticks play out the same whenever they run
+        let dt = scheduler.period();
         loop {
             let start_time = Instant::now();
+            let input_queue = self.input_queue.len();
+            let chat_queue = self.chat_queue.len();
+            let intent_queue = self.intent_queue.len();
 
             let mut state = self.state.write().await;
+            performance_tracker.start_timer("input");
+            if state.world.scripts.hot_reload {
+                for script in state.world.scripts.reload_changed() {
+                    state.record(script_reloaded(&script));
+                }
+            }
             while let Some((id, input)) = self.input_queue.pop() {
+                state.record(ReplayEvent::Input(id, input.clone()));
                 state.world.entities.process_player_inputs(id, input);
             }
+            while let Some(message) = self.chat_queue.pop() {
+                state.record(ReplayEvent::Chat(message.clone()));
+                state.world.push_chat_message(message);
+            }
+            while let Some((player, role, intent)) = self.intent_queue.pop() {
+                state.record(ReplayEvent::ObjectIntent {
+                    player,
+                    role,
+                    intent: intent.clone(),
+                });
+                state.world.push_intent(player, role, intent);
+            }
+            performance_tracker.stop_timer();
 
-            let output = state.world.tick();
+            performance_tracker.start_timer("world tick");
+            let tick = state.world.current_tick();
+            let mut output = state.world.tick(dt);
+            performance_tracker.stop_timer();
+            output.tick_duration_micros = start_time.elapsed().as_micros() as u64;
             trace!("Tick output: {:?}", output);
+            performance_tracker.start_timer("output");
+            let replay = state.record_tick(tick, dt, &output);
             let output = Arc::new(output);
-            state.output_queue.retain(|id, channel| {
-                let result = channel.send(output.clone());
+            let ServerState {
+                world,
+                output_queue,
+                ..
+            } = &mut *state;
+            output_queue.retain(|id, channel| {
+                let output = match self.config.interest.radius {
+                    Some(radius) => Arc::new(channel.interest.filter(
+                        &world.entities,
+                        *id,
+                        radius,
+                        &output,
+                    )),
+                    None => output.clone(),
+                };
+                let result = channel.sender.send(output);
                 if result.is_err() {
                     warn!("Output channel for id {:?} was closed", id);
                 }
                 result.is_ok()
             });
+            performance_tracker.stop_timer();
+            let snapshot = TickSnapshot {
+                entity_counts: state
+                    .world
+                    .entities
+                    .counts()
+                    .map(|(kind, count)| (kind.clone(), count))
+                    .collect(),
+                input_queue,
+                chat_queue,
+                intent_queue,
+                scheduled_messages: state.world.scheduled_messages(),
+            };
 
             drop(state);
+            if let Some(replay) = replay {
+                self.save_replay(&replay).await;
+            }
 
             let end_time = Instant::now();
             let frame_time = end_time - start_time;
             performance_tracker.add_sample(frame_time, end_time);
+            self.metrics
+                .record_tick(frame_time, snapshot, self.traffic.stats(), end_time);
 
-            if let Some(avg_tick_time) = performance_tracker.avg_frame_time() {
-                let sleep_time = target_frame_time - avg_tick_time;
-                sleep(sleep_time).await;
-            } else {
-                unreachable!("Missing average tick time data");
+            trace!("TPS: {:?}", performance_tracker.fps());
+            trace!("Tick time: {:?}", performance_tracker.stats());
+            trace!("Tick timers: {:?}", performance_tracker.last_frame_timers());
+            trace!("Traffic: {:?}", self.traffic.stats());
+
+            let wakeup = scheduler.next(Instant::now());
+            if wakeup.skipped > 0 {
+                warn!("Server is behind, skipped {} ticks", wakeup.skipped);
+                self.metrics.record_skipped_ticks(wakeup.skipped);
             }
+            sleep_until(wakeup.at.into()).await;
+        }
+    }
 
-            trace!("TPS: {:?}", performance_tracker.fps());
+    /// Record every following event and tick to a replay file. Has to be
+    /// started before the world first ticks.
+    pub async fn start_recording(&self, file: File) -> Result<(), ReplayError> {
+        self.state.write().await.start_recording()?;
+        *self.replay_file.lock().await = Some(BufWriter::new(fs::File::from_std(file)));
+        Ok(())
+    }
+
+    /// Append recorded ticks to the replay file, without holding the state.
+    async fn save_replay(&self, data: &[u8]) {
+        let mut file = self.replay_file.lock().await;
+        let Some(writer) = file.as_mut() else {
+            return;
+        };
+        let written: io::Result<()> = async {
+            writer.write_all(data).await?;
+            writer.flush().await
+        }
+        .await;
+        if let Err(err) = written {
+            warn!("Recording stopped: {}", err);
+            *file = None;
+            drop(file);
+            self.state.write().await.stop_recording();
         }
     }
 
     pub async fn serve<S: Serve>(&self, serve: S) -> Result<(), ServeError<S>> {
         let transport = serve.serve().await.map_err(ServeError::Connect)?;
         let connection = Connection::new(transport, self);
-        match connection.run().await {
+        self.metrics.connection_opened();
+        let result = connection.run().await;
+        self.metrics.connection_closed();
+        match result {
             Ok(mut transport) => {
                 transport.close().await.map_err(ServeError::Close)?;
                 Ok(())
@@ -0,0 +1,155 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// What to do with ticks whose deadline passed while an earlier tick was
+/// still running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CatchUp {
+    /// Run one tick right away and drop the other missed ones.
+    Skip,
+    /// Run missed ticks back to back, at most `max_ticks` of them, and drop
+    /// the rest.
+    Burst { max_ticks: u32 },
+}
+
+impl CatchUp {
+    fn max_ticks(&self) -> u64 {
+        match self {
+            CatchUp::Skip => 1,
+            CatchUp::Burst { max_ticks } => (*max_ticks).max(1) as u64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TickConfig {
+    /// Ticks per second.
+    pub rate: u32,
+    pub catch_up: CatchUp,
+}
+
+impl Default for TickConfig {
+    fn default() -> Self {
+        Self {
+            // Number from a legendary game
+            rate: 20,
+            catch_up: CatchUp::Burst { max_ticks: 5 },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Wakeup {
+    /// When to start the next tick. Already passed when behind.
+    pub at: Instant,
+    /// Ticks dropped to catch up.
+    pub skipped: u64,
+}
+
+/// Fixed timestep schedule. Tick `n` is due `n` periods after the start,
+/// so time spent in a tick doesn't push the following ones back.
+#[derive(Debug)]
+pub struct TickScheduler {
+    period: Duration,
+    catch_up: CatchUp,
+    deadline: Instant,
+}
+
+impl TickScheduler {
+    /// Schedule the first tick at `start`.
+    pub fn new(config: &TickConfig, start: Instant) -> Self {
+        assert!(config.rate > 0, "Tick rate must be positive");
+        Self {
+            period: Duration::from_secs(1) / config.rate,
+            catch_up: config.catch_up,
+            deadline: start,
+        }
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Schedule the tick after the one that just finished at `now`.
+    pub fn next(&mut self, now: Instant) -> Wakeup {
+        self.deadline += self.period;
+        if now < self.deadline {
+            return Wakeup {
+                at: self.deadline,
+                skipped: 0,
+            };
+        }
+
+        // Deadlines at or before now
+        let passed = ((now - self.deadline).as_nanos() / self.period.as_nanos()) as u64 + 1;
+        let skipped = passed.saturating_sub(self.catch_up.max_ticks());
+        let skipped_time = self.period.as_nanos() * skipped as u128;
+        self.deadline += Duration::from_nanos(skipped_time.min(u64::MAX as u128) as u64);
+        Wakeup {
+            at: self.deadline,
+            skipped,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::{CatchUp, TickConfig, TickScheduler, Wakeup};
+
+    const PERIOD: Duration = Duration::from_millis(50);
+
+    fn scheduler(catch_up: CatchUp, start: Instant) -> TickScheduler {
+        TickScheduler::new(&TickConfig { rate: 20, catch_up }, start)
+    }
+
+    #[test]
+    fn test_no_drift() {
+        let start = Instant::now();
+        let mut scheduler = scheduler(CatchUp::Skip, start);
+        assert_eq!(scheduler.period(), PERIOD);
+        // Ticks of varying length end up on the same grid
+        for (n, busy) in [10, 49, 0, 30].into_iter().enumerate() {
+            let tick_start = start + PERIOD * n as u32;
+            let wakeup = scheduler.next(tick_start + Duration::from_millis(busy));
+            assert_eq!(
+                wakeup,
+                Wakeup {
+                    at: start + PERIOD * (n as u32 + 1),
+                    skipped: 0,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_catch_up() {
+        let start = Instant::now();
+        // A tick that takes 3.5 periods, so 3 more deadlines passed
+        let late = start + PERIOD * 7 / 2;
+
+        let mut skip = scheduler(CatchUp::Skip, start);
+        let wakeup = skip.next(late);
+        assert_eq!(wakeup.skipped, 2);
+        assert_eq!(wakeup.at, start + PERIOD * 3);
+        assert_eq!(skip.next(late).at, start + PERIOD * 4);
+
+        let mut burst = scheduler(CatchUp::Burst { max_ticks: 2 }, start);
+        let wakeup = burst.next(late);
+        assert_eq!(wakeup.skipped, 1);
+        assert!(wakeup.at <= late);
+        // The second tick of the burst is due right away too
+        let wakeup = burst.next(late);
+        assert_eq!(wakeup.skipped, 0);
+        assert!(wakeup.at <= late);
+        assert_eq!(burst.next(late).at, start + PERIOD * 4);
+
+        let mut burst = scheduler(CatchUp::Burst { max_ticks: 10 }, start);
+        for _ in 0..3 {
+            assert_eq!(burst.next(late).skipped, 0);
+        }
+        assert_eq!(burst.next(late).at, start + PERIOD * 4);
+    }
+}
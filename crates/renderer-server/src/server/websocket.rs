@@ -10,11 +10,22 @@ use std::{
 use bytes::{Bytes, BytesMut};
 use futures::SinkExt;
 use futures::StreamExt;
-use log::{info, warn};
-use renderer_protocol::message::{ClientMessage, ServerMessage};
+use futures::{future, TryFutureExt};
+use log::{debug, info, warn};
+use renderer_protocol::{
+    compression::{
+        CompressionConfig, CompressionError, CompressionType, FrameCodec, COMPRESSION_HEADER,
+    },
+    message::{ClientMessage, ServerMessage},
+};
 use tokio::net::TcpListener;
 use tokio_serde::{Deserializer, Framed, Serializer};
-use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::tungstenite::{
+    self,
+    handshake::server::{Callback, ErrorResponse, Request, Response},
+    http::HeaderValue,
+    Message,
+};
 
 use super::{serve::serve, Server};
 
@@ -22,6 +33,7 @@ use super::{serve::serve, Server};
 pub enum WebSocketServerError<SE> {
     WebSocket(tungstenite::Error),
     Serialize(SE),
+    Compression(CompressionError),
 }
 
 impl<SE: Display> Display for WebSocketServerError<SE> {
@@ -29,6 +41,7 @@ impl<SE: Display> Display for WebSocketServerError<SE> {
         match self {
             WebSocketServerError::WebSocket(error) => Display::fmt(error, f),
             WebSocketServerError::Serialize(error) => Display::fmt(error, f),
+            WebSocketServerError::Compression(error) => Display::fmt(error, f),
         }
     }
 }
@@ -41,6 +54,33 @@ impl<SE> From<SE> for WebSocketServerError<SE> {
     }
 }
 
+/// Answers the compression offer in the client's upgrade request.
+struct NegotiateCompression<'a> {
+    config: &'a CompressionConfig,
+    accepted: &'a mut Option<CompressionType>,
+}
+
+impl Callback for NegotiateCompression<'_> {
+    fn on_request(
+        self,
+        request: &Request,
+        mut response: Response,
+    ) -> Result<Response, ErrorResponse> {
+        let offer = request
+            .headers()
+            .get(COMPRESSION_HEADER)
+            .and_then(|offer| offer.to_str().ok());
+        if let Some(accepted) = offer.and_then(|offer| self.config.accept(offer)) {
+            response.headers_mut().insert(
+                COMPRESSION_HEADER,
+                HeaderValue::from_static(accepted.name()),
+            );
+            *self.accepted = Some(accepted);
+        }
+        Ok(response)
+    }
+}
+
 #[derive(Debug)]
 pub struct WebSocketServer<Codec> {
     listen_addr: SocketAddr,
@@ -77,34 +117,55 @@ where
             info!("Connection from {}", address);
             let server = server.clone();
             let codec_factory = codec_factory.clone();
+            let compression_config = server.config.compression.clone();
+            let traffic = server.traffic.clone();
             tokio::spawn(async move {
                 let serve = serve(|| async move {
-                    let stream = tokio_tungstenite::accept_async(stream)
-                        .await
-                        .map_err(WebSocketServerError::WebSocket)?;
+                    let mut compression = None;
+                    let negotiate = NegotiateCompression {
+                        config: &compression_config,
+                        accepted: &mut compression,
+                    };
+                    let stream = tokio_tungstenite::accept_hdr_async(stream, negotiate)
+                        .map_err(WebSocketServerError::WebSocket)
+                        .await?;
+                    debug!("Compression for {}: {:?}", address, compression);
+                    let frame_codec =
+                        FrameCodec::new(compression, compression_config.threshold, traffic);
+                    let decoder = frame_codec.clone();
                     let stream = stream
                         .filter_map::<_, Result<BytesMut, WebSocketServerError<SE>>, _>(
-                            |data| async {
+                            move |data| {
                                 let data = match data {
                                     Ok(data) => data,
                                     Err(err) => {
-                                        return Some(Err(WebSocketServerError::WebSocket(err)))
+                                        return future::ready(Some(Err(
+                                            WebSocketServerError::WebSocket(err),
+                                        )))
                                     }
                                 };
-                                match data {
-                                    Message::Binary(vec) => {
-                                        Some(Ok(BytesMut::from(vec.as_slice())))
+                                let frame = match &data {
+                                    Message::Binary(vec) => vec.as_slice(),
+                                    Message::Text(text) => text.as_bytes(),
+                                    Message::Ping(_) | Message::Pong(_) | Message::Close(_) => {
+                                        return future::ready(None)
                                     }
-                                    Message::Ping(_) | Message::Pong(_) | Message::Close(_) => None,
-                                    Message::Text(text) => Some(Ok(BytesMut::from(text.as_str()))),
                                     Message::Frame(_) => unreachable!(),
-                                }
+                                };
+                                let result = decoder
+                                    .decode(frame)
+                                    .map(|payload| BytesMut::from(payload.as_slice()))
+                                    .map_err(WebSocketServerError::Compression);
+                                future::ready(Some(result))
                             },
                         )
                         .sink_map_err(WebSocketServerError::WebSocket)
-                        .with::<Bytes, _, _, WebSocketServerError<SE>>(|message| async move {
-                            let message = Message::binary(message);
-                            Ok(message)
+                        .with::<Bytes, _, _, WebSocketServerError<SE>>(move |message: Bytes| {
+                            let result = frame_codec
+                                .encode(&message)
+                                .map(Message::binary)
+                                .map_err(WebSocketServerError::Compression);
+                            future::ready(result)
                         });
                     let framed = Framed::new(stream, codec_factory());
                     Ok::<_, WebSocketServerError<SE>>(framed)
@@ -0,0 +1,361 @@
+use glam::Vec3;
+use log::info;
+use renderer_protocol::{
+    codec::position::POSITION_LIMIT,
+    entity::{BaseEntityData, EntityResourceData, ObjectEntityState, ObjectPermissions},
+    intent::{IntentRejectReason, IntentRejection, ObjectIntent, Role},
+};
+use uuid::Uuid;
+
+use crate::entity::object::{ObjectEntity, ObjectEntityMessage};
+
+use super::World;
+
+#[derive(Debug, Clone)]
+pub struct IntentConfig {
+    /// Whether players without the admin role may spawn objects.
+    pub player_spawn: bool,
+    /// Most objects a player may own at once. Admins have no limit.
+    pub max_objects_per_player: usize,
+    /// Links of the external models that may be spawned, by admins too.
+    /// Boxes can always be spawned, crosshairs never.
+    pub spawn_links: Vec<String>,
+}
+
+impl Default for IntentConfig {
+    fn default() -> Self {
+        Self {
+            player_spawn: true,
+            max_objects_per_player: 32,
+            spawn_links: Vec::new(),
+        }
+    }
+}
+
+/// Positions have to survive the quantization of the codec.
+pub(crate) fn check_position(position: Vec3) -> Result<(), IntentRejectReason> {
+    if position.is_finite() && position.abs().max_element() < POSITION_LIMIT {
+        Ok(())
+    } else {
+        Err(IntentRejectReason::BadPosition)
+    }
+}
+
+impl World {
+    /// Check an intent of `player` against the object permissions and apply
+    /// it. Its effects show in the output of the next tick.
+    pub fn apply_intent(
+        &mut self,
+        player: Uuid,
+        role: Role,
+        intent: ObjectIntent,
+    ) -> Result<(), IntentRejectReason> {
+        match intent {
+            ObjectIntent::Spawn {
+                id,
+                position,
+                resource,
+                permissions,
+            } => {
+                check_position(position)?;
+                self.check_resource(&resource)?;
+                if role != Role::Admin {
+                    if !self.intents.player_spawn {
+                        return Err(IntentRejectReason::SpawnDisabled);
+                    }
+                    let max_objects = self.intents.max_objects_per_player;
+                    if self.owned_objects(player) >= max_objects {
+                        return Err(IntentRejectReason::TooManyObjects { max_objects });
+                    }
+                }
+                if self.entities.spatial().kind(id).is_some() {
+                    return Err(IntentRejectReason::AlreadyExists);
+                }
+                let object = ObjectEntity::from(ObjectEntityState {
+                    base: BaseEntityData { id, position },
+                    resource,
+                    owner: Some(player),
+                    permissions,
+                });
+                self.entities
+                    .insert(object, &mut self.tick_output)
+                    .map_err(|_| IntentRejectReason::AlreadyExists)
+            }
+            ObjectIntent::Move { id, position } => {
+                check_position(position)?;
+                self.check_object(player, role, id, ObjectPermissions::MOVE)?;
+                self.send::<ObjectEntity>(id, ObjectEntityMessage::NewPosition(position));
+                Ok(())
+            }
+            ObjectIntent::Delete { id } => {
+                self.check_object(player, role, id, ObjectPermissions::DELETE)?;
+                self.entities.queue_remove::<ObjectEntity>(id);
+                Ok(())
+            }
+        }
+    }
+
+    /// Apply an intent, telling the player in the tick output when it is
+    /// rejected.
+    pub fn push_intent(&mut self, player: Uuid, role: Role, intent: ObjectIntent) {
+        if let Err(reason) = self.apply_intent(player, role, intent.clone()) {
+            info!("Intent from {} rejected: {}", player, reason);
+            self.tick_output.intent_rejections.push(IntentRejection {
+                sender: player,
+                intent,
+                reason,
+            });
+        }
+    }
+
+    fn check_resource(&self, resource: &EntityResourceData) -> Result<(), IntentRejectReason> {
+        let allowed = match resource {
+            EntityResourceData::Box => true,
+            EntityResourceData::Crosshair => false,
+            EntityResourceData::External { link, .. } => self.intents.spawn_links.contains(link),
+        };
+        if allowed {
+            Ok(())
+        } else {
+            Err(IntentRejectReason::ResourceNotAllowed)
+        }
+    }
+
+    fn owned_objects(&self, player: Uuid) -> usize {
+        self.entities
+            .items::<ObjectEntity>()
+            .map(|objects| {
+                objects
+                    .iter()
+                    .filter(|object| object.owner() == Some(player))
+                    .count()
+            })
+            .unwrap_or(0)
+    }
+
+    fn check_object(
+        &self,
+        player: Uuid,
+        role: Role,
+        id: Uuid,
+        permission: ObjectPermissions,
+    ) -> Result<(), IntentRejectReason> {
+        let object = self
+            .entities
+            .items::<ObjectEntity>()
+            .and_then(|objects| objects.get(id))
+            .ok_or(IntentRejectReason::UnknownObject)?;
+        if object.permits(player, role, permission) {
+            Ok(())
+        } else {
+            Err(IntentRejectReason::NotPermitted)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use glam::Vec3;
+    use renderer_asset::index::BundleIndex;
+    use renderer_protocol::{
+        entity::{EntityKind, EntityResourceData, ObjectPermissions},
+        intent::{IntentRejectReason, IntentRejection, ObjectIntent, Role},
+    };
+    use uuid::Uuid;
+
+    use crate::{
+        entity::{object::ObjectEntity, Entity},
+        world::World,
+    };
+
+    const DT: Duration = Duration::from_millis(50);
+
+    fn spawn(id: Uuid, permissions: ObjectPermissions) -> ObjectIntent {
+        ObjectIntent::Spawn {
+            id,
+            position: Vec3::ZERO,
+            resource: EntityResourceData::Box,
+            permissions,
+        }
+    }
+
+    #[test]
+    fn test_permissions() {
+        let mut world = World::default();
+        let (owner, other) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let (private, public) = (Uuid::from_u128(10), Uuid::from_u128(11));
+        let position = Vec3::new(1.0, 2.0, 3.0);
+
+        world
+            .apply_intent(owner, Role::Player, spawn(private, ObjectPermissions::NONE))
+            .unwrap();
+        world
+            .apply_intent(owner, Role::Player, spawn(public, ObjectPermissions::MOVE))
+            .unwrap();
+        assert_eq!(
+            world.apply_intent(other, Role::Player, spawn(public, ObjectPermissions::ALL)),
+            Err(IntentRejectReason::AlreadyExists)
+        );
+        let output = world.tick(DT);
+        assert_eq!(output.new_entity_states.get(&EntityKind::OBJECT).len(), 2);
+
+        let move_to = |id| ObjectIntent::Move { id, position };
+        assert_eq!(
+            world.apply_intent(other, Role::Player, move_to(private)),
+            Err(IntentRejectReason::NotPermitted)
+        );
+        world
+            .apply_intent(other, Role::Player, move_to(public))
+            .unwrap();
+        world
+            .apply_intent(owner, Role::Player, move_to(private))
+            .unwrap();
+        assert_eq!(
+            world.apply_intent(other, Role::Player, ObjectIntent::Delete { id: public }),
+            Err(IntentRejectReason::NotPermitted)
+        );
+        // The crosshair belongs to the server
+        assert_eq!(
+            world.apply_intent(
+                owner,
+                Role::Player,
+                ObjectIntent::Delete { id: Uuid::nil() }
+            ),
+            Err(IntentRejectReason::NotPermitted)
+        );
+        world
+            .apply_intent(other, Role::Admin, ObjectIntent::Delete { id: private })
+            .unwrap();
+        assert_eq!(
+            world.apply_intent(other, Role::Player, ObjectIntent::Delete { id: other }),
+            Err(IntentRejectReason::UnknownObject)
+        );
+
+        // The owner's move is dropped with the object
+        let output = world.tick(DT);
+        let moved = output.entity_outputs.get(&EntityKind::OBJECT);
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].0, public);
+        assert_eq!(
+            output.removed_entity_uuids.get(&EntityKind::OBJECT),
+            [private]
+        );
+        let objects = world.entities.items::<ObjectEntity>().unwrap();
+        assert_eq!(objects.get(public).unwrap().base_data().position, position);
+    }
+
+    #[test]
+    fn test_spawn_limits() {
+        let mut world = World::default();
+        world.intents.max_objects_per_player = 1;
+        let player = Uuid::from_u128(1);
+        let permissions = ObjectPermissions::NONE;
+
+        world
+            .apply_intent(
+                player,
+                Role::Player,
+                spawn(Uuid::from_u128(10), permissions),
+            )
+            .unwrap();
+        assert_eq!(
+            world.apply_intent(
+                player,
+                Role::Player,
+                spawn(Uuid::from_u128(11), permissions)
+            ),
+            Err(IntentRejectReason::TooManyObjects { max_objects: 1 })
+        );
+        world
+            .apply_intent(player, Role::Admin, spawn(Uuid::from_u128(11), permissions))
+            .unwrap();
+
+        world.intents.player_spawn = false;
+        assert_eq!(
+            world.apply_intent(
+                Uuid::from_u128(2),
+                Role::Player,
+                spawn(Uuid::from_u128(12), permissions)
+            ),
+            Err(IntentRejectReason::SpawnDisabled)
+        );
+    }
+
+    #[test]
+    fn test_spawn_resources() {
+        let mut world = World::default();
+        world.intents.spawn_links = vec![String::from("models/chair.pmx")];
+        let player = Uuid::from_u128(1);
+        let spawn_resource = |id, resource| ObjectIntent::Spawn {
+            id: Uuid::from_u128(id),
+            position: Vec3::ZERO,
+            resource,
+            permissions: ObjectPermissions::NONE,
+        };
+        let external = |link: &str| EntityResourceData::External {
+            bundle_index: BundleIndex([0; 32]),
+            link: String::from(link),
+        };
+
+        // Crosshairs belong to the server, whoever asks
+        let crosshair = spawn_resource(10, EntityResourceData::Crosshair);
+        for role in [Role::Player, Role::Admin] {
+            assert_eq!(
+                world.apply_intent(player, role, crosshair.clone()),
+                Err(IntentRejectReason::ResourceNotAllowed)
+            );
+        }
+        let unlisted = spawn_resource(10, external("../../secret"));
+        assert_eq!(
+            world.apply_intent(player, Role::Player, unlisted),
+            Err(IntentRejectReason::ResourceNotAllowed)
+        );
+        let listed = spawn_resource(10, external("models/chair.pmx"));
+        world.apply_intent(player, Role::Player, listed).unwrap();
+    }
+
+    #[test]
+    fn test_bad_positions() {
+        let mut world = World::default();
+        let player = Uuid::from_u128(1);
+        let id = Uuid::from_u128(10);
+        for position in [
+            Vec3::NAN,
+            Vec3::new(0.0, f32::INFINITY, 0.0),
+            Vec3::new(0.0, 0.0, -3_000_000.0),
+        ] {
+            let intent = ObjectIntent::Spawn {
+                id,
+                position,
+                resource: EntityResourceData::Box,
+                permissions: ObjectPermissions::NONE,
+            };
+            assert_eq!(
+                world.apply_intent(player, Role::Admin, intent),
+                Err(IntentRejectReason::BadPosition)
+            );
+        }
+        world
+            .apply_intent(player, Role::Player, spawn(id, ObjectPermissions::NONE))
+            .unwrap();
+
+        // Rejections reach the sender through the tick output
+        let move_to = ObjectIntent::Move {
+            id,
+            position: Vec3::new(f32::NEG_INFINITY, 0.0, 0.0),
+        };
+        world.push_intent(player, Role::Player, move_to.clone());
+        let output = world.tick(DT);
+        assert_eq!(
+            output.intent_rejections,
+            [IntentRejection {
+                sender: player,
+                intent: move_to,
+                reason: IntentRejectReason::BadPosition,
+            }]
+        );
+        assert!(output.entity_outputs.is_empty());
+    }
+}
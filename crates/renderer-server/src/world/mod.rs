@@ -1,29 +1,76 @@
 use std::{
-    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+    any::Any,
+    collections::{hash_map::Entry, BTreeMap, HashMap, HashSet, VecDeque},
     error::Error,
-    fmt::{self, Display, Formatter},
+    fmt::{self, Debug, Display, Formatter},
+    time::Duration,
 };
 
+use glam::Vec3;
+use intent::IntentConfig;
 use log::warn;
+use physics::{PhysicsBody, PhysicsConfig};
 use renderer_protocol::{
-    entity::{BaseEntityData, EntityResourceData, EntityStates, ObjectEntityState},
+    chat::{ChatMessage, ChatRejectReason, ChatRejection},
+    entity::{
+        BaseEntityData, EntitiesIds, EntitiesOutputs, EntityData, EntityKind, EntityResourceData,
+        EntityStates, ObjectEntityState, ObjectPermissions,
+    },
+    environment::Environment,
     input::PlayerEntityInput,
     tick::TickOutput,
 };
+use serde::Serialize;
+use spatial::{Aabb, SpatialIndex};
 use uuid::Uuid;
 
-use crate::entity::{object::ObjectEntity, player::PlayerEntity, Entity};
+use crate::{
+    entity::{
+        context::{AnyMessage, EntityContext, Envelope},
+        light::LightEntity,
+        object::ObjectEntity,
+        player::PlayerEntity,
+        Entity,
+    },
+    script::Scripts,
+};
+
+pub mod intent;
+pub mod physics;
+pub mod spatial;
+
+/// Rounds of message passing in one tick before the rest is left for the
+/// next tick, so entities messaging each other forever can't stall it.
+const MAX_MESSAGE_ROUNDS: usize = 1024;
 
 #[derive(Debug)]
-pub struct EntityAlreadyExists;
+pub enum InsertEntityError {
+    AlreadyExists,
+    UnregisteredKind(EntityKind),
+}
 
-impl Display for EntityAlreadyExists {
+impl Display for InsertEntityError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "Entity already exists")
+        match self {
+            InsertEntityError::AlreadyExists => write!(f, "Entity already exists"),
+            InsertEntityError::UnregisteredKind(kind) => {
+                write!(f, "Entity kind {} is not registered", kind)
+            }
+        }
     }
 }
 
-impl Error for EntityAlreadyExists {}
+impl Error for InsertEntityError {}
+
+fn encode<T: Serialize>(kind: &EntityKind, value: &T) -> Option<EntityData> {
+    match EntityData::encode(value) {
+        Ok(data) => Some(data),
+        Err(err) => {
+            warn!("Failed to encode {} data: {}", kind, err);
+            None
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct EntityItem<E: Entity> {
@@ -42,19 +89,52 @@ impl<E: Entity> From<E> for EntityItem<E> {
 
 impl<E: Entity> EntityItem<E> {
     #[must_use]
-    fn process_messages(&mut self, output: &mut Vec<(Uuid, E::Output)>) -> bool {
+    fn process_messages(
+        &mut self,
+        tick: u64,
+        outbox: &mut Vec<Envelope>,
+        index: &mut SpatialIndex,
+        output: &mut Vec<(Uuid, E::Output)>,
+    ) -> bool {
+        let position = self.entity.position();
         let mut has_message = false;
         while let Some(message) = self.messages.pop_back() {
             has_message = true;
             let id = self.entity.id();
+            let mut context = EntityContext::new(id, tick, &mut self.messages, outbox);
             self.entity
-                .process_message(message, &mut self.messages, |change| {
+                .process_message(message, &mut context, |change| {
                     output.push((id, change));
                 });
         }
+        self.reindex(position, index);
         has_message
     }
 
+    fn update(
+        &mut self,
+        dt: Duration,
+        tick: u64,
+        outbox: &mut Vec<Envelope>,
+        index: &mut SpatialIndex,
+        output: &mut Vec<(Uuid, E::Output)>,
+    ) {
+        let position = self.entity.position();
+        let id = self.entity.id();
+        let mut context = EntityContext::new(id, tick, &mut self.messages, outbox);
+        self.entity.update(dt, &mut context, |change| {
+            output.push((id, change));
+        });
+        self.reindex(position, index);
+    }
+
+    fn reindex(&self, old_position: Vec3, index: &mut SpatialIndex) {
+        let position = self.entity.position();
+        if position != old_position && !index.update(self.entity.id(), position) {
+            index.insert(E::KIND, self.entity.id(), position);
+        }
+    }
+
     fn clone_state(&self) -> E::State {
         self.entity.clone_state()
     }
@@ -94,114 +174,407 @@ impl<E: Entity> FromIterator<E> for EntityItems<E> {
 }
 
 impl<E: Entity> EntityItems<E> {
-    fn clone_state(&self) -> Vec<E::State> {
-        self.items.values().map(EntityItem::clone_state).collect()
+    pub fn get(&self, id: Uuid) -> Option<&E> {
+        self.items.get(&id).map(|item| &item.entity)
+    }
+
+    pub fn contains(&self, id: Uuid) -> bool {
+        self.items.contains_key(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &E> {
+        self.items.values().map(|item| &item.entity)
     }
 
     fn queue_remove(&mut self, id: Uuid) {
         self.pending_removed.insert(id);
     }
 
-    fn clear_removed(&mut self, ids: &mut Vec<Uuid>) {
+    fn insert_new(&mut self, entity: E) -> Result<E::State, InsertEntityError> {
+        match self.items.entry(entity.id()) {
+            Entry::Occupied(_) => Err(InsertEntityError::AlreadyExists),
+            Entry::Vacant(entry) => {
+                let item: EntityItem<E> = entity.into();
+                let state = item.clone_state();
+                entry.insert(item);
+                Ok(state)
+            }
+        }
+    }
+}
+
+impl EntityItems<PlayerEntity> {
+    fn process_inputs(&mut self, id: Uuid, input: PlayerEntityInput) {
+        if let Some(player) = self.items.get_mut(&id) {
+            player.process_input(input);
+        } else {
+            warn!("Input with unknown player: {}", id)
+        }
+    }
+}
+
+fn push_outputs<E: Entity>(output: Vec<(Uuid, E::Output)>, outputs: &mut EntitiesOutputs) {
+    for (id, output) in output {
+        if let Some(data) = encode(&E::KIND, &output) {
+            outputs.push(&E::KIND, (id, data));
+        }
+    }
+}
+
+/// Type erased [`EntityItems`], so that kinds can be stored together.
+trait EntityCollection: Debug + Send + Sync {
+    fn clone_state(&self, states: &mut EntityStates);
+    fn clone_state_of(&self, id: Uuid, states: &mut EntityStates);
+    fn len(&self) -> usize;
+    fn queue_remove(&mut self, id: Uuid);
+    fn clear_removed(&mut self, ids: &mut EntitiesIds, index: &mut SpatialIndex);
+    fn index(&self, index: &mut SpatialIndex);
+    fn deliver(&mut self, target: Uuid, message: Box<dyn AnyMessage>);
+    fn update(
+        &mut self,
+        dt: Duration,
+        tick: u64,
+        outbox: &mut Vec<Envelope>,
+        index: &mut SpatialIndex,
+        outputs: &mut EntitiesOutputs,
+    );
+    #[must_use]
+    fn process_messages(
+        &mut self,
+        tick: u64,
+        outbox: &mut Vec<Envelope>,
+        index: &mut SpatialIndex,
+        outputs: &mut EntitiesOutputs,
+    ) -> bool;
+    fn collect_bodies(&self, bodies: &mut BTreeMap<Uuid, PhysicsBody>);
+    fn apply_bodies(
+        &mut self,
+        bodies: &BTreeMap<Uuid, PhysicsBody>,
+        index: &mut SpatialIndex,
+        outputs: &mut EntitiesOutputs,
+    );
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<E: Entity> EntityCollection for EntityItems<E> {
+    fn clone_state(&self, states: &mut EntityStates) {
+        for item in self.items.values() {
+            if let Some(data) = encode(&E::KIND, &item.clone_state()) {
+                states.push(&E::KIND, data);
+            }
+        }
+    }
+
+    fn clone_state_of(&self, id: Uuid, states: &mut EntityStates) {
+        let Some(item) = self.items.get(&id) else {
+            return;
+        };
+        if let Some(data) = encode(&E::KIND, &item.clone_state()) {
+            states.push(&E::KIND, data);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    fn queue_remove(&mut self, id: Uuid) {
+        EntityItems::queue_remove(self, id);
+    }
+
+    fn clear_removed(&mut self, ids: &mut EntitiesIds, index: &mut SpatialIndex) {
         for id in self.pending_removed.drain() {
             match self.items.remove(&id) {
                 Some(_) => {
-                    ids.push(id);
+                    index.remove(id);
+                    ids.push(&E::KIND, id);
                 }
                 None => {
-                    warn!("Remove non-existing entity id: {:?}", id);
+                    warn!("Remove non-existing {} id: {:?}", E::KIND, id);
                 }
             }
         }
     }
 
-    fn insert_new(&mut self, entity: E) -> Result<E::State, EntityAlreadyExists> {
-        match self.items.entry(entity.id()) {
-            Entry::Occupied(_) => Err(EntityAlreadyExists),
-            Entry::Vacant(entry) => {
-                let item: EntityItem<E> = entity.into();
-                let state = item.clone_state();
-                entry.insert(item);
-                Ok(state)
-            }
+    fn index(&self, index: &mut SpatialIndex) {
+        for item in self.items.values() {
+            index.insert(E::KIND, item.entity.id(), item.entity.position());
         }
     }
 
-    #[must_use]
-    fn process_messages(&mut self, output: &mut Vec<(Uuid, E::Output)>) -> bool {
+    fn deliver(&mut self, target: Uuid, message: Box<dyn AnyMessage>) {
+        let Some(item) = self.items.get_mut(&target) else {
+            warn!("Message to unknown {}: {}", E::KIND, target);
+            return;
+        };
+        match message.into_any().downcast::<E::Message>() {
+            Ok(message) => item.messages.push_back(*message),
+            Err(_) => warn!("Message of wrong type to {} {}", E::KIND, target),
+        }
+    }
+
+    fn update(
+        &mut self,
+        dt: Duration,
+        tick: u64,
+        outbox: &mut Vec<Envelope>,
+        index: &mut SpatialIndex,
+        outputs: &mut EntitiesOutputs,
+    ) {
+        let mut output = Vec::new();
+        for item in self.items.values_mut() {
+            item.update(dt, tick, outbox, index, &mut output);
+        }
+        push_outputs::<E>(output, outputs);
+    }
+
+    fn process_messages(
+        &mut self,
+        tick: u64,
+        outbox: &mut Vec<Envelope>,
+        index: &mut SpatialIndex,
+        outputs: &mut EntitiesOutputs,
+    ) -> bool {
+        let mut output = Vec::new();
         let mut has_message = false;
         for item in self.items.values_mut() {
-            if item.process_messages(output) {
+            if item.process_messages(tick, outbox, index, &mut output) {
                 has_message = true;
             }
         }
+        push_outputs::<E>(output, outputs);
         has_message
     }
-}
 
-impl EntityItems<PlayerEntity> {
-    fn process_inputs(&mut self, id: Uuid, input: PlayerEntityInput) {
-        if let Some(player) = self.items.get_mut(&id) {
-            player.process_input(input);
-        } else {
-            warn!("Input with unknown player: {}", id)
+    fn collect_bodies(&self, bodies: &mut BTreeMap<Uuid, PhysicsBody>) {
+        for item in self.items.values() {
+            if let Some(body) = item.entity.body() {
+                bodies.insert(
+                    item.entity.id(),
+                    PhysicsBody {
+                        kind: E::KIND,
+                        position: item.entity.position(),
+                        body: *body,
+                    },
+                );
+            }
         }
     }
+
+    fn apply_bodies(
+        &mut self,
+        bodies: &BTreeMap<Uuid, PhysicsBody>,
+        index: &mut SpatialIndex,
+        outputs: &mut EntitiesOutputs,
+    ) {
+        let mut output = Vec::new();
+        for (id, body) in bodies {
+            if body.kind != E::KIND {
+                continue;
+            }
+            let Some(item) = self.items.get_mut(id) else {
+                continue;
+            };
+            let position = item.entity.position();
+            item.entity
+                .apply_physics(body.position, body.body, |change| {
+                    output.push((*id, change))
+                });
+            item.reindex(position, index);
+        }
+        push_outputs::<E>(output, outputs);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
 }
 
+/// Entities of every registered kind.
 #[derive(Debug, Default)]
 pub struct Entities {
-    object: EntityItems<ObjectEntity>,
-    player: EntityItems<PlayerEntity>,
+    kinds: BTreeMap<EntityKind, Box<dyn EntityCollection>>,
+    spatial: SpatialIndex,
 }
 
 impl Entities {
+    /// Register an entity kind without any entity.
+    pub fn register<E: Entity>(&mut self) {
+        self.register_items(EntityItems::<E>::default());
+    }
+
+    /// Register an entity kind with initial entities, replacing any
+    /// entities of that kind.
+    pub fn register_items<E: Entity>(&mut self, items: EntityItems<E>) {
+        self.spatial.remove_kind(&E::KIND);
+        items.index(&mut self.spatial);
+        self.kinds.insert(E::KIND, Box::new(items));
+    }
+
+    pub fn items<E: Entity>(&self) -> Option<&EntityItems<E>> {
+        self.kinds.get(&E::KIND)?.as_any().downcast_ref()
+    }
+
+    fn items_mut<E: Entity>(&mut self) -> Option<&mut EntityItems<E>> {
+        self.kinds.get_mut(&E::KIND)?.as_any_mut().downcast_mut()
+    }
+
+    pub fn contains<E: Entity>(&self, id: Uuid) -> bool {
+        self.items::<E>().is_some_and(|items| items.contains(id))
+    }
+
+    /// Number of entities of each registered kind.
+    pub fn counts(&self) -> impl Iterator<Item = (&EntityKind, usize)> + '_ {
+        self.kinds
+            .iter()
+            .map(|(kind, collection)| (kind, collection.len()))
+    }
+
+    pub fn spatial(&self) -> &SpatialIndex {
+        &self.spatial
+    }
+
+    /// Entities of a kind within `radius` of `center`.
+    pub fn query_radius<E: Entity>(&self, center: Vec3, radius: f32) -> Vec<&E> {
+        self.filter_kind(self.spatial.query_radius(center, radius))
+    }
+
+    /// Entities of a kind inside `aabb`.
+    pub fn query_aabb<E: Entity>(&self, aabb: &Aabb) -> Vec<&E> {
+        self.filter_kind(self.spatial.query_aabb(aabb))
+    }
+
+    fn filter_kind<E: Entity>(&self, ids: Vec<Uuid>) -> Vec<&E> {
+        let Some(items) = self.items::<E>() else {
+            return Vec::new();
+        };
+        ids.into_iter()
+            .filter(|id| self.spatial.kind(*id) == Some(&E::KIND))
+            .filter_map(|id| items.get(id))
+            .collect()
+    }
+
     pub fn state(&self) -> EntityStates {
-        EntityStates {
-            object: self.object.clone_state(),
-            player: self.player.clone_state(),
+        let mut states = EntityStates::default();
+        for collection in self.kinds.values() {
+            collection.clone_state(&mut states);
         }
+        states
     }
 
-    pub fn queue_remove_player(&mut self, id: Uuid) {
-        self.player.queue_remove(id);
+    /// States of the given entities, skipping unknown ones.
+    pub fn state_of(&self, ids: impl IntoIterator<Item = Uuid>) -> EntityStates {
+        let mut states = EntityStates::default();
+        for id in ids {
+            let Some(kind) = self.spatial.kind(id) else {
+                continue;
+            };
+            if let Some(collection) = self.kinds.get(kind) {
+                collection.clone_state_of(id, &mut states);
+            }
+        }
+        states
+    }
+
+    pub fn queue_remove<E: Entity>(&mut self, id: Uuid) {
+        match self.kinds.get_mut(&E::KIND) {
+            Some(collection) => collection.queue_remove(id),
+            None => warn!("Remove entity of unregistered kind {}", E::KIND),
+        }
     }
 
     pub fn process_player_inputs(&mut self, id: Uuid, input: PlayerEntityInput) {
-        self.player.process_inputs(id, input);
+        match self.items_mut::<PlayerEntity>() {
+            Some(players) => players.process_inputs(id, input),
+            None => warn!("Input without player kind registered: {}", id),
+        }
     }
 
-    pub fn insert_player(
+    pub fn insert<E: Entity>(
         &mut self,
-        player: PlayerEntity,
+        entity: E,
         output: &mut TickOutput,
-    ) -> Result<(), EntityAlreadyExists> {
-        let state = self.player.insert_new(player)?;
-        output.new_entity_states.player.push(state);
+    ) -> Result<(), InsertEntityError> {
+        let items = self
+            .items_mut::<E>()
+            .ok_or(InsertEntityError::UnregisteredKind(E::KIND))?;
+        let (id, position) = (entity.id(), entity.position());
+        let state = items.insert_new(entity)?;
+        self.spatial.insert(E::KIND, id, position);
+        if let Some(data) = encode(&E::KIND, &state) {
+            output.new_entity_states.push(&E::KIND, data);
+        }
         Ok(())
     }
 
     pub fn clear_removed_entities(&mut self, output: &mut TickOutput) {
-        self.object
-            .clear_removed(&mut output.removed_entity_uuids.object);
-        self.player
-            .clear_removed(&mut output.removed_entity_uuids.player);
+        for collection in self.kinds.values_mut() {
+            collection.clear_removed(&mut output.removed_entity_uuids, &mut self.spatial);
+        }
     }
 
-    pub fn process_messages(&mut self, output: &mut TickOutput) {
-        loop {
-            let mut has_message = false;
+    fn deliver(&mut self, envelope: Envelope) {
+        match self.kinds.get_mut(&envelope.kind) {
+            Some(collection) => collection.deliver(envelope.target, envelope.message),
+            None => warn!("Message to unregistered kind {}", envelope.kind),
+        }
+    }
 
-            has_message |= self
-                .object
-                .process_messages(&mut output.entity_outputs.object);
-            has_message |= self
-                .player
-                .process_messages(&mut output.entity_outputs.player);
+    fn update(
+        &mut self,
+        dt: Duration,
+        tick: u64,
+        outbox: &mut Vec<Envelope>,
+        outputs: &mut EntitiesOutputs,
+    ) {
+        for collection in self.kinds.values_mut() {
+            collection.update(dt, tick, outbox, &mut self.spatial, outputs);
+        }
+    }
 
-            if !has_message {
-                break;
-            }
+    #[must_use]
+    fn process_messages(
+        &mut self,
+        tick: u64,
+        outbox: &mut Vec<Envelope>,
+        outputs: &mut EntitiesOutputs,
+    ) -> bool {
+        let mut has_message = false;
+        for collection in self.kinds.values_mut() {
+            has_message |= collection.process_messages(tick, outbox, &mut self.spatial, outputs);
+        }
+        has_message
+    }
+
+    fn step_physics(
+        &mut self,
+        config: &PhysicsConfig,
+        dt: Duration,
+        outputs: &mut EntitiesOutputs,
+    ) {
+        let mut bodies = BTreeMap::new();
+        for collection in self.kinds.values() {
+            collection.collect_bodies(&mut bodies);
+        }
+        if bodies.is_empty() {
+            return;
+        }
+        physics::step(config, dt, &mut bodies, &self.spatial);
+        for collection in self.kinds.values_mut() {
+            collection.apply_bodies(&bodies, &mut self.spatial, outputs);
         }
     }
 }
@@ -210,37 +583,646 @@ impl Entities {
 pub struct World {
     pub entities: Entities,
     pub tick_output: TickOutput,
+    pub physics: PhysicsConfig,
+    pub intents: IntentConfig,
+    pub scripts: Scripts,
+    environment: Environment,
+    current_tick: u64,
+    /// Messages waiting for a later tick, by the tick they are due.
+    scheduled: BTreeMap<u64, Vec<Envelope>>,
 }
 
 impl Default for World {
     fn default() -> Self {
+        let mut entities = Entities::default();
+        entities.register_items(
+            [ObjectEntity::from(ObjectEntityState {
+                base: BaseEntityData {
+                    id: Uuid::nil(),
+                    position: [0.0, 0.0, 0.0].into(),
+                },
+                resource: EntityResourceData::Crosshair,
+                owner: None,
+                permissions: ObjectPermissions::NONE,
+            })]
+            .into_iter()
+            .collect(),
+        );
+        entities.register::<PlayerEntity>();
+        entities.register::<LightEntity>();
         Self {
-            entities: Entities {
-                object: [ObjectEntity::from(ObjectEntityState {
-                    base: BaseEntityData {
-                        id: Uuid::nil(),
-                        position: [0.0, 0.0, 0.0].into(),
-                    },
-                    resource: EntityResourceData::Crosshair,
-                })]
-                .into_iter()
-                .collect(),
-                player: EntityItems::default(),
-            },
+            entities,
             tick_output: TickOutput::default(),
+            physics: PhysicsConfig::default(),
+            intents: IntentConfig::default(),
+            scripts: Scripts::default(),
+            environment: Environment::default(),
+            current_tick: 0,
+            scheduled: BTreeMap::new(),
         }
     }
 }
 
 impl World {
-    pub fn insert_player(&mut self, player: PlayerEntity) -> Result<(), EntityAlreadyExists> {
-        self.entities.insert_player(player, &mut self.tick_output)
+    /// Start with `environment` instead of the default one.
+    pub fn with_environment(mut self, environment: Environment) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    pub fn insert_player(&mut self, player: PlayerEntity) -> Result<(), InsertEntityError> {
+        self.entities.insert(player, &mut self.tick_output)
+    }
+
+    pub fn push_chat_message(&mut self, message: ChatMessage) {
+        if let Some(target) = message.target {
+            if !self.entities.contains::<PlayerEntity>(target) {
+                warn!("Chat message to unknown player: {}", target);
+                self.tick_output.chat_rejections.push(ChatRejection {
+                    sender: message.sender,
+                    reason: ChatRejectReason::UnknownTarget,
+                });
+                return;
+            }
+        }
+        self.tick_output.chat_messages.push(message);
+    }
+
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    /// Replace the environment, sent to clients with the next tick.
+    pub fn set_environment(&mut self, environment: Environment) {
+        self.tick_output.environment = Some(environment.clone());
+        self.environment = environment;
+    }
+
+    /// Number of the tick about to run, starting from zero.
+    pub fn current_tick(&self) -> u64 {
+        self.current_tick
+    }
+
+    /// Number of messages waiting for a later tick.
+    pub fn scheduled_messages(&self) -> usize {
+        self.scheduled.values().map(Vec::len).sum()
+    }
+
+    /// Send a message to an entity, handled in the next tick.
+    pub fn send<E: Entity>(&mut self, target: Uuid, message: E::Message) {
+        self.schedule::<E>(target, 0, message);
+    }
+
+    /// Send a message to an entity, handled `delay` ticks after the next
+    /// one.
+    pub fn schedule<E: Entity>(&mut self, target: Uuid, delay: u64, message: E::Message) {
+        let envelope = Envelope {
+            kind: E::KIND,
+            target,
+            deliver_at: self.current_tick + delay,
+            message: Box::new(message),
+        };
+        self.scheduled
+            .entry(envelope.deliver_at)
+            .or_default()
+            .push(envelope);
+    }
+
+    fn route(&mut self, outbox: &mut Vec<Envelope>) {
+        for envelope in outbox.drain(..) {
+            if envelope.deliver_at <= self.current_tick {
+                self.entities.deliver(envelope);
+            } else {
+                self.scheduled
+                    .entry(envelope.deliver_at)
+                    .or_default()
+                    .push(envelope);
+            }
+        }
     }
 
+    /// Run one tick, `dt` after the previous one.
     #[must_use]
-    pub fn tick(&mut self) -> TickOutput {
+    pub fn tick(&mut self, dt: Duration) -> TickOutput {
+        let tick = self.current_tick;
         self.entities.clear_removed_entities(&mut self.tick_output);
-        self.entities.process_messages(&mut self.tick_output);
-        self.tick_output.take()
+
+        while let Some(entry) = self.scheduled.first_entry() {
+            if *entry.key() > tick {
+                break;
+            }
+            for envelope in entry.remove() {
+                self.entities.deliver(envelope);
+            }
+        }
+
+        let mut outbox = Vec::new();
+        self.entities
+            .update(dt, tick, &mut outbox, &mut self.tick_output.entity_outputs);
+
+        let mut rounds = 0;
+        loop {
+            self.route(&mut outbox);
+            if rounds == MAX_MESSAGE_ROUNDS {
+                warn!("Messages still pending after {} rounds", rounds);
+                break;
+            }
+            rounds += 1;
+            if !self.entities.process_messages(
+                tick,
+                &mut outbox,
+                &mut self.tick_output.entity_outputs,
+            ) {
+                break;
+            }
+        }
+
+        self.entities
+            .step_physics(&self.physics, dt, &mut self.tick_output.entity_outputs);
+
+        self.current_tick += 1;
+        let mut output = self.tick_output.take();
+        output.tick = tick;
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use glam::Vec3;
+    use renderer_protocol::{
+        chat::{ChatMessage, ChatRejectReason, ChatRejection},
+        entity::{
+            BaseEntityData, EntityKind, EntityResourceData, LightEntityOutput, LightEntityState,
+            LightSource, ObjectEntityState, ObjectPermissions,
+        },
+        environment::Environment,
+        tick::TickOutput,
+    };
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    use crate::{
+        entity::{
+            context::EntityContext,
+            light::{LightEntity, LightEntityMessage},
+            object::{ObjectEntity, ObjectEntityMessage},
+            player::{PlayerEntity, PlayerEntityMessage},
+            Entity, Message, Output,
+        },
+        script::EntityScript,
+    };
+
+    use super::{spatial::Aabb, InsertEntityError, World};
+
+    #[derive(Debug)]
+    struct TriggerEntity {
+        base: BaseEntityData,
+        fired: u32,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct Fire;
+
+    impl Message for Fire {}
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct Fired(u32);
+
+    impl Output for Fired {}
+
+    impl Entity for TriggerEntity {
+        const KIND: EntityKind = EntityKind::new("trigger");
+
+        type Message = Fire;
+        type Output = Fired;
+        type State = BaseEntityData;
+
+        fn base_data(&self) -> &BaseEntityData {
+            &self.base
+        }
+
+        fn clone_state(&self) -> Self::State {
+            self.base.clone()
+        }
+
+        fn process_message(
+            &mut self,
+            _message: Self::Message,
+            _context: &mut EntityContext<Self>,
+            mut on_change: impl FnMut(Self::Output),
+        ) {
+            self.fired += 1;
+            on_change(Fired(self.fired));
+        }
+    }
+
+    #[test]
+    fn test_custom_kind() {
+        let mut world = World::default();
+        let base = BaseEntityData {
+            id: Uuid::from_u128(1),
+            position: [1.0, 2.0, 3.0].into(),
+        };
+        let trigger = TriggerEntity {
+            base: base.clone(),
+            fired: 0,
+        };
+        let mut output = Default::default();
+        assert!(matches!(
+            world.entities.insert(trigger, &mut output),
+            Err(InsertEntityError::UnregisteredKind(_))
+        ));
+
+        world.entities.register::<TriggerEntity>();
+        let trigger = TriggerEntity {
+            base: base.clone(),
+            fired: 0,
+        };
+        world.entities.insert(trigger, &mut output).unwrap();
+        let states = output.new_entity_states.get(&TriggerEntity::KIND);
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].decode::<BaseEntityData>().unwrap(), base);
+
+        let synced = world.entities.state();
+        assert_eq!(synced.get(&TriggerEntity::KIND).len(), 1);
+        assert_eq!(synced.get(&EntityKind::OBJECT).len(), 1);
+
+        world.send::<TriggerEntity>(base.id, Fire);
+        let output = world.tick(Duration::from_millis(50));
+        let outputs = output.entity_outputs.get(&TriggerEntity::KIND);
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].0, base.id);
+        assert_eq!(outputs[0].1.decode::<Fired>().unwrap(), Fired(1));
+    }
+
+    /// Counts up every update and, when switched, tells the door to open
+    /// and to close again two ticks later.
+    #[derive(Debug)]
+    struct SwitchEntity {
+        base: BaseEntityData,
+        door: Uuid,
+        elapsed: Duration,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    struct Switch;
+
+    impl Message for Switch {}
+
+    impl Entity for SwitchEntity {
+        const KIND: EntityKind = EntityKind::new("switch");
+
+        type Message = Switch;
+        type Output = ();
+        type State = BaseEntityData;
+
+        fn base_data(&self) -> &BaseEntityData {
+            &self.base
+        }
+
+        fn clone_state(&self) -> Self::State {
+            self.base.clone()
+        }
+
+        fn process_message(
+            &mut self,
+            _message: Self::Message,
+            context: &mut EntityContext<Self>,
+            _on_change: impl FnMut(Self::Output),
+        ) {
+            context.send::<DoorEntity>(self.door, DoorMessage::Open);
+            context.schedule::<DoorEntity>(self.door, 2, DoorMessage::Close);
+        }
+
+        fn update(
+            &mut self,
+            dt: Duration,
+            _context: &mut EntityContext<Self>,
+            _on_change: impl FnMut(Self::Output),
+        ) {
+            self.elapsed += dt;
+        }
+    }
+
+    #[derive(Debug)]
+    struct DoorEntity {
+        base: BaseEntityData,
+        open: bool,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    enum DoorMessage {
+        Open,
+        Close,
+    }
+
+    impl Message for DoorMessage {}
+
+    impl Output for bool {}
+
+    impl Entity for DoorEntity {
+        const KIND: EntityKind = EntityKind::new("door");
+
+        type Message = DoorMessage;
+        type Output = bool;
+        type State = BaseEntityData;
+
+        fn base_data(&self) -> &BaseEntityData {
+            &self.base
+        }
+
+        fn clone_state(&self) -> Self::State {
+            self.base.clone()
+        }
+
+        fn process_message(
+            &mut self,
+            message: Self::Message,
+            _context: &mut EntityContext<Self>,
+            mut on_change: impl FnMut(Self::Output),
+        ) {
+            self.open = matches!(message, DoorMessage::Open);
+            on_change(self.open);
+        }
+    }
+
+    fn door_outputs(output: &TickOutput) -> Vec<bool> {
+        output
+            .entity_outputs
+            .get(&DoorEntity::KIND)
+            .iter()
+            .map(|(_, data)| data.decode().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_scheduled_messages() {
+        let dt = Duration::from_millis(50);
+        let mut world = World::default();
+        world.entities.register::<SwitchEntity>();
+        world.entities.register::<DoorEntity>();
+
+        let switch_id = Uuid::from_u128(1);
+        let door_id = Uuid::from_u128(2);
+        let mut output = Default::default();
+        let base = |id| BaseEntityData {
+            id,
+            position: Vec3::ZERO,
+        };
+        world
+            .entities
+            .insert(
+                SwitchEntity {
+                    base: base(switch_id),
+                    door: door_id,
+                    elapsed: Duration::ZERO,
+                },
+                &mut output,
+            )
+            .unwrap();
+        world
+            .entities
+            .insert(
+                DoorEntity {
+                    base: base(door_id),
+                    open: false,
+                },
+                &mut output,
+            )
+            .unwrap();
+
+        world.schedule::<SwitchEntity>(switch_id, 1, Switch);
+        assert!(door_outputs(&world.tick(dt)).is_empty());
+        assert_eq!(door_outputs(&world.tick(dt)), [true]);
+        assert!(door_outputs(&world.tick(dt)).is_empty());
+        assert_eq!(door_outputs(&world.tick(dt)), [false]);
+        assert_eq!(world.current_tick(), 4);
+
+        let switch = world.entities.items::<SwitchEntity>().unwrap();
+        assert_eq!(switch.get(switch_id).unwrap().elapsed, dt * 4);
+
+        // Messages to unknown entities are dropped
+        world.send::<DoorEntity>(Uuid::from_u128(3), DoorMessage::Open);
+        assert!(door_outputs(&world.tick(dt)).is_empty());
+    }
+
+    #[test]
+    fn test_spatial_queries() {
+        let dt = Duration::from_millis(50);
+        let mut world = World::default();
+        let id = Uuid::from_u128(1);
+        world
+            .insert_player(PlayerEntity::new(id, Vec3::ZERO))
+            .unwrap();
+        let near = |world: &World, center| {
+            world
+                .entities
+                .query_radius::<PlayerEntity>(center, 1.0)
+                .iter()
+                .map(|player| player.id())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(near(&world, Vec3::ZERO), [id]);
+
+        let target = Vec3::new(100.0, 0.0, -40.0);
+        world.send::<PlayerEntity>(id, PlayerEntityMessage::NewPosition(target));
+        let _ = world.tick(dt);
+        assert!(near(&world, Vec3::ZERO).is_empty());
+        assert_eq!(near(&world, target), [id]);
+        // The crosshair object is indexed, but is not a player
+        assert_eq!(
+            world
+                .entities
+                .query_aabb::<ObjectEntity>(&Aabb::from_center(Vec3::ZERO, Vec3::ONE))
+                .len(),
+            1
+        );
+
+        world.entities.queue_remove::<PlayerEntity>(id);
+        let _ = world.tick(dt);
+        assert!(near(&world, target).is_empty());
+        assert_eq!(world.entities.spatial().len(), 1);
+    }
+
+    #[test]
+    fn test_lights_and_environment() {
+        let dt = Duration::from_millis(50);
+        let mut world = World::default();
+        let id = Uuid::from_u128(1);
+        let source = LightSource::Parallel {
+            direction: Vec3::NEG_Y,
+            color: Vec3::ONE,
+            strength: 0.5,
+        };
+        let mut output = TickOutput::default();
+        world
+            .entities
+            .insert(
+                LightEntity::from(LightEntityState {
+                    base: BaseEntityData {
+                        id,
+                        position: Vec3::ZERO,
+                    },
+                    source: source.clone(),
+                }),
+                &mut output,
+            )
+            .unwrap();
+        let states = output.new_entity_states.get(&EntityKind::LIGHT);
+        assert_eq!(
+            states[0].decode::<LightEntityState>().unwrap().source,
+            source
+        );
+
+        let source = LightSource::Parallel {
+            direction: Vec3::NEG_Y,
+            color: Vec3::ONE,
+            strength: 1.0,
+        };
+        world.send::<LightEntity>(id, LightEntityMessage::NewSource(source.clone()));
+        let output = world.tick(dt);
+        let outputs = output.entity_outputs.get(&EntityKind::LIGHT);
+        assert_eq!(
+            outputs[0].1.decode::<LightEntityOutput>().unwrap(),
+            LightEntityOutput::NewSource(source)
+        );
+        // Unchanged environment is not sent
+        assert!(output.environment.is_none());
+
+        let environment = Environment {
+            background_color: Vec3::ZERO,
+            ..Default::default()
+        };
+        world.set_environment(environment.clone());
+        assert_eq!(world.tick(dt).environment, Some(environment.clone()));
+        assert!(world.tick(dt).environment.is_none());
+        assert_eq!(world.environment(), &environment);
+    }
+
+    #[test]
+    fn test_scripted_objects() {
+        let dt = Duration::from_millis(50);
+        let path = std::env::temp_dir().join(format!("ping-{}.rhai", Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            r#"
+            fn on_message(sender, message) {
+                this.position.x += 1.0;
+                if message == "ping" {
+                    this.send(sender, "pong");
+                }
+            }
+            "#,
+        )
+        .unwrap();
+        let mut world = World::default();
+        let script = world.scripts.load(&path).unwrap();
+        let (a, b) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        for id in [a, b] {
+            let object = ObjectEntity::from(ObjectEntityState {
+                base: BaseEntityData {
+                    id,
+                    position: Vec3::ZERO,
+                },
+                resource: EntityResourceData::Box,
+                owner: None,
+                permissions: ObjectPermissions::NONE,
+            })
+            .with_script(EntityScript::new(script.clone()));
+            world
+                .entities
+                .insert(object, &mut world.tick_output)
+                .unwrap();
+        }
+
+        world.send::<ObjectEntity>(
+            a,
+            ObjectEntityMessage::Script {
+                sender: b,
+                message: String::from("ping"),
+            },
+        );
+        let output = world.tick(dt);
+        let moved = output
+            .entity_outputs
+            .get(&EntityKind::OBJECT)
+            .iter()
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        assert_eq!(moved, [a, b]);
+        let objects = world.entities.items::<ObjectEntity>().unwrap();
+        assert_eq!(objects.get(b).unwrap().position(), Vec3::X);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_scripted_bad_position() {
+        let path = std::env::temp_dir().join(format!("escape-{}.rhai", Uuid::new_v4()));
+        std::fs::write(
+            &path,
+            r#"
+            fn on_message(sender, message) {
+                this.position.y = 1.0e30;
+                this.send(sender, "escaped");
+            }
+            "#,
+        )
+        .unwrap();
+        let mut world = World::default();
+        let script = world.scripts.load(&path).unwrap();
+        let id = Uuid::from_u128(1);
+        let object = ObjectEntity::from(ObjectEntityState {
+            base: BaseEntityData {
+                id,
+                position: Vec3::ZERO,
+            },
+            resource: EntityResourceData::Box,
+            owner: None,
+            permissions: ObjectPermissions::NONE,
+        })
+        .with_script(EntityScript::new(script));
+        world
+            .entities
+            .insert(object, &mut world.tick_output)
+            .unwrap();
+
+        world.send::<ObjectEntity>(
+            id,
+            ObjectEntityMessage::Script {
+                sender: id,
+                message: String::new(),
+            },
+        );
+        let output = world.tick(Duration::from_millis(50));
+        assert!(output.entity_outputs.is_empty());
+        let objects = world.entities.items::<ObjectEntity>().unwrap();
+        let object = objects.get(id).unwrap();
+        assert_eq!(object.position(), Vec3::ZERO);
+        assert!(object.script().unwrap().has_failed());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_whisper_to_unknown_player() {
+        let mut world = World::default();
+        let sender = Uuid::from_u128(1);
+        world.push_chat_message(ChatMessage {
+            sender,
+            timestamp: 0,
+            target: Some(Uuid::from_u128(2)),
+            text: String::from("hello"),
+        });
+        let output = world.tick(Duration::from_millis(50));
+        assert!(output.chat_messages.is_empty());
+        assert_eq!(
+            output.chat_rejections,
+            [ChatRejection {
+                sender,
+                reason: ChatRejectReason::UnknownTarget,
+            }]
+        );
     }
 }
@@ -0,0 +1,632 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
+
+use glam::Vec3;
+use renderer_protocol::{
+    entity::EntityKind,
+    movement::{GRAVITY, GROUND_HEIGHT},
+};
+use uuid::Uuid;
+
+use super::spatial::SpatialIndex;
+
+/// Below this, distances are treated as zero when picking a contact normal.
+const EPSILON: f32 = 1e-5;
+/// Contacts pushing a body up at least this steeply count as standing on
+/// something.
+const MIN_GROUND_NORMAL_Y: f32 = 0.7;
+
+#[derive(Debug, Clone)]
+pub struct PhysicsConfig {
+    pub gravity: Vec3,
+    /// Height of the infinite ground plane, none for no ground.
+    pub ground_height: Option<f32>,
+    /// Fraction of horizontal velocity lost per second on the ground.
+    pub ground_friction: f32,
+    /// Collision resolving passes per tick.
+    pub iterations: usize,
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        Self {
+            gravity: GRAVITY,
+            ground_height: Some(GROUND_HEIGHT),
+            ground_friction: 4.0,
+            iterations: 4,
+        }
+    }
+}
+
+/// Collision shape, centered on the entity position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Aabb {
+        half_extents: Vec3,
+    },
+    /// Upright capsule, `half_height` being half the length of its segment.
+    Capsule {
+        radius: f32,
+        half_height: f32,
+    },
+}
+
+impl Shape {
+    /// Distance from the center to the bottom of the shape.
+    fn bottom(&self) -> f32 {
+        match self {
+            Shape::Aabb { half_extents } => half_extents.y,
+            Shape::Capsule {
+                radius,
+                half_height,
+            } => half_height + radius,
+        }
+    }
+
+    fn bounding_radius(&self) -> f32 {
+        match self {
+            Shape::Aabb { half_extents } => half_extents.length(),
+            Shape::Capsule {
+                radius,
+                half_height,
+            } => half_height + radius,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Motion {
+    /// Never moves, only pushes others out.
+    Static,
+    /// Moved by its entity, pushed out of static bodies but not affected by
+    /// gravity or dynamic bodies.
+    Kinematic,
+    /// Falls and gets pushed around.
+    Dynamic { velocity: Vec3, mass: f32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Body {
+    pub shape: Shape,
+    pub motion: Motion,
+    /// Whether the body stood on the ground or on another body after the
+    /// last step.
+    pub grounded: bool,
+}
+
+impl Body {
+    pub fn new(shape: Shape, motion: Motion) -> Self {
+        Self {
+            shape,
+            motion,
+            grounded: false,
+        }
+    }
+
+    pub fn velocity(&self) -> Vec3 {
+        match self.motion {
+            Motion::Dynamic { velocity, .. } => velocity,
+            Motion::Static | Motion::Kinematic => Vec3::ZERO,
+        }
+    }
+}
+
+/// Body of an entity while the step runs.
+#[derive(Debug, Clone)]
+pub(super) struct PhysicsBody {
+    pub kind: EntityKind,
+    pub position: Vec3,
+    pub body: Body,
+}
+
+/// How much of a penetration `motion` takes when pushed out of `other`.
+fn push_weight(motion: &Motion, other: &Motion) -> f32 {
+    match (motion, other) {
+        (Motion::Static, _) => 0.0,
+        (Motion::Kinematic, Motion::Dynamic { .. }) => 0.0,
+        (Motion::Kinematic, _) => 1.0,
+        (Motion::Dynamic { mass, .. }, Motion::Dynamic { .. }) => 1.0 / mass.max(EPSILON),
+        (Motion::Dynamic { .. }, _) => 1.0,
+    }
+}
+
+fn aabb_contact(a: Vec3, a_half: Vec3, b: Vec3, b_half: Vec3) -> Option<(Vec3, f32)> {
+    let delta = a - b;
+    let overlap = a_half + b_half - delta.abs();
+    if overlap.min_element() <= 0.0 {
+        return None;
+    }
+    let sign = |value: f32| if value < 0.0 { -1.0 } else { 1.0 };
+    Some(if overlap.x <= overlap.y && overlap.x <= overlap.z {
+        (Vec3::X * sign(delta.x), overlap.x)
+    } else if overlap.y <= overlap.z {
+        (Vec3::Y * sign(delta.y), overlap.y)
+    } else {
+        (Vec3::Z * sign(delta.z), overlap.z)
+    })
+}
+
+/// Closest points of two vertical ranges, as the offset from the second to
+/// the first, zero when they overlap.
+fn vertical_gap(a_min: f32, a_max: f32, b_min: f32, b_max: f32) -> f32 {
+    if a_min > b_max {
+        a_min - b_max
+    } else if b_min > a_max {
+        a_max - b_min
+    } else {
+        0.0
+    }
+}
+
+fn capsule_aabb_contact(
+    center: Vec3,
+    radius: f32,
+    half_height: f32,
+    b: Vec3,
+    b_half: Vec3,
+) -> Option<(Vec3, f32)> {
+    let (b_min, b_max) = (b - b_half, b + b_half);
+    let closest = center.clamp(b_min, b_max);
+    let offset = Vec3::new(
+        center.x - closest.x,
+        vertical_gap(
+            center.y - half_height,
+            center.y + half_height,
+            b_min.y,
+            b_max.y,
+        ),
+        center.z - closest.z,
+    );
+    let distance = offset.length();
+    if distance >= radius {
+        return None;
+    }
+    if distance > EPSILON {
+        return Some((offset / distance, radius - distance));
+    }
+    // Segment inside the box, fall back to the bounding box
+    aabb_contact(
+        center,
+        Vec3::new(radius, half_height + radius, radius),
+        b,
+        b_half,
+    )
+}
+
+fn capsule_contact(
+    a: Vec3,
+    a_radius: f32,
+    a_half_height: f32,
+    b: Vec3,
+    b_radius: f32,
+    b_half_height: f32,
+) -> Option<(Vec3, f32)> {
+    let offset = Vec3::new(
+        a.x - b.x,
+        vertical_gap(
+            a.y - a_half_height,
+            a.y + a_half_height,
+            b.y - b_half_height,
+            b.y + b_half_height,
+        ),
+        a.z - b.z,
+    );
+    let radius = a_radius + b_radius;
+    let distance = offset.length();
+    if distance >= radius {
+        return None;
+    }
+    if distance > EPSILON {
+        Some((offset / distance, radius - distance))
+    } else if a.y >= b.y {
+        Some((Vec3::Y, radius))
+    } else {
+        Some((Vec3::NEG_Y, radius))
+    }
+}
+
+/// Contact normal pointing from `b` to `a`, and penetration depth.
+fn contact(a: &PhysicsBody, b: &PhysicsBody) -> Option<(Vec3, f32)> {
+    match (a.body.shape, b.body.shape) {
+        (
+            Shape::Aabb {
+                half_extents: a_half,
+            },
+            Shape::Aabb {
+                half_extents: b_half,
+            },
+        ) => aabb_contact(a.position, a_half, b.position, b_half),
+        (
+            Shape::Capsule {
+                radius,
+                half_height,
+            },
+            Shape::Aabb { half_extents },
+        ) => capsule_aabb_contact(a.position, radius, half_height, b.position, half_extents),
+        (
+            Shape::Aabb { half_extents },
+            Shape::Capsule {
+                radius,
+                half_height,
+            },
+        ) => capsule_aabb_contact(b.position, radius, half_height, a.position, half_extents)
+            .map(|(normal, depth)| (-normal, depth)),
+        (
+            Shape::Capsule {
+                radius: a_radius,
+                half_height: a_half_height,
+            },
+            Shape::Capsule {
+                radius: b_radius,
+                half_height: b_half_height,
+            },
+        ) => capsule_contact(
+            a.position,
+            a_radius,
+            a_half_height,
+            b.position,
+            b_radius,
+            b_half_height,
+        ),
+    }
+}
+
+/// Move `body` by `offset`, and stop its velocity going against `normal`.
+fn push(body: &mut PhysicsBody, offset: Vec3, normal: Vec3) {
+    body.position += offset;
+    if normal.y >= MIN_GROUND_NORMAL_Y {
+        body.body.grounded = true;
+    }
+    if let Motion::Dynamic { velocity, .. } = &mut body.body.motion {
+        let into = velocity.dot(normal);
+        if into < 0.0 {
+            *velocity -= normal * into;
+        }
+    }
+}
+
+fn resolve_pair(bodies: &mut BTreeMap<Uuid, PhysicsBody>, a_id: Uuid, b_id: Uuid) {
+    let (Some(a), Some(b)) = (bodies.get(&a_id), bodies.get(&b_id)) else {
+        return;
+    };
+    let Some((normal, depth)) = contact(a, b) else {
+        return;
+    };
+    let a_weight = push_weight(&a.body.motion, &b.body.motion);
+    let b_weight = push_weight(&b.body.motion, &a.body.motion);
+    let total = a_weight + b_weight;
+    if total <= 0.0 {
+        return;
+    }
+    if let Some(a) = bodies.get_mut(&a_id) {
+        push(a, normal * depth * a_weight / total, normal);
+    }
+    if let Some(b) = bodies.get_mut(&b_id) {
+        push(b, -normal * depth * b_weight / total, -normal);
+    }
+}
+
+/// Lift the body out of the ground. Returns whether it rests on it.
+fn resolve_ground(body: &mut PhysicsBody, ground: f32) -> bool {
+    if body.body.motion == Motion::Static {
+        return false;
+    }
+    let bottom = body.position.y - body.body.shape.bottom();
+    if bottom > ground + EPSILON {
+        return false;
+    }
+    push(body, Vec3::Y * (ground - bottom).max(0.0), Vec3::Y);
+    true
+}
+
+/// Pairs of bodies that may touch this step, at least one of them moving.
+/// Sorted, so that resolving is the same on every run.
+fn candidate_pairs(
+    bodies: &BTreeMap<Uuid, PhysicsBody>,
+    index: &SpatialIndex,
+    dt: f32,
+) -> BTreeSet<(Uuid, Uuid)> {
+    let max_radius = bodies
+        .values()
+        .map(|body| body.body.shape.bounding_radius())
+        .fold(0.0, f32::max);
+    let max_travel = bodies
+        .values()
+        .map(|body| body.body.velocity().length() * dt)
+        .fold(0.0, f32::max);
+
+    let mut pairs = BTreeSet::new();
+    for (id, body) in bodies {
+        if body.body.motion == Motion::Static {
+            continue;
+        }
+        // The index still has the positions from before this step
+        let radius = body.body.shape.bounding_radius() + max_radius + max_travel * 2.0;
+        for other in index.query_radius(body.position, radius) {
+            if other != *id && bodies.contains_key(&other) {
+                pairs.insert((*id.min(&other), *id.max(&other)));
+            }
+        }
+    }
+    pairs
+}
+
+/// Run one physics step over the bodies, keyed by entity id.
+pub(super) fn step(
+    config: &PhysicsConfig,
+    dt: Duration,
+    bodies: &mut BTreeMap<Uuid, PhysicsBody>,
+    index: &SpatialIndex,
+) {
+    let dt = dt.as_secs_f32();
+    for body in bodies.values_mut() {
+        body.body.grounded = false;
+        if let Motion::Dynamic { velocity, .. } = &mut body.body.motion {
+            *velocity += config.gravity * dt;
+            body.position += *velocity * dt;
+        }
+    }
+
+    let pairs = candidate_pairs(bodies, index, dt);
+    for _ in 0..config.iterations {
+        for (a, b) in &pairs {
+            resolve_pair(bodies, *a, *b);
+        }
+        if let Some(ground) = config.ground_height {
+            for body in bodies.values_mut() {
+                resolve_ground(body, ground);
+            }
+        }
+    }
+
+    let Some(ground) = config.ground_height else {
+        return;
+    };
+    let friction = (1.0 - config.ground_friction * dt).max(0.0);
+    for body in bodies.values_mut() {
+        if !resolve_ground(body, ground) {
+            continue;
+        }
+        if let Motion::Dynamic { velocity, .. } = &mut body.body.motion {
+            velocity.x *= friction;
+            velocity.z *= friction;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use glam::Vec3;
+    use renderer_protocol::{
+        entity::{BaseEntityData, EntityResourceData, ObjectEntityState, ObjectPermissions},
+        input::{PlayerEntityInput, PlayerMove},
+    };
+    use uuid::Uuid;
+
+    use crate::{
+        entity::{
+            object::ObjectEntity,
+            player::{PlayerEntity, PlayerEntityMessage},
+            Entity,
+        },
+        world::World,
+    };
+
+    use super::{Body, Motion, Shape};
+
+    const DT: Duration = Duration::from_millis(50);
+
+    fn object(id: u128, position: Vec3, half_extents: Vec3, motion: Motion) -> ObjectEntity {
+        ObjectEntity::from(ObjectEntityState {
+            base: BaseEntityData {
+                id: Uuid::from_u128(id),
+                position,
+            },
+            resource: EntityResourceData::Crosshair,
+            owner: None,
+            permissions: ObjectPermissions::NONE,
+        })
+        .with_body(Body::new(Shape::Aabb { half_extents }, motion))
+    }
+
+    fn dynamic() -> Motion {
+        Motion::Dynamic {
+            velocity: Vec3::ZERO,
+            mass: 1.0,
+        }
+    }
+
+    fn position(world: &World, id: u128) -> Vec3 {
+        world
+            .entities
+            .items::<ObjectEntity>()
+            .unwrap()
+            .get(Uuid::from_u128(id))
+            .unwrap()
+            .position()
+    }
+
+    fn run(world: &mut World, ticks: usize) {
+        for _ in 0..ticks {
+            let _ = world.tick(DT);
+        }
+    }
+
+    #[test]
+    fn test_fall_to_ground() {
+        let mut world = World::default();
+        let mut output = Default::default();
+        world
+            .entities
+            .insert(
+                object(1, Vec3::new(0.0, 10.0, 0.0), Vec3::splat(0.5), dynamic()),
+                &mut output,
+            )
+            .unwrap();
+
+        run(&mut world, 5);
+        let falling = position(&world, 1);
+        assert!(falling.y < 10.0 && falling.y > 0.5);
+
+        run(&mut world, 100);
+        let landed = position(&world, 1);
+        assert!((landed.y - 0.5).abs() < 1e-4, "{:?}", landed);
+        let object = world.entities.items::<ObjectEntity>().unwrap();
+        let body = object.get(Uuid::from_u128(1)).unwrap().body().unwrap();
+        assert!(body.velocity().length() < 1e-3);
+
+        // Resting objects don't send positions
+        let output = world.tick(DT);
+        assert!(output.entity_outputs.is_empty());
+    }
+
+    #[test]
+    fn test_stack_on_static() {
+        let mut world = World::default();
+        let mut output = Default::default();
+        world
+            .entities
+            .insert(
+                object(
+                    1,
+                    Vec3::new(0.0, 1.0, 0.0),
+                    Vec3::splat(1.0),
+                    Motion::Static,
+                ),
+                &mut output,
+            )
+            .unwrap();
+        world
+            .entities
+            .insert(
+                object(2, Vec3::new(0.2, 5.0, 0.0), Vec3::splat(0.5), dynamic()),
+                &mut output,
+            )
+            .unwrap();
+
+        run(&mut world, 100);
+        assert_eq!(position(&world, 1), Vec3::new(0.0, 1.0, 0.0));
+        let stacked = position(&world, 2);
+        assert!((stacked.y - 2.5).abs() < 1e-3, "{:?}", stacked);
+        assert!((stacked.x - 0.2).abs() < 1e-3, "{:?}", stacked);
+    }
+
+    #[test]
+    fn test_player_collision() {
+        let mut world = World::default();
+        let mut output = Default::default();
+        let player_id = Uuid::from_u128(10);
+        world
+            .insert_player(PlayerEntity::new(player_id, Vec3::ZERO))
+            .unwrap();
+        world
+            .entities
+            .insert(
+                object(
+                    1,
+                    Vec3::new(5.0, 1.0, 0.0),
+                    Vec3::splat(1.0),
+                    Motion::Static,
+                ),
+                &mut output,
+            )
+            .unwrap();
+        world
+            .entities
+            .insert(
+                object(2, Vec3::new(0.0, 0.5, 5.0), Vec3::splat(0.5), dynamic()),
+                &mut output,
+            )
+            .unwrap();
+        let player_position = |world: &World| {
+            world
+                .entities
+                .items::<PlayerEntity>()
+                .unwrap()
+                .get(player_id)
+                .unwrap()
+                .position()
+        };
+
+        let walk = |world: &mut World, direction: Vec3, jump: bool, ticks: usize| {
+            for sequence in 0..ticks {
+                world.entities.process_player_inputs(
+                    player_id,
+                    PlayerEntityInput::Move(PlayerMove {
+                        sequence: sequence as u32,
+                        direction,
+                        jump,
+                    }),
+                );
+                let _ = world.tick(DT);
+            }
+        };
+
+        // Lifted out of the ground
+        run(&mut world, 1);
+        assert!(player_position(&world).abs_diff_eq(Vec3::new(0.0, 0.9, 0.0), 1e-4));
+
+        // Positions sent by clients are ignored
+        world.entities.process_player_inputs(
+            player_id,
+            PlayerEntityInput::NewPosition(Vec3::new(0.0, 50.0, 0.0)),
+        );
+        run(&mut world, 1);
+        assert!(player_position(&world).abs_diff_eq(Vec3::new(0.0, 0.9, 0.0), 1e-4));
+
+        // Can't walk into a static box
+        walk(&mut world, Vec3::X, false, 40);
+        let blocked = player_position(&world);
+        assert!((blocked.x - 3.7).abs() < 1e-3, "{:?}", blocked);
+        assert!((blocked.y - 0.9).abs() < 1e-3, "{:?}", blocked);
+
+        // Jumping only works from the ground, so holding it doesn't fly
+        walk(&mut world, Vec3::ZERO, true, 4);
+        let jumped = player_position(&world).y;
+        assert!(jumped > 1.2, "{}", jumped);
+        walk(&mut world, Vec3::ZERO, true, 2);
+        let highest = (0..40)
+            .map(|_| {
+                walk(&mut world, Vec3::ZERO, true, 1);
+                player_position(&world).y
+            })
+            .fold(0.0, f32::max);
+        assert!(highest < 0.9 + 1.5, "{}", highest);
+
+        // Pushes a dynamic box out of the way
+        world.send::<PlayerEntity>(
+            player_id,
+            PlayerEntityMessage::NewPosition(Vec3::new(0.0, 0.9, 3.0)),
+        );
+        walk(&mut world, Vec3::Z, false, 10);
+        let pushed = position(&world, 2);
+        assert!(pushed.z > 5.5, "{:?}", pushed);
+        assert!(player_position(&world).z > 4.0);
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let simulate = || {
+            let mut world = World::default();
+            let mut output = Default::default();
+            for i in 1..=20 {
+                let position =
+                    Vec3::new((i % 4) as f32 * 0.7, 2.0 + i as f32, (i / 4) as f32 * 0.3);
+                world
+                    .entities
+                    .insert(
+                        object(i, position, Vec3::splat(0.5), dynamic()),
+                        &mut output,
+                    )
+                    .unwrap();
+            }
+            run(&mut world, 60);
+            (1..=20).map(|i| position(&world, i)).collect::<Vec<_>>()
+        };
+        assert_eq!(simulate(), simulate());
+    }
+}
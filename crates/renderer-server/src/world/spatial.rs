@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use glam::{I64Vec3, IVec3, Vec3};
+use renderer_protocol::entity::EntityKind;
+use uuid::Uuid;
+
+pub const DEFAULT_CELL_SIZE: f32 = 16.0;
+
+/// Axis aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self {
+            min: min.min(max),
+            max: min.max(max),
+        }
+    }
+
+    pub fn from_center(center: Vec3, half_extents: Vec3) -> Self {
+        Self::new(center - half_extents, center + half_extents)
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.max).all() && self.max.cmpge(other.min).all()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Placement {
+    kind: EntityKind,
+    position: Vec3,
+    cell: IVec3,
+}
+
+/// Uniform grid over entity positions, so that proximity queries only look
+/// at nearby cells instead of every entity.
+#[derive(Debug)]
+pub struct SpatialIndex {
+    cell_size: f32,
+    cells: HashMap<IVec3, Vec<Uuid>>,
+    placements: HashMap<Uuid, Placement>,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self::new(DEFAULT_CELL_SIZE)
+    }
+}
+
+impl SpatialIndex {
+    pub fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "Cell size must be positive");
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            placements: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.placements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.placements.is_empty()
+    }
+
+    pub fn kind(&self, id: Uuid) -> Option<&EntityKind> {
+        self.placements.get(&id).map(|placement| &placement.kind)
+    }
+
+    pub fn position(&self, id: Uuid) -> Option<Vec3> {
+        self.placements.get(&id).map(|placement| placement.position)
+    }
+
+    fn cell(&self, position: Vec3) -> IVec3 {
+        (position / self.cell_size).floor().as_ivec3()
+    }
+
+    fn remove_from_cell(&mut self, cell: IVec3, id: Uuid) {
+        if let Some(ids) = self.cells.get_mut(&cell) {
+            if let Some(index) = ids.iter().position(|other| *other == id) {
+                ids.swap_remove(index);
+            }
+            if ids.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+
+    /// Insert an entity, or move it if it is already indexed.
+    pub fn insert(&mut self, kind: EntityKind, id: Uuid, position: Vec3) {
+        self.remove(id);
+        let cell = self.cell(position);
+        self.cells.entry(cell).or_default().push(id);
+        self.placements.insert(
+            id,
+            Placement {
+                kind,
+                position,
+                cell,
+            },
+        );
+    }
+
+    /// Move an indexed entity. Returns false when the entity is unknown.
+    pub fn update(&mut self, id: Uuid, position: Vec3) -> bool {
+        let cell = self.cell(position);
+        let Some(placement) = self.placements.get_mut(&id) else {
+            return false;
+        };
+        let old_cell = placement.cell;
+        placement.position = position;
+        placement.cell = cell;
+        if old_cell != cell {
+            self.remove_from_cell(old_cell, id);
+            self.cells.entry(cell).or_default().push(id);
+        }
+        true
+    }
+
+    pub fn remove(&mut self, id: Uuid) -> bool {
+        match self.placements.remove(&id) {
+            Some(placement) => {
+                self.remove_from_cell(placement.cell, id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove every entity of a kind.
+    pub fn remove_kind(&mut self, kind: &EntityKind) {
+        let ids: Vec<Uuid> = self
+            .placements
+            .iter()
+            .filter(|(_, placement)| placement.kind == *kind)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids {
+            self.remove(id);
+        }
+    }
+
+    /// Call `f` with every entity in a cell touching `aabb`, to be filtered
+    /// further by the caller.
+    fn for_each_candidate(&self, aabb: &Aabb, mut f: impl FnMut(Uuid, &Placement)) {
+        let min = self.cell(aabb.min);
+        let max = self.cell(aabb.max);
+        let span = max.as_i64vec3() - min.as_i64vec3() + I64Vec3::ONE;
+        let cell_count = span.x.saturating_mul(span.y).saturating_mul(span.z);
+
+        // Huge queries are cheaper over the occupied cells than over the
+        // covered ones
+        if cell_count > self.cells.len() as i64 {
+            for (cell, ids) in &self.cells {
+                if cell.cmpge(min).all() && cell.cmple(max).all() {
+                    for id in ids {
+                        f(*id, &self.placements[id]);
+                    }
+                }
+            }
+            return;
+        }
+
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    let Some(ids) = self.cells.get(&IVec3::new(x, y, z)) else {
+                        continue;
+                    };
+                    for id in ids {
+                        f(*id, &self.placements[id]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Entities within `radius` of `center`.
+    pub fn query_radius(&self, center: Vec3, radius: f32) -> Vec<Uuid> {
+        let aabb = Aabb::from_center(center, Vec3::splat(radius));
+        let radius_squared = radius * radius;
+        let mut ids = Vec::new();
+        self.for_each_candidate(&aabb, |id, placement| {
+            if placement.position.distance_squared(center) <= radius_squared {
+                ids.push(id);
+            }
+        });
+        ids
+    }
+
+    /// Entities inside `aabb`.
+    pub fn query_aabb(&self, aabb: &Aabb) -> Vec<Uuid> {
+        let mut ids = Vec::new();
+        self.for_each_candidate(aabb, |id, placement| {
+            if aabb.contains(placement.position) {
+                ids.push(id);
+            }
+        });
+        ids
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use glam::Vec3;
+    use renderer_protocol::entity::EntityKind;
+    use uuid::Uuid;
+
+    use super::{Aabb, SpatialIndex};
+
+    fn linear_radius(positions: &HashMap<Uuid, Vec3>, center: Vec3, radius: f32) -> Vec<Uuid> {
+        let mut ids: Vec<Uuid> = positions
+            .iter()
+            .filter(|(_, position)| position.distance_squared(center) <= radius * radius)
+            .map(|(id, _)| *id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_spatial_index() {
+        let mut index = SpatialIndex::new(4.0);
+        let mut positions = HashMap::new();
+        for i in 0..1000u32 {
+            let id = Uuid::from_u128(i as u128);
+            // Spread over negative and positive cells
+            let position = Vec3::new(
+                (i % 10) as f32 * 3.0 - 15.0,
+                (i / 10 % 10) as f32 * 3.0 - 15.0,
+                (i / 100) as f32 * 3.0 - 15.0,
+            );
+            index.insert(EntityKind::OBJECT, id, position);
+            positions.insert(id, position);
+        }
+        assert_eq!(index.len(), 1000);
+
+        for (center, radius) in [
+            (Vec3::ZERO, 5.0),
+            (Vec3::new(-14.0, 3.0, 7.5), 8.0),
+            (Vec3::ZERO, 1000.0),
+            (Vec3::splat(100.0), 10.0),
+        ] {
+            let mut ids = index.query_radius(center, radius);
+            ids.sort();
+            assert_eq!(ids, linear_radius(&positions, center, radius));
+        }
+
+        // Moving across cells
+        let id = Uuid::from_u128(0);
+        assert!(index.update(id, Vec3::splat(50.0)));
+        assert_eq!(index.query_radius(Vec3::splat(50.0), 1.0), [id]);
+        assert!(!index.query_radius(Vec3::splat(-15.0), 0.5).contains(&id));
+
+        let aabb = Aabb::new(Vec3::splat(49.0), Vec3::splat(51.0));
+        assert_eq!(index.query_aabb(&aabb), [id]);
+
+        assert!(index.remove(id));
+        assert!(index.query_aabb(&aabb).is_empty());
+        assert!(!index.update(id, Vec3::ZERO));
+
+        index.remove_kind(&EntityKind::OBJECT);
+        assert!(index.is_empty());
+    }
+}
@@ -0,0 +1,43 @@
+use std::collections::VecDeque;
+
+use renderer_protocol::{
+    chat::{ChatMessage, ChatRejectReason},
+    intent::{IntentRejectReason, ObjectIntent},
+};
+
+const CHAT_HISTORY_LENGTH: usize = 200;
+
+#[derive(Debug, Clone)]
+pub enum ChatEntry {
+    Message(ChatMessage),
+    Rejected(ChatRejectReason),
+    IntentRejected {
+        intent: ObjectIntent,
+        reason: IntentRejectReason,
+    },
+}
+
+/// Recent chat history, oldest entry first.
+#[derive(Debug, Default)]
+pub struct ChatLog {
+    entries: VecDeque<ChatEntry>,
+}
+
+impl ChatLog {
+    pub fn push(&mut self, entry: ChatEntry) {
+        if self.entries.len() == CHAT_HISTORY_LENGTH {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn extend(&mut self, messages: impl IntoIterator<Item = ChatMessage>) {
+        for message in messages {
+            self.push(ChatEntry::Message(message));
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ChatEntry> {
+        self.entries.iter()
+    }
+}
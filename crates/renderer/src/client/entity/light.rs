@@ -0,0 +1,111 @@
+use glam::Vec3;
+use renderer_protocol::entity::{
+    BaseEntityData, EntityKind, LightEntityOutput, LightEntityState, LightSource,
+};
+use uuid::Uuid;
+
+use crate::renderer::uniform::light::LightData;
+
+use super::{Entity, Output, State};
+
+#[derive(Debug, Clone)]
+pub struct LightEntity {
+    base: BaseEntityData,
+    source: LightSource,
+}
+
+impl Output for LightEntityOutput {}
+
+impl State for LightEntityState {
+    fn id(&self) -> Uuid {
+        self.base.id()
+    }
+
+    fn position(&self) -> Vec3 {
+        self.base.position()
+    }
+}
+
+impl From<LightEntityState> for LightEntity {
+    fn from(state: LightEntityState) -> Self {
+        Self {
+            base: state.base,
+            source: state.source,
+        }
+    }
+}
+
+impl LightEntity {
+    /// The light as the renderer takes it, placed at the entity position.
+    pub fn light_data(&self) -> LightData {
+        let position = self.position();
+        match self.source.clone() {
+            LightSource::Point {
+                color,
+                constant,
+                linear,
+                quadratic,
+            } => LightData::Point {
+                position,
+                color,
+                constant,
+                linear,
+                quadratic,
+            },
+            LightSource::Directional {
+                color,
+                direction,
+                constant,
+                linear,
+                quadratic,
+                range_inner,
+                range_outer,
+            } => LightData::Directional {
+                position,
+                color,
+                direction,
+                constant,
+                linear,
+                quadratic,
+                range_inner,
+                range_outer,
+            },
+            LightSource::Parallel {
+                direction,
+                color,
+                strength,
+            } => LightData::Parallel {
+                direction,
+                color,
+                strength,
+            },
+        }
+    }
+}
+
+impl Entity for LightEntity {
+    const KIND: EntityKind = EntityKind::LIGHT;
+
+    type Output = LightEntityOutput;
+    type State = LightEntityState;
+
+    fn base_data(&self) -> &BaseEntityData {
+        &self.base
+    }
+
+    fn process_output(&mut self, output: Self::Output) {
+        match output {
+            LightEntityOutput::NewPosition(new_position) => self.base.position = new_position,
+            LightEntityOutput::NewSource(source) => self.source = source,
+        }
+    }
+
+    fn describe(&self) -> Vec<String> {
+        let kind = match &self.source {
+            LightSource::Point { .. } => "Point light",
+            LightSource::Directional { .. } => "Directional light",
+            LightSource::Parallel { .. } => "Parallel light",
+        };
+        vec![String::from(kind)]
+    }
+}
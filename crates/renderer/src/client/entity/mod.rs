@@ -1,12 +1,13 @@
 use std::fmt::Debug;
 
 use glam::Vec3;
-use renderer_protocol::entity::BaseEntityData;
+use renderer_protocol::entity::{BaseEntityData, EntityKind};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::renderer::OngoingRenderState;
 
+pub mod light;
 pub mod object;
 pub mod player;
 
@@ -19,7 +20,10 @@ pub trait State: Serialize + for<'a> Deserialize<'a> + Debug + Clone + Send + Sy
 
 impl Output for () {}
 
-pub trait Entity: Debug + From<Self::State> {
+pub trait Entity: Debug + From<Self::State> + 'static {
+    /// Tag of this entity kind on the wire.
+    const KIND: EntityKind;
+
     type Output: Output;
     type State: State;
 
@@ -33,6 +37,11 @@ pub trait Entity: Debug + From<Self::State> {
 
     fn render(&self, _render_state: &mut OngoingRenderState) {}
 
+    /// Lines describing kind specific details, shown in the entity window.
+    fn describe(&self) -> Vec<String> {
+        Vec::new()
+    }
+
     fn process_output(&mut self, output: Self::Output);
 }
 
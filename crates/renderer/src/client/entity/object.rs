@@ -1,6 +1,7 @@
 use glam::Vec3;
 use renderer_protocol::entity::{
-    BaseEntityData, EntityResourceData, ObjectEntityOutput, ObjectEntityState,
+    BaseEntityData, EntityKind, EntityResourceData, ObjectEntityOutput, ObjectEntityState,
+    ObjectPermissions,
 };
 use uuid::Uuid;
 
@@ -10,6 +11,8 @@ use super::{Entity, Output, State};
 pub struct ObjectEntity {
     base: BaseEntityData,
     resource: EntityResourceData,
+    owner: Option<Uuid>,
+    permissions: ObjectPermissions,
 }
 
 impl Output for ObjectEntityOutput {}
@@ -29,11 +32,15 @@ impl From<ObjectEntityState> for ObjectEntity {
         Self {
             base: state.base,
             resource: state.resource,
+            owner: state.owner,
+            permissions: state.permissions,
         }
     }
 }
 
 impl Entity for ObjectEntity {
+    const KIND: EntityKind = EntityKind::OBJECT;
+
     type Output = ObjectEntityOutput;
     type State = ObjectEntityState;
 
@@ -46,10 +53,31 @@ impl Entity for ObjectEntity {
             ObjectEntityOutput::NewPosition(new_position) => self.base.position = new_position,
         }
     }
-}
 
-impl ObjectEntity {
-    pub fn resource(&self) -> &EntityResourceData {
-        &self.resource
+    fn describe(&self) -> Vec<String> {
+        let resource = match &self.resource {
+            EntityResourceData::Box => String::from("Box"),
+            EntityResourceData::Crosshair => String::from("Crosshair"),
+            EntityResourceData::External { bundle_index, link } => {
+                format!("Resource: {} ({})", bundle_index, link)
+            }
+        };
+        let owner = match self.owner {
+            Some(owner) => format!("Owner: {}", owner),
+            None => String::from("Owner: server"),
+        };
+        let mut others = Vec::new();
+        if self.permissions.contains(ObjectPermissions::MOVE) {
+            others.push("move");
+        }
+        if self.permissions.contains(ObjectPermissions::DELETE) {
+            others.push("delete");
+        }
+        let permissions = if others.is_empty() {
+            String::from("Others may: nothing")
+        } else {
+            format!("Others may: {}", others.join(", "))
+        };
+        vec![resource, owner, permissions]
     }
 }
@@ -1,9 +1,5 @@
-use renderer_protocol::{
-    entity::{BaseEntityData, PlayerEntityOutput},
-    input::PlayerEntityInput,
-};
-
-use crate::renderer::camera::Camera;
+use glam::Vec3;
+use renderer_protocol::entity::{BaseEntityData, EntityKind, PlayerEntityOutput};
 
 use super::{Entity, Output};
 
@@ -21,6 +17,8 @@ impl From<BaseEntityData> for PlayerEntity {
 }
 
 impl Entity for PlayerEntity {
+    const KIND: EntityKind = EntityKind::PLAYER;
+
     type Output = PlayerEntityOutput;
     type State = BaseEntityData;
 
@@ -33,16 +31,16 @@ impl Entity for PlayerEntity {
             PlayerEntityOutput::NewPosition(new_position) => {
                 self.base_data.position = new_position;
             }
+            PlayerEntityOutput::Acknowledged { position, .. } => {
+                self.base_data.position = position;
+            }
         }
     }
 }
 
 impl PlayerEntity {
-    pub fn send_input(&self, input: &mut Vec<PlayerEntityInput>) {
-        input.push(PlayerEntityInput::NewPosition(self.position()));
-    }
-
-    pub fn update(&mut self, camera: &Camera) {
-        self.base_data.position = camera.view.eye;
+    /// Show the local player where the client predicts it.
+    pub fn set_predicted_position(&mut self, position: Vec3) {
+        self.base_data.position = position;
     }
 }
@@ -1,11 +1,24 @@
 use std::{
     error::Error,
     fmt::{self, Display, Formatter},
+    mem,
+    time::Duration,
 };
 
+use chat::{ChatEntry, ChatLog};
+use entity::{player::PlayerEntity, Entity};
+
+use glam::Vec3;
 use log::{info, warn};
 use renderer_protocol::{
+    entity::{EntityKind, PlayerEntityOutput},
+    environment::Environment,
+    input::PlayerEntityInput,
+    intent::{ObjectIntent, Role},
     message::{ClientMessage, ServerMessage},
+    movement::Prediction,
+    tick::TickOutput,
+    traffic::TrafficStats,
     version::VersionData,
 };
 use uuid::Uuid;
@@ -16,10 +29,14 @@ use crate::{
         connect::{ConnectParam, ConnectionStatus},
         GuiState,
     },
-    renderer::Renderer,
+    renderer::{camera::PositionController, Renderer},
     transport::{Transport, TransportState},
 };
 
+/// Height of the camera above the center of the player.
+const EYE_OFFSET: Vec3 = Vec3::new(0.0, 0.7, 0.0);
+
+pub mod chat;
 pub mod entity;
 pub mod world;
 
@@ -28,6 +45,7 @@ enum ConnectionError {
     Handshake,
     WorldSync,
     Message,
+    NotConnected,
 }
 
 impl Display for ConnectionError {
@@ -36,16 +54,41 @@ impl Display for ConnectionError {
             ConnectionError::Handshake => write!(f, "Bad handshake"),
             ConnectionError::WorldSync => write!(f, "Bad world sync message"),
             ConnectionError::Message => writeln!(f, "Bad message"),
+            ConnectionError::NotConnected => write!(f, "Not connected to the server"),
         }
     }
 }
 
 impl Error for ConnectionError {}
 
-#[derive(Debug, Default)]
+fn apply_environment(renderer: &mut Renderer, environment: Environment) {
+    renderer.set_background_color(environment.background_color);
+    renderer.set_global_light_param(environment.light_param.into());
+}
+
+/// Correct the prediction with the latest move the server acknowledged for
+/// the local player.
+fn reconcile(prediction: &mut Prediction, player_id: Uuid, tick_output: &TickOutput) {
+    for (id, data) in tick_output.entity_outputs.get(&EntityKind::PLAYER) {
+        if *id != player_id {
+            continue;
+        }
+        if let Ok(PlayerEntityOutput::Acknowledged {
+            sequence,
+            position,
+            velocity,
+        }) = data.decode()
+        {
+            prediction.reconcile(sequence, position, velocity);
+        }
+    }
+}
+
+#[derive(Debug)]
 pub enum ConnectionState {
-    #[default]
-    SendClientHandshake,
+    SendClientHandshake {
+        admin_token: Option<String>,
+    },
     WaitingServerHandshake,
     WaitingWorldSync {
         server_version: VersionData,
@@ -53,7 +96,12 @@ pub enum ConnectionState {
     Connected {
         server_version: VersionData,
         player_id: Uuid,
+        role: Role,
         world: World,
+        chat: ChatLog,
+        prediction: Prediction,
+        /// Time since the last move was sent, in seconds.
+        move_time: f32,
     },
 }
 
@@ -62,12 +110,15 @@ impl ConnectionState {
         &mut self,
         transport: &mut dyn Transport,
         renderer: &mut Renderer,
+        controller: &PositionController,
+        duration: Duration,
     ) -> Result<bool, Box<dyn Error>> {
         match self {
-            ConnectionState::SendClientHandshake => {
+            ConnectionState::SendClientHandshake { admin_token } => {
                 info!("Handshake sent");
                 transport.send(ClientMessage::Handshake {
                     version: VersionData::current(),
+                    admin_token: admin_token.take(),
                 })?;
 
                 *self = ConnectionState::WaitingServerHandshake;
@@ -96,46 +147,96 @@ impl ConnectionState {
                 let ServerMessage::SyncWorld {
                     player_id,
                     entity_states,
+                    environment,
+                    role,
                 } = message
                 else {
                     return Err(Box::new(ConnectionError::WorldSync));
                 };
 
-                info!("Server world sync received with player id {:?}", player_id);
+                info!(
+                    "Server world sync received with player id {:?} as {:?}",
+                    player_id, role
+                );
 
-                let world = World::new(entity_states);
+                let mut world = World::new(entity_states);
+                apply_environment(renderer, environment);
+                if let Some(lights) = world.take_changed_lights() {
+                    renderer.set_lights(lights);
+                }
+                let position = world
+                    .entities
+                    .get::<PlayerEntity>(&player_id)
+                    .map_or(Vec3::ZERO, PlayerEntity::position);
 
                 *self = ConnectionState::Connected {
                     server_version: server_version.clone(),
                     player_id,
+                    role,
                     world,
+                    chat: ChatLog::default(),
+                    prediction: Prediction::new(position),
+                    move_time: 0.0,
                 };
                 Ok(true)
             }
             ConnectionState::Connected {
-                world, player_id, ..
+                world,
+                player_id,
+                chat,
+                prediction,
+                move_time,
+                ..
             } => {
-                if let Some(player) = world.entities.player.get_mut(player_id) {
-                    player.update(renderer.camera());
-
-                    let mut input = vec![];
-                    player.send_input(&mut input);
-                    transport.send(ClientMessage::PlayerInput(input))?;
-                } else {
-                    warn!("Player not found: {:?}", player_id);
-                }
-
+                *move_time += duration.as_secs_f32();
+                let mut ticked = false;
                 while let Some(message) = transport.receive()? {
                     match message {
                         ServerMessage::Handshake { .. } | ServerMessage::SyncWorld { .. } => {
                             return Err(Box::new(ConnectionError::Message));
                         }
-                        ServerMessage::TickOutput(tick_output) => {
+                        ServerMessage::TickOutput(mut tick_output) => {
                             info!("Tick output: {:?}", tick_output);
+                            chat.extend(mem::take(&mut tick_output.chat_messages));
+                            if let Some(environment) = tick_output.environment.take() {
+                                apply_environment(renderer, environment);
+                            }
+                            reconcile(prediction, *player_id, &tick_output);
                             world.update(tick_output);
+                            ticked = true;
+                        }
+                        ServerMessage::ChatRejected(reason) => {
+                            warn!("Chat message rejected: {}", reason);
+                            chat.push(ChatEntry::Rejected(reason));
+                        }
+                        ServerMessage::IntentRejected { intent, reason } => {
+                            warn!("Intent {:?} rejected: {}", intent, reason);
+                            chat.push(ChatEntry::IntentRejected { intent, reason });
                         }
                     }
                 }
+                if let Some(lights) = world.take_changed_lights() {
+                    renderer.set_lights(lights);
+                }
+
+                // The server applies one move per tick, so moves are sent as
+                // its outputs arrive, covering the frames since the last one
+                let direction = controller.walk_direction(renderer.camera());
+                let jump = controller.up > 0.0;
+                if ticked {
+                    let input = prediction.push(direction, jump, mem::take(move_time));
+                    transport.send(ClientMessage::PlayerInput(vec![PlayerEntityInput::Move(
+                        input,
+                    )]))?;
+                }
+
+                let position = prediction.preview(direction, jump, *move_time);
+                if let Some(player) = world.entities.get_mut::<PlayerEntity>(player_id) {
+                    player.set_predicted_position(position);
+                } else {
+                    warn!("Player not found: {:?}", player_id);
+                }
+                renderer.update_camera(|camera| camera.view.eye = position + EYE_OFFSET);
                 Ok(true)
             }
         }
@@ -147,12 +248,28 @@ impl ConnectionState {
             _ => None,
         }
     }
+
+    fn role(&self) -> Option<Role> {
+        match self {
+            ConnectionState::Connected { role, .. } => Some(*role),
+            _ => None,
+        }
+    }
+
+    fn chat(&self) -> Option<(Uuid, &ChatLog)> {
+        match self {
+            ConnectionState::Connected {
+                player_id, chat, ..
+            } => Some((*player_id, chat)),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug)]
 enum ClientState {
     Connecting,
-    Connected(ConnectionState),
+    Connected(Box<ConnectionState>),
     Closed,
 }
 
@@ -160,13 +277,18 @@ enum ClientState {
 pub struct Client {
     state: ClientState,
     transport: Box<dyn Transport>,
+    admin_token: Option<String>,
 }
 
 impl Client {
+    /// Exchange messages with the server. Once connected, the local player
+    /// walks as `controller` says for the `duration` of the frame.
     pub fn tick<CP: ConnectParam>(
         &mut self,
         renderer: &mut Renderer,
         gui_state: &mut GuiState<CP>,
+        controller: &PositionController,
+        duration: Duration,
     ) -> bool {
         match self.transport.state() {
             TransportState::Connecting => {
@@ -175,7 +297,8 @@ impl Client {
             }
             TransportState::Connected => {
                 if let ClientState::Connected(ref mut state) = self.state {
-                    let result = state.tick(self.transport.as_mut(), renderer);
+                    let result =
+                        state.tick(self.transport.as_mut(), renderer, controller, duration);
                     match result {
                         Ok(result) => result,
                         Err(error) => {
@@ -184,9 +307,12 @@ impl Client {
                         }
                     }
                 } else {
-                    let mut state = ConnectionState::default();
-                    let result = state.tick(self.transport.as_mut(), renderer);
-                    self.state = ClientState::Connected(state);
+                    let mut state = ConnectionState::SendClientHandshake {
+                        admin_token: self.admin_token.take(),
+                    };
+                    let result =
+                        state.tick(self.transport.as_mut(), renderer, controller, duration);
+                    self.state = ClientState::Connected(Box::new(state));
                     match result {
                         Ok(result) => result,
                         Err(error) => {
@@ -215,13 +341,48 @@ impl Client {
         }
     }
 
+    /// The local player id, along with the chat history.
+    pub fn chat(&self) -> Option<(Uuid, &ChatLog)> {
+        if let ClientState::Connected(ref state) = self.state {
+            state.chat()
+        } else {
+            None
+        }
+    }
+
+    /// Role granted by the server, once connected.
+    pub fn role(&self) -> Option<Role> {
+        if let ClientState::Connected(ref state) = self.state {
+            state.role()
+        } else {
+            None
+        }
+    }
+
+    pub fn send_intent(&mut self, intent: ObjectIntent) -> Result<(), Box<dyn Error>> {
+        if self.role().is_none() {
+            return Err(Box::new(ConnectionError::NotConnected));
+        }
+        self.transport.send(ClientMessage::ObjectIntent(intent))
+    }
+
+    pub fn send_chat(&mut self, text: String, target: Option<Uuid>) -> Result<(), Box<dyn Error>> {
+        if self.chat().is_none() {
+            return Err(Box::new(ConnectionError::NotConnected));
+        }
+        self.transport.send(ClientMessage::Chat { text, target })
+    }
+
+    pub fn traffic(&self) -> TrafficStats {
+        self.transport.traffic()
+    }
+
     pub fn connection_status(&self) -> ConnectionStatus {
         match &self.state {
             ClientState::Connecting => ConnectionStatus::Connecting,
-            ClientState::Connected(state) => match state {
-                ConnectionState::SendClientHandshake | ConnectionState::WaitingServerHandshake => {
-                    ConnectionStatus::Handshaking
-                }
+            ClientState::Connected(state) => match &**state {
+                ConnectionState::SendClientHandshake { .. }
+                | ConnectionState::WaitingServerHandshake => ConnectionStatus::Handshaking,
                 ConnectionState::WaitingWorldSync { server_version } => {
                     ConnectionStatus::SyncingWorld {
                         server_version: server_version.clone(),
@@ -233,10 +394,13 @@ impl Client {
         }
     }
 
-    pub fn new(transport: Box<dyn Transport>) -> Self {
+    /// Connect over `transport`, asking for the admin role with
+    /// `admin_token` if set.
+    pub fn new(transport: Box<dyn Transport>, admin_token: Option<String>) -> Self {
         Self {
             state: ClientState::Connecting,
             transport,
+            admin_token,
         }
     }
 }
@@ -1,122 +1,262 @@
-use std::collections::hash_map::Entry;
+use std::{
+    any::Any,
+    collections::{hash_map::Entry, BTreeMap},
+    fmt::Debug,
+    mem,
+};
 
 use egui::ahash::HashMap;
+use glam::Vec3;
 use log::warn;
 use renderer_protocol::{
-    entity::{EntitiesIds, EntitiesOutputs, EntityStates},
+    entity::{EntitiesIds, EntitiesOutputs, EntityData, EntityKind, EntityStates},
     tick::TickOutput,
 };
 use uuid::Uuid;
 
-use crate::renderer::OngoingRenderState;
+use crate::renderer::{uniform::light::LightData, OngoingRenderState};
 
-use super::entity::{object::ObjectEntity, player::PlayerEntity, Entity, State};
+use super::entity::{
+    light::LightEntity, object::ObjectEntity, player::PlayerEntity, Entity, State,
+};
+
+/// What the GUI shows of an entity, whatever its kind.
+#[derive(Debug, Clone)]
+pub struct EntitySummary {
+    pub id: Uuid,
+    pub position: Vec3,
+    pub details: Vec<String>,
+}
+
+/// Type erased map of one entity kind.
+trait EntityCollection: Debug {
+    fn add(&mut self, states: Vec<EntityData>);
+    fn remove(&mut self, ids: Vec<Uuid>);
+    fn process_output(&mut self, outputs: Vec<(Uuid, EntityData)>);
+    fn render(&self, render_state: &mut OngoingRenderState);
+    fn summaries(&self) -> Vec<EntitySummary>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
 
+#[derive(Debug)]
+struct EntityMap<E: Entity>(HashMap<Uuid, E>);
+
+impl<E: Entity> EntityCollection for EntityMap<E> {
+    fn add(&mut self, states: Vec<EntityData>) {
+        for data in states {
+            let state: E::State = match data.decode() {
+                Ok(state) => state,
+                Err(err) => {
+                    warn!("Bad {} state: {}", E::KIND, err);
+                    continue;
+                }
+            };
+            match self.0.entry(state.id()) {
+                Entry::Occupied(_) => warn!("Insert existing {}: {:?}", E::KIND, state.id()),
+                Entry::Vacant(entry) => {
+                    entry.insert(E::from(state));
+                }
+            }
+        }
+    }
+
+    fn remove(&mut self, ids: Vec<Uuid>) {
+        for id in ids {
+            if self.0.remove(&id).is_none() {
+                warn!("Remove unknown {}: {:?}", E::KIND, id)
+            }
+        }
+    }
+
+    fn process_output(&mut self, outputs: Vec<(Uuid, EntityData)>) {
+        for (id, data) in outputs {
+            let Some(entity) = self.0.get_mut(&id) else {
+                warn!("Handle output for unknown {}: {:?}", E::KIND, id);
+                continue;
+            };
+            match data.decode() {
+                Ok(output) => entity.process_output(output),
+                Err(err) => warn!("Bad {} output: {}", E::KIND, err),
+            }
+        }
+    }
+
+    fn render(&self, render_state: &mut OngoingRenderState) {
+        self.0
+            .values()
+            .for_each(|entity| entity.render(render_state));
+    }
+
+    fn summaries(&self) -> Vec<EntitySummary> {
+        self.0
+            .values()
+            .map(|entity| EntitySummary {
+                id: entity.id(),
+                position: entity.position(),
+                details: entity.describe(),
+            })
+            .collect()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Entities of every registered kind. Kinds the server sends without a
+/// matching registration are skipped.
 #[derive(Debug)]
 pub struct Entities {
-    pub player: HashMap<Uuid, PlayerEntity>,
-    pub object: HashMap<Uuid, ObjectEntity>,
+    kinds: BTreeMap<EntityKind, Box<dyn EntityCollection>>,
+}
+
+impl Default for Entities {
+    fn default() -> Self {
+        let mut entities = Self {
+            kinds: BTreeMap::new(),
+        };
+        entities.register::<ObjectEntity>();
+        entities.register::<PlayerEntity>();
+        entities.register::<LightEntity>();
+        entities
+    }
 }
 
 impl From<EntityStates> for Entities {
     fn from(states: EntityStates) -> Self {
-        macro_rules! create_entities {
-            ($type:ty, $entity:ident) => {
-                states
-                    .$entity
-                    .into_iter()
-                    .map(|state| (state.id(), <$type>::from(state)))
-                    .collect()
-            };
-        }
-        Self {
-            player: create_entities!(PlayerEntity, player),
-            object: create_entities!(ObjectEntity, object),
-        }
+        let mut entities = Self::default();
+        entities.add_entity(states);
+        entities
     }
 }
 
 impl Entities {
+    pub fn register<E: Entity>(&mut self) {
+        self.kinds
+            .insert(E::KIND, Box::new(EntityMap::<E>(HashMap::default())));
+    }
+
+    fn map<E: Entity>(&self) -> Option<&HashMap<Uuid, E>> {
+        let map: &EntityMap<E> = self.kinds.get(&E::KIND)?.as_any().downcast_ref()?;
+        Some(&map.0)
+    }
+
+    pub fn get<E: Entity>(&self, id: &Uuid) -> Option<&E> {
+        self.map::<E>()?.get(id)
+    }
+
+    pub fn get_mut<E: Entity>(&mut self, id: &Uuid) -> Option<&mut E> {
+        let map: &mut EntityMap<E> = self.kinds.get_mut(&E::KIND)?.as_any_mut().downcast_mut()?;
+        map.0.get_mut(id)
+    }
+
+    pub fn ids<E: Entity>(&self) -> impl Iterator<Item = &Uuid> {
+        self.map::<E>().into_iter().flat_map(|map| map.keys())
+    }
+
+    pub fn iter<E: Entity>(&self) -> impl Iterator<Item = &E> {
+        self.map::<E>().into_iter().flat_map(|map| map.values())
+    }
+
+    pub fn summaries(&self) -> impl Iterator<Item = (&EntityKind, Vec<EntitySummary>)> {
+        self.kinds
+            .iter()
+            .map(|(kind, collection)| (kind, collection.summaries()))
+    }
+
     pub fn render(&self, render_state: &mut OngoingRenderState) {
-        macro_rules! render {
-            ($map:expr, $render_state:expr) => {
-                $map.values()
-                    .for_each(|entity| entity.render($render_state));
-            };
-        }
-        render!(self.object, render_state);
-        render!(self.player, render_state);
+        self.kinds
+            .values()
+            .for_each(|collection| collection.render(render_state));
     }
 
     fn remove(&mut self, ids: EntitiesIds) {
-        macro_rules! remove {
-            ($entity:ident, $name:literal) => {
-                ids.$entity.into_iter().for_each(|id| {
-                    if self.$entity.remove(&id).is_none() {
-                        warn!("Remove unknown {}: {:?}", $name, id)
-                    }
-                });
-            };
+        for (kind, ids) in ids {
+            match self.kinds.get_mut(&kind) {
+                Some(collection) => collection.remove(ids),
+                None => warn!("Remove entities of unknown kind {}", kind),
+            }
         }
-        remove!(object, "object");
-        remove!(player, "player");
-    }
-
-    fn add_entity(&mut self, state: EntityStates) {
-        macro_rules! add {
-            ($entry:ident, $type:ty) => {
-                state
-                    .$entry
-                    .into_iter()
-                    .map(<$type>::from)
-                    .for_each(|entity| match self.$entry.entry(entity.id()) {
-                        Entry::Occupied(_) => {
-                            warn!("Insert existing {}: {:?}", stringify!($entry), entity.id())
-                        }
-                        Entry::Vacant(entry) => {
-                            entry.insert(entity);
-                        }
-                    });
-            };
+    }
+
+    fn add_entity(&mut self, states: EntityStates) {
+        for (kind, states) in states {
+            match self.kinds.get_mut(&kind) {
+                Some(collection) => collection.add(states),
+                None => warn!("Add entities of unknown kind {}", kind),
+            }
         }
-        add!(object, ObjectEntity);
-        add!(player, PlayerEntity);
-    }
-
-    fn process_output(&mut self, output: EntitiesOutputs) {
-        macro_rules! process {
-            ($entry:ident, $name:literal) => {
-                output
-                    .$entry
-                    .into_iter()
-                    .for_each(|(id, output)| match self.$entry.get_mut(&id) {
-                        Some(entity) => entity.process_output(output),
-                        None => warn!("Handle output for unknown {}: {:?}", $name, id),
-                    });
-            };
+    }
+
+    fn process_output(&mut self, outputs: EntitiesOutputs) {
+        for (kind, outputs) in outputs {
+            match self.kinds.get_mut(&kind) {
+                Some(collection) => collection.process_output(outputs),
+                None => warn!("Handle output of unknown kind {}", kind),
+            }
         }
-        process!(object, "object");
-        process!(player, "player");
     }
 }
 
 #[derive(Debug)]
 pub struct World {
     pub entities: Entities,
+    last_tick: Option<u64>,
+    lights_changed: bool,
 }
 
 impl World {
     pub fn new(entity_states: EntityStates) -> Self {
         Self {
             entities: Entities::from(entity_states),
+            last_tick: None,
+            lights_changed: true,
         }
     }
 
+    /// Number of the last server tick applied.
+    pub fn last_tick(&self) -> Option<u64> {
+        self.last_tick
+    }
+
     pub fn render(&self, render_state: &mut OngoingRenderState) {
         self.entities.render(render_state);
     }
 
+    pub fn lights(&self) -> Vec<LightData> {
+        self.entities
+            .iter::<LightEntity>()
+            .map(LightEntity::light_data)
+            .collect()
+    }
+
+    /// Lights, if any were added, removed or changed since the last call.
+    pub fn take_changed_lights(&mut self) -> Option<Vec<LightData>> {
+        if !mem::take(&mut self.lights_changed) {
+            return None;
+        }
+        Some(self.lights())
+    }
+
     pub fn update(&mut self, tick_output: TickOutput) {
+        if let Some(last_tick) = self.last_tick {
+            if tick_output.tick != last_tick + 1 {
+                warn!(
+                    "Tick {} follows tick {}, some outputs were lost",
+                    tick_output.tick, last_tick
+                );
+            }
+        }
+        self.last_tick = Some(tick_output.tick);
+        let kind = &LightEntity::KIND;
+        self.lights_changed |= !tick_output.removed_entity_uuids.get(kind).is_empty()
+            || !tick_output.new_entity_states.get(kind).is_empty()
+            || !tick_output.entity_outputs.get(kind).is_empty();
         self.entities.remove(tick_output.removed_entity_uuids);
         self.entities.add_entity(tick_output.new_entity_states);
         self.entities.process_output(tick_output.entity_outputs)
@@ -0,0 +1,141 @@
+use std::{mem, sync::mpsc::Sender};
+
+use egui::{Align2, ComboBox, Context, Key, ScrollArea, TextEdit, Window};
+use renderer_protocol::chat::{ChatMessage, MAX_CHAT_MESSAGE_LENGTH};
+use uuid::Uuid;
+
+use crate::client::{
+    chat::{ChatEntry, ChatLog},
+    entity::player::PlayerEntity,
+    world::Entities,
+};
+
+use super::GuiAction;
+
+#[derive(Debug, Default)]
+pub struct ChatState {
+    input: String,
+    target: Option<Uuid>,
+}
+
+fn player_name(id: Uuid, player_id: Uuid) -> String {
+    if id == player_id {
+        String::from("You")
+    } else {
+        id.simple().to_string()[..8].to_string()
+    }
+}
+
+fn format_time(timestamp: u64) -> String {
+    let seconds = timestamp / 1000 % 86400;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn format_message(message: &ChatMessage, player_id: Uuid) -> String {
+    let sender = player_name(message.sender, player_id);
+    match message.target {
+        Some(target) => format!(
+            "[{}] {} -> {}: {}",
+            format_time(message.timestamp),
+            sender,
+            player_name(target, player_id),
+            message.text
+        ),
+        None => format!(
+            "[{}] {}: {}",
+            format_time(message.timestamp),
+            sender,
+            message.text
+        ),
+    }
+}
+
+pub fn chat(
+    ctx: &Context,
+    player_id: Uuid,
+    log: &ChatLog,
+    entities: Option<&Entities>,
+    state: &mut ChatState,
+    gui_actions_tx: &mut Sender<GuiAction>,
+) {
+    // Drop the target once the player has left
+    if let Some(target) = state.target {
+        let player = entities.and_then(|entities| entities.get::<PlayerEntity>(&target));
+        if player.is_none() {
+            state.target = None;
+        }
+    }
+
+    Window::new("Chat")
+        .pivot(Align2::LEFT_BOTTOM)
+        .default_width(320.0)
+        .show(ctx, |ui| {
+            ScrollArea::vertical()
+                .max_height(200.0)
+                .stick_to_bottom(true)
+                .auto_shrink([false, true])
+                .show(ui, |ui| {
+                    for entry in log.iter() {
+                        match entry {
+                            ChatEntry::Message(message) => {
+                                ui.label(format_message(message, player_id));
+                            }
+                            ChatEntry::Rejected(reason) => {
+                                ui.weak(reason.to_string());
+                            }
+                            ChatEntry::IntentRejected { intent, reason } => {
+                                ui.weak(format!(
+                                    "Failed to {} object {}: {}",
+                                    intent.action(),
+                                    intent.id(),
+                                    reason
+                                ));
+                            }
+                        }
+                    }
+                });
+
+            ui.horizontal(|ui| {
+                let target_name = match state.target {
+                    Some(target) => player_name(target, player_id),
+                    None => String::from("Everyone"),
+                };
+                ComboBox::from_id_salt("Chat target")
+                    .selected_text(target_name)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut state.target, None, "Everyone");
+                        if let Some(entities) = entities {
+                            for id in entities.ids::<PlayerEntity>() {
+                                if *id != player_id {
+                                    ui.selectable_value(
+                                        &mut state.target,
+                                        Some(*id),
+                                        player_name(*id, player_id),
+                                    );
+                                }
+                            }
+                        }
+                    });
+
+                let response = ui.add(
+                    TextEdit::singleline(&mut state.input)
+                        .char_limit(MAX_CHAT_MESSAGE_LENGTH)
+                        .hint_text("Message"),
+                );
+                let submitted =
+                    response.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter));
+                if (submitted || ui.button("Send").clicked()) && !state.input.trim().is_empty() {
+                    let _ = gui_actions_tx.send(GuiAction::SendChat {
+                        text: mem::take(&mut state.input),
+                        target: state.target,
+                    });
+                    response.request_focus();
+                }
+            });
+        });
+}
@@ -15,6 +15,10 @@ pub trait ConnectParam: Serialize + for<'a> Deserialize<'a> + Clone + Default {
     fn name(&self) -> &str;
     fn ui(&mut self, ui: &mut Ui);
     fn param(&self) -> Option<Box<dyn TransportParam>>;
+    /// Token to ask the server for the admin role with.
+    fn admin_token(&self) -> Option<String> {
+        None
+    }
 }
 
 pub fn connect<Param: ConnectParam>(
@@ -62,7 +66,10 @@ pub fn connect<Param: ConnectParam>(
                     if let Some(selected_param) = params.get_mut(selected_index) {
                         if let Some(param) = selected_param.param() {
                             if ui.button("Connect").clicked() {
-                                let _ = gui_actions_tx.send(GuiAction::Connect(param));
+                                let _ = gui_actions_tx.send(GuiAction::Connect {
+                                    param,
+                                    admin_token: selected_param.admin_token(),
+                                });
                             }
                         } else {
                             ui.add_enabled(false, Button::new("Connect"));
@@ -1,11 +1,12 @@
 use std::fmt::{self, Display, Formatter};
 
-use egui::{ComboBox, Grid, Ui};
+use egui::{ComboBox, Grid, TextEdit, Ui};
+use renderer_protocol::{codec::Compact, compression::CompressionConfig};
 use serde::{Deserialize, Serialize};
 use tokio_serde::formats::{Bincode, Json};
 use tokio_tungstenite::tungstenite::{client::IntoClientRequest, http::Uri};
 
-use crate::transport::{tokio::TokioTransportParam, TransportParam};
+use crate::transport::{replay::ReplayTransportParam, tokio::TokioTransportParam, TransportParam};
 
 use super::ConnectParam;
 
@@ -14,6 +15,7 @@ pub enum SerializeType {
     #[default]
     Json,
     Bincode,
+    Compact,
 }
 
 impl Display for SerializeType {
@@ -21,6 +23,7 @@ impl Display for SerializeType {
         match self {
             SerializeType::Json => write!(f, "Json"),
             SerializeType::Bincode => write!(f, "Bincode"),
+            SerializeType::Compact => write!(f, "Compact"),
         }
     }
 }
@@ -30,6 +33,10 @@ pub struct TokioConnectParam {
     serialize_type: SerializeType,
     uri: String,
     name: String,
+    #[serde(default)]
+    disable_compression: bool,
+    #[serde(default)]
+    admin_token: String,
 }
 
 impl ConnectParam for TokioConnectParam {
@@ -61,25 +68,67 @@ impl ConnectParam for TokioConnectParam {
                         SerializeType::Bincode,
                         SerializeType::Bincode.to_string(),
                     );
+                    ui.selectable_value(
+                        &mut self.serialize_type,
+                        SerializeType::Compact,
+                        SerializeType::Compact.to_string(),
+                    );
                 });
             ui.end_row();
 
+            ui.label("Compression");
+            let mut compression = !self.disable_compression;
+            if ui.checkbox(&mut compression, "").changed() {
+                self.disable_compression = !compression;
+            }
+            ui.end_row();
+
             ui.label("Name");
             ui.text_edit_singleline(&mut self.name);
             ui.end_row();
 
             ui.label("URI");
-            ui.text_edit_singleline(&mut self.uri);
+            ui.add(
+                TextEdit::singleline(&mut self.uri)
+                    .hint_text("ws://host:port, or file://path to play a replay"),
+            );
+            ui.end_row();
+
+            ui.label("Admin token");
+            ui.add(
+                TextEdit::singleline(&mut self.admin_token)
+                    .password(true)
+                    .hint_text("Optional"),
+            );
             ui.end_row();
         });
     }
 
+    fn admin_token(&self) -> Option<String> {
+        Some(self.admin_token.clone()).filter(|token| !token.is_empty())
+    }
+
     fn param(&self) -> Option<Box<dyn TransportParam>> {
+        if let Some(path) = self.uri.strip_prefix("file://") {
+            return Some(Box::new(ReplayTransportParam::new(path)));
+        }
         let uri = Uri::try_from(&self.uri).map_err(|_| ()).ok()?;
         let request = uri.into_client_request().map_err(|_| ()).ok()?;
+        let compression = if self.disable_compression {
+            CompressionConfig::disabled()
+        } else {
+            CompressionConfig::default()
+        };
         Some(match self.serialize_type {
-            SerializeType::Json => Box::new(TokioTransportParam::new(request, Json::default)),
-            SerializeType::Bincode => Box::new(TokioTransportParam::new(request, Bincode::default)),
+            SerializeType::Json => Box::new(
+                TokioTransportParam::new(request, Json::default).with_compression(compression),
+            ),
+            SerializeType::Bincode => Box::new(
+                TokioTransportParam::new(request, Bincode::default).with_compression(compression),
+            ),
+            SerializeType::Compact => Box::new(
+                TokioTransportParam::new(request, Compact::default).with_compression(compression),
+            ),
         })
     }
 }
@@ -1,50 +1,72 @@
-use egui::{Align2, CollapsingHeader, Context, ScrollArea, Ui, Window};
-use renderer_protocol::entity::EntityResourceData;
+use std::sync::mpsc::Sender;
 
-use crate::client::{entity::Entity, world::Entities};
+use egui::{Align2, CollapsingHeader, Context, ScrollArea, Window};
+use renderer_protocol::{
+    entity::{EntityKind, EntityResourceData, ObjectPermissions},
+    intent::ObjectIntent,
+};
+use uuid::Uuid;
 
-fn resource(ui: &mut Ui, resource: &EntityResourceData) {
-    match resource {
-        EntityResourceData::Box => {
-            ui.label("Box");
-        }
-        EntityResourceData::Crosshair => {
-            ui.label("Crosshair");
-        }
-        EntityResourceData::External { bundle_index, link } => {
-            ui.label(format!("Resource: {} ({})", bundle_index, link));
-        }
-    }
-}
+use crate::{client::world::Entities, renderer::camera::Camera};
+
+use super::GuiAction;
 
-pub fn entities(ctx: &Context, entities: &Entities) {
+/// Distance in front of the camera to spawn and move objects to.
+const PLACE_DISTANCE: f32 = 2.0;
+
+pub fn entities(
+    ctx: &Context,
+    entities: &Entities,
+    camera: &Camera,
+    gui_actions_tx: &mut Sender<GuiAction>,
+) {
+    let place_position = camera.view.eye + camera.view.front() * PLACE_DISTANCE;
     Window::new("Entities")
         .pivot(Align2::LEFT_TOP)
         .resizable([false, true])
         .show(ctx, |ui| {
+            if ui.button("Spawn box").clicked() {
+                let _ = gui_actions_tx.send(GuiAction::ObjectIntent(ObjectIntent::Spawn {
+                    id: Uuid::new_v4(),
+                    position: place_position,
+                    resource: EntityResourceData::Box,
+                    permissions: ObjectPermissions::NONE,
+                }));
+            }
             ScrollArea::vertical().show(ui, |ui| {
-                for (id, entity) in entities.object.iter() {
-                    CollapsingHeader::new(format!("Object {:?}", id))
-                        .id_salt(id)
-                        .show(ui, |ui| {
-                            let position = entity.position();
-                            ui.label(format!(
-                                "Position: [{:#.2}, {:#.2}, {:#.2}]",
-                                position.x, position.y, position.z
-                            ));
-                            resource(ui, entity.resource());
-                        });
-                }
-                for (id, entity) in entities.player.iter() {
-                    CollapsingHeader::new(format!("Player {:?}", id))
-                        .id_salt(id)
-                        .show(ui, |ui| {
-                            let position = entity.position();
-                            ui.label(format!(
-                                "Position: [{:#.2}, {:#.2}, {:#.2}]",
-                                position.x, position.y, position.z
-                            ))
-                        });
+                for (kind, summaries) in entities.summaries() {
+                    for summary in summaries {
+                        CollapsingHeader::new(format!("{} {:?}", kind, summary.id))
+                            .id_salt(summary.id)
+                            .show(ui, |ui| {
+                                let position = summary.position;
+                                ui.label(format!(
+                                    "Position: [{:#.2}, {:#.2}, {:#.2}]",
+                                    position.x, position.y, position.z
+                                ));
+                                for detail in &summary.details {
+                                    ui.label(detail);
+                                }
+                                if *kind == EntityKind::OBJECT {
+                                    ui.horizontal(|ui| {
+                                        let id = summary.id;
+                                        if ui.button("Move here").clicked() {
+                                            let _ = gui_actions_tx.send(GuiAction::ObjectIntent(
+                                                ObjectIntent::Move {
+                                                    id,
+                                                    position: place_position,
+                                                },
+                                            ));
+                                        }
+                                        if ui.button("Delete").clicked() {
+                                            let _ = gui_actions_tx.send(GuiAction::ObjectIntent(
+                                                ObjectIntent::Delete { id },
+                                            ));
+                                        }
+                                    });
+                                }
+                            });
+                    }
                 }
             })
         });
@@ -7,11 +7,21 @@ use crate::renderer::{uniform::light::GlobalLightParam, Renderer};
 
 use super::GuiAction;
 
-pub fn light_param(ctx: &Context, renderer: &Renderer, gui_actions_tx: &mut Sender<GuiAction>) {
+/// Lighting is only `editable` without a server, which owns it otherwise.
+pub fn light_param(
+    ctx: &Context,
+    renderer: &Renderer,
+    editable: bool,
+    gui_actions_tx: &mut Sender<GuiAction>,
+) {
     Window::new("Light Params")
         .resizable([false, false])
         .pivot(Align2::RIGHT_BOTTOM)
         .show(ctx, |ui| {
+            if !editable {
+                ui.label("Set by the server");
+                ui.disable();
+            }
             ui.label("Background color:");
             let background_color = renderer.background_color().to_array();
             let mut edit_background_color = background_color;
@@ -1,5 +1,6 @@
 use std::{sync::mpsc::Sender, time::Instant};
 
+use chat::{chat, ChatState};
 use connect::{connect, connecting, ConnectParam, ConnectionStatus};
 use egui::Context;
 use entity::entities;
@@ -8,13 +9,16 @@ use glam::Vec3;
 use light::light_param;
 use perf::perf_info;
 use renderer_perf_tracker::PerformanceTracker;
+use renderer_protocol::{intent::ObjectIntent, traffic::TrafficStats};
+use uuid::Uuid;
 
 use crate::{
-    client::world::Entities,
+    client::{chat::ChatLog, world::Entities},
     renderer::{camera::PositionController, uniform::light::GlobalLightParam, Renderer},
     transport::TransportParam,
 };
 
+mod chat;
 pub mod connect;
 mod entity;
 mod error;
@@ -27,6 +31,7 @@ pub struct GuiState<CP: ConnectParam> {
     errors: Vec<String>,
     selected_param: usize,
     connect_params: Vec<CP>,
+    chat: ChatState,
 }
 
 impl<CP: ConnectParam> Default for GuiState<CP> {
@@ -35,6 +40,7 @@ impl<CP: ConnectParam> Default for GuiState<CP> {
             errors: Vec::default(),
             selected_param: 0,
             connect_params: Vec::default(),
+            chat: ChatState::default(),
         }
     }
 }
@@ -48,7 +54,15 @@ impl<CP: ConnectParam> GuiState<CP> {
 pub enum GuiAction {
     SetLightParam(GlobalLightParam),
     SetBackgroundColor(Vec3),
-    Connect(Box<dyn TransportParam>),
+    Connect {
+        param: Box<dyn TransportParam>,
+        admin_token: Option<String>,
+    },
+    SendChat {
+        text: String,
+        target: Option<Uuid>,
+    },
+    ObjectIntent(ObjectIntent),
 }
 
 pub struct GuiParam<'a> {
@@ -57,13 +71,20 @@ pub struct GuiParam<'a> {
     pub perf_tracker: &'a PerformanceTracker,
     pub position_controller: &'a mut PositionController,
     pub connection_status: Option<ConnectionStatus>,
+    pub traffic: Option<TrafficStats>,
     pub entities: Option<&'a Entities>,
+    pub chat: Option<(Uuid, &'a ChatLog)>,
     pub gui_actions_tx: &'a mut Sender<GuiAction>,
 }
 
 pub fn gui_main<CP: ConnectParam>(ctx: &Context, param: GuiParam, state: &mut GuiState<CP>) {
-    perf_info(ctx, param.perf_tracker);
-    light_param(ctx, param.renderer, param.gui_actions_tx);
+    perf_info(ctx, param.perf_tracker, param.traffic);
+    light_param(
+        ctx,
+        param.renderer,
+        param.connection_status.is_none(),
+        param.gui_actions_tx,
+    );
     if let Some(connection_status) = param.connection_status {
         match connection_status {
             ConnectionStatus::Connecting
@@ -82,7 +103,22 @@ pub fn gui_main<CP: ConnectParam>(ctx: &Context, param: GuiParam, state: &mut Gu
         );
     }
     if let Some(current_entities) = param.entities {
-        entities(ctx, current_entities);
+        entities(
+            ctx,
+            current_entities,
+            param.renderer.camera(),
+            param.gui_actions_tx,
+        );
+    }
+    if let Some((player_id, log)) = param.chat {
+        chat(
+            ctx,
+            player_id,
+            log,
+            param.entities,
+            &mut state.chat,
+            param.gui_actions_tx,
+        );
     }
 
     let mut remove_index = Vec::new();
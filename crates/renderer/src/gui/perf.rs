@@ -1,36 +1,179 @@
-use egui::{Align2, Context, Window};
-use renderer_perf_tracker::PerformanceTracker;
+use std::time::Duration;
 
-pub fn perf_info(ctx: &Context, perf_tracker: &PerformanceTracker) {
+use egui::{Align2, CollapsingHeader, Color32, Context, RichText, Ui, Window};
+use egui_plot::{Bar, BarChart, Line, Plot, PlotPoints};
+use renderer_perf_tracker::{Histogram, PerformanceTracker};
+use renderer_protocol::traffic::{DirectionStats, TrafficStats};
+
+const PLOT_SIZE: [f32; 2] = [260.0, 80.0];
+const HISTOGRAM_BUCKET: Duration = Duration::from_millis(2);
+const HISTOGRAM_BUCKETS: usize = 25;
+const TIMER_COLORS: [Color32; 6] = [
+    Color32::LIGHT_BLUE,
+    Color32::LIGHT_GREEN,
+    Color32::GOLD,
+    Color32::LIGHT_RED,
+    Color32::from_rgb(200, 150, 255),
+    Color32::from_rgb(255, 180, 100),
+];
+
+fn direction_label(name: &str, stats: &DirectionStats) -> String {
+    format!(
+        "{}: {} messages, {:.1}KiB ({:.1}KiB uncompressed)",
+        name,
+        stats.messages,
+        stats.bytes as f64 / 1024.0,
+        stats.uncompressed_bytes as f64 / 1024.0
+    )
+}
+
+fn millis(duration: Duration) -> f32 {
+    duration.as_secs_f32() * 1000.0
+}
+
+fn plot(id: &str) -> Plot<'static> {
+    Plot::new(id)
+        .width(PLOT_SIZE[0])
+        .height(PLOT_SIZE[1])
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .include_y(0.0)
+}
+
+/// Plot series of milliseconds against their sample index, aligned to the
+/// newest sample.
+fn line_plot(ui: &mut Ui, id: &str, series: &[(&str, Color32, Vec<f32>)]) {
+    let samples = series
+        .iter()
+        .map(|(_, _, values)| values.len())
+        .max()
+        .unwrap_or_default();
+    plot(id).show(ui, |plot_ui| {
+        for (name, color, values) in series {
+            let offset = samples - values.len();
+            let points: PlotPoints = values
+                .iter()
+                .enumerate()
+                .map(|(index, value)| [(offset + index) as f64, *value as f64])
+                .collect();
+            plot_ui.line(Line::new(points).color(*color).name(name));
+        }
+    });
+}
+
+/// Plot frame counts against the frame time in milliseconds.
+fn histogram_plot(ui: &mut Ui, histogram: &Histogram) {
+    let width = millis(histogram.bucket_width) as f64;
+    let bars = histogram
+        .counts
+        .iter()
+        .enumerate()
+        .map(|(index, count)| Bar::new((index as f64 + 0.5) * width, *count as f64).width(width))
+        .collect();
+    plot("histogram").show(ui, |plot_ui| {
+        plot_ui.bar_chart(BarChart::new(bars).color(Color32::LIGHT_BLUE));
+    });
+}
+
+fn frame_time_info(ui: &mut Ui, perf_tracker: &PerformanceTracker) {
+    match perf_tracker.last_frame_time() {
+        Some(time) => {
+            ui.label(format!("Frame time: {}ms", time.as_millis()));
+        }
+        None => {
+            ui.label("Frame time: unknown");
+        }
+    };
+
+    match perf_tracker.avg_frame_time() {
+        Some(time) => {
+            ui.label(format!("Avg frame time: {}ms", time.as_millis()));
+        }
+        None => {
+            ui.label("Avg frame time: unknown");
+        }
+    };
+
+    if let Some(stats) = perf_tracker.stats() {
+        ui.label(format!(
+            "p50 {:.1}ms, p95 {:.1}ms, p99 {:.1}ms, max {:.1}ms",
+            millis(stats.p50),
+            millis(stats.p95),
+            millis(stats.p99),
+            millis(stats.max)
+        ));
+    }
+
+    match perf_tracker.fps() {
+        Some(fps) => {
+            ui.label(format!("FPS: {:#.2}", fps));
+        }
+        None => {
+            ui.label("FPS: unknown");
+        }
+    };
+}
+
+fn timer_info(ui: &mut Ui, perf_tracker: &PerformanceTracker) {
+    for timer in perf_tracker.last_frame_timers() {
+        ui.label(format!(
+            "{}{}: {:.2}ms",
+            "  ".repeat(timer.depth),
+            timer.name,
+            millis(timer.duration)
+        ));
+    }
+
+    let series: Vec<(&str, Color32, Vec<f32>)> = perf_tracker
+        .timer_history()
+        .iter()
+        .zip(TIMER_COLORS.iter().cycle())
+        .map(|((name, history), color)| {
+            (*name, *color, history.iter().copied().map(millis).collect())
+        })
+        .collect();
+    line_plot(ui, "timers", &series);
+    ui.horizontal_wrapped(|ui| {
+        for (name, color) in perf_tracker
+            .timer_history()
+            .keys()
+            .zip(TIMER_COLORS.iter().cycle())
+        {
+            ui.label(RichText::new(*name).color(*color));
+        }
+    });
+}
+
+pub fn perf_info(ctx: &Context, perf_tracker: &PerformanceTracker, traffic: Option<TrafficStats>) {
     Window::new("Performance Info")
         .resizable([false, false])
         .pivot(Align2::RIGHT_BOTTOM)
         .show(ctx, |ui| {
-            match perf_tracker.last_frame_time() {
-                Some(time) => {
-                    ui.label(format!("Frame time: {}ms", time.as_millis()));
-                }
-                None => {
-                    ui.label("Frame time: unknown");
-                }
-            };
-
-            match perf_tracker.avg_frame_time() {
-                Some(time) => {
-                    ui.label(format!("Avg frame time: {}ms", time.as_millis()));
-                }
-                None => {
-                    ui.label("Avg frame time: unknown");
-                }
-            };
-
-            match perf_tracker.fps() {
-                Some(fps) => {
-                    ui.label(format!("FPS: {:#.2}", fps));
-                }
-                None => {
-                    ui.label("FPS: unknown");
-                }
-            };
+            frame_time_info(ui, perf_tracker);
+
+            CollapsingHeader::new("Frame time").show(ui, |ui| {
+                let frame_time = perf_tracker.frame_time().iter().copied().map(millis);
+                let series = [("frame time", Color32::LIGHT_BLUE, frame_time.collect())];
+                line_plot(ui, "frame time", &series);
+            });
+
+            CollapsingHeader::new("Histogram").show(ui, |ui| {
+                let histogram = perf_tracker.histogram(HISTOGRAM_BUCKET, HISTOGRAM_BUCKETS);
+                histogram_plot(ui, &histogram);
+                ui.label(format!(
+                    "{}ms buckets, the last one counts every longer frame",
+                    histogram.bucket_width.as_millis()
+                ));
+            });
+
+            CollapsingHeader::new("Timers").show(ui, |ui| {
+                timer_info(ui, perf_tracker);
+            });
+
+            if let Some(traffic) = traffic {
+                ui.label(direction_label("Sent", &traffic.sent));
+                ui.label(direction_label("Received", &traffic.received));
+            }
         });
 }
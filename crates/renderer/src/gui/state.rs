@@ -71,7 +71,9 @@ impl<CP: ConnectParam> EguiState<CP> {
                     perf_tracker,
                     position_controller,
                     connection_status,
+                    traffic: client.map(Client::traffic),
                     entities: client.and_then(Client::world).map(|world| &world.entities),
+                    chat: client.and_then(Client::chat),
                     gui_actions_tx: &mut self.gui_actions_tx,
                 },
                 &mut self.state,
@@ -197,4 +197,12 @@ impl PositionController {
 
         camera.move_eye(movement)
     }
+
+    /// Horizontal direction the pressed keys walk in, relative to where the
+    /// camera looks.
+    pub fn walk_direction(&self, camera: &Camera) -> Vec3 {
+        let forward = camera.view.front_ignore_pitch(0.0);
+        let left = camera.view.front_ignore_pitch(-90.0);
+        forward * (self.forward - self.backward) + left * (self.left - self.right)
+    }
 }
@@ -7,7 +7,7 @@ use image::GrayImage;
 use texture::{TextureItem, TextureTransform};
 use uniform::{
     camera::CameraUniformBuffer,
-    light::{GlobalLightParam, LightUniformBuffer},
+    light::{GlobalLightParam, LightData, LightUniformBuffer},
     texture::TextureUniformBuffer,
     transform::InstanceUniformBuffer,
 };
@@ -337,6 +337,7 @@ pub struct Renderer {
     bind_group_layout: RendererBindGroupLayout,
     camera_uniform: CameraUniformBuffer,
     light_uniform: LightUniformBuffer,
+    light_updated: bool,
 }
 
 impl Renderer {
@@ -375,6 +376,7 @@ impl Renderer {
             camera_updated: false,
             global_bind_group,
             light_uniform,
+            light_updated: false,
             background_color: Vec3::new(0.8, 0.8, 1.0),
             global_defaults,
         }
@@ -399,6 +401,16 @@ impl Renderer {
 
     pub fn set_global_light_param(&mut self, param: GlobalLightParam) {
         self.light_uniform.set_param(param);
+        self.light_updated = true;
+    }
+
+    pub fn set_lights(&mut self, lights: Vec<LightData>) {
+        self.light_uniform.items = lights;
+        self.light_updated = true;
+    }
+
+    pub fn lights(&self) -> &[LightData] {
+        &self.light_uniform.items
     }
 
     pub fn global_light_param(&self) -> &GlobalLightParam {
@@ -430,6 +442,10 @@ impl Renderer {
                 .update_uniform(&mut self.camera_uniform, self.view_aspect);
             self.camera_uniform.update(queue);
         }
+        if self.light_updated {
+            self.light_updated = false;
+            self.light_uniform.update(queue);
+        }
     }
 
     pub fn render<'a>(&'a self, ongoing_state: &mut OngoingRenderState<'a>, world: &World) {
@@ -1,7 +1,7 @@
 use std::{cmp::Ordering, collections::BTreeMap, ops::Deref};
 
 use glam::Vec3;
-use renderer_asset::{primitive::PrimitiveAssetMode, tangent::calculate_tangent};
+use renderer_asset::{normal::smooth_normals, primitive::PrimitiveAssetMode};
 
 struct PositionFloat {
     value: f32,
@@ -62,7 +62,7 @@ pub fn _calculate_tangent(
     positions: &[[f32; 3]],
     indices: Option<&[u32]>,
 ) -> Vec<[f32; 3]> {
-    let normals = calculate_tangent(mode, positions, indices);
+    let normals = smooth_normals(mode, positions, indices);
     let mut points = BTreeMap::new();
     for (index, position) in positions.iter().enumerate() {
         let normal = Vec3::from_array(normals[index]);
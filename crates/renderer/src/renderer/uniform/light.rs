@@ -1,5 +1,7 @@
 use bytemuck::{cast_slice, Pod, Zeroable};
 use glam::Vec3;
+use log::warn;
+use renderer_protocol::environment::LightParam;
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
     Buffer, BufferUsages, Device, Queue,
@@ -82,6 +84,20 @@ pub struct GlobalLightParam {
     pub ambient_strength: f32,
 }
 
+impl From<LightParam> for GlobalLightParam {
+    fn from(param: LightParam) -> Self {
+        Self {
+            start_strength: param.start_strength,
+            stop_strength: param.stop_strength,
+            max_strength: param.max_strength,
+            border_start_strength: param.border_start_strength,
+            border_stop_strength: param.border_stop_strength,
+            border_max_strength: param.border_max_strength,
+            ambient_strength: param.ambient_strength,
+        }
+    }
+}
+
 impl Default for GlobalLightParam {
     fn default() -> Self {
         Self {
@@ -109,24 +125,27 @@ pub struct LightUniform {
     parallel: [ParallelLightData; MAX_PARALLEL_LIGHTS],
 }
 
-#[derive(Debug)]
-pub struct LightUniformBuffer {
-    buffer: Buffer,
-    uniform: LightUniform,
-    pub items: Vec<LightData>,
-}
+impl LightUniform {
+    fn new(light_param: GlobalLightParam) -> Self {
+        Self {
+            point_length: 0,
+            directional_length: 0,
+            parallel_length: 0,
+            light_param,
+            padding: [0; 8],
+            point: [Default::default(); MAX_POINT_LIGHTS],
+            directional: [Default::default(); MAX_DIRECTIONAL_LIGHTS],
+            parallel: [Default::default(); MAX_PARALLEL_LIGHTS],
+        }
+    }
 
-impl LightUniformBuffer {
-    pub fn new(device: &Device, items: Vec<LightData>, light_param: GlobalLightParam) -> Self {
-        let mut point_length: u32 = 0;
-        let mut directional_length: u32 = 0;
-        let mut parallel_length: u32 = 0;
-        let mut point: [PointLightItem; MAX_POINT_LIGHTS] = [Default::default(); MAX_POINT_LIGHTS];
-        let mut directional: [DirectionalLightItem; MAX_DIRECTIONAL_LIGHTS] =
-            [Default::default(); MAX_DIRECTIONAL_LIGHTS];
-        let mut parallel: [ParallelLightData; MAX_PARALLEL_LIGHTS] =
-            [Default::default(); MAX_PARALLEL_LIGHTS];
-        for item in &items {
+    /// Pack lights into the arrays, dropping the ones over the limits.
+    fn set_items(&mut self, items: &[LightData]) {
+        let mut point_length = 0;
+        let mut directional_length = 0;
+        let mut parallel_length = 0;
+        let mut dropped = 0;
+        for item in items {
             match item {
                 LightData::Point {
                     position,
@@ -135,7 +154,10 @@ impl LightUniformBuffer {
                     linear,
                     quadratic,
                 } => {
-                    let point = &mut point[point_length as usize];
+                    let Some(point) = self.point.get_mut(point_length) else {
+                        dropped += 1;
+                        continue;
+                    };
                     point.position = position.to_array();
                     point.color = color.to_array();
                     point.constant = *constant;
@@ -153,7 +175,10 @@ impl LightUniformBuffer {
                     range_inner,
                     range_outer,
                 } => {
-                    let directional = &mut directional[directional_length as usize];
+                    let Some(directional) = self.directional.get_mut(directional_length) else {
+                        dropped += 1;
+                        continue;
+                    };
                     directional.position = position.to_array();
                     directional.color = color.to_array();
                     directional.direction = direction.to_array();
@@ -169,7 +194,10 @@ impl LightUniformBuffer {
                     color,
                     strength,
                 } => {
-                    let parallel = &mut parallel[parallel_length as usize];
+                    let Some(parallel) = self.parallel.get_mut(parallel_length) else {
+                        dropped += 1;
+                        continue;
+                    };
                     parallel.direction = direction.to_array();
                     parallel.color = color.to_array();
                     parallel.strength = *strength;
@@ -177,16 +205,26 @@ impl LightUniformBuffer {
                 }
             }
         }
-        let uniform = LightUniform {
-            point_length,
-            directional_length,
-            parallel_length,
-            light_param,
-            padding: [0; 8],
-            point,
-            directional,
-            parallel,
-        };
+        if dropped > 0 {
+            warn!("Too many lights, {} not rendered", dropped);
+        }
+        self.point_length = point_length as u32;
+        self.directional_length = directional_length as u32;
+        self.parallel_length = parallel_length as u32;
+    }
+}
+
+#[derive(Debug)]
+pub struct LightUniformBuffer {
+    buffer: Buffer,
+    uniform: LightUniform,
+    pub items: Vec<LightData>,
+}
+
+impl LightUniformBuffer {
+    pub fn new(device: &Device, items: Vec<LightData>, light_param: GlobalLightParam) -> Self {
+        let mut uniform = LightUniform::new(light_param);
+        uniform.set_items(&items);
         let buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Light Uniform Buffer"),
             contents: cast_slice(&[uniform]),
@@ -208,63 +246,7 @@ impl LightUniformBuffer {
     }
 
     pub fn update(&mut self, queue: &Queue) {
-        let mut point_length: u32 = 0;
-        let mut directional_length: u32 = 0;
-        let mut parallel_length: u32 = 0;
-        for item in &self.items {
-            match item {
-                LightData::Point {
-                    position,
-                    color,
-                    constant,
-                    linear,
-                    quadratic,
-                } => {
-                    let point = &mut self.uniform.point[point_length as usize];
-                    point.position = position.to_array();
-                    point.color = color.to_array();
-                    point.constant = *constant;
-                    point.linear = *linear;
-                    point.quadratic = *quadratic;
-                    point_length += 1;
-                }
-                LightData::Directional {
-                    position,
-                    color,
-                    direction,
-                    constant,
-                    linear,
-                    quadratic,
-                    range_inner,
-                    range_outer,
-                } => {
-                    let directional = &mut self.uniform.directional[directional_length as usize];
-                    directional.position = position.to_array();
-                    directional.color = color.to_array();
-                    directional.direction = direction.to_array();
-                    directional.constant = *constant;
-                    directional.linear = *linear;
-                    directional.quadratic = *quadratic;
-                    directional.range_inner = *range_inner;
-                    directional.range_outer = *range_outer;
-                    directional_length += 1;
-                }
-                LightData::Parallel {
-                    direction,
-                    color,
-                    strength,
-                } => {
-                    let parallel = &mut self.uniform.parallel[parallel_length as usize];
-                    parallel.direction = direction.to_array();
-                    parallel.color = color.to_array();
-                    parallel.strength = *strength;
-                    parallel_length += 1;
-                }
-            }
-        }
-        self.uniform.point_length = point_length;
-        self.uniform.directional_length = directional_length;
-        self.uniform.parallel_length = parallel_length;
+        self.uniform.set_items(&self.items);
         queue.write_buffer(&self.buffer, 0, cast_slice(&[self.uniform]));
     }
 
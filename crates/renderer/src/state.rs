@@ -220,14 +220,33 @@ impl<'a, CP: ConnectParam> State<'a, CP> {
     fn handle_gui_events(&mut self) {
         while let Ok(action) = self.gui_state.recv_events() {
             match action {
+                // The server owns the environment of a session
                 GuiAction::SetLightParam(param) => {
-                    self.renderer.set_global_light_param(param);
+                    if self.client.is_none() {
+                        self.renderer.set_global_light_param(param);
+                    }
                 }
                 GuiAction::SetBackgroundColor(color) => {
-                    self.renderer.set_background_color(color);
+                    if self.client.is_none() {
+                        self.renderer.set_background_color(color);
+                    }
                 }
-                GuiAction::Connect(param) => {
-                    self.client = Some(Client::new(param.connect()));
+                GuiAction::Connect { param, admin_token } => {
+                    self.client = Some(Client::new(param.connect(), admin_token));
+                }
+                GuiAction::ObjectIntent(intent) => {
+                    if let Some(client) = self.client.as_mut() {
+                        if let Err(error) = client.send_intent(intent) {
+                            self.gui_state.state.add_error(error.to_string());
+                        }
+                    }
+                }
+                GuiAction::SendChat { text, target } => {
+                    if let Some(client) = self.client.as_mut() {
+                        if let Err(error) = client.send_chat(text, target) {
+                            self.gui_state.state.add_error(error.to_string());
+                        }
+                    }
                 }
             }
         }
@@ -235,8 +254,20 @@ impl<'a, CP: ConnectParam> State<'a, CP> {
 
     pub fn render(&mut self, display_target: &impl RenderTarget) -> RenderResult {
         self.handle_gui_events();
+        let start_time = Instant::now();
+        let frame_duration = self
+            .last_render_time
+            .map(|last_render_time| start_time - last_render_time);
         if let Some(client) = self.client.as_mut() {
-            if !client.tick(&mut self.renderer, &mut self.gui_state.state) {
+            self.perf_tracker.start_timer("client tick");
+            let running = client.tick(
+                &mut self.renderer,
+                &mut self.gui_state.state,
+                &self.position_controller,
+                frame_duration.unwrap_or_default(),
+            );
+            self.perf_tracker.stop_timer();
+            if !running {
                 self.client = None;
             }
         }
@@ -246,14 +277,16 @@ impl<'a, CP: ConnectParam> State<'a, CP> {
             None => return RenderResult::NoSurface,
         };
 
-        let start_time = Instant::now();
-        if let Some(last_renderer_time) = self.last_render_time {
-            let duration = start_time - last_renderer_time;
+        // While connected, the camera follows the local player instead
+        let connected = self.client.as_ref().and_then(Client::world).is_some();
+        if let (Some(duration), false) = (frame_duration, connected) {
             self.renderer
                 .update_camera(|camera| self.position_controller.update(duration, camera));
         }
         self.last_render_time = Some(start_time);
+        self.perf_tracker.start_timer("scene upload");
         self.renderer.prepare(&self.queue);
+        self.perf_tracker.stop_timer();
 
         let output = loop {
             match surface.get_current_texture() {
@@ -277,10 +310,13 @@ impl<'a, CP: ConnectParam> State<'a, CP> {
             OngoingRenderState::new(&self.device, &texture_view, &self.renderer);
 
         if let Some(world) = self.client.as_ref().and_then(Client::world) {
+            self.perf_tracker.start_timer("scene render");
             self.renderer.render(&mut ongoing_state, world);
+            self.perf_tracker.stop_timer();
         }
 
         // Egui
+        self.perf_tracker.start_timer("egui");
         let full_output = self.gui_state.run(
             &self.renderer,
             &self.perf_tracker,
@@ -316,12 +352,15 @@ impl<'a, CP: ConnectParam> State<'a, CP> {
             &paint_jobs,
             &screen_descriptor,
         );
+        self.perf_tracker.stop_timer();
 
+        self.perf_tracker.start_timer("submit");
         ongoing_state.finish(&self.queue);
 
         display_target.pre_present_notify();
         output.present();
         display_target.request_redraw();
+        self.perf_tracker.stop_timer();
 
         let end_time = Instant::now();
         let frame_time = end_time - start_time;
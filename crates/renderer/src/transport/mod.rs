@@ -1,7 +1,11 @@
 use std::{error::Error, fmt::Debug};
 
-use renderer_protocol::message::{ClientMessage, ServerMessage};
+use renderer_protocol::{
+    message::{ClientMessage, ServerMessage},
+    traffic::TrafficStats,
+};
 
+pub mod replay;
 #[cfg(feature = "tokio-transport")]
 pub mod tokio;
 
@@ -21,5 +25,6 @@ pub trait Transport: Debug {
     fn state(&self) -> TransportState;
     fn receive(&mut self) -> Result<Option<ServerMessage>, Box<dyn Error>>;
     fn send(&mut self, message: ClientMessage) -> Result<(), Box<dyn Error>>;
+    fn traffic(&self) -> TrafficStats;
     fn close(self);
 }
@@ -0,0 +1,156 @@
+use std::{
+    collections::VecDeque,
+    error::Error as StdError,
+    fmt::{self, Display, Formatter},
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use renderer_protocol::{
+    intent::Role,
+    message::{ClientMessage, ServerMessage},
+    replay::{ReplayError, ReplayEvent, ReplayReader},
+    traffic::TrafficStats,
+};
+use uuid::Uuid;
+
+use super::{Transport, TransportParam, TransportState};
+
+#[derive(Debug, Clone)]
+pub struct Error(Arc<ReplayError>);
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl StdError for Error {}
+
+#[derive(Debug, Clone)]
+pub struct ReplayTransportParam {
+    path: PathBuf,
+}
+
+impl ReplayTransportParam {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl TransportParam for ReplayTransportParam {
+    fn connect(&self) -> Box<dyn Transport> {
+        Box::new(ReplayTransport::open(&self.path))
+    }
+}
+
+#[derive(Debug)]
+enum State {
+    Failed(Error),
+    Playing {
+        start: Instant,
+        /// Messages with the time they are due since the start.
+        messages: VecDeque<(Duration, ServerMessage)>,
+    },
+}
+
+/// Plays a recorded session back as if it came from a live server, seen by
+/// the first player that joined it. Input is ignored.
+#[derive(Debug)]
+pub struct ReplayTransport {
+    state: State,
+}
+
+fn load(path: &Path) -> Result<VecDeque<(Duration, ServerMessage)>, ReplayError> {
+    let reader = ReplayReader::new(BufReader::new(File::open(path)?))?;
+    let header = reader.header().clone();
+    let ticks = reader.collect::<Result<Vec<_>, _>>()?;
+
+    let player_id = ticks
+        .iter()
+        .flat_map(|tick| &tick.events)
+        .find_map(|event| match event {
+            ReplayEvent::PlayerJoined { id, .. } => Some(*id),
+            _ => None,
+        })
+        .unwrap_or(Uuid::nil());
+
+    let mut messages = VecDeque::with_capacity(ticks.len() + 2);
+    messages.push_back((
+        Duration::ZERO,
+        ServerMessage::Handshake {
+            version: header.version,
+        },
+    ));
+    messages.push_back((
+        Duration::ZERO,
+        ServerMessage::SyncWorld {
+            player_id,
+            entity_states: header.entity_states,
+            environment: header.environment,
+            role: Role::Player,
+        },
+    ));
+    let mut time = Duration::ZERO;
+    for mut tick in ticks {
+        time += tick.dt;
+        // Whispers are recorded for everyone
+        tick.output
+            .chat_messages
+            .retain(|message| message.visible_to(player_id));
+        messages.push_back((time, ServerMessage::TickOutput(tick.output)));
+    }
+    Ok(messages)
+}
+
+impl ReplayTransport {
+    pub fn open(path: &Path) -> Self {
+        let state = match load(path) {
+            Ok(messages) => State::Playing {
+                start: Instant::now(),
+                messages,
+            },
+            Err(err) => State::Failed(Error(Arc::new(err))),
+        };
+        Self { state }
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn state(&self) -> TransportState {
+        match &self.state {
+            State::Failed(err) => TransportState::Failed(Box::new(err.clone())),
+            State::Playing { messages, .. } if messages.is_empty() => TransportState::Closed,
+            State::Playing { .. } => TransportState::Connected,
+        }
+    }
+
+    fn receive(&mut self) -> Result<Option<ServerMessage>, Box<dyn StdError>> {
+        match &mut self.state {
+            State::Failed(err) => Err(Box::new(err.clone())),
+            State::Playing { start, messages } => {
+                let due = messages
+                    .front()
+                    .is_some_and(|(time, _)| *time <= start.elapsed());
+                Ok(if due {
+                    messages.pop_front().map(|(_, message)| message)
+                } else {
+                    None
+                })
+            }
+        }
+    }
+
+    fn send(&mut self, _message: ClientMessage) -> Result<(), Box<dyn StdError>> {
+        Ok(())
+    }
+
+    fn traffic(&self) -> TrafficStats {
+        TrafficStats::default()
+    }
+
+    fn close(self) {}
+}
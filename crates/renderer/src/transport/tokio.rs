@@ -8,16 +8,24 @@ use std::{
 };
 
 use bytes::{Bytes, BytesMut};
-use futures::{SinkExt, StreamExt};
-use log::warn;
-use renderer_protocol::message::{ClientMessage, ServerMessage};
+use futures::{future, SinkExt, StreamExt};
+use log::{debug, warn};
+use renderer_protocol::{
+    compression::{CompressionConfig, CompressionError, FrameCodec, COMPRESSION_HEADER},
+    message::{ClientMessage, ServerMessage},
+    traffic::{TrafficCounters, TrafficStats},
+};
 use tokio::{
     runtime::Runtime,
     select,
     sync::{mpsc, oneshot, Mutex},
 };
 use tokio_serde::{Deserializer, Framed, Serializer};
-use tokio_tungstenite::tungstenite::{self, http::Request, Message};
+use tokio_tungstenite::tungstenite::{
+    self,
+    http::{HeaderValue, Request},
+    Message,
+};
 
 use super::{Transport, TransportParam, TransportState};
 
@@ -69,12 +77,14 @@ pub struct TokioTransport {
     cancel_tx: Option<oneshot::Sender<()>>,
     close_tx: Option<oneshot::Sender<()>>,
     state: Arc<Mutex<State>>,
+    traffic: Arc<TrafficCounters>,
 }
 
 #[derive(Debug)]
 pub enum TransportError<SE> {
     WebSocket(tungstenite::Error),
     Serialize(SE),
+    Compression(CompressionError),
 }
 
 impl<SE: Display> Display for TransportError<SE> {
@@ -82,6 +92,7 @@ impl<SE: Display> Display for TransportError<SE> {
         match self {
             TransportError::WebSocket(error) => Display::fmt(error, f),
             TransportError::Serialize(error) => Display::fmt(error, f),
+            TransportError::Compression(error) => Display::fmt(error, f),
         }
     }
 }
@@ -95,11 +106,13 @@ impl<SE> From<SE> for TransportError<SE> {
 }
 
 fn transport_thread<SE, Codec>(
-    request: Request<()>,
+    mut request: Request<()>,
     mut cancel_rx: oneshot::Receiver<()>,
     mut close_rx: oneshot::Receiver<()>,
     state: Arc<Mutex<State>>,
     codec: Codec,
+    compression_config: CompressionConfig,
+    traffic: Arc<TrafficCounters>,
 ) where
     SE: StdError + Send + Sync + 'static,
     Codec: Deserializer<ServerMessage, Error = SE>
@@ -119,6 +132,12 @@ fn transport_thread<SE, Codec>(
         }
     };
 
+    if let Some(offer) = compression_config.offer() {
+        if let Ok(offer) = HeaderValue::from_str(&offer) {
+            request.headers_mut().insert(COMPRESSION_HEADER, offer);
+        }
+    }
+
     let result = runtime.block_on(async {
         let stream = select! {
             biased;
@@ -126,7 +145,7 @@ fn transport_thread<SE, Codec>(
             _ = &mut close_rx => { return Ok(()); }
             stream = tokio_tungstenite::connect_async(request) => stream
         };
-        let (stream, _response) = match stream {
+        let (stream, response) = match stream {
             Ok(stream) => stream,
             Err(err) => {
                 let mut state = state.lock().await;
@@ -146,23 +165,42 @@ fn transport_thread<SE, Codec>(
             drop(state);
         }
 
+        let compression = response
+            .headers()
+            .get(COMPRESSION_HEADER)
+            .and_then(|answer| answer.to_str().ok())
+            .and_then(|answer| compression_config.accepted(answer));
+        debug!("Compression: {:?}", compression);
+        let frame_codec = FrameCodec::new(compression, compression_config.threshold, traffic);
+        let decoder = frame_codec.clone();
+
         let stream = stream
-            .filter_map::<_, Result<BytesMut, TransportError<SE>>, _>(|data| async {
+            .filter_map::<_, Result<BytesMut, TransportError<SE>>, _>(move |data| {
                 let data = match data {
                     Ok(data) => data,
-                    Err(err) => return Some(Err(TransportError::WebSocket(err))),
+                    Err(err) => return future::ready(Some(Err(TransportError::WebSocket(err)))),
                 };
-                match data {
-                    Message::Binary(vec) => Some(Ok(BytesMut::from(vec.as_slice()))),
-                    Message::Ping(_) | Message::Pong(_) | Message::Close(_) => None,
-                    Message::Text(text) => Some(Ok(BytesMut::from(text.as_str()))),
+                let frame = match &data {
+                    Message::Binary(vec) => vec.as_slice(),
+                    Message::Text(text) => text.as_bytes(),
+                    Message::Ping(_) | Message::Pong(_) | Message::Close(_) => {
+                        return future::ready(None)
+                    }
                     Message::Frame(_) => unreachable!(),
-                }
+                };
+                let result = decoder
+                    .decode(frame)
+                    .map(|payload| BytesMut::from(payload.as_slice()))
+                    .map_err(TransportError::Compression);
+                future::ready(Some(result))
             })
             .sink_map_err(TransportError::WebSocket)
-            .with::<Bytes, _, _, TransportError<SE>>(|message| async move {
-                let message = Message::binary(message);
-                Ok(message)
+            .with::<Bytes, _, _, TransportError<SE>>(move |message: Bytes| {
+                let result = frame_codec
+                    .encode(&message)
+                    .map(Message::binary)
+                    .map_err(TransportError::Compression);
+                future::ready(result)
             });
         let framed = Framed::new(stream, codec);
         let mut transport = pin!(framed);
@@ -270,6 +308,10 @@ impl Transport for TokioTransport {
         })
     }
 
+    fn traffic(&self) -> TrafficStats {
+        self.traffic.stats()
+    }
+
     fn close(mut self) {
         if let Some(close_tx) = self.close_tx.take() {
             let _ = close_tx.send(());
@@ -294,6 +336,7 @@ impl Drop for TokioTransport {
 pub struct TokioTransportParam<CodecBuilder> {
     request: Request<()>,
     codec_builder: CodecBuilder,
+    compression: CompressionConfig,
 }
 
 impl<SE, Codec, CodecBuilder> TransportParam for TokioTransportParam<CodecBuilder>
@@ -313,13 +356,25 @@ where
         let (close_tx, close_rx) = oneshot::channel();
         let request = self.request.clone();
         let codec = (self.codec_builder)();
+        let compression = self.compression.clone();
+        let traffic = Arc::new(TrafficCounters::default());
+        let thread_traffic = traffic.clone();
         let transport = TokioTransport {
             thread_handle: Some(thread::spawn(move || {
-                transport_thread(request, cancel_rx, close_rx, thread_state, codec);
+                transport_thread(
+                    request,
+                    cancel_rx,
+                    close_rx,
+                    thread_state,
+                    codec,
+                    compression,
+                    thread_traffic,
+                );
             })),
             cancel_tx: Some(cancel_tx),
             close_tx: Some(close_tx),
             state,
+            traffic,
         };
         Box::new(transport)
     }
@@ -330,6 +385,12 @@ impl<CodecBuilder> TokioTransportParam<CodecBuilder> {
         Self {
             request,
             codec_builder,
+            compression: CompressionConfig::default(),
         }
     }
+
+    pub fn with_compression(mut self, compression: CompressionConfig) -> Self {
+        self.compression = compression;
+        self
+    }
 }